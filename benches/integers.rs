@@ -0,0 +1,50 @@
+//! Decoding of integer-heavy payloads, such as XHProf profiles.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde_php::{from_bytes, to_vec, Decoder};
+use std::collections::BTreeMap;
+
+type Profile = BTreeMap<String, BTreeMap<String, i64>>;
+
+/// Build a profile resembling XHProf output, i.e. call counts and timings per call edge.
+fn xhprof_profile(edges: usize) -> Vec<u8> {
+    let mut profile = Profile::new();
+    for n in 0..edges {
+        let mut metrics = BTreeMap::new();
+        metrics.insert("ct".to_owned(), (n % 17) as i64 + 1);
+        metrics.insert("wt".to_owned(), (n * 7919) as i64);
+        metrics.insert("cpu".to_owned(), (n * 6007) as i64);
+        metrics.insert("mu".to_owned(), (n * 104_729) as i64);
+        metrics.insert("pmu".to_owned(), -((n * 31) as i64));
+        profile.insert(format!("fn_{}==>fn_{}", n / 3, n), metrics);
+    }
+    to_vec(&profile).unwrap()
+}
+
+fn integers(c: &mut Criterion) {
+    let numbers: Vec<i64> = (0..10_000).map(|n| n * n * 7_654_321 - 1_000_000).collect();
+    let list = to_vec(&numbers).unwrap();
+    let profile = xhprof_profile(2_000);
+
+    let mut group = c.benchmark_group("integers");
+
+    group.throughput(Throughput::Bytes(list.len() as u64));
+    group.bench_function("list", |b| {
+        b.iter(|| from_bytes::<Vec<i64>>(black_box(&list)).unwrap())
+    });
+
+    group.throughput(Throughput::Bytes(profile.len() as u64));
+    group.bench_function("xhprof", |b| {
+        b.iter(|| from_bytes::<Profile>(black_box(&profile)).unwrap())
+    });
+
+    let mut decoder = Decoder::new();
+    group.bench_function("xhprof_decoder", |b| {
+        b.iter(|| decoder.deserialize::<Profile>(black_box(&profile)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, integers);
+criterion_main!(benches);
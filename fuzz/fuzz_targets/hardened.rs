@@ -0,0 +1,57 @@
+//! Deserializes arbitrary input using `Profile::Hardened`.
+//!
+//! Run with `cargo fuzz run hardened -- -rss_limit_mb=256 -timeout=1`, so that
+//! excessive memory use and run time are reported as failures along with
+//! panics and stack overflows.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_php::{PhpDeserializerBuilder, Profile};
+use std::collections::HashMap;
+
+/// Accepts any value, exercising `deserialize_any` on all of the input.
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Any {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    List(Vec<Any>),
+    Map(HashMap<String, Any>),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct Record {
+    #[serde(default)]
+    id: u64,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    children: Vec<Record>,
+}
+
+fuzz_target!(|data: &[u8]| {
+    let builder = PhpDeserializerBuilder::new().profile(Profile::Hardened);
+    let lenient = builder
+        .clone()
+        .tolerant(true)
+        .tolerant_floats(true)
+        .std_class_as_map(true)
+        .positional_structs(true)
+        .nested_unserialize(true);
+
+    let _ = builder.deserialize::<IgnoredAny>(data);
+    let _ = builder.deserialize::<Any>(data);
+    let _ = builder.deserialize::<Record>(data);
+    let _ = lenient.deserialize::<Record>(data);
+
+    // Without a known input length, only the limits guard against bogus sizes.
+    let _ = Any::deserialize(&mut builder.build_reader(data));
+});
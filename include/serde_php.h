@@ -0,0 +1,47 @@
+/* C interface to serde_php, available when built with the `ffi` feature. */
+
+#ifndef SERDE_PHP_H
+#define SERDE_PHP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes returned by all functions. */
+#define SERDE_PHP_OK 0
+#define SERDE_PHP_ERR_NULL_ARGUMENT 1
+#define SERDE_PHP_ERR_INVALID_PHP 2
+#define SERDE_PHP_ERR_INVALID_JSON 3
+
+/*
+ * `error` parameters may be NULL. If not, they receive an error message on
+ * failure, which must be released using `serde_php_free_string`.
+ */
+
+/*
+ * Convert PHP-serialized data into a NUL-terminated JSON string. Input nested
+ * more than 128 levels deep is rejected as invalid, here and in
+ * `serde_php_validate`.
+ */
+int serde_php_to_json(const uint8_t *input, size_t input_len, char **output, char **error);
+
+/* Check whether the input is a single valid PHP-serialized value. */
+int serde_php_validate(const uint8_t *input, size_t input_len, char **error);
+
+/* Convert a NUL-terminated JSON string into PHP-serialized data (not NUL-terminated). */
+int serde_php_from_json(const char *json, uint8_t **output, size_t *output_len, char **error);
+
+/* Release strings returned by this library. */
+void serde_php_free_string(char *s);
+
+/* Release buffers returned by `serde_php_from_json`. */
+void serde_php_free_bytes(uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SERDE_PHP_H */
@@ -0,0 +1,301 @@
+//! Arena-allocated values.
+//!
+//! [`ArenaValue`] is a variant of [`PhpValue`] whose strings and arrays live
+//! in a caller-provided [`bumpalo::Bump`] arena. Parsing does not allocate
+//! anything besides arena memory and a single scratch buffer, and all of the
+//! value is freed at once by dropping or resetting the arena. This suits
+//! request-scoped parsing in servers, where the arena can be reused for every
+//! request.
+//!
+//! ```rust
+//! use bumpalo::Bump;
+//! use serde_php::arena::ArenaValue;
+//!
+//! let mut bump = Bump::new();
+//!
+//! for _ in 0..3 {
+//!     let value = ArenaValue::parse_in(br#"a:1:{s:4:"user";s:3:"bob";}"#, &bump).unwrap();
+//!     assert_eq!(value.get("user").and_then(|v| v.as_str()), Some("bob"));
+//!
+//!     // Free all of the value at once, keeping the memory for the next request.
+//!     bump.reset();
+//! }
+//! ```
+
+use crate::digits;
+use crate::error::{Error, ErrorKind, Result};
+use crate::lex::Lookahead1;
+use crate::number::Number;
+use crate::value::{array_key, PhpValue};
+use bumpalo::Bump;
+use std::io::Read;
+
+/// A PHP value allocated in an arena, see the [module documentation](self).
+///
+/// Unlike [`PhpValue`], numbers are split into their variants, as arena
+/// memory is never dropped and thus cannot own heap allocations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArenaValue<'a> {
+    /// `null`
+    Null,
+    /// boolean
+    Bool(bool),
+    /// integer
+    Int(i64),
+    /// integer outside of the `i64` range, as decimal digits with optional
+    /// leading `-`
+    BigInt(&'a str),
+    /// float
+    Float(f64),
+    /// string (PHP strings are bytestrings)
+    String(&'a [u8]),
+    /// array (both numeric and associative)
+    Array(&'a [(ArenaValue<'a>, ArenaValue<'a>)]),
+}
+
+impl<'a> ArenaValue<'a> {
+    /// Parse a value, allocating strings and arrays in `bump`.
+    ///
+    /// As with [`PhpValue::parse`], the input must contain exactly one value.
+    pub fn parse_in(input: &[u8], bump: &'a Bump) -> Result<ArenaValue<'a>> {
+        let mut lexer = Lookahead1::new(input);
+        lexer.set_input_len(input.len() as u64);
+
+        let mut parser = Parser {
+            input: lexer,
+            bump,
+            scratch: Vec::new(),
+        };
+        let value = parser
+            .parse_value()
+            .map_err(|err| err.with_offset(parser.input.position()))?;
+
+        if parser.input.peek()?.is_some() {
+            return Err(Error::from(ErrorKind::TrailingData).with_offset(parser.input.position()));
+        }
+
+        Ok(value)
+    }
+
+    /// Look up an array entry by string key, see [`PhpValue::get`].
+    pub fn get(&self, key: &str) -> Option<&ArenaValue<'a>> {
+        match array_key(key.as_bytes().to_vec()) {
+            PhpValue::Number(n) => self.get_index(n.as_i64()?),
+            _ => self.find(&ArenaValue::String(key.as_bytes())),
+        }
+    }
+
+    /// Look up an array entry by integer key.
+    pub fn get_index(&self, index: i64) -> Option<&ArenaValue<'a>> {
+        self.find(&ArenaValue::Int(index))
+    }
+
+    /// Find the value stored under `key`.
+    fn find(&self, key: &ArenaValue<'_>) -> Option<&ArenaValue<'a>> {
+        self.as_array()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns whether the value is `null`.
+    pub fn is_null(&self) -> bool {
+        *self == ArenaValue::Null
+    }
+
+    /// Returns the boolean, if the value is one.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            ArenaValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer, if the value is an integer that fits into `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ArenaValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float, if it is a float or an integer.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            ArenaValue::Int(v) => Some(v as f64),
+            ArenaValue::BigInt(v) => v.parse().ok(),
+            ArenaValue::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw string data, if the value is a string.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            ArenaValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the string, if the value is a string that is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    /// Returns the array entries, if the value is an array.
+    pub fn as_array(&self) -> Option<&'a [(ArenaValue<'a>, ArenaValue<'a>)]> {
+        match *self {
+            ArenaValue::Array(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Copy the value out of the arena.
+    pub fn to_value(&self) -> PhpValue {
+        match *self {
+            ArenaValue::Null => PhpValue::Null,
+            ArenaValue::Bool(v) => PhpValue::Bool(v),
+            ArenaValue::Int(v) => PhpValue::from(v),
+            ArenaValue::BigInt(v) => PhpValue::Number(Number::from_int_text(v)),
+            ArenaValue::Float(v) => PhpValue::Number(v.into()),
+            ArenaValue::String(v) => PhpValue::String(v.to_vec()),
+            ArenaValue::Array(entries) => PhpValue::Array(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.to_value(), value.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&ArenaValue<'_>> for PhpValue {
+    fn from(value: &ArenaValue<'_>) -> Self {
+        value.to_value()
+    }
+}
+
+/// Parser state, shared across nested values.
+struct Parser<'a, R> {
+    input: Lookahead1<R>,
+    bump: &'a Bump,
+    /// String data is read here first, then copied into the arena.
+    scratch: Vec<u8>,
+}
+
+impl<'a, R: Read> Parser<'a, R> {
+    fn parse_value(&mut self) -> Result<ArenaValue<'a>> {
+        let sym = self.input.read1()?;
+
+        if sym == b'N' {
+            self.input.expect(b';')?;
+            return Ok(ArenaValue::Null);
+        }
+
+        self.input.expect(b':')?;
+
+        match sym {
+            b'b' => Ok(ArenaValue::Bool(self.input.read_bool()?)),
+            b'i' => {
+                let text = self.input.read_int_bytes()?;
+                match digits::parse_i64(&text) {
+                    Some(v) => Ok(ArenaValue::Int(v)),
+                    None => {
+                        // Normalize the same way `PhpValue` does.
+                        let text = std::str::from_utf8(&text).expect("sign and digits are ASCII");
+                        let number = Number::from_int_text(text);
+                        let digits = number.as_big_int().expect("out of `i64` range");
+                        Ok(ArenaValue::BigInt(self.bump.alloc_str(digits)))
+                    }
+                }
+            }
+            b'd' => Ok(ArenaValue::Float(self.input.read_float()?)),
+            b's' => {
+                self.input.read_raw_string_into(&mut self.scratch)?;
+                Ok(ArenaValue::String(
+                    self.bump.alloc_slice_copy(&self.scratch),
+                ))
+            }
+            b'a' => {
+                let num_elements = self.input.read_array_header()?;
+                let mut entries =
+                    bumpalo::collections::Vec::with_capacity_in(num_elements.min(1024), self.bump);
+
+                for _ in 0..num_elements {
+                    let key = match self.input.peek()? {
+                        Some(b'i') | Some(b's') => self.parse_value()?,
+                        Some(c) => {
+                            return Err(ErrorKind::UnsupportedArrayKeyType(char::from(c)).into())
+                        }
+                        None => return Err(ErrorKind::UnexpectedEof.into()),
+                    };
+                    let value = self.parse_value()?;
+                    entries.push((key, value));
+                }
+
+                self.input.read_array_end()?;
+                Ok(ArenaValue::Array(entries.into_bump_slice()))
+            }
+            b'O' => Err(ErrorKind::UnsupportedPhpType {
+                type_indicator: 'O',
+            }
+            .into()),
+            c => Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArenaValue;
+    use crate::error::ErrorKind;
+    use crate::value::PhpValue;
+    use bumpalo::Bump;
+
+    #[test]
+    fn parses_nested_values() {
+        let bump = Bump::new();
+        let input = br#"a:4:{s:4:"name";s:3:"bob";i:7;b:1;s:5:"float";d:0.5;s:4:"tags";a:2:{i:0;N;i:1;i:-3;}}"#;
+        let value = ArenaValue::parse_in(input, &bump).unwrap();
+
+        assert_eq!(value.get("name").and_then(ArenaValue::as_str), Some("bob"));
+        assert_eq!(value.get("7"), Some(&ArenaValue::Bool(true)));
+        assert_eq!(value.get("float").and_then(ArenaValue::as_f64), Some(0.5));
+
+        let tags = value.get("tags").unwrap();
+        assert!(tags.get_index(0).unwrap().is_null());
+        assert_eq!(tags.get_index(1).and_then(ArenaValue::as_i64), Some(-3));
+        assert_eq!(value.get("missing"), None);
+
+        assert!(bump.allocated_bytes() > 0);
+        assert_eq!(value.to_value(), PhpValue::parse(input).unwrap());
+    }
+
+    #[test]
+    fn keeps_big_integers() {
+        let bump = Bump::new();
+        let value = ArenaValue::parse_in(b"i:+0018446744073709551616;", &bump).unwrap();
+
+        assert_eq!(value, ArenaValue::BigInt("18446744073709551616"));
+        assert_eq!(
+            PhpValue::from(&value),
+            PhpValue::parse(b"i:18446744073709551616;").unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let bump = Bump::new();
+
+        let err = ArenaValue::parse_in(b"i:1;i:2;", &bump).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TrailingData));
+        assert_eq!(err.offset(), Some(4));
+
+        let err = ArenaValue::parse_in(b"a:1:{d:1.0;i:1;}", &bump).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnsupportedArrayKeyType('d')
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+//! Arbitrary-precision integers of PHP's `GMP` and BCMath extensions.
+//!
+//! PHP applications keep numbers exceeding 64 bits either as `GMP` objects,
+//! which hold their value as a hexadecimal string, or as decimal strings
+//! processed by the BCMath functions (`BcMath\Number` objects as of PHP 8.4):
+//!
+//! ```php
+//! serialize(gmp_init("18446744073709551616")); // O:3:"GMP":1:{i:0;s:17:"10000000000000000";}
+//! serialize(bcadd("18446744073709551615", "1")); // s:20:"18446744073709551616";
+//! ```
+//!
+//! The [`gmp`] and [`bcmath`] modules map these onto [`BigInt`] for use with
+//! `#[serde(with = "...")]`. Both deserialize any of the representations, as
+//! well as plain integers, and only differ in what they serialize to:
+//!
+//! ```rust
+//! use num_bigint::BigInt;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, PartialEq, Serialize)]
+//! struct Account {
+//!     #[serde(with = "serde_php::bigint::gmp")]
+//!     balance: BigInt,
+//!     #[serde(with = "serde_php::bigint::bcmath")]
+//!     limit: BigInt,
+//! }
+//!
+//! let input = br#"a:2:{s:7:"balance";O:3:"GMP":1:{i:0;s:3:"-ff";}s:5:"limit";i:1000;}"#;
+//! let account: Account = serde_php::from_bytes(input).unwrap();
+//! assert_eq!(account.balance, BigInt::from(-255));
+//!
+//! assert_eq!(
+//!     serde_php::to_vec(&account).unwrap(),
+//!     &br#"a:2:{s:7:"balance";O:3:"GMP":1:{i:0;s:3:"-ff";}s:5:"limit";s:4:"1000";}"#[..]
+//! );
+//! ```
+//!
+//! BCMath numbers with a fractional part are rejected, unless all of its
+//! digits are zero. Only the object format of PHP 8.1 and later is supported
+//! for `GMP`, earlier versions used `C:` (custom serialization).
+
+use num_bigint::BigInt;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, Unexpected, Visitor};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Struct name recognized by the deserializer, accepting any representation
+/// of a number.
+pub(crate) const BIG_NUMBER_TOKEN: &str = "$serde_php::private::BigNumber";
+
+/// Tuple struct name recognized by the serializer, writing a `GMP` object.
+pub(crate) const GMP_TOKEN: &str = "$serde_php::private::Gmp";
+
+/// Serialize a [`BigInt`] as `GMP` object.
+///
+/// Serializers of other formats receive a tuple struct holding the
+/// hexadecimal digits.
+pub mod gmp {
+    use num_bigint::BigInt;
+    use serde::ser::{SerializeTupleStruct, Serializer};
+    use serde::Deserializer;
+
+    /// Serialize `value` as `GMP` object.
+    pub fn serialize<S>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut object = serializer.serialize_tuple_struct(super::GMP_TOKEN, 1)?;
+        object.serialize_field(&value.to_str_radix(16))?;
+        object.end()
+    }
+
+    /// Deserialize a `GMP` object, BCMath number or integer.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+/// Serialize a [`BigInt`] as decimal string, as used by BCMath.
+pub mod bcmath {
+    use num_bigint::BigInt;
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize `value` as decimal string.
+    pub fn serialize<S>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(value)
+    }
+
+    /// Deserialize a `GMP` object, BCMath number or integer.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_struct(BIG_NUMBER_TOKEN, &["value"], BigIntVisitor)
+}
+
+struct BigIntVisitor;
+
+impl<'de> Visitor<'de> for BigIntVisitor {
+    type Value = BigInt;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a GMP object, BCMath number or integer")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<BigInt, E> {
+        Ok(BigInt::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<BigInt, E> {
+        Ok(BigInt::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<BigInt, E> {
+        parse_decimal(v).ok_or_else(|| E::invalid_value(Unexpected::Bytes(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<BigInt, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<BigInt, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut value = None;
+
+        while let Some(property) = map.next_key::<Property>()? {
+            let parse = match property {
+                Property::Hex => parse_hex,
+                Property::Decimal => parse_decimal,
+                Property::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+            };
+            let digits: Digits = map.next_value()?;
+            value =
+                Some(parse(&digits.0).ok_or_else(|| {
+                    de::Error::invalid_value(Unexpected::Bytes(&digits.0), &self)
+                })?);
+        }
+
+        value.ok_or_else(|| de::Error::missing_field("value"))
+    }
+}
+
+/// Parse the hexadecimal digits of a `GMP` object.
+fn parse_hex(digits: &[u8]) -> Option<BigInt> {
+    BigInt::parse_bytes(digits, 16)
+}
+
+/// Parse a BCMath number, which must not have a fractional part other than
+/// zeros.
+fn parse_decimal(digits: &[u8]) -> Option<BigInt> {
+    let (int, frac) = match digits.iter().position(|&c| c == b'.') {
+        Some(idx) => (&digits[..idx], &digits[idx + 1..]),
+        None => (digits, &b""[..]),
+    };
+    if !frac.iter().all(|&c| c == b'0') {
+        return None;
+    }
+    BigInt::parse_bytes(int, 10)
+}
+
+/// Property of a number object, `0` of `GMP` and `value` of `BcMath\Number`.
+enum Property {
+    Hex,
+    Decimal,
+    Other,
+}
+
+impl<'de> de::Deserialize<'de> for Property {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PropertyVisitor;
+
+        impl<'de> Visitor<'de> for PropertyVisitor {
+            type Value = Property;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a property index or name")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Property, E> {
+                Ok(if v == 0 {
+                    Property::Hex
+                } else {
+                    Property::Other
+                })
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Property, E> {
+                self.visit_i64(i64::try_from(v).unwrap_or(-1))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Property, E> {
+                Ok(if v == "value" {
+                    Property::Decimal
+                } else {
+                    Property::Other
+                })
+            }
+        }
+
+        deserializer.deserialize_any(PropertyVisitor)
+    }
+}
+
+/// Digits of a number, as bytes.
+struct Digits(Vec<u8>);
+
+impl<'de> de::Deserialize<'de> for Digits {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DigitsVisitor;
+
+        impl<'de> Visitor<'de> for DigitsVisitor {
+            type Value = Digits;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a string of digits")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Digits, E> {
+                Ok(Digits(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Digits, E> {
+                Ok(Digits(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Digits, E> {
+                self.visit_bytes(v.as_bytes())
+            }
+        }
+
+        deserializer.deserialize_byte_buf(DigitsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bcmath, gmp};
+    use crate::{from_bytes, to_vec};
+    use num_bigint::BigInt;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Gmp(#[serde(with = "gmp")] BigInt);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct BcMath(#[serde(with = "bcmath")] BigInt);
+
+    fn big(digits: &str) -> BigInt {
+        digits.parse().unwrap()
+    }
+
+    #[test]
+    fn roundtrips_gmp_objects() {
+        // PHP: serialize(gmp_init("-340282366920938463463374607431768211456"))
+        let input = br#"O:3:"GMP":1:{i:0;s:34:"-100000000000000000000000000000000";}"#;
+        let value: Gmp = from_bytes(input).unwrap();
+        assert_eq!(value.0, big("-340282366920938463463374607431768211456"));
+        assert_eq!(to_vec(&value).unwrap(), &input[..]);
+
+        // Properties of subclasses follow the value and are ignored.
+        let input = br#"O:5:"Money":2:{i:0;s:2:"2a";i:1;a:1:{s:8:"currency";s:3:"EUR";}}"#;
+        assert_eq!(from_bytes::<Gmp>(input).unwrap().0, big("42"));
+    }
+
+    #[test]
+    fn roundtrips_bcmath_numbers() {
+        let input = br#"s:21:"-18446744073709551616";"#;
+        let value: BcMath = from_bytes(input).unwrap();
+        assert_eq!(value.0, big("-18446744073709551616"));
+        assert_eq!(to_vec(&value).unwrap(), &input[..]);
+
+        assert_eq!(
+            from_bytes::<BcMath>(br#"s:5:"12.00";"#).unwrap().0,
+            big("12")
+        );
+        // PHP 8.4: serialize(new BcMath\Number("7"))
+        let input = br#"O:13:"BcMath\Number":1:{s:5:"value";s:1:"7";}"#;
+        assert_eq!(from_bytes::<BcMath>(input).unwrap().0, big("7"));
+    }
+
+    #[test]
+    fn accepts_integers() {
+        assert_eq!(from_bytes::<Gmp>(b"i:-5;").unwrap().0, big("-5"));
+        assert_eq!(
+            to_vec(&BcMath(big("9223372036854775807"))).unwrap(),
+            br#"s:19:"9223372036854775807";"#
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        assert!(from_bytes::<BcMath>(br#"s:3:"1.5";"#).is_err());
+        assert!(from_bytes::<BcMath>(br#"s:3:"abc";"#).is_err());
+        assert!(from_bytes::<Gmp>(br#"O:3:"GMP":1:{i:0;s:2:"xy";}"#).is_err());
+        assert!(from_bytes::<Gmp>(br#"O:3:"GMP":0:{}"#).is_err());
+        assert!(from_bytes::<Gmp>(b"d:1.5;").is_err());
+    }
+}
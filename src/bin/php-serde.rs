@@ -0,0 +1,276 @@
+//! Command line tool for working with PHP-serialized data.
+//!
+//! Requires the `cli` feature. Run `php-serde help` for usage information.
+
+use serde_php::{lex::Lexer, repair, PhpValue};
+use std::io::{self, Read, Write};
+use std::{env, fs, process};
+
+const USAGE: &str = "\
+Usage: php-serde <COMMAND> [FILE]
+
+Reads from FILE, or standard input if FILE is missing or `-`.
+
+Commands:
+  inspect    Show structure of a serialized value, similar to `var_dump`
+  export     Show a serialized value as PHP source code, similar to `var_export`
+  to-json    Convert a serialized value to JSON
+  from-json  Convert JSON to a serialized value
+  validate   Check that the input is a single valid serialized value
+  repair     Fix string length prefixes that do not match the string data
+  tokens     List the tokens of the input along with their byte ranges
+  help       Show this message
+";
+
+/// Command line error, printed before exiting.
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (command, path) = match args.as_slice() {
+        [command] => (command.as_str(), None),
+        [command, path] => (command.as_str(), Some(path.as_str())),
+        _ => {
+            eprint!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match command {
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            return;
+        }
+        command if COMMANDS.contains(&command) => {}
+        other => {
+            eprintln!("unknown command `{}`\n", other);
+            eprint!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+
+    let stdout = io::stdout();
+    let result = read_input(path).and_then(|input| run(command, &input, &mut stdout.lock()));
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Commands taking input.
+const COMMANDS: &[&str] = &[
+    "inspect",
+    "export",
+    "to-json",
+    "from-json",
+    "validate",
+    "repair",
+    "tokens",
+];
+
+/// Read all of the input, either from a file or stdin.
+fn read_input(path: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match path {
+        None | Some("-") => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+        Some(path) => Ok(fs::read(path)?),
+    }
+}
+
+/// Run `command` on `input`, writing the result to `out`.
+///
+/// Input is parsed with a depth limit, as files passed to the tool may come
+/// from anywhere.
+fn run(command: &str, input: &[u8], out: &mut dyn Write) -> CliResult {
+    match command {
+        "inspect" => inspect(input, out),
+        "export" => export(input, out),
+        "to-json" => to_json(input, out),
+        "from-json" => from_json(input, out),
+        "validate" => validate(input, out),
+        "repair" => out
+            .write_all(&repair::fix_string_lengths(input))
+            .map_err(Into::into),
+        "tokens" => tokens(input, out),
+        other => Err(format!("unknown command `{}`", other).into()),
+    }?;
+    out.flush()?;
+    Ok(())
+}
+
+fn inspect(input: &[u8], out: &mut dyn Write) -> CliResult {
+    let value = PhpValue::parse_bounded(input)?;
+    let mut dumped = String::new();
+    dump(&mut dumped, &value, 0);
+    out.write_all(dumped.as_bytes())?;
+    Ok(())
+}
+
+fn export(input: &[u8], out: &mut dyn Write) -> CliResult {
+    let value = PhpValue::parse_bounded(input)?;
+    writeln!(out, "{:#}", value)?;
+    Ok(())
+}
+
+fn to_json(input: &[u8], out: &mut dyn Write) -> CliResult {
+    let value = PhpValue::parse_bounded(input)?;
+    let mut json = serde_json::to_vec_pretty(&value)?;
+    json.push(b'\n');
+    out.write_all(&json)?;
+    Ok(())
+}
+
+fn from_json(input: &[u8], out: &mut dyn Write) -> CliResult {
+    let json: serde_json::Value = serde_json::from_slice(input)?;
+    out.write_all(&PhpValue::from(json).to_vec())?;
+    Ok(())
+}
+
+fn tokens(input: &[u8], out: &mut dyn Write) -> CliResult {
+    let mut listing = String::new();
+    let mut result = Ok(());
+    for token in Lexer::new(input) {
+        match token {
+            Ok((_, span)) => listing.push_str(&format!(
+                "{}..{}\t{}\n",
+                span.start,
+                span.end,
+                String::from_utf8_lossy(&input[span.clone()])
+            )),
+            Err(err) => result = Err(err.into()),
+        }
+    }
+    out.write_all(listing.as_bytes())?;
+    result
+}
+
+fn validate(input: &[u8], out: &mut dyn Write) -> CliResult {
+    PhpValue::parse_bounded(input)?;
+    writeln!(out, "ok")?;
+    Ok(())
+}
+
+/// Render value in a format resembling PHP's `var_dump`.
+fn dump(out: &mut String, value: &PhpValue, indent: usize) {
+    match value {
+        PhpValue::Null => out.push_str("NULL\n"),
+        PhpValue::Bool(v) => out.push_str(&format!("bool({})\n", v)),
+        PhpValue::Number(n) if n.is_int() => out.push_str(&format!("int({})\n", n)),
+        PhpValue::Number(n) => out.push_str(&format!("float({})\n", n)),
+        PhpValue::String(v) => out.push_str(&format!(
+            "string({}) \"{}\"\n",
+            v.len(),
+            String::from_utf8_lossy(v)
+        )),
+        PhpValue::Array(entries) => {
+            out.push_str(&format!("array({}) {{\n", entries.len()));
+            for (key, value) in entries {
+                out.push_str(&"  ".repeat(indent + 1));
+                match key {
+                    PhpValue::String(k) => {
+                        out.push_str(&format!("[\"{}\"]=>\n", String::from_utf8_lossy(k)))
+                    }
+                    PhpValue::Number(k) => out.push_str(&format!("[{}]=>\n", k)),
+                    other => out.push_str(&format!("[{:?}]=>\n", other)),
+                }
+                out.push_str(&"  ".repeat(indent + 1));
+                dump(out, value, indent + 1);
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push_str("}\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, COMMANDS};
+
+    /// Run `command`, returning its output as text.
+    fn output(command: &str, input: &[u8]) -> Result<String, String> {
+        let mut out = Vec::new();
+        run(command, input, &mut out).map_err(|err| err.to_string())?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    const INPUT: &[u8] = br#"a:2:{s:4:"name";s:3:"Bob";i:7;a:1:{i:0;b:1;}}"#;
+
+    #[test]
+    fn inspects() {
+        assert_eq!(
+            output("inspect", INPUT).unwrap(),
+            "array(2) {\n  [\"name\"]=>\n  string(3) \"Bob\"\n  [7]=>\n  array(1) {\n    [0]=>\n    bool(true)\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn exports() {
+        assert_eq!(
+            output("export", INPUT).unwrap(),
+            "[\n    'name' => 'Bob',\n    7 => [\n        0 => true,\n    ],\n]\n"
+        );
+    }
+
+    #[test]
+    fn converts_json() {
+        let json = output("to-json", INPUT).unwrap();
+        assert_eq!(
+            json,
+            "{\n  \"name\": \"Bob\",\n  \"7\": [\n    true\n  ]\n}\n"
+        );
+        assert_eq!(
+            output("from-json", br#"{"name":"Bob","list":[true]}"#).unwrap(),
+            r#"a:2:{s:4:"name";s:3:"Bob";s:4:"list";a:1:{i:0;b:1;}}"#
+        );
+        assert!(output("from-json", b"{").is_err());
+    }
+
+    #[test]
+    fn validates() {
+        assert_eq!(output("validate", INPUT).unwrap(), "ok\n");
+        assert!(output("validate", b"i:1").is_err());
+        assert!(output("validate", b"i:1;i:2;").is_err());
+    }
+
+    #[test]
+    fn repairs() {
+        assert_eq!(
+            output("repair", br#"a:1:{i:0;s:1:"abc";}"#).unwrap(),
+            r#"a:1:{i:0;s:3:"abc";}"#
+        );
+    }
+
+    #[test]
+    fn lists_tokens() {
+        assert_eq!(output("tokens", b"i:5;").unwrap(), "0..4\ti:5;\n");
+        assert!(output("tokens", b"x:5;").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for command in COMMANDS {
+            if *command == "repair" {
+                continue;
+            }
+            assert!(output(command, b"a:1:{i:0;x:1;}").is_err(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        // Deep enough to overflow the stack without a limit.
+        let mut deep = b"a:1:{i:0;".repeat(200_000);
+        deep.extend_from_slice(b"N;");
+        deep.extend_from_slice(&b"}".repeat(200_000));
+
+        for command in &["inspect", "export", "to-json", "validate"] {
+            let err = output(command, &deep).unwrap_err();
+            assert!(err.contains("128"), "{}: {}", command, err);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Validating passthrough.
+
+use crate::de::{PhpDeserializer, ReferencePolicy};
+use crate::error::{Error, ErrorKind, Result};
+use std::io::{self, BufReader, Read, Write};
+
+/// Copy a single serialized value from `reader` to `writer`, validating it.
+///
+/// The input is forwarded unchanged while it is being parsed, without
+/// building up any values in memory. References (`R:` and `r:`) are
+/// rejected, as checking them would require retaining the input. Returns the number of bytes copied.
+/// Input that is not exactly one valid value, including input with trailing
+/// data, results in an error.
+///
+/// Data is written as soon as it is read, so on error some or all of the
+/// input may already have been written. Buffer the output if it must only be
+/// forwarded after successful validation.
+///
+/// ```rust
+/// let mut output = Vec::new();
+/// let copied = serde_php::copy_validate(&b"a:1:{i:0;b:1;}"[..], &mut output).unwrap();
+/// assert_eq!(copied, 14);
+/// assert_eq!(output, b"a:1:{i:0;b:1;}");
+///
+/// assert!(serde_php::copy_validate(&b"a:1:{i:0;b:1;"[..], &mut Vec::new()).is_err());
+/// ```
+pub fn copy_validate<R, W>(reader: R, writer: W) -> Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    let mut tee = Tee {
+        reader,
+        writer,
+        copied: 0,
+        write_error: None,
+    };
+
+    let result = {
+        let mut des = PhpDeserializer::new(BufReader::new(&mut tee));
+        des.set_references(ReferencePolicy::Error);
+        des.skip_value().and_then(|()| match des.peek()? {
+            Some(_) => Err(ErrorKind::TrailingData.into()),
+            None => Ok(()),
+        })
+    };
+
+    // Failing to forward takes precedence, since it also fails the read.
+    if let Some(err) = tee.write_error.take() {
+        return Err(ErrorKind::WriteSerialized(err).into());
+    }
+    result?;
+
+    tee.writer
+        .flush()
+        .map_err(|err| Error::from(ErrorKind::WriteSerialized(err)))?;
+    Ok(tee.copied)
+}
+
+/// Reader that writes everything read to a writer.
+struct Tee<R, W> {
+    reader: R,
+    writer: W,
+    copied: u64,
+    /// Error while writing, which is reported as a generic read error.
+    write_error: Option<io::Error>,
+}
+
+impl<R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+
+        if let Err(err) = self.writer.write_all(&buf[..len]) {
+            self.write_error = Some(err);
+            return Err(io::Error::other("writing failed"));
+        }
+
+        self.copied += len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::copy_validate;
+    use crate::error::ErrorKind;
+    use std::io::{self, Write};
+
+    #[test]
+    fn copies_unchanged() {
+        // Non-canonical, but valid input must be kept as-is.
+        let input = br#"a:3:{i:0;i:+05;s:1:"x";d:1.50;i:2;a:1:{s:3:"a;}";N;}}"#;
+        let mut output = Vec::new();
+
+        assert_eq!(
+            copy_validate(&input[..], &mut output).unwrap(),
+            input.len() as u64
+        );
+        assert_eq!(output, input.to_vec());
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let inputs: &[&[u8]] = &[
+            b"",
+            b"i:1;i:2;",
+            b"a:1:{d:1.5;N;}",
+            br#"s:5:"abc";"#,
+            b"b:2;",
+        ];
+
+        for input in inputs {
+            assert!(copy_validate(*input, &mut Vec::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn reports_write_errors() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let err = copy_validate(&b"N;"[..], Broken).unwrap_err();
+        match err.kind() {
+            ErrorKind::WriteSerialized(_) => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
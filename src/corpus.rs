@@ -0,0 +1,185 @@
+//! Golden-file regression corpus.
+//!
+//! A corpus is a directory of test cases. Each case consists of two files
+//! sharing a name: `<name>.serialized` holds the raw PHP-serialized input and
+//! `<name>.json` the expected result of converting it to JSON (as done by
+//! serializing a [`PhpValue`] using `serde_json`). For example:
+//!
+//! ```text
+//! tests/corpus/
+//!     list.serialized     a:2:{i:0;s:1:"a";i:1;b:1;}
+//!     list.json           ["a", true]
+//! ```
+//!
+//! Every case is checked to parse, to match its expected JSON and to
+//! serialize back to exactly the original bytes. Real-world payloads that
+//! caused failures can simply be dropped into the directory. To run a corpus
+//! from a test:
+//!
+//! ```rust,no_run
+//! #[test]
+//! fn corpus() {
+//!     serde_php::corpus::assert_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus"));
+//! }
+//! ```
+
+use crate::value::PhpValue;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A single corpus case.
+#[derive(Clone, Debug)]
+pub struct Case {
+    /// Name of the case, i.e. the common file stem.
+    pub name: String,
+    /// Serialized input.
+    pub serialized: Vec<u8>,
+    /// Expected JSON representation.
+    pub expected: serde_json::Value,
+}
+
+/// A failed corpus case.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    /// Name of the failed case.
+    pub name: String,
+    /// Description of the failure.
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+/// Load all cases from a corpus directory, ordered by name.
+///
+/// Fails if a `.serialized` file has no `.json` sibling or the JSON is
+/// invalid. Other files are ignored.
+pub fn load_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<Case>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    paths.sort();
+
+    let mut cases = Vec::new();
+    for path in paths {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("serialized") {
+            continue;
+        }
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let serialized = fs::read(&path)?;
+        let expected = serde_json::from_slice(&fs::read(path.with_extension("json"))?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        cases.push(Case {
+            name,
+            serialized,
+            expected,
+        });
+    }
+
+    Ok(cases)
+}
+
+impl Case {
+    /// Check the case, returning a description of the first problem found.
+    pub fn check(&self) -> Result<(), Failure> {
+        let fail = |message: String| Failure {
+            name: self.name.clone(),
+            message,
+        };
+
+        let value = PhpValue::parse(&self.serialized)
+            .map_err(|err| fail(format!("parsing failed: {}", err)))?;
+
+        let actual = serde_json::to_value(&value)
+            .map_err(|err| fail(format!("JSON conversion failed: {}", err)))?;
+        if actual != self.expected {
+            return Err(fail(format!(
+                "JSON mismatch\n  expected: {}\n    actual: {}",
+                self.expected, actual
+            )));
+        }
+
+        let reserialized = value.to_vec();
+        if reserialized != self.serialized {
+            return Err(fail(format!(
+                "round-trip mismatch\n  expected: {}\n    actual: {}",
+                String::from_utf8_lossy(&self.serialized),
+                String::from_utf8_lossy(&reserialized)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Check all cases of a corpus directory, collecting failures.
+pub fn check_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<Failure>> {
+    Ok(load_dir(dir)?
+        .iter()
+        .filter_map(|case| case.check().err())
+        .collect())
+}
+
+/// Check all cases of a corpus directory, panicking with a report if any fail.
+///
+/// Also panics if the directory cannot be read or contains no cases.
+pub fn assert_corpus<P: AsRef<Path>>(dir: P) {
+    let dir = dir.as_ref();
+    let cases = load_dir(dir)
+        .unwrap_or_else(|err| panic!("could not load corpus {}: {}", dir.display(), err));
+    assert!(!cases.is_empty(), "corpus {} is empty", dir.display());
+
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| case.check().err())
+        .map(|failure| failure.to_string())
+        .collect();
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} corpus cases failed:\n{}",
+            failures.len(),
+            cases.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_corpus, Case};
+
+    #[test]
+    fn crate_corpus() {
+        assert_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus"));
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let case = Case {
+            name: "float".to_owned(),
+            serialized: b"d:0.50;".to_vec(),
+            expected: serde_json::json!(0.5),
+        };
+        let failure = case.check().unwrap_err();
+        assert!(failure.message.starts_with("round-trip mismatch"));
+
+        let case = Case {
+            name: "int".to_owned(),
+            serialized: b"i:1;".to_vec(),
+            expected: serde_json::json!(2),
+        };
+        let failure = case.check().unwrap_err();
+        assert!(failure.to_string().starts_with("int: JSON mismatch"));
+    }
+}
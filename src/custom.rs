@@ -0,0 +1,229 @@
+//! Objects of classes implementing PHP's `Serializable` interface.
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::lex::Lookahead1;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+/// Newtype struct name recognized by the serializer and deserializer,
+/// carrying a complete `C:` record as bytes.
+pub(crate) const CUSTOM_TOKEN: &str = "$serde_php::private::Custom";
+
+/// An object serialized by its class, rather than by PHP.
+///
+/// Classes implementing the `Serializable` interface write their own data,
+/// which PHP stores along with the class name in a `C:` record:
+///
+/// ```php
+/// $queue = new SplQueue();
+/// $queue->push("job");
+/// serialize($queue); // C:8:"SplQueue":15:{i:4;:s:3:"job";}
+/// ```
+///
+/// The data is kept as is, so such objects survive a round-trip through
+/// Rust unchanged:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_php::PhpCustom;
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Worker {
+///     name: String,
+///     queue: PhpCustom,
+/// }
+///
+/// let input = br#"a:2:{s:4:"name";s:4:"mail";s:5:"queue";C:8:"SplQueue":15:{i:4;:s:3:"job";}}"#;
+/// let worker: Worker = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(worker.queue.class(), b"SplQueue");
+/// assert_eq!(worker.queue.data(), br#"i:4;:s:3:"job";"#);
+///
+/// assert_eq!(serde_php::to_vec(&worker).unwrap(), &input[..]);
+/// ```
+///
+/// Other formats see the complete record as bytes.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PhpCustom {
+    class: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl PhpCustom {
+    /// Create an object of class `class`, holding `data`.
+    pub fn new(class: impl Into<Vec<u8>>, data: impl Into<Vec<u8>>) -> Self {
+        PhpCustom {
+            class: class.into(),
+            data: data.into(),
+        }
+    }
+
+    /// Parse a complete `C:` record.
+    pub fn from_bytes(record: &[u8]) -> Result<Self> {
+        let mut lexer = Lookahead1::new(record);
+        lexer.set_input_len(record.len() as u64);
+
+        let parse = |lexer: &mut Lookahead1<&[u8]>| {
+            lexer.expect_type(b'C')?;
+            lexer.expect(b':')?;
+            let (class, data) = lexer.read_custom()?;
+            if lexer.peek()?.is_some() {
+                return Err(ErrorKind::TrailingData.into());
+            }
+            Ok(PhpCustom { class, data })
+        };
+        parse(&mut lexer).map_err(|err: Error| err.with_offset(lexer.position()))
+    }
+
+    /// Name of the class.
+    pub fn class(&self) -> &[u8] {
+        &self.class
+    }
+
+    /// Data written by the `serialize` method of the class.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the object, returning its data.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Complete `C:` record, as written by PHP.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        record(&self.class, &self.data)
+    }
+}
+
+/// Assemble a `C:` record.
+pub(crate) fn record(class: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(class.len() + data.len() + 32);
+    buf.extend_from_slice(format!("C:{}:\"", class.len()).as_bytes());
+    buf.extend_from_slice(class);
+    buf.extend_from_slice(format!("\":{}:{{", data.len()).as_bytes());
+    buf.extend_from_slice(data);
+    buf.push(b'}');
+    buf
+}
+
+impl Serialize for PhpCustom {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(CUSTOM_TOKEN, &Record(&self.to_bytes()))
+    }
+}
+
+/// A record, serialized as bytes. The PHP serializer writes it verbatim.
+struct Record<'a>(&'a [u8]);
+
+impl Serialize for Record<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for PhpCustom {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(CUSTOM_TOKEN, CustomVisitor)
+    }
+}
+
+struct CustomVisitor;
+
+impl<'de> Visitor<'de> for CustomVisitor {
+    type Value = PhpCustom;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a custom serialized object")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<PhpCustom, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<PhpCustom, E> {
+        PhpCustom::from_bytes(v).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<PhpCustom, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhpCustom;
+    use crate::error::ErrorKind;
+    use crate::{from_bytes, to_vec};
+    use serde::de::IgnoredAny;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn roundtrips_records() {
+        // PHP 7.3: serialize(new ArrayObject([1]))
+        let input = br#"C:11:"ArrayObject":29:{x:i:0;a:1:{i:0;i:1;};m:a:0:{}}"#;
+        let object: PhpCustom = from_bytes(input).unwrap();
+        assert_eq!(object.class(), b"ArrayObject");
+        assert_eq!(object.data(), br#"x:i:0;a:1:{i:0;i:1;};m:a:0:{}"#);
+        assert_eq!(object.to_bytes(), &input[..]);
+        assert_eq!(to_vec(&object).unwrap(), &input[..]);
+        assert_eq!(PhpCustom::from_bytes(input).unwrap(), object);
+
+        // Data may contain braces and quotes, only the length counts.
+        let object = PhpCustom::new("Token", &b"}\";{"[..]);
+        let record = to_vec(&vec![Some(object.clone()), None]).unwrap();
+        assert_eq!(record, &br#"a:2:{i:0;C:5:"Token":4:{}";{}i:1;N;}"#[..]);
+        assert_eq!(
+            from_bytes::<Vec<Option<PhpCustom>>>(&record).unwrap(),
+            vec![Some(object), None]
+        );
+    }
+
+    #[test]
+    fn skips_records() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Job {
+            id: u32,
+        }
+
+        let input = br#"a:2:{s:5:"queue";C:8:"SplQueue":5:{i:4;:}s:2:"id";i:3;}"#;
+        assert_eq!(from_bytes::<Job>(input).unwrap(), Job { id: 3 });
+
+        let map: BTreeMap<String, IgnoredAny> = from_bytes(input).unwrap();
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_records() {
+        let err = PhpCustom::from_bytes(br#"C:5:"Token":4:{ab}"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnexpectedEof));
+
+        let err = PhpCustom::from_bytes(br#"C:5:"Token":1:{a}i:1;"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TrailingData));
+        assert_eq!(err.offset(), Some(17));
+
+        assert!(from_bytes::<PhpCustom>(br#"O:5:"Token":0:{}"#).is_err());
+        assert!(from_bytes::<PhpCustom>(br#"C:5:"Token":3:{a}}"#).is_err());
+
+        let err = crate::PhpValue::parse(br#"C:5:"Token":0:{}"#).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnsupportedPhpType {
+                type_indicator: 'C'
+            }
+        ));
+    }
+}
@@ -1,781 +1,5413 @@
 //! PHP deserialization.
 
-use crate::error::{Error, Result};
+use crate::custom;
+use crate::error::{Error, ErrorKind, Result};
+use crate::input::Input;
+use crate::lex::{self, check_expected, parse_bytes, Lookahead1};
+use crate::metrics::{Metrics, Observer, Operation};
+use crate::options::{Limits, Options};
+use crate::raw;
+use crate::repair::{self, FixLengths, RepairStrategy};
+use crate::spl;
+use crate::version::FormatVersion;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
 use serde::de::MapAccess;
-use serde::de::{Deserialize, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use serde::de::{
+    Deserialize, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, SeqAccess,
+    VariantAccess, Visitor,
+};
 use serde::{forward_to_deserialize_any, Deserializer};
 use smallvec::SmallVec;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
+use std::fmt;
 use std::io;
-use std::io::{BufRead, Read};
+use std::io::Read;
+use std::iter;
+use std::sync::Arc;
+use std::time::Instant;
+use std::vec;
 
 /// Deserialize from byte slice.
 pub fn from_bytes<'de, T>(s: &'de [u8]) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    let buffered = io::BufReader::new(s);
-    let mut des = PhpDeserializer::new(buffered);
+    let mut des = PhpDeserializer::from_slice(s);
     let value = T::deserialize(&mut des)?;
     Ok(value)
 }
 
-/// Lookahead buffer with integrated lexer.
+/// Deserialize many independent values, one from each byte slice.
 ///
-/// Supports peeking ahead a single byte.
+/// Behaves like calling [`from_bytes`] on every slice, but reuses buffers
+/// across slices, e.g. for decoding a whole database column. Failing slices
+/// do not affect the others. See
+/// [`PhpDeserializerBuilder::deserialize_batch`] for other options and
+/// [`PhpDeserializerBuilder::deserialize_batch_parallel`] for decoding on
+/// multiple threads.
+///
+/// ```rust
+/// let rows: Vec<&[u8]> = vec![b"i:1;", b"i:x;", b"i:3;"];
+/// let values = serde_php::from_bytes_batch::<i64, _>(rows);
+/// assert_eq!(values[0].as_ref().unwrap(), &1);
+/// assert!(values[1].is_err());
+/// assert_eq!(values[2].as_ref().unwrap(), &3);
+/// ```
+pub fn from_bytes_batch<'de, T, I>(inputs: I) -> Vec<Result<T>>
+where
+    T: Deserialize<'de>,
+    I: IntoIterator<Item = &'de [u8]>,
+{
+    PhpDeserializerBuilder::default().deserialize_batch(inputs)
+}
+
+/// Deserialize from a reader.
+///
+/// Reads exactly the bytes making up a single value, leaving `reader`
+/// positioned right after it, so that whatever follows can be read from the
+/// same reader. The reader is not wrapped in a buffer and is read one byte at
+/// a time outside of string data, so a `BufReader` should be passed for
+/// unbuffered sources such as files or sockets, by reference if it is to be
+/// used afterwards. Trailing data is not checked for.
+///
+/// ```rust
+/// use std::io::Read;
+///
+/// let mut input = &b"a:2:{i:0;i:1;i:1;i:2;}\nrest"[..];
+/// let list: Vec<u8> = serde_php::from_reader(&mut input).unwrap();
+/// assert_eq!(list, vec![1, 2]);
+///
+/// let mut rest = Vec::new();
+/// input.read_to_end(&mut rest).unwrap();
+/// assert_eq!(rest, b"\nrest");
+/// ```
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut des = PhpDeserializer::from_reader(reader);
+    T::deserialize(&mut des)
+}
+
+/// Deserialize from byte slice, reporting metrics to `observer`.
+///
+/// Behaves like [`from_bytes`]. The number of bytes reported is the number of
+/// bytes consumed, which may be less than the length of `s`.
+pub fn from_bytes_observed<'de, T, O>(s: &'de [u8], observer: &O) -> Result<T>
+where
+    T: Deserialize<'de>,
+    O: Observer + ?Sized,
+{
+    let start = Instant::now();
+    let mut des = PhpDeserializer::from_slice(s);
+    let result = T::deserialize(&mut des);
+
+    if result.is_ok() {
+        // The top-level value itself.
+        des.values += 1;
+    }
+
+    observer.observe(
+        Operation::Deserialize,
+        &Metrics {
+            bytes: des.input.position(),
+            values: des.values,
+            duration: start.elapsed(),
+            failed: result.is_err(),
+        },
+    );
+
+    result
+}
+
+/// PHP deserializer.
+///
+/// Deserializes the format used by PHP's `serialize` function.
 #[derive(Debug)]
-struct Lookahead1<R> {
-    reader: R,
-    buffer: Option<u8>,
+pub struct PhpDeserializer<R> {
+    input: Lookahead1<R>,
+    /// Offset of the input within the original one, for values captured
+    /// from it.
+    base_offset: u64,
+    /// Number of array elements deserialized so far.
+    values: u64,
+    /// How keys are matched against struct fields.
+    key_matching: KeyMatching,
+    /// Whether numeric arrays may be deserialized into structs.
+    positional_structs: bool,
+    /// Alternative keys for struct fields, mapping keys onto field names.
+    aliases: Arc<HashMap<String, String>>,
+    /// Whether `null` is deserialized as the default value of non-`Option` types.
+    null_as_default: bool,
+    /// Whether `stdClass` objects are deserialized like associative arrays.
+    std_class_as_map: bool,
+    /// Registered classes, mapping class names onto enum variants.
+    classes: Arc<HashMap<String, String>>,
+    /// Whether strings that are not valid UTF-8 are decoded as Latin-1.
+    latin1_fallback: bool,
+    /// Whether strings holding serialized data are unserialized when a
+    /// collection is expected.
+    nested_unserialize: bool,
+    /// Whether string lengths of nested serialized data are repaired.
+    repair_lengths: bool,
+    /// Whether self-describing types receive strings as text or bytes.
+    byte_strings: bool,
+    /// Whether floats without fractional part are accepted as integers.
+    integral_floats: bool,
+    /// Whether numeric arrays with out-of-order indices are reordered.
+    reorder_arrays: bool,
+    /// Which map entries are deserialized, all if `None`.
+    key_filter: Option<KeyFilter>,
+    /// Keys not matching any struct field, only collected if `Some`.
+    unknown_keys: Option<Vec<UnknownKey>>,
+    /// Repeated struct fields, only collected if `Some`.
+    duplicate_fields: Option<Vec<DuplicateField>>,
+    /// Keys leading to the current value, only tracked while collecting
+    /// unknown keys, duplicate fields or skipped objects.
+    path: Vec<String>,
+    /// Handling of objects nested in arrays.
+    object_policy: ObjectPolicy,
+    /// Entries dropped due to `ObjectPolicy::Skip`.
+    skipped_objects: Vec<SkippedObject>,
+    /// Version of PHP the input is expected from.
+    format_version: FormatVersion,
+    /// Buffer for reading keys, reused across keys.
+    scratch: Vec<u8>,
+}
+
+impl<'a> PhpDeserializer<io::BufReader<&'a [u8]>> {
+    /// Create deserializer reading from a byte slice.
+    ///
+    /// Only required when driving deserialization manually, e.g. to wrap the
+    /// deserializer using `serde_ignored`. Otherwise use [`from_bytes`].
+    pub fn from_slice(s: &'a [u8]) -> Self {
+        let mut des = PhpDeserializer::new(io::BufReader::new(s));
+        // The input size is known, allowing for early sanity checks.
+        des.input.set_input_len(s.len() as u64);
+        des
+    }
+}
+
+impl<'a> PhpDeserializer<&'a [u8]> {
+    /// Create deserializer reading from a byte slice without an extra buffer.
+    pub(crate) fn from_slice_unbuffered(s: &'a [u8]) -> Self {
+        let mut des = PhpDeserializer::new(s);
+        des.input.set_input_len(s.len() as u64);
+        des
+    }
+}
+
+/// Parsing profile, bundling options into a named set of guarantees.
+///
+/// Selected using [`PhpDeserializerBuilder::profile`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Profile {
+    /// Reject anything PHP's `serialize` would not produce.
+    ///
+    /// On top of what [`Profile::Php`] rejects, integers and lengths must not
+    /// have a `+` sign or leading zeros and floats must be formatted the way
+    /// PHP formats them (e.g. `0.5`, `1.0E+25`, but not `.5` or `1e25`).
+    ///
+    /// Numbers that do not fit their target type are reported as
+    /// [`ErrorKind::OutOfRange`](crate::ErrorKind::OutOfRange), and errors
+    /// carry the path of keys leading to the offending value.
+    Strict,
+    /// Accept what PHP's `unserialize` accepts. This is the default.
+    #[default]
+    Php,
+    /// Additionally accept malformed input from third-party producers.
+    ///
+    /// Enables all options that make deserialization more forgiving, such as
+    /// [`PhpDeserializerBuilder::tolerant`], [`KeyMatching::Normalize`],
+    /// [`PhpDeserializerBuilder::positional_structs`],
+    /// [`PhpDeserializerBuilder::null_as_default`],
+    /// [`PhpDeserializerBuilder::std_class_as_map`] and
+    /// [`PhpDeserializerBuilder::tolerant_floats`].
+    Lenient,
+    /// Accept what [`Profile::Php`] accepts, with all limits enabled.
+    ///
+    /// Intended for untrusted input. Nesting is limited to 128 levels, arrays
+    /// to 65536 entries and keys to 1024 bytes, unless lower limits have been
+    /// set already (see [`PhpDeserializerBuilder::max_depth`],
+    /// [`PhpDeserializerBuilder::max_map_entries`] and
+    /// [`PhpDeserializerBuilder::max_key_length`]). Memory for strings and
+    /// arrays is allocated in bounded chunks as data arrives, never based on
+    /// declared lengths alone.
+    ///
+    /// For arbitrary input, deserializing with this profile does not panic
+    /// or overflow the stack, allocates memory proportional to the input size
+    /// and takes time proportional to the input size. This holds for types
+    /// whose `Deserialize` implementations do not recurse or allocate on their
+    /// own beyond the values they are given; it is exercised by the
+    /// `hardened` fuzz target.
+    Hardened,
+}
+
+/// Nesting depth allowed by [`Profile::Hardened`].
+pub(crate) const HARDENED_MAX_DEPTH: usize = 128;
+
+/// Number of array entries allowed by [`Profile::Hardened`].
+const HARDENED_MAX_ENTRIES: usize = 64 * 1024;
+
+/// Key length allowed by [`Profile::Hardened`].
+const HARDENED_MAX_KEY_LENGTH: usize = 1024;
+
+/// Options tuned for data written by a specific application.
+///
+/// Selected using [`PhpDeserializerBuilder::preset`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Magento 1 and OpenMage stores.
+    ///
+    /// Configuration and quote data of these stores often contains arrays
+    /// serialized into strings of other serialized arrays, Latin-1 text left
+    /// over from charset migrations and string lengths broken by such
+    /// migrations. Enables [`PhpDeserializerBuilder::latin1_fallback`],
+    /// [`PhpDeserializerBuilder::nested_unserialize`] and
+    /// [`PhpDeserializerBuilder::repair_lengths`].
+    Magento,
+    /// Drupal 7 `variable` and cache tables.
+    ///
+    /// Values stored by Drupal frequently contain `stdClass` objects, binary
+    /// strings such as compressed cache data and numbers that were turned into
+    /// floats by arithmetic, e.g. timestamps. Enables
+    /// [`PhpDeserializerBuilder::std_class_as_map`],
+    /// [`PhpDeserializerBuilder::byte_strings`] and
+    /// [`PhpDeserializerBuilder::integral_floats`].
+    Drupal,
+}
+
+/// Handling of objects (`O:`) nested in arrays.
+///
+/// Selected using [`PhpDeserializerBuilder::object_policy`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectPolicy {
+    /// Fail on objects, except for `stdClass` objects if
+    /// [`std_class_as_map`](PhpDeserializerBuilder::std_class_as_map) is
+    /// enabled. This is the default.
+    #[default]
+    Error,
+    /// Drop array entries holding an object, whatever its class, recording
+    /// them as [`SkippedObject`]s. Objects outside of arrays still fail.
+    Skip,
+    /// Deserialize objects of any class like associative arrays of their
+    /// properties. The class name is discarded.
+    AsMap,
+}
+
+/// Handling of references (`R:` and `r:`) to earlier values.
+///
+/// PHP writes a reference whenever a value occurs more than once, e.g. for
+/// objects stored in several places or for PHP references (`&$value`).
+/// Selected using [`PhpDeserializerBuilder::references`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferencePolicy {
+    /// Deserialize a copy of the referenced value in place of the reference.
+    /// This is the default.
+    ///
+    /// Finding referenced values requires keeping a copy of the input read
+    /// so far, which doubles memory use when deserializing from a reader.
+    #[default]
+    Resolve,
+    /// Fail on references. Input is not retained, so memory use stays
+    /// constant however large the input.
+    Error,
+}
+
+/// Array entry holding an object, dropped due to [`ObjectPolicy::Skip`].
+///
+/// See [`PhpDeserializer::skipped_objects`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkippedObject {
+    path: String,
+    offset: u64,
+    class: String,
 }
 
-impl<R: Read> Lookahead1<R> {
-    fn new(reader: R) -> Self {
-        Lookahead1 {
-            reader,
-            buffer: None,
+impl SkippedObject {
+    /// Path to the entry, with keys separated by `.`, as in
+    /// [`Error::path`](crate::Error::path).
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Byte offset of the object in the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Class name of the object.
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+}
+
+impl fmt::Display for SkippedObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} object at {} (offset {})",
+            self.class, self.path, self.offset
+        )
+    }
+}
+
+/// How array keys are matched against struct field names.
+///
+/// Selected using [`PhpDeserializerBuilder::key_matching`]. Only applies to
+/// structs; keys of maps are always passed on as-is.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyMatching {
+    /// Keys must be equal to field names. This is the default.
+    #[default]
+    Exact,
+    /// Keys may differ from field names in ASCII case, e.g. `UserID` matches
+    /// `userid`.
+    IgnoreCase,
+    /// Keys may additionally differ in the use of `_` and `-`, so that
+    /// `userId`, `UserID` and `user-id` all match `user_id`.
+    Normalize,
+}
+
+impl KeyMatching {
+    /// Check whether `key` matches `field`.
+    fn matches(self, field: &str, key: &str) -> bool {
+        match self {
+            KeyMatching::Exact => field == key,
+            KeyMatching::IgnoreCase => field.eq_ignore_ascii_case(key),
+            KeyMatching::Normalize => {
+                let normalize = |s: &'_ str| {
+                    s.bytes()
+                        .filter(|c| !matches!(c, b'_' | b'-'))
+                        .map(|c| c.to_ascii_lowercase())
+                        .collect::<Vec<u8>>()
+                };
+                normalize(field) == normalize(key)
+            }
         }
     }
+}
 
-    /// Fill `buffer` with the next byte if there is one.
+/// Predicate selecting the entries of maps to deserialize.
+///
+/// Set using [`PhpDeserializerBuilder::key_filter`]. Keys are passed to the
+/// predicate as raw bytes; integer keys in their decimal representation.
+///
+/// ```rust
+/// use serde_php::KeyFilter;
+///
+/// let filter = KeyFilter::new(|key: &[u8]| key.ends_with(b"_id"));
+/// assert!(filter.matches(b"user_id"));
+/// assert!(!filter.matches(b"name"));
+/// ```
+#[derive(Clone)]
+pub struct KeyFilter(Arc<KeyPredicate>);
+
+/// Predicate wrapped by [`KeyFilter`].
+type KeyPredicate = dyn Fn(&[u8]) -> bool + Send + Sync;
+
+impl KeyFilter {
+    /// Keep entries for which `predicate` returns `true`.
     ///
-    /// Has no effect if `buffer` is already full.
-    fn fill(&mut self) -> Result<()> {
-        if self.buffer.is_none() {
-            self.buffer = {
-                let mut buf: [u8; 1] = [0];
-                let length = self.reader.read(&mut buf).map_err(Error::ReadSerialized)?;
+    /// Any matcher can be used, e.g. a `regex::bytes::Regex` by wrapping its
+    /// `is_match` method.
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        KeyFilter(Arc::new(predicate))
+    }
 
-                if length == 0 {
-                    None
-                } else {
-                    Some(buf[0])
-                }
-            };
+    /// Keep entries whose key starts with `prefix`.
+    pub fn prefix(prefix: impl Into<Vec<u8>>) -> Self {
+        let prefix = prefix.into();
+        KeyFilter::new(move |key| key.starts_with(&prefix))
+    }
+
+    /// Check whether the entry with `key` is kept.
+    pub fn matches(&self, key: &[u8]) -> bool {
+        (self.0)(key)
+    }
+}
+
+impl fmt::Debug for KeyFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyFilter(..)")
+    }
+}
+
+impl From<Options> for PhpDeserializerBuilder {
+    fn from(options: Options) -> Self {
+        PhpDeserializerBuilder {
+            tolerant: options.tolerant,
+            strict: options.strict,
+            tolerant_floats: options.tolerant_floats,
+            reject_floats: options.reject_floats,
+            key_matching: options.key_matching,
+            positional_structs: options.positional_structs,
+            aliases: Arc::new(options.aliases.into_iter().collect()),
+            null_as_default: options.null_as_default,
+            std_class_as_map: options.std_class_as_map,
+            classes: Arc::new(options.classes.into_iter().collect()),
+            latin1_fallback: options.latin1_fallback,
+            nested_unserialize: options.nested_unserialize,
+            repair_lengths: options.repair_lengths,
+            byte_strings: options.byte_strings,
+            integral_floats: options.integral_floats,
+            reorder_arrays: options.reorder_arrays,
+            object_policy: options.object_policy,
+            references: options.references,
+            format_version: options.format_version,
+            max_map_entries: options.limits.max_map_entries,
+            max_key_length: options.limits.max_key_length,
+            max_depth: options.limits.max_depth,
+            ..PhpDeserializerBuilder::default()
         }
+    }
+}
 
-        Ok(())
+/// Key of the input that does not match any field of the struct it was
+/// found in.
+///
+/// Returned by [`PhpDeserializerBuilder::deserialize_with_unknown_keys`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnknownKey {
+    path: String,
+    offset: u64,
+}
+
+impl UnknownKey {
+    /// Path to the key, with keys separated by `.`, as in
+    /// [`Error::path`](crate::Error::path).
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Byte offset of the key in the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.path, self.offset)
+    }
+}
+
+/// Key of the input repeating a struct field that occurred before in the
+/// same array.
+///
+/// Returned by [`PhpDeserializerBuilder::deserialize_with_duplicate_fields`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateField {
+    path: String,
+    offset: u64,
+    used_offset: u64,
+}
+
+impl DuplicateField {
+    /// Path to the key, with keys separated by `.`, as in
+    /// [`Error::path`](crate::Error::path).
+    pub fn path(&self) -> &str {
+        &self.path
     }
 
-    /// Peek at the next byte, without removing it. Returns `None` on EOF.
-    fn peek(&mut self) -> Result<Option<u8>> {
-        self.fill()?;
-        Ok(self.buffer)
+    /// Byte offset of the skipped key in the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
     }
 
-    /// Reed a single byte, returning an error on EOF.
-    fn read1(&mut self) -> Result<u8> {
-        self.fill()?;
+    /// Byte offset of the key whose value was used instead.
+    pub fn used_offset(&self) -> u64 {
+        self.used_offset
+    }
+}
 
-        self.buffer.take().ok_or(Error::UnexpectedEof)
+impl fmt::Display for DuplicateField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at offset {} (using offset {})",
+            self.path, self.offset, self.used_offset
+        )
     }
+}
 
-    /// Expect a specific character.
-    fn expect(&mut self, expected: u8) -> Result<()> {
-        let actual = self.read1()?;
-        if actual == expected {
-            Ok(())
-        } else {
-            Err(Error::Unexpected {
-                expected: char::from(expected),
-                actual: char::from(actual),
-            })
+/// Builder for deserializers with non-default options.
+///
+/// ```rust
+/// use serde_php::PhpDeserializerBuilder;
+///
+/// let builder = PhpDeserializerBuilder::new().tolerant(true);
+/// let value: Vec<i64> = builder.deserialize(b"A: 1:{I: 0;I: 5;}").unwrap();
+/// assert_eq!(value, vec![5]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PhpDeserializerBuilder {
+    tolerant: bool,
+    strict: bool,
+    key_matching: KeyMatching,
+    positional_structs: bool,
+    aliases: Arc<HashMap<String, String>>,
+    null_as_default: bool,
+    std_class_as_map: bool,
+    classes: Arc<HashMap<String, String>>,
+    latin1_fallback: bool,
+    nested_unserialize: bool,
+    repair_lengths: bool,
+    byte_strings: bool,
+    integral_floats: bool,
+    reorder_arrays: bool,
+    tolerant_floats: bool,
+    reject_floats: bool,
+    max_map_entries: Option<usize>,
+    max_key_length: Option<usize>,
+    max_depth: Option<usize>,
+    key_filter: Option<KeyFilter>,
+    object_policy: ObjectPolicy,
+    references: ReferencePolicy,
+    format_version: FormatVersion,
+    repair_strategies: Vec<Arc<dyn RepairStrategy>>,
+}
+
+impl PhpDeserializerBuilder {
+    /// Create a builder with default options, matching [`from_bytes`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the options governed by a profile.
+    ///
+    /// A profile decides how closely input has to follow PHP's output: token
+    /// tolerance, strictness, key matching, positional structs, `null` as
+    /// default, `stdClass` as map and tolerant floats. [`Profile::Hardened`]
+    /// also lowers the limits. All other options, such as classes or
+    /// rejected floats, are kept, and individual options can still be
+    /// changed afterwards.
+    ///
+    /// ```rust
+    /// use serde_php::{PhpDeserializerBuilder, Profile};
+    ///
+    /// let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+    /// assert!(strict.deserialize::<i64>(b"i:+5;").is_err());
+    /// assert_eq!(serde_php::from_bytes::<i64>(b"i:+5;").unwrap(), 5);
+    /// ```
+    pub fn profile(self, profile: Profile) -> Self {
+        let lenient = profile == Profile::Lenient;
+        let limit = |current: Option<usize>, hardened: usize| {
+            if profile == Profile::Hardened {
+                Some(current.map_or(hardened, |current| current.min(hardened)))
+            } else {
+                current
+            }
+        };
+
+        PhpDeserializerBuilder {
+            max_map_entries: limit(self.max_map_entries, HARDENED_MAX_ENTRIES),
+            max_key_length: limit(self.max_key_length, HARDENED_MAX_KEY_LENGTH),
+            max_depth: limit(self.max_depth, HARDENED_MAX_DEPTH),
+            tolerant: lenient,
+            strict: profile == Profile::Strict,
+            key_matching: if lenient {
+                KeyMatching::Normalize
+            } else {
+                KeyMatching::Exact
+            },
+            positional_structs: lenient,
+            null_as_default: lenient,
+            std_class_as_map: lenient,
+            tolerant_floats: lenient,
+            ..self
         }
     }
 
-    /// Reads an unsigned integer, fails on EOF and non-digit, but stops on
-    /// the first invalid character after at least one digit has been read.
-    fn collect_unsigned(&mut self, buf: &mut SmallVec<[u8; 32]>) -> Result<()> {
-        // Read the first character and ensure it is a digit.
-        let c = self.read1()?;
-        if !c.is_ascii_digit() {
-            return Err(Error::ExpectedDigit {
-                actual: char::from(c),
-            });
+    /// Snapshot of the options, see [`Options`].
+    pub fn options(&self) -> Options {
+        Options {
+            tolerant: self.tolerant,
+            strict: self.strict,
+            tolerant_floats: self.tolerant_floats,
+            reject_floats: self.reject_floats,
+            key_matching: self.key_matching,
+            positional_structs: self.positional_structs,
+            aliases: self
+                .aliases
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            null_as_default: self.null_as_default,
+            std_class_as_map: self.std_class_as_map,
+            classes: self
+                .classes
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            latin1_fallback: self.latin1_fallback,
+            nested_unserialize: self.nested_unserialize,
+            repair_lengths: self.repair_lengths,
+            byte_strings: self.byte_strings,
+            integral_floats: self.integral_floats,
+            reorder_arrays: self.reorder_arrays,
+            object_policy: self.object_policy,
+            references: self.references,
+            format_version: self.format_version,
+            limits: Limits {
+                max_map_entries: self.max_map_entries,
+                max_key_length: self.max_key_length,
+                max_depth: self.max_depth,
+            },
         }
-        buf.push(c);
+    }
 
-        // Keep reading digits until we hit EOF or a non-digit.
-        while let Some(c) = self.peek()? {
-            if !c.is_ascii_digit() {
-                break;
-            }
-            self.expect(c)?;
-            buf.push(c);
+    /// Enable the options of a preset.
+    ///
+    /// Unlike [`profile`](PhpDeserializerBuilder::profile), other options are
+    /// left as they are.
+    ///
+    /// ```rust
+    /// use serde_php::{PhpDeserializerBuilder, Preset};
+    /// use std::collections::HashMap;
+    ///
+    /// // Latin-1 text inside a nested serialized array.
+    /// let input = b"a:1:{s:7:\"options\";s:28:\"a:1:{s:4:\"city\";s:4:\"K\xf6ln\";}\";}";
+    ///
+    /// let builder = PhpDeserializerBuilder::new().preset(Preset::Magento);
+    /// let config: HashMap<String, HashMap<String, String>> = builder.deserialize(input).unwrap();
+    /// assert_eq!(config["options"]["city"], "K\u{f6}ln");
+    /// ```
+    pub fn preset(self, preset: Preset) -> Self {
+        match preset {
+            Preset::Magento => self
+                .latin1_fallback(true)
+                .nested_unserialize(true)
+                .repair_lengths(true),
+            Preset::Drupal => self
+                .std_class_as_map(true)
+                .byte_strings(true)
+                .integral_floats(true),
         }
+    }
+
+    /// Accept common deviations from the format.
+    ///
+    /// Some producers other than PHP emit slightly off tokens, such as
+    /// uppercase type indicators (`B:1;`, `I:42;`) or spaces after colons
+    /// (`i: 42;`). When enabled, these are accepted and interpreted the same
+    /// way as their regular counterparts. Disabled by default.
+    pub fn tolerant(mut self, enable: bool) -> Self {
+        self.tolerant = enable;
+        self
+    }
+
+    /// Accept floats written with a decimal comma.
+    ///
+    /// Some old PHP versions and extensions formatted serialized floats
+    /// according to the current locale, so setups running with e.g. a German
+    /// locale wrote `d:0,5;`. When enabled, a comma is accepted in place of the decimal
+    /// dot. Mantissas with more digits than a `f64` can hold, as written with
+    /// a high `serialize_precision`, are always accepted and rounded to the
+    /// nearest `f64`. Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// let builder = PhpDeserializerBuilder::new().tolerant_floats(true);
+    /// assert_eq!(builder.deserialize::<f64>(b"d:0,5;").unwrap(), 0.5);
+    /// assert_eq!(builder.deserialize::<f64>(b"d:0.10000000000000001;").unwrap(), 0.1);
+    /// ```
+    pub fn tolerant_floats(mut self, enable: bool) -> Self {
+        self.tolerant_floats = enable;
+        self
+    }
+
+    /// Fail on floats (`d:`), wherever they occur.
+    ///
+    /// For data that must not hold any floats, e.g. monetary amounts kept as
+    /// integer cents. Floats are rejected even where they would be ignored,
+    /// such as in unknown fields, and the error carries their offset, as
+    /// well as their path when deserializing with [`Profile::Strict`].
+    /// Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::{ErrorKind, PhpDeserializerBuilder};
+    ///
+    /// let builder = PhpDeserializerBuilder::new().reject_floats(true);
+    /// let err = builder.deserialize::<Vec<i64>>(b"a:2:{i:0;i:5;i:1;d:0.5;}").unwrap_err();
+    /// assert!(matches!(err.kind(), ErrorKind::FloatRejected));
+    /// assert_eq!(err.offset(), Some(17));
+    /// ```
+    ///
+    /// See [`PhpSerializerBuilder::reject_floats`](crate::PhpSerializerBuilder::reject_floats)
+    /// for the serializer counterpart.
+    pub fn reject_floats(mut self, enable: bool) -> Self {
+        self.reject_floats = enable;
+        self
+    }
+
+    /// Limit the number of entries of each array or object.
+    ///
+    /// Arrays declaring more entries are rejected with
+    /// [`ErrorKind::TooManyEntries`](crate::ErrorKind::TooManyEntries) as soon
+    /// as their header has been read, before any entries are processed. This
+    /// applies to all arrays, whether they are deserialized as maps,
+    /// sequences or structs, or skipped. Unlimited by default.
+    ///
+    /// Payloads with millions of tiny keys are cheap to send but expensive to
+    /// decode, so a limit should be set when decoding untrusted data, such as
+    /// session payloads.
+    ///
+    /// ```rust
+    /// use serde_php::{ErrorKind, PhpDeserializerBuilder};
+    /// use std::collections::HashMap;
+    ///
+    /// let builder = PhpDeserializerBuilder::new().max_map_entries(1);
+    /// let err = builder
+    ///     .deserialize::<HashMap<String, i64>>(br#"a:2:{s:1:"a";i:1;s:1:"b";i:2;}"#)
+    ///     .unwrap_err();
+    /// assert!(matches!(err.kind(), ErrorKind::TooManyEntries { declared: 2, limit: 1 }));
+    /// ```
+    pub fn max_map_entries(mut self, limit: usize) -> Self {
+        self.max_map_entries = Some(limit);
+        self
+    }
+
+    /// Limit the length of string keys of arrays and objects, in bytes.
+    ///
+    /// Longer keys are rejected with
+    /// [`ErrorKind::KeyTooLong`](crate::ErrorKind::KeyTooLong) before their
+    /// data is read. String values are not affected. Unlimited by default.
+    pub fn max_key_length(mut self, limit: usize) -> Self {
+        self.max_key_length = Some(limit);
+        self
+    }
+
+    /// Limit the number of arrays and objects nested inside each other.
+    ///
+    /// Deeply nested input is rejected with
+    /// [`ErrorKind::DepthLimitExceeded`](crate::ErrorKind::DepthLimitExceeded)
+    /// instead of overflowing the stack. A top-level array has a depth of 1.
+    /// Nested serialized data (see
+    /// [`nested_unserialize`](PhpDeserializerBuilder::nested_unserialize))
+    /// counts towards the depth of the array containing it. Unlimited by
+    /// default.
+    ///
+    /// ```rust
+    /// use serde_php::{ErrorKind, PhpDeserializerBuilder};
+    ///
+    /// let builder = PhpDeserializerBuilder::new().max_depth(1);
+    /// assert!(builder.deserialize::<Vec<u8>>(b"a:1:{i:0;i:1;}").is_ok());
+    /// let err = builder
+    ///     .deserialize::<Vec<Vec<u8>>>(b"a:1:{i:0;a:0:{}}")
+    ///     .unwrap_err();
+    /// assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(1)));
+    /// ```
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.max_depth = Some(limit);
+        self
+    }
+
+    /// Set how array keys are matched against struct field names.
+    ///
+    /// Useful when data written by different versions of an application only
+    /// differs in the casing or style of its keys.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::{KeyMatching, PhpDeserializerBuilder};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct User {
+    ///     user_id: u32,
+    /// }
+    ///
+    /// let builder = PhpDeserializerBuilder::new().key_matching(KeyMatching::Normalize);
+    /// let user: User = builder.deserialize(br#"a:1:{s:6:"UserID";i:7;}"#).unwrap();
+    /// assert_eq!(user.user_id, 7);
+    /// ```
+    pub fn key_matching(mut self, key_matching: KeyMatching) -> Self {
+        self.key_matching = key_matching;
+        self
+    }
+
+    /// Allow structs to be deserialized from numeric arrays.
+    ///
+    /// Elements are assigned to struct fields in declaration order, based on
+    /// their index. Elements with an index beyond the last field are treated
+    /// as unknown fields. Intended for data that changed from lists to
+    /// associative arrays over time. Disabled by default.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// let builder = PhpDeserializerBuilder::new().positional_structs(true);
+    /// let point: Point = builder.deserialize(b"a:2:{i:0;i:3;i:1;i:4;}").unwrap();
+    /// assert_eq!(point, Point { x: 3, y: 4 });
+    /// ```
+    pub fn positional_structs(mut self, enable: bool) -> Self {
+        self.positional_structs = enable;
+        self
+    }
+
+    /// Add aliases for struct fields, mapping keys found in the input onto
+    /// field names.
+    ///
+    /// Works like `#[serde(alias = "...")]`, but is configured at runtime.
+    /// An alias only applies to structs that have a field of the given name
+    /// and is ignored if the key matches a field itself.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::PhpDeserializerBuilder;
+    /// use std::collections::HashMap;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Post {
+    ///     title: String,
+    /// }
+    ///
+    /// let mut aliases = HashMap::new();
+    /// aliases.insert("post_title", "title");
+    ///
+    /// let builder = PhpDeserializerBuilder::new().aliases(aliases);
+    /// let post: Post = builder.deserialize(br#"a:1:{s:10:"post_title";s:2:"Hi";}"#).unwrap();
+    /// assert_eq!(post.title, "Hi");
+    /// ```
+    pub fn aliases<I, K, F>(mut self, aliases: I) -> Self
+    where
+        I: IntoIterator<Item = (K, F)>,
+        K: Into<String>,
+        F: Into<String>,
+    {
+        Arc::make_mut(&mut self.aliases).extend(
+            aliases
+                .into_iter()
+                .map(|(key, field)| (key.into(), field.into())),
+        );
+        self
+    }
+
+    /// Deserialize `null` as the default value for types other than `Option`.
+    ///
+    /// Applies to booleans, numbers, strings, sequences, maps and structs,
+    /// which are deserialized as `false`, zero, empty and empty respectively.
+    /// Structs only deserialize successfully from `null` if all of their
+    /// fields have defaults. To apply this to individual fields instead, see
+    /// [`helpers::null_as_default`](crate::helpers::null_as_default).
+    /// Disabled by default.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Author {
+    ///     bio: String,
+    ///     age: u32,
+    /// }
+    ///
+    /// let builder = PhpDeserializerBuilder::new().null_as_default(true);
+    /// let input = br#"a:2:{s:3:"bio";N;s:3:"age";N;}"#;
+    /// let author: Author = builder.deserialize(input).unwrap();
+    /// assert_eq!(author.bio, "");
+    /// assert_eq!(author.age, 0);
+    /// ```
+    pub fn null_as_default(mut self, enable: bool) -> Self {
+        self.null_as_default = enable;
+        self
+    }
+
+    /// Deserialize `stdClass` objects like associative arrays.
+    ///
+    /// Payloads created by serializing the result of `json_decode()` contain
+    /// `stdClass` objects instead of arrays. When enabled, these can be
+    /// deserialized into maps and structs, as well as skipped. Objects of any
+    /// other class are still rejected, see
+    /// [`object_policy`](PhpDeserializerBuilder::object_policy) for
+    /// alternatives. Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::PhpDeserializerBuilder;
+    /// use std::collections::HashMap;
+    ///
+    /// let builder = PhpDeserializerBuilder::new().std_class_as_map(true);
+    /// let input = br#"O:8:"stdClass":1:{s:4:"name";s:3:"Bob";}"#;
+    /// let map: HashMap<String, String> = builder.deserialize(input).unwrap();
+    /// assert_eq!(map["name"], "Bob");
+    /// ```
+    pub fn std_class_as_map(mut self, enable: bool) -> Self {
+        self.std_class_as_map = enable;
+        self
+    }
+
+    /// Register a class, deserializing its objects as `variant` of enums.
+    ///
+    /// Fields holding objects of several classes, e.g. subclasses of a
+    /// common base class, can be modeled as an enum with a variant per
+    /// class. An object deserialized into an enum selects the variant
+    /// registered for its class, which receives the properties like an
+    /// associative array. Objects of registered classes are accepted in
+    /// place of arrays everywhere else, e.g. for struct fields of a specific
+    /// class. Objects of other classes are rejected with
+    /// [`ErrorKind::ClassNotAllowed`] when deserialized into an enum.
+    /// Registering a class again replaces its variant.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// enum Payment {
+    ///     Card { last4: String },
+    ///     Transfer { iban: String },
+    /// }
+    ///
+    /// let builder = PhpDeserializerBuilder::new()
+    ///     .register_class(r"App\Payment\Card", "Card")
+    ///     .register_class(r"App\Payment\Transfer", "Transfer");
+    ///
+    /// let input = br#"a:2:{i:0;O:16:"App\Payment\Card":1:{s:5:"last4";s:4:"4242";}i:1;O:20:"App\Payment\Transfer":1:{s:4:"iban";s:4:"DE89";}}"#;
+    /// let payments: Vec<Payment> = builder.deserialize(input).unwrap();
+    /// assert_eq!(
+    ///     payments,
+    ///     [
+    ///         Payment::Card { last4: "4242".to_owned() },
+    ///         Payment::Transfer { iban: "DE89".to_owned() },
+    ///     ]
+    /// );
+    /// ```
+    ///
+    /// [`ErrorKind::ClassNotAllowed`]: crate::ErrorKind::ClassNotAllowed
+    pub fn register_class<C, V>(mut self, class: C, variant: V) -> Self
+    where
+        C: Into<String>,
+        V: Into<String>,
+    {
+        Arc::make_mut(&mut self.classes).insert(class.into(), variant.into());
+        self
+    }
+
+    /// Set how objects nested in arrays are handled.
+    ///
+    /// By default, a single object anywhere in the input fails the whole
+    /// payload. Skipping objects keeps mostly-array payloads usable; the
+    /// dropped entries are available from
+    /// [`PhpDeserializer::skipped_objects`] afterwards:
+    ///
+    /// ```rust
+    /// use serde_php::{ObjectPolicy, PhpDeserializerBuilder};
+    /// use serde::Deserialize;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let builder = PhpDeserializerBuilder::new().object_policy(ObjectPolicy::Skip);
+    /// let input = br#"a:2:{s:1:"a";i:1;s:1:"b";O:4:"Cart":1:{s:5:"items";i:2;}}"#;
+    ///
+    /// let mut des = builder.build(input);
+    /// let map = BTreeMap::<String, i64>::deserialize(&mut des).unwrap();
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(des.skipped_objects()[0].to_string(), "Cart object at b (offset 25)");
+    /// ```
+    pub fn object_policy(mut self, policy: ObjectPolicy) -> Self {
+        self.object_policy = policy;
+        self
+    }
+
+    /// Set how references (`R:` and `r:`) to earlier values are handled.
+    ///
+    /// PHP numbers the values it serializes, except for array keys, and
+    /// refers to repeated values by number. By default, such references are
+    /// resolved to a copy of the referenced value:
+    ///
+    /// ```rust
+    /// use serde_php::{PhpDeserializerBuilder, ReferencePolicy};
+    ///
+    /// // $tags = ["php"]; serialize([&$tags, &$tags]);
+    /// let input = br#"a:2:{i:0;a:1:{i:0;s:3:"php";}i:1;R:2;}"#;
+    /// let lists: Vec<Vec<String>> = serde_php::from_bytes(input).unwrap();
+    /// assert_eq!(lists[0], lists[1]);
+    ///
+    /// let builder = PhpDeserializerBuilder::new().references(ReferencePolicy::Error);
+    /// assert!(builder.deserialize::<Vec<Vec<String>>>(input).is_err());
+    /// ```
+    ///
+    /// The total size of all copies is limited to 16 times the size of the
+    /// input, but at least 1 MiB, as nested references can make it grow
+    /// exponentially.
+    pub fn references(mut self, policy: ReferencePolicy) -> Self {
+        self.references = policy;
+        self
+    }
+
+    /// Accept the format written by the given version of PHP.
+    ///
+    /// Enum cases (`E:`) are only accepted for [`FormatVersion::Php81`] and
+    /// later. They deserialize into unit variants named like the case, or
+    /// as a string of the form `Enum:Case` into self-describing types:
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::{FormatVersion, PhpDeserializerBuilder};
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// enum Suit {
+    ///     Hearts,
+    ///     Spades,
+    /// }
+    ///
+    /// let input = br#"E:11:"Suit:Hearts";"#;
+    /// assert!(serde_php::from_bytes::<Suit>(input).is_err());
+    ///
+    /// let php81 = PhpDeserializerBuilder::new().format_version(FormatVersion::Php81);
+    /// assert_eq!(php81.deserialize::<Suit>(input).unwrap(), Suit::Hearts);
+    /// ```
+    ///
+    /// Defaults to [`FormatVersion::Php74`].
+    pub fn format_version(mut self, version: FormatVersion) -> Self {
+        self.format_version = version;
+        self
+    }
+
+    /// Decode strings that are not valid UTF-8 as Latin-1 (ISO-8859-1).
+    ///
+    /// Applies to strings deserialized into `String`s and to array keys.
+    /// Since any byte sequence is valid Latin-1, such strings no longer cause
+    /// errors. Disabled by default.
+    pub fn latin1_fallback(mut self, enable: bool) -> Self {
+        self.latin1_fallback = enable;
+        self
+    }
+
+    /// Unserialize strings holding serialized data where a collection is
+    /// expected.
+    ///
+    /// Some applications serialize arrays and then store the result as a
+    /// string inside another serialized array. When enabled, such strings are
+    /// deserialized like the array they contain whenever a sequence, map or
+    /// struct is expected. Disabled by default.
+    pub fn nested_unserialize(mut self, enable: bool) -> Self {
+        self.nested_unserialize = enable;
+        self
+    }
+
+    /// Repair string length prefixes that do not match the string data.
+    ///
+    /// If deserialization fails, it is retried once after fixing string
+    /// lengths using [`repair::fix_string_lengths`](crate::repair::fix_string_lengths).
+    /// Nested serialized data (see
+    /// [`nested_unserialize`](PhpDeserializerBuilder::nested_unserialize)) is
+    /// repaired before deserializing. Only applies to
+    /// [`deserialize`](PhpDeserializerBuilder::deserialize), not to
+    /// deserializers returned by [`build`](PhpDeserializerBuilder::build).
+    /// Disabled by default.
+    pub fn repair_lengths(mut self, enable: bool) -> Self {
+        self.repair_lengths = enable;
+        self
+    }
+
+    /// Add a strategy for repairing input that fails to deserialize.
+    ///
+    /// Strategies are tried in the order they were added, after fixing
+    /// string lengths if [`repair_lengths`](PhpDeserializerBuilder::repair_lengths)
+    /// is enabled, see the [`repair`](crate::repair) module. Like repairing
+    /// lengths, this only applies to
+    /// [`deserialize`](PhpDeserializerBuilder::deserialize).
+    pub fn repair_strategy<S>(mut self, strategy: S) -> Self
+    where
+        S: RepairStrategy + 'static,
+    {
+        self.repair_strategies.push(Arc::new(strategy));
+        self
+    }
+
+    /// Pass strings to self-describing types as text or bytes.
+    ///
+    /// Types that do not know what to expect, such as untagged enums, receive
+    /// strings that are valid UTF-8 as text, and all others as sequences of
+    /// bytes by default. When enabled, the latter are passed as byte buffers
+    /// instead, so that binary data can be told apart from text. Strings are
+    /// still accepted where a sequence such as `Vec<u8>` is expected.
+    /// Disabled by default.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_bytes::ByteBuf;
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// #[serde(untagged)]
+    /// enum Data {
+    ///     Text(String),
+    ///     Binary(ByteBuf),
+    /// }
+    ///
+    /// let builder = PhpDeserializerBuilder::new().byte_strings(true);
+    /// let text: Data = builder.deserialize(br#"s:2:"hi";"#).unwrap();
+    /// assert_eq!(text, Data::Text("hi".to_owned()));
+    /// let binary: Data = builder.deserialize(b"s:2:\"\x1f\x8b\";").unwrap();
+    /// assert_eq!(binary, Data::Binary(ByteBuf::from(vec![0x1f, 0x8b])));
+    /// ```
+    pub fn byte_strings(mut self, enable: bool) -> Self {
+        self.byte_strings = enable;
+        self
+    }
+
+    /// Accept floats without a fractional part where integers are expected.
+    ///
+    /// PHP silently turns integers into floats in arithmetic such as
+    /// division, so values like `d:1700000000;` end up where an integer was
+    /// meant. When enabled, such floats are converted, as long as they fit
+    /// the target type. Floats with a fractional part are still rejected.
+    /// Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// let builder = PhpDeserializerBuilder::new().integral_floats(true);
+    /// assert_eq!(builder.deserialize::<u32>(b"d:3600;").unwrap(), 3600);
+    /// assert!(builder.deserialize::<u32>(b"d:0.5;").is_err());
+    /// ```
+    pub fn integral_floats(mut self, enable: bool) -> Self {
+        self.integral_floats = enable;
+        self
+    }
+
+    /// Deserialize numeric arrays with out-of-order indices into sequences.
+    ///
+    /// PHP keeps array entries in insertion order, so arrays filled by index
+    /// are often written with shuffled keys. Such arrays are normally
+    /// rejected with an [`IndexMismatch`](crate::ErrorKind::IndexMismatch)
+    /// error. When enabled, the remaining entries of an array are buffered
+    /// once an unexpected index is found and handed out sorted by index.
+    /// Holes are closed, as with
+    /// [`deserialize_unordered_array`](crate::deserialize_unordered_array).
+    /// Arrays written in order are not buffered. Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// let input = br#"a:3:{i:2;s:1:"c";i:0;s:1:"a";i:1;s:1:"b";}"#;
+    /// assert!(serde_php::from_bytes::<Vec<String>>(input).is_err());
+    ///
+    /// let builder = PhpDeserializerBuilder::new().reorder_arrays(true);
+    /// assert_eq!(builder.deserialize::<Vec<String>>(input).unwrap(), ["a", "b", "c"]);
+    /// ```
+    pub fn reorder_arrays(mut self, enable: bool) -> Self {
+        self.reorder_arrays = enable;
+        self
+    }
+
+    /// Only deserialize map entries whose key matches `filter`.
+    ///
+    /// Other entries are skipped without building up their values, which
+    /// makes picking a few entries out of a large array cheap. Applies to all
+    /// maps, including nested ones, but not to structs. Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::{KeyFilter, PhpDeserializerBuilder};
+    /// use std::collections::HashMap;
+    ///
+    /// let builder = PhpDeserializerBuilder::new().key_filter(KeyFilter::prefix("wp_user_"));
+    /// let input = br#"a:3:{s:11:"wp_user_ids";s:1:"1";s:7:"siteurl";s:1:"x";s:12:"wp_user_role";s:5:"admin";}"#;
+    /// let options: HashMap<String, String> = builder.deserialize(input).unwrap();
+    ///
+    /// assert_eq!(options.len(), 2);
+    /// assert_eq!(options["wp_user_role"], "admin");
+    /// ```
+    pub fn key_filter(mut self, filter: KeyFilter) -> Self {
+        self.key_filter = Some(filter);
+        self
+    }
+
+    /// Create a deserializer reading from a byte slice.
+    pub fn build<'a>(&self, s: &'a [u8]) -> PhpDeserializer<io::BufReader<&'a [u8]>> {
+        let mut des = PhpDeserializer::from_slice(s);
+        self.configure(&mut des);
+        des
+    }
+
+    /// Create a deserializer reading directly from `reader`.
+    ///
+    /// See [`PhpDeserializer::from_reader`]. String lengths are not repaired,
+    /// regardless of [`repair_lengths`](PhpDeserializerBuilder::repair_lengths).
+    pub fn build_reader<R: Read>(&self, reader: R) -> PhpDeserializer<R> {
+        let mut des = PhpDeserializer::from_reader(reader);
+        self.configure(&mut des);
+        des
+    }
+
+    /// Apply the options to a deserializer.
+    pub(crate) fn configure<R: Input>(&self, des: &mut PhpDeserializer<R>) {
+        des.input.set_tolerant(self.tolerant);
+        des.input.set_strict(self.strict);
+        des.input.set_tolerant_floats(self.tolerant_floats);
+        des.input.set_reject_floats(self.reject_floats);
+        des.input
+            .set_map_limits(self.max_map_entries, self.max_key_length);
+        des.input.set_max_depth(self.max_depth);
+        des.input.set_references(self.references);
+        des.key_matching = self.key_matching;
+        des.positional_structs = self.positional_structs;
+        des.aliases = self.aliases.clone();
+        des.null_as_default = self.null_as_default;
+        des.std_class_as_map = self.std_class_as_map;
+        des.classes = self.classes.clone();
+        des.latin1_fallback = self.latin1_fallback;
+        des.nested_unserialize = self.nested_unserialize;
+        des.repair_lengths = self.repair_lengths;
+        des.byte_strings = self.byte_strings;
+        des.integral_floats = self.integral_floats;
+        des.reorder_arrays = self.reorder_arrays;
+        des.key_filter = self.key_filter.clone();
+        des.object_policy = self.object_policy;
+        des.format_version = self.format_version;
+    }
+
+    /// Deserialize from byte slice, like [`from_bytes`].
+    pub fn deserialize<'de, T>(&self, s: &'de [u8]) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        T::deserialize(&mut self.build(s)).or_else(|err| self.retry_repaired(s, err))
+    }
+
+    /// Deserialize many independent values, one from each byte slice, like
+    /// [`from_bytes_batch`].
+    pub fn deserialize_batch<'de, T, I>(&self, inputs: I) -> Vec<Result<T>>
+    where
+        T: Deserialize<'de>,
+        I: IntoIterator<Item = &'de [u8]>,
+    {
+        let mut scratch = Vec::new();
+        inputs
+            .into_iter()
+            .map(|s| {
+                // Slices need no read buffer, only the key buffer is reused.
+                let mut des = PhpDeserializer::from_slice_unbuffered(s);
+                self.configure(&mut des);
+                des.set_scratch(std::mem::take(&mut scratch));
+                let result = T::deserialize(&mut des);
+                scratch = des.take_scratch();
+                result.or_else(|err| self.retry_repaired(s, err))
+            })
+            .collect()
+    }
+
+    /// Deserialize many independent values like
+    /// [`deserialize_batch`](PhpDeserializerBuilder::deserialize_batch),
+    /// splitting the slices among up to `threads` threads.
+    ///
+    /// Results are returned in the order of `inputs`. Only worthwhile for
+    /// large batches, as threads are spawned for every call.
+    ///
+    /// ```rust
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// let rows: Vec<Vec<u8>> = (0..100).map(|n| format!("i:{};", n).into_bytes()).collect();
+    /// let inputs: Vec<&[u8]> = rows.iter().map(Vec::as_slice).collect();
+    ///
+    /// let values = PhpDeserializerBuilder::new().deserialize_batch_parallel::<u8>(&inputs, 4);
+    /// assert_eq!(values[42].as_ref().unwrap(), &42);
+    /// ```
+    pub fn deserialize_batch_parallel<'de, T>(
+        &self,
+        inputs: &[&'de [u8]],
+        threads: usize,
+    ) -> Vec<Result<T>>
+    where
+        T: Deserialize<'de> + Send,
+    {
+        let threads = threads.clamp(1, inputs.len().max(1));
+        if threads == 1 {
+            return self.deserialize_batch(inputs.iter().copied());
+        }
+
+        let chunk_size = inputs.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = inputs
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || self.deserialize_batch(chunk.iter().copied())))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("deserialization panicked"))
+                .collect()
+        })
+    }
+
+    /// Deserialize from byte slice, skipping keys that do not match any
+    /// struct field and returning them along with the value.
+    ///
+    /// Unknown keys are skipped before the struct sees them, so this works
+    /// for structs using `#[serde(deny_unknown_fields)]` as well, reporting
+    /// all offending keys at once instead of failing on the first one. Keys
+    /// inside nested serialized strings (see
+    /// [`nested_unserialize`](PhpDeserializerBuilder::nested_unserialize))
+    /// are not reported.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// #[serde(deny_unknown_fields)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// let input = br#"a:3:{s:4:"name";s:3:"bob";s:3:"age";i:42;s:4:"role";N;}"#;
+    /// let (user, unknown) = PhpDeserializerBuilder::new()
+    ///     .deserialize_with_unknown_keys::<User>(input)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(user.name, "bob");
+    /// let unknown: Vec<String> = unknown.iter().map(ToString::to_string).collect();
+    /// assert_eq!(unknown, ["age at offset 26", "role at offset 41"]);
+    /// ```
+    pub fn deserialize_with_unknown_keys<'de, T>(
+        &self,
+        s: &'de [u8],
+    ) -> Result<(T, Vec<UnknownKey>)>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut des = self.build(s);
+        des.unknown_keys = Some(Vec::new());
+        let value = T::deserialize(&mut des)?;
+        Ok((value, des.unknown_keys.take().unwrap_or_default()))
+    }
+
+    /// Deserialize from byte slice, skipping repeated struct fields and
+    /// returning them along with the value.
+    ///
+    /// PHP arrays cannot contain a key twice, but hand-written or
+    /// concatenated data may, and keys that differ in the input can match the
+    /// same field through [aliases](PhpDeserializerBuilder::aliases) or
+    /// [`KeyMatching`]. Structs normally fail on repeated fields. Here, the
+    /// first occurrence is used and all later ones are skipped and reported
+    /// in input order, for auditing the data. Keys of maps are not checked.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::PhpDeserializerBuilder;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// let input = br#"a:2:{s:4:"name";s:3:"bob";s:4:"name";s:5:"alice";}"#;
+    /// let (user, duplicates) = PhpDeserializerBuilder::new()
+    ///     .deserialize_with_duplicate_fields::<User>(input)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(user.name, "bob");
+    /// assert_eq!(duplicates[0].to_string(), "name at offset 26 (using offset 5)");
+    /// ```
+    pub fn deserialize_with_duplicate_fields<'de, T>(
+        &self,
+        s: &'de [u8],
+    ) -> Result<(T, Vec<DuplicateField>)>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut des = self.build(s);
+        des.duplicate_fields = Some(Vec::new());
+        let value = T::deserialize(&mut des)?;
+        Ok((value, des.duplicate_fields.take().unwrap_or_default()))
+    }
+
+    /// Retry deserialization after repairing the input with each of the
+    /// enabled repair strategies in turn.
+    ///
+    /// Returns the most recent error if no strategy succeeds. Input exceeding
+    /// the nesting or reference limits is not repaired, as it is not broken.
+    pub(crate) fn retry_repaired<'de, T>(&self, s: &[u8], mut err: Error) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        if matches!(
+            err.kind(),
+            ErrorKind::DepthLimitExceeded(_) | ErrorKind::ReferenceLimitExceeded(_)
+        ) {
+            return Err(err);
+        }
+
+        let fix_lengths = Some(&FixLengths as &dyn RepairStrategy).filter(|_| self.repair_lengths);
+        let strategies = fix_lengths
+            .into_iter()
+            .chain(self.repair_strategies.iter().map(Arc::as_ref));
+
+        let mut input = Cow::Borrowed(s);
+        for strategy in strategies {
+            let repaired = match strategy.repair(&input, &err) {
+                Some(repaired) if repaired != *input => repaired,
+                _ => continue,
+            };
+
+            // Nothing is borrowed from the input, so the repaired copy can stand in.
+            match T::deserialize(&mut self.build(&repaired)) {
+                Ok(value) => return Ok(value),
+                Err(next) => err = next,
+            }
+            input = Cow::Owned(repaired);
+        }
+
+        Err(err)
+    }
+}
+
+impl<R> PhpDeserializer<R>
+where
+    R: Input,
+{
+    /// Create a deserializer reading directly from `reader`.
+    ///
+    /// Like [`from_reader`], the deserializer does not read beyond the end of
+    /// the value, except for a single byte of lookahead in some error cases.
+    /// Use [`into_inner`](PhpDeserializer::into_inner) to get the reader and
+    /// that byte back.
+    pub fn from_reader(reader: R) -> Self {
+        PhpDeserializer::new(reader)
+    }
+
+    /// Create a deserializer reading from a custom [`Input`].
+    ///
+    /// Behaves like [`from_reader`](PhpDeserializer::from_reader).
+    pub fn from_input(input: R) -> Self {
+        PhpDeserializer::new(input)
+    }
+
+    /// Byte read ahead of the current position, if any.
+    ///
+    /// Such a byte has been taken from the reader already, but does not
+    /// belong to the values deserialized so far. It is always `None` after
+    /// successfully deserializing a complete value.
+    pub fn lookahead(&self) -> Option<u8> {
+        self.input.buffered()
+    }
+
+    /// Consume the deserializer, returning the reader and the byte read
+    /// ahead of the current position, if any (see
+    /// [`lookahead`](PhpDeserializer::lookahead)).
+    pub fn into_inner(self) -> (R, Option<u8>) {
+        self.input.into_parts()
+    }
+
+    /// Array entries dropped so far due to [`ObjectPolicy::Skip`], in input
+    /// order.
+    pub fn skipped_objects(&self) -> &[SkippedObject] {
+        &self.skipped_objects
+    }
+
+    pub(crate) fn new(input: R) -> PhpDeserializer<R> {
+        let mut input = Lookahead1::new(input);
+        input.set_references(ReferencePolicy::Resolve);
+        PhpDeserializer {
+            input,
+            base_offset: 0,
+            values: 0,
+            key_matching: KeyMatching::Exact,
+            positional_structs: false,
+            aliases: Arc::default(),
+            null_as_default: false,
+            std_class_as_map: false,
+            classes: Arc::default(),
+            latin1_fallback: false,
+            nested_unserialize: false,
+            repair_lengths: false,
+            byte_strings: false,
+            integral_floats: false,
+            reorder_arrays: false,
+            key_filter: None,
+            unknown_keys: None,
+            duplicate_fields: None,
+            path: Vec::new(),
+            object_policy: ObjectPolicy::Error,
+            skipped_objects: Vec::new(),
+            format_version: FormatVersion::default(),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub(crate) fn peek(&mut self) -> Result<Option<u8>> {
+        self.input.peek()
+    }
+
+    /// Number of bytes consumed so far.
+    pub(crate) fn position(&self) -> u64 {
+        self.input.position()
+    }
+
+    /// Consume a `null` if it is to be deserialized as a default value.
+    fn take_null_as_default(&mut self) -> Result<bool> {
+        if self.null_as_default && self.input.peek_type()? == Some(b'N') {
+            self.input.expect(b'N')?;
+            self.input.expect(b';')?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Read the header of an object following `O:`, if it is a `stdClass`
+    /// object to be treated like an array.
+    ///
+    /// Returns the number of properties.
+    fn read_std_class_header(&mut self) -> Result<usize> {
+        let unsupported = || {
+            Error::from(ErrorKind::UnsupportedPhpType {
+                type_indicator: 'O',
+            })
+        };
+
+        let as_map = self.object_policy == ObjectPolicy::AsMap;
+        if !self.std_class_as_map && !as_map && self.classes.is_empty() {
+            return Err(unsupported());
+        }
+
+        let (class, num_properties) = self.input.read_object_header()?;
+        let allowed = as_map
+            || (self.std_class_as_map && class == b"stdClass")
+            || self.registered_variant(&class).is_some();
+        if !allowed {
+            return Err(unsupported());
+        }
+
+        Ok(num_properties)
+    }
+
+    /// Enum variant registered for `class`, if any.
+    fn registered_variant(&self, class: &[u8]) -> Option<&str> {
+        let class = std::str::from_utf8(class).ok()?;
+        self.classes.get(class).map(String::as_str)
+    }
+
+    /// Read a string holding nested serialized data, if enabled and the next
+    /// value is a string.
+    fn take_nested(&mut self) -> Result<Option<Vec<u8>>> {
+        if !self.nested_unserialize || self.input.peek_type()? != Some(b's') {
+            return Ok(None);
+        }
+
+        self.input.expect_type(b's')?;
+        self.input.expect(b':')?;
+        let inner = self.input.read_raw_string()?;
+
+        Ok(Some(if self.repair_lengths {
+            repair::fix_string_lengths(&inner)
+        } else {
+            inner
+        }))
+    }
+
+    /// Deserialize nested serialized data using the same options.
+    ///
+    /// The nested data must consist of exactly one value.
+    fn deserialize_nested<T, F>(&self, inner: &[u8], f: F) -> Result<T>
+    where
+        F: FnOnce(&mut PhpDeserializer<&[u8]>) -> Result<T>,
+    {
+        let mut des = self.nested(inner);
+        let value = f(&mut des)?;
+        des.end()?;
+        Ok(value)
+    }
+
+    /// Deserialize a value captured from the input at `offset`, as if it
+    /// was read in place.
+    ///
+    /// Offsets of errors and of collected diagnostics refer to the input.
+    fn deserialize_captured<T, F>(&mut self, offset: u64, data: &[u8], f: F) -> Result<T>
+    where
+        F: FnOnce(&mut PhpDeserializer<&[u8]>) -> Result<T>,
+    {
+        let mut des = self.nested(data);
+        des.base_offset = self.base_offset + offset;
+        des.path = self.path.clone();
+        des.unknown_keys = self.unknown_keys.as_ref().map(|_| Vec::new());
+        des.duplicate_fields = self.duplicate_fields.as_ref().map(|_| Vec::new());
+
+        let result = f(&mut des).and_then(|value| des.end().map(|()| value));
+
+        self.values += des.values;
+        if let (Some(keys), Some(found)) = (&mut self.unknown_keys, des.unknown_keys) {
+            keys.extend(found.into_iter().map(|key| UnknownKey {
+                offset: key.offset + offset,
+                ..key
+            }));
+        }
+        if let (Some(fields), Some(found)) = (&mut self.duplicate_fields, des.duplicate_fields) {
+            fields.extend(found.into_iter().map(|field| DuplicateField {
+                offset: field.offset + offset,
+                used_offset: field.used_offset + offset,
+                ..field
+            }));
+        }
+        self.skipped_objects
+            .extend(des.skipped_objects.into_iter().map(|object| SkippedObject {
+                offset: object.offset + offset,
+                ..object
+            }));
+
+        result.map_err(|err| err.with_base_offset(offset))
+    }
+
+    /// Create a deserializer for nested data, using the same options.
+    fn nested<'b>(&self, inner: &'b [u8]) -> PhpDeserializer<&'b [u8]> {
+        let mut des = PhpDeserializer::from_slice_unbuffered(inner);
+        des.input.nest_in(&self.input);
+        des.key_matching = self.key_matching;
+        des.positional_structs = self.positional_structs;
+        des.aliases = self.aliases.clone();
+        des.null_as_default = self.null_as_default;
+        des.std_class_as_map = self.std_class_as_map;
+        des.classes = self.classes.clone();
+        des.latin1_fallback = self.latin1_fallback;
+        des.nested_unserialize = self.nested_unserialize;
+        des.repair_lengths = self.repair_lengths;
+        des.byte_strings = self.byte_strings;
+        des.integral_floats = self.integral_floats;
+        des.reorder_arrays = self.reorder_arrays;
+        des.key_filter = self.key_filter.clone();
+        des.object_policy = self.object_policy;
+        des.format_version = self.format_version;
+        des
+    }
+
+    /// Convert string data to a `String`, falling back to Latin-1 if enabled.
+    fn decode_string(&self, raw: Vec<u8>) -> Result<String> {
+        match String::from_utf8(raw) {
+            Ok(s) => Ok(s),
+            Err(err) if self.latin1_fallback => Ok(latin1(err.as_bytes())),
+            Err(err) => Err(ErrorKind::Utf8Error(err.utf8_error()).into()),
+        }
+    }
+
+    /// Read an integer, reporting values that do not fit into `T` as
+    /// `OutOfRange` errors.
+    fn read_int_checked<T: std::str::FromStr>(&mut self, type_name: &'static str) -> Result<T> {
+        let offset = self.input.position();
+        self.input.expect_type(b'i')?;
+        self.input.expect(b':')?;
+        let value = self.input.read_int_text()?;
+
+        value.parse().map_err(|_| {
+            Error::from(ErrorKind::OutOfRange { value, type_name }).with_offset(offset)
+        })
+    }
+
+    /// Read a float without fractional part as an integer, reporting other
+    /// values as `OutOfRange` errors.
+    fn read_integral_float<T: std::str::FromStr>(&mut self, type_name: &'static str) -> Result<T> {
+        let offset = self.input.position();
+        self.input.expect_type(b'd')?;
+        self.input.expect(b':')?;
+        // Adding zero turns `-0.0` into `0.0`, which unsigned types accept.
+        let value = self.input.read_float()? + 0.0;
+
+        let parsed = if value.fract() == 0.0 {
+            format!("{:.0}", value).parse().ok()
+        } else {
+            None
+        };
+        parsed.ok_or_else(|| {
+            Error::from(ErrorKind::OutOfRange {
+                value: value.to_string(),
+                type_name,
+            })
+            .with_offset(offset)
+        })
+    }
+
+    /// Read a string as a sequence of bytes, whether or not it is valid
+    /// UTF-8.
+    fn deserialize_string_seq<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.input.expect_type(b's')?;
+        self.input.expect(b':')?;
+        let data = self.input.read_raw_string()?;
+        visitor.visit_seq(SeqDeserializer::new(data.into_iter()))
+    }
+
+    /// Whether the keys of map entries are recorded while deserializing.
+    fn records_keys(&self) -> bool {
+        self.input.strict || self.tracks_path()
+    }
+
+    /// Whether the path to the current value is tracked.
+    fn tracks_path(&self) -> bool {
+        self.unknown_keys.is_some()
+            || self.duplicate_fields.is_some()
+            || self.object_policy == ObjectPolicy::Skip
+    }
+
+    /// Skip the next value if it is an object to be dropped according to the
+    /// object policy, recording it under `key`.
+    ///
+    /// Returns whether the value was skipped.
+    fn skip_object(&mut self, key: &dyn fmt::Display) -> Result<bool> {
+        if self.object_policy != ObjectPolicy::Skip || self.input.peek_type()? != Some(b'O') {
+            return Ok(false);
+        }
+
+        let offset = self.position();
+        self.input.expect_type(b'O')?;
+        self.input.expect(b':')?;
+        let (class, num_properties) = self.input.read_object_header()?;
+        self.skip_entries(num_properties, false)?;
+
+        let mut path = self.path.clone();
+        path.push(key.to_string());
+        self.skipped_objects.push(SkippedObject {
+            path: path.join("."),
+            offset,
+            class: String::from_utf8_lossy(&class).into_owned(),
+        });
+        Ok(true)
+    }
+
+    /// Path to `key` of the current value.
+    fn key_path(&self, key: &[u8]) -> String {
+        let key = String::from_utf8_lossy(key);
+        if self.path.is_empty() {
+            key.into_owned()
+        } else {
+            format!("{}.{}", self.path.join("."), key)
+        }
+    }
+
+    /// Record a key that does not match any field of the current struct.
+    fn record_unknown_key(&mut self, key: &[u8], offset: u64) {
+        let path = self.key_path(key);
+        if let Some(unknown_keys) = &mut self.unknown_keys {
+            unknown_keys.push(UnknownKey { path, offset });
+        }
+    }
+
+    /// Record a key repeating a field of the current struct, which was
+    /// first found at `used_offset`.
+    fn record_duplicate_field(&mut self, key: &[u8], offset: u64, used_offset: u64) {
+        let path = self.key_path(key);
+        if let Some(duplicate_fields) = &mut self.duplicate_fields {
+            duplicate_fields.push(DuplicateField {
+                path,
+                offset,
+                used_offset,
+            });
+        }
+    }
+
+    /// Attach a path segment to an error, if paths are recorded.
+    fn annotate(&self, err: Error, segment: &dyn std::fmt::Display) -> Error {
+        if self.input.strict {
+            err.with_path_segment(&segment.to_string())
+        } else {
+            err
+        }
+    }
+
+    /// Deserialize an array as a map, matching keys against `fields`.
+    fn deserialize_array_map<'de, V>(
+        &mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.take_null_as_default()? {
+            return visitor.visit_map(MapDeserializer::<_, Error>::new(iter::empty::<(u8, u8)>()));
+        }
+
+        if let Some(inner) = self.take_nested()? {
+            return self
+                .deserialize_nested(&inner, |des| des.deserialize_array_map(fields, visitor));
+        }
+
+        let num_elements = match self.input.read_type()? {
+            b'O' => {
+                self.input.expect(b':')?;
+                self.read_std_class_header()?
+            }
+            sym => {
+                check_expected(b'a', sym)?;
+                self.input.expect(b':')?;
+                self.input.read_array_header()?
+            }
+        };
+        let filter = if fields.is_empty() {
+            self.key_filter.clone()
+        } else {
+            None
+        };
+        let mapping = ArrayMapping::new(self, num_elements)
+            .with_fields(fields)
+            .with_filter(filter);
+        let rval = visitor.visit_map(mapping)?;
+        self.input.read_array_end()?;
+
+        Ok(rval)
+    }
+
+    /// Deserialize the properties of an object of any class, e.g. an SPL list
+    /// (see [`spl::SplList`]).
+    fn deserialize_object_properties<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.input.expect_type(b'O')?;
+        self.input.expect(b':')?;
+        let (_class, num_properties) = self.input.read_object_header()?;
+
+        // Properties are keyed by position, so they are passed on as a plain map.
+        let rval = visitor.visit_map(ArrayMapping::new(self, num_properties))?;
+        self.input.read_array_end()?;
+
+        Ok(rval)
+    }
+
+    /// Deserialize an arbitrary-precision number, see [`crate::bigint`].
+    ///
+    /// `GMP` objects are passed on as maps of their properties, BCMath strings
+    /// as bytes and anything else as an integer.
+    #[cfg(feature = "bigint")]
+    fn deserialize_big_number<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.input.peek_type()? {
+            Some(b'O') => self.deserialize_object_properties(visitor),
+            Some(b's') => self.deserialize_byte_buf(visitor),
+            _ => self.deserialize_i64(visitor),
+        }
+    }
+
+    /// Map a key onto the struct field it matches according to `aliases` and
+    /// `key_matching`.
+    ///
+    /// Keys that match a field exactly or do not match any are returned as-is.
+    fn match_field<'k>(&self, key: &'k str, fields: &[&'static str]) -> &'k str {
+        if fields.is_empty() || fields.contains(&key) {
+            return key;
+        }
+
+        if let Some(alias) = self.aliases.get(key) {
+            if let Some(field) = fields.iter().find(|field| **field == alias.as_str()) {
+                return field;
+            }
+        }
+
+        if self.key_matching == KeyMatching::Exact {
+            return key;
+        }
+
+        fields
+            .iter()
+            .find(|field| self.key_matching.matches(field, key))
+            .map_or(key, |field| field)
+    }
+
+    /// Take the scratch buffer, e.g. to keep it for another deserializer.
+    pub(crate) fn take_scratch(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.scratch)
+    }
+
+    /// Replace the scratch buffer.
+    pub(crate) fn set_scratch(&mut self, scratch: Vec<u8>) {
+        self.scratch = scratch;
+    }
+
+    /// Set how references to earlier values are handled.
+    pub(crate) fn set_references(&mut self, policy: ReferencePolicy) {
+        self.input.set_references(policy);
+    }
+
+    /// Read an array header, up to and including the opening brace.
+    ///
+    /// Returns the number of elements declared.
+    pub(crate) fn read_array_start(&mut self) -> Result<usize> {
+        self.input.expect_type(b'a')?;
+        self.input.expect(b':')?;
+        self.input.read_array_header()
+    }
+
+    /// Read an array key, returning integer keys in their decimal representation.
+    pub(crate) fn read_key(&mut self) -> Result<Vec<u8>> {
+        match self.input.read_type()? {
+            b'i' => {
+                self.input.expect(b':')?;
+                Ok(self.input.read_int_text()?.into_bytes())
+            }
+            b's' => {
+                self.input.expect(b':')?;
+                self.input.read_key_string()
+            }
+            c => Err(ErrorKind::UnsupportedArrayKeyType(char::from(c)).into()),
+        }
+    }
+
+    /// Read the closing brace of an array.
+    pub(crate) fn read_array_end(&mut self) -> Result<()> {
+        self.input.read_array_end()
+    }
+
+    /// Skip a complete value, including nested values.
+    pub(crate) fn skip_value(&mut self) -> Result<()> {
+        self.skip(false)
+    }
+
+    /// Skip a complete value, accepting objects of any class if
+    /// `any_object` is set. Otherwise, objects are subject to the same
+    /// restrictions as when deserializing them.
+    fn skip(&mut self, any_object: bool) -> Result<()> {
+        let sym = self.input.read_type()?;
+
+        if sym == b'N' {
+            return self.input.expect(b';');
+        }
+
+        self.input.expect(b':')?;
+
+        match sym {
+            b'b' => self.input.read_bool().map(drop),
+            b'i' => self.input.read_int().map(drop),
+            b'd' => self.input.read_float().map(drop),
+            b's' => self.input.read_raw_string().map(drop),
+            b'a' => {
+                let num_elements = self.input.read_array_header()?;
+                self.skip_entries(num_elements, any_object)
+            }
+            b'O' => {
+                let num_properties = if any_object {
+                    self.input.read_object_header()?.1
+                } else {
+                    self.read_std_class_header()?
+                };
+                self.skip_entries(num_properties, any_object)
+            }
+            b'C' => self.input.read_custom().map(drop),
+            b'E' if any_object => self.input.read_enum_case().map(drop),
+            b'E' => self.read_enum_case().map(drop),
+            c => Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+        }
+    }
+
+    /// Read an enum case that follows after the `b"E:"` part, if the format
+    /// version supports them.
+    fn read_enum_case(&mut self) -> Result<(Vec<u8>, Vec<u8>)> {
+        if !self.format_version.has_enums() {
+            return Err(ErrorKind::UnsupportedPhpType {
+                type_indicator: 'E',
+            }
+            .into());
+        }
+        self.input.read_enum_case()
+    }
+
+    /// Read a complete value of any type as it appears in the input, with
+    /// references replaced by copies of the values they refer to.
+    pub(crate) fn read_verbatim(&mut self) -> Result<Vec<u8>> {
+        // Resolve a reference first, so that only the copy is captured.
+        self.input.peek_type()?;
+        self.input.start_capture();
+        let result = self.skip(true);
+        let data = self.input.end_capture();
+        result.map(|()| data)
+    }
+
+    /// Fail if any input is left.
+    pub(crate) fn end(&mut self) -> Result<()> {
+        if self.input.peek()?.is_some() {
+            return Err(Error::from(ErrorKind::TrailingData).with_offset(self.input.position()));
+        }
+        Ok(())
+    }
+
+    /// Skip the entries of an array or object, including the closing brace.
+    fn skip_entries(&mut self, num_entries: usize, any_object: bool) -> Result<()> {
+        for _ in 0..num_entries {
+            self.read_key()?;
+            self.skip(any_object)?;
+        }
+        self.input.read_array_end()
+    }
+}
+
+/// Decode Latin-1 (ISO-8859-1) text, which maps every byte onto the code
+/// point of the same value.
+fn latin1(raw: &[u8]) -> String {
+    raw.iter().copied().map(char::from).collect()
+}
+
+/// Implement scalar `deserialize_*` methods, which visit a default value for
+/// `null` if enabled and forward to `deserialize_any` otherwise.
+macro_rules! deserialize_null_as_default {
+    ($($method:ident => $visit:ident($default:expr)),* $(,)?) => {
+        $(
+            #[inline]
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                if self.take_null_as_default()? {
+                    return visitor.$visit($default);
+                }
+
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+/// Implement integer `deserialize_*` methods, which check the range of
+/// integers when deserializing strictly and otherwise behave like
+/// `deserialize_null_as_default`.
+macro_rules! deserialize_integer {
+    ($($method:ident => $ty:ty, $visit:ident),* $(,)?) => {
+        $(
+            #[inline]
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                if self.take_null_as_default()? {
+                    return visitor.$visit(0);
+                }
+
+                if self.input.strict && self.input.peek_type()? == Some(b'i') {
+                    let v = self.read_int_checked::<$ty>(stringify!($ty))?;
+                    return visitor.$visit(v);
+                }
+
+                if self.integral_floats && self.input.peek_type()? == Some(b'd') {
+                    let v = self.read_integral_float::<$ty>(stringify!($ty))?;
+                    return visitor.$visit(v);
+                }
+
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, R> Deserializer<'de> for &mut PhpDeserializer<R>
+where
+    R: Input,
+{
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // All fields start with a type, followed by a colon.
+        let sym = self.input.read_type()?;
+
+        if sym == b'N' {
+            // `null` is a special case, since it is not followed by a colon.
+            self.input.expect(b';')?;
+            return visitor.visit_unit();
+        }
+
+        self.input.expect(b':')?;
+
+        // See https://stackoverflow.com/questions/14297926/structure-of-a-serialized-php-string
+        match sym {
+            b'b' => visitor.visit_bool(self.input.read_bool()?),
+            b'i' => visitor.visit_i64(self.input.read_int()?),
+            b'd' => visitor.visit_f64(self.input.read_float()?),
+            b's' => {
+                // PHP String.
+
+                let data = self.input.read_raw_string()?;
+
+                // Text is passed on as such, so that it survives being
+                // buffered, as for untagged and internally tagged enums.
+                let data = match String::from_utf8(data) {
+                    Ok(s) => return visitor.visit_string(s),
+                    Err(err) => err.into_bytes(),
+                };
+
+                if self.byte_strings {
+                    return visitor.visit_byte_buf(data);
+                }
+
+                // We now have the complete bytestring, no further parsing required.
+                visitor.visit_seq(serde::de::value::SeqDeserializer::new(data.into_iter()))
+            }
+            b'a' => {
+                // Array.
+                let num_elements = self.input.read_array_header()?;
+
+                // We support two ways of array deserialization: tuple and struct.
+                //
+                // Numeric arrays are deserialized as tuples and assumed to
+                // contain no missing keys.
+                //
+                // Associative arrays must contain only string keys and are
+                // serialized as mappings.
+                //
+                // Other variants are currently not supported and would require
+                // hashmaps and variant types.
+
+                let rval = match self.input.peek_type()? {
+                    Some(b'i') | Some(b'}') => {
+                        // Numeric or empty array.
+                        visitor.visit_seq(ArraySequence::new(self, num_elements))
+                    }
+                    Some(b's') => {
+                        // Associative array.
+                        visitor.visit_map(ArrayMapping::new(self, num_elements))
+                    }
+                    Some(c) => Err(ErrorKind::UnsupportedArrayKeyType(char::from(c)).into()),
+                    None => return Err(ErrorKind::UnexpectedEof.into()),
+                }?;
+                self.input.read_array_end()?;
+                Ok(rval)
+            }
+            b'O' => {
+                // Object, only supported for `stdClass`, which is treated
+                // like an associative array.
+                let num_properties = self.read_std_class_header()?;
+                let rval = visitor.visit_map(ArrayMapping::new(self, num_properties))?;
+                self.input.read_array_end()?;
+                Ok(rval)
+            }
+            // Custom serialized objects only have a meaning to their class,
+            // see `PhpCustom`.
+            b'C' => Err(ErrorKind::UnsupportedPhpType {
+                type_indicator: 'C',
+            }
+            .into()),
+            b'E' => {
+                let (mut name, case) = self.read_enum_case()?;
+                name.push(b':');
+                name.extend_from_slice(&case);
+                visitor.visit_string(self.decode_string(name)?)
+            }
+            // Unknown character, not valid.
+            c => Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let offset = self.input.position();
+
+        // Characters are serialized as code points, or by PHP code as strings
+        // holding the UTF-8 encoded character.
+        if self.input.peek_type()? == Some(b's') {
+            self.input.expect_type(b's')?;
+            self.input.expect(b':')?;
+            let data = self.input.read_raw_string()?;
+            let s = std::str::from_utf8(&data).map_err(ErrorKind::Utf8Error)?;
+
+            let mut chars = s.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => visitor.visit_char(c),
+                _ => {
+                    Err(Error::from(ErrorKind::NotASingleChar(s.chars().count()))
+                        .with_offset(offset))
+                }
+            };
+        }
+
+        self.input.expect_type(b'i')?;
+        self.input.expect(b':')?;
+
+        let mut buf = SmallVec::new();
+        self.input.collect_unsigned(&mut buf)?;
+        // No sign.
+
+        self.input.expect(b';')?;
+
+        // Parse wider than `char`, to report the offending value.
+        let raw: u64 = parse_bytes(&buf)?;
+        match u32::try_from(raw).ok().and_then(char::from_u32) {
+            Some(c) => visitor.visit_char(c),
+            None => Err(Error::from(ErrorKind::InvalidCodepoint(raw)).with_offset(offset)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.take_null_as_default()? {
+            return visitor.visit_string(String::new());
+        }
+
+        self.input.expect_type(b's')?;
+        self.input.expect(b':')?;
+        // Actual UTF-8 strings are not a thing in PHP, but we offer this conversion
+        // as a convenience.
+        let raw = self.input.read_raw_string()?;
+        visitor.visit_string(self.decode_string(raw)?)
+    }
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Input is not borrowed, so there are no `&str`s to hand out.
+        self.deserialize_string(visitor)
+    }
+
+    #[inline]
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.take_null_as_default()? {
+            return visitor.visit_byte_buf(Vec::new());
+        }
+
+        // Strings can be handed out as a whole, instead of byte by byte as
+        // `deserialize_any` does. Anything else, e.g. arrays of integers, is
+        // left to the visitor.
+        if self.input.peek_type()? == Some(b's') {
+            self.input.expect_type(b's')?;
+            self.input.expect(b':')?;
+            visitor.visit_byte_buf(self.input.read_raw_string()?)
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.take_null_as_default()? {
+            return visitor.visit_seq(SeqDeserializer::<_, Error>::new(iter::empty::<u8>()));
+        }
+
+        if let Some(inner) = self.take_nested()? {
+            return self.deserialize_nested(&inner, |des| des.deserialize_tuple(len, visitor));
+        }
+
+        match self.input.peek_type()? {
+            Some(b'a') => {}
+            Some(b's') => return self.deserialize_string_seq(visitor),
+            _ => return self.deserialize_any(visitor),
+        }
+
+        self.input.expect_type(b'a')?;
+        self.input.expect(b':')?;
+        let num_elements = self.input.read_array_header()?;
+
+        // Some producers write tuples using numeric string keys (`"0"`, `"1"`, ...).
+        let string_keys = self.input.peek_type()? == Some(b's');
+        let sequence = ArraySequence::new(self, num_elements)
+            .with_string_keys(string_keys)
+            .with_tuple_len(len);
+        let rval = visitor.visit_seq(sequence)?;
+        self.input.read_array_end()?;
+
+        Ok(rval)
+    }
+
+    #[inline]
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // A `null` value indicates our `None` here.
+        if let Some(b'N') = self.input.peek_type()? {
+            self.input.expect(b'N')?;
+            self.input.expect(b';')?;
+            visitor.visit_none()
+        } else {
+            // Otherwise, we can parse the actual value.
+            visitor.visit_some(self)
+        }
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(
+        self,
+        name: &str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == spl::SPL_LIST_TOKEN {
+            return self.deserialize_object_properties(visitor);
+        }
+        #[cfg(feature = "bigint")]
+        {
+            if name == crate::bigint::BIG_NUMBER_TOKEN {
+                return self.deserialize_big_number(visitor);
+            }
+        }
+
+        // We need to explicitly implement struct deserialization to be able
+        // to distinguish between empty numeric arrays and empty associative
+        // arrays.
+        self.deserialize_array_map(fields, visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Similar to `deserialize_struct`, we need to cover the case of the empty map.
+        self.deserialize_array_map(&[], visitor)
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // No need to build up any values, just consume the input.
+        self.skip_value()?;
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == raw::RAW_TOKEN {
+            let offset = self.base_offset + self.position();
+            let data = self.read_verbatim()?;
+            return visitor.visit_map(raw::Captured::new(offset, data));
+        }
+        if name == custom::CUSTOM_TOKEN {
+            self.input.expect_type(b'C')?;
+            self.input.expect(b':')?;
+            let (class, data) = self.input.read_custom()?;
+            return visitor.visit_byte_buf(custom::record(&class, &data));
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.take_null_as_default()? {
+            return visitor.visit_seq(SeqDeserializer::<_, Error>::new(iter::empty::<u8>()));
+        }
+
+        if let Some(inner) = self.take_nested()? {
+            return self.deserialize_nested(&inner, |des| des.deserialize_seq(visitor));
+        }
+
+        if self.input.peek_type()? == Some(b's') {
+            return self.deserialize_string_seq(visitor);
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    deserialize_null_as_default! {
+        deserialize_bool => visit_bool(false),
+        deserialize_f64 => visit_f64(0.0),
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => i8, visit_i8,
+        deserialize_i16 => i16, visit_i16,
+        deserialize_i32 => i32, visit_i32,
+        deserialize_i64 => i64, visit_i64,
+        deserialize_i128 => i128, visit_i128,
+        deserialize_u8 => u8, visit_u8,
+        deserialize_u16 => u16, visit_u16,
+        deserialize_u32 => u32, visit_u32,
+        deserialize_u64 => u64, visit_u64,
+        deserialize_u128 => u128, visit_u128,
+    }
+
+    #[inline]
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.take_null_as_default()? {
+            return visitor.visit_f32(0.0);
+        }
+
+        if self.input.strict && self.input.peek_type()? == Some(b'd') {
+            let offset = self.input.position();
+            self.input.expect_type(b'd')?;
+            self.input.expect(b':')?;
+            let v = self.input.read_float()?;
+
+            // Finite values must not overflow into infinity.
+            if v.is_finite() && !(v as f32).is_finite() {
+                return Err(Error::from(ErrorKind::OutOfRange {
+                    value: v.to_string(),
+                    type_name: "f32",
+                })
+                .with_offset(offset));
+            }
+            return visitor.visit_f32(v as f32);
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.input.peek_type()? {
+            // Unit variants are written as their name.
+            Some(b's') => {
+                self.input.expect_type(b's')?;
+                self.input.expect(b':')?;
+                let raw = self.input.read_raw_string()?;
+                let variant = self.decode_string(raw)?;
+                return visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(variant));
+            }
+            // Other variants are arrays holding the payload under their name.
+            Some(b'a') => {
+                self.input.expect_type(b'a')?;
+                self.input.expect(b':')?;
+                let num_elements = self.input.read_array_header()?;
+                if num_elements != 1 {
+                    return Err(serde::de::Error::invalid_length(
+                        num_elements,
+                        &"an array with a single entry",
+                    ));
+                }
+                let key = self.read_key()?;
+                let variant = self.decode_string(key)?;
+                return visitor.visit_enum(ArrayVariant { de: self, variant });
+            }
+            // Enum cases select the variant of the same name.
+            Some(b'E') => {
+                self.input.expect_type(b'E')?;
+                self.input.expect(b':')?;
+                let (_, case) = self.read_enum_case()?;
+                let variant = self.decode_string(case)?;
+                return visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(variant));
+            }
+            Some(b'O') if !self.classes.is_empty() => {}
+            _ => return self.deserialize_any(visitor),
+        }
+
+        // Objects select the variant registered for their class.
+        self.input.expect_type(b'O')?;
+        self.input.expect(b':')?;
+        let (class, num_properties) = self.input.read_object_header()?;
+        let variant = match self.registered_variant(&class) {
+            Some(variant) => variant.to_owned(),
+            None => {
+                let class = String::from_utf8_lossy(&class).into_owned();
+                return Err(ErrorKind::ClassNotAllowed(class).into());
+            }
+        };
+
+        visitor.visit_enum(ObjectVariant {
+            de: self,
+            variant,
+            num_properties,
+        })
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Names given as values, such as the tag of internally tagged enums,
+        // are text rather than a sequence of bytes.
+        if self.input.peek_type()? == Some(b's') {
+            return self.deserialize_string(visitor);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Unit structs are written as `null`, or as empty arrays by PHP code
+        // expecting an array.
+        if self.input.peek_type()? == Some(b'a') {
+            let offset = self.input.position();
+            self.input.expect_type(b'a')?;
+            self.input.expect(b':')?;
+            let num_elements = self.input.read_array_header()?;
+            if num_elements != 0 {
+                let err: Error = serde::de::Error::invalid_length(num_elements, &"an empty array");
+                return Err(err.with_offset(offset));
+            }
+            self.input.read_array_end()?;
+            return visitor.visit_unit();
+        }
+        self.deserialize_any(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        unit
+    }
+}
+
+/// Array with a single entry, as externally tagged variant of an enum.
+///
+/// Its header and key have been read, the payload follows.
+struct ArrayVariant<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    variant: String,
+}
+
+impl<'a, 'de, R> EnumAccess<'de> for ArrayVariant<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+    type Variant = VariantPayload<'a, R>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant =
+            seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.variant))?;
+        Ok((variant, VariantPayload { de: self.de }))
+    }
+}
+
+/// Payload of an externally tagged variant, followed by the end of the
+/// array holding it.
+struct VariantPayload<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+}
+
+impl<'a, 'de, R> VariantAccess<'de> for VariantPayload<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        <()>::deserialize(&mut *self.de)?;
+        self.de.read_array_end()
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(&mut *self.de)?;
+        self.de.read_array_end()?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.de.deserialize_tuple(len, visitor)?;
+        self.de.read_array_end()?;
+        Ok(value)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.de.deserialize_array_map(fields, visitor)?;
+        self.de.read_array_end()?;
+        Ok(value)
+    }
+}
+
+/// Object of a registered class, as variant of an enum.
+///
+/// Its header has been read, the properties follow.
+struct ObjectVariant<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    variant: String,
+    num_properties: usize,
+}
+
+impl<'a, 'de, R> EnumAccess<'de> for ObjectVariant<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+    type Variant = ObjectProperties<'a, R>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant =
+            seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.variant))?;
+        Ok((
+            variant,
+            ObjectProperties {
+                de: self.de,
+                num_properties: self.num_properties,
+            },
+        ))
+    }
+}
+
+/// Deserializer for the properties of an object whose header has been read.
+struct ObjectProperties<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    num_properties: usize,
+}
+
+impl<'a, R> ObjectProperties<'a, R>
+where
+    R: Input,
+{
+    fn visit_properties<'de, V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mapping = ArrayMapping::new(self.de, self.num_properties).with_fields(fields);
+        let rval = visitor.visit_map(mapping)?;
+        self.de.input.read_array_end()?;
+        Ok(rval)
+    }
+}
+
+impl<'a, 'de, R> VariantAccess<'de> for ObjectProperties<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        // Properties are not needed to select the variant.
+        self.de.skip_entries(self.num_properties, false)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_properties(&[], visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_properties(fields, visitor)
+    }
+}
+
+impl<'a, 'de, R> Deserializer<'de> for ObjectProperties<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_properties(&[], visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_properties(fields, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+/// Numeric array sequence helper.
+#[derive(Debug)]
+struct ArraySequence<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    num_elements: usize,
+    index: usize,
+    /// Whether indices are written as numeric strings instead of integers.
+    string_keys: bool,
+    /// Number of elements a tuple expects beyond the end of the array.
+    missing: usize,
+    /// Number of missing elements handed out so far.
+    padded: usize,
+    /// Remaining elements sorted by index, with their offsets, once an
+    /// out-of-order index has been found.
+    buffered: Option<vec::IntoIter<(usize, u64, Vec<u8>)>>,
+}
+
+impl<'a, R> ArraySequence<'a, R> {
+    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
+        ArraySequence {
+            de,
+            num_elements,
+            index: 0,
+            string_keys: false,
+            missing: 0,
+            padded: 0,
+            buffered: None,
+        }
+    }
+
+    fn with_string_keys(mut self, string_keys: bool) -> Self {
+        self.string_keys = string_keys;
+        self
+    }
+
+    /// Pad the array to the length of a tuple, handing out missing elements
+    /// that can only be deserialized as `None`.
+    fn with_tuple_len(mut self, len: usize) -> Self {
+        self.missing = len.saturating_sub(self.num_elements);
+        self
+    }
+}
+
+impl<'a, R> ArraySequence<'a, R>
+where
+    R: Input,
+{
+    /// Read the key of the next element, which is its index.
+    fn read_index(&mut self) -> Result<usize> {
+        let idx = if self.string_keys {
+            self.de.input.expect_type(b's')?;
+            self.de.input.expect(b':')?;
+            parse_bytes(self.de.input.read_key_string()?)?
+        } else {
+            usize::deserialize(&mut *self.de)?
+        };
+        self.index += 1;
+        Ok(idx)
+    }
+
+    /// Capture the element with index `idx` and all remaining ones, to be
+    /// handed out sorted by index.
+    fn buffer_remaining(&mut self, mut idx: usize) -> Result<()> {
+        let mut elements = Vec::new();
+        loop {
+            if !self.de.skip_object(&idx)? {
+                let offset = self.de.position();
+                elements.push((idx, offset, self.de.read_verbatim()?));
+            }
+            if self.index == self.num_elements {
+                break;
+            }
+            idx = self.read_index()?;
+        }
+
+        // Stable, so that duplicate indices keep their order.
+        elements.sort_by_key(|&(idx, ..)| idx);
+        self.buffered = Some(elements.into_iter());
+        Ok(())
+    }
+
+    /// Deserialize the element with index `idx`, recording it in the path.
+    fn deserialize_element<T, F>(&mut self, idx: usize, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut PhpDeserializer<R>) -> Result<T>,
+    {
+        let tracked = self.de.tracks_path();
+        if tracked {
+            self.de.path.push(idx.to_string());
+        }
+        let result = f(&mut *self.de);
+        if tracked {
+            self.de.path.pop();
+        }
+
+        let value = result.map_err(|err| self.de.annotate(err, &idx))?;
+        self.de.values += 1;
+        Ok(value)
+    }
+}
+
+impl<'a, 'de, R> SeqAccess<'de> for ArraySequence<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+
+    fn size_hint(&self) -> Option<usize> {
+        // Entries holding objects may be dropped.
+        if self.de.object_policy == ObjectPolicy::Skip {
+            return None;
+        }
+
+        // Declared counts are only passed on when they have been checked
+        // against the input length, so they cannot cause huge allocations.
+        let buffered = self.buffered.as_ref().map_or(0, ExactSizeIterator::len);
+        self.de
+            .input
+            .input_len
+            .map(|_| self.num_elements - self.index + buffered + self.missing - self.padded)
+    }
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if let Some((idx, offset, data)) = self.buffered.as_mut().and_then(Iterator::next) {
+            return self
+                .deserialize_element(idx, |de| {
+                    de.deserialize_captured(offset, &data, |des| seed.deserialize(des))
+                })
+                .map(Some);
+        }
+
+        let idx = loop {
+            if self.num_elements == self.index {
+                if self.padded == self.missing {
+                    return Ok(None);
+                }
+
+                let idx = self.index + self.padded;
+                self.padded += 1;
+                return seed.deserialize(MissingElement(idx)).map(Some);
+            }
+
+            // Indices are expected in order, as PHP writes arrays built by
+            // appending.
+            let expected = self.index;
+            let idx = self.read_index()?;
+            if idx != expected {
+                if !self.de.reorder_arrays {
+                    return Err(ErrorKind::IndexMismatch {
+                        expected,
+                        actual: idx,
+                    }
+                    .into());
+                }
+                self.buffer_remaining(idx)?;
+                return self.next_element_seed(seed);
+            }
+            if !self.de.skip_object(&idx)? {
+                break idx;
+            }
+        };
+
+        self.deserialize_element(idx, |de| seed.deserialize(de))
+            .map(Some)
+    }
+}
+
+/// Element of a tuple beyond the end of an array, deserializing as `None`.
+struct MissingElement(usize);
+
+impl<'de> Deserializer<'de> for MissingElement {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorKind::MissingElement(self.0).into())
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_none()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Associative array helper.
+#[derive(Debug)]
+struct ArrayMapping<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    num_elements: usize,
+    index: usize,
+    /// Struct fields keys are matched against, empty for plain maps.
+    fields: &'static [&'static str],
+    /// Most recent key as found in the input, only recorded when paths are.
+    key: Option<String>,
+    /// Entries whose key does not match are skipped.
+    filter: Option<KeyFilter>,
+    /// Struct fields found so far and the offsets of their keys, only
+    /// recorded while collecting duplicate fields.
+    seen: Vec<(&'static str, u64)>,
+}
+
+impl<'a, R> ArrayMapping<'a, R> {
+    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
+        ArrayMapping {
+            de,
+            num_elements,
+            index: 0,
+            fields: &[],
+            key: None,
+            filter: None,
+            seen: Vec::new(),
+        }
+    }
+
+    fn with_fields(mut self, fields: &'static [&'static str]) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    fn with_filter(mut self, filter: Option<KeyFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+impl<'a, R> ArrayMapping<'a, R>
+where
+    R: Input,
+{
+    /// Read a string key into `scratch` and pass it on to `seed`.
+    fn string_key<'de, K>(&mut self, scratch: &mut Vec<u8>, seed: K) -> Result<K::Value>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.de.input.expect_type(b's')?;
+        self.de.input.expect(b':')?;
+        self.de.input.read_key_string_into(scratch)?;
+        self.visit_string_key(scratch, seed)
+    }
+
+    /// Pass a string key read into `scratch` on to `seed`.
+    fn visit_string_key<'de, K>(&mut self, scratch: &[u8], seed: K) -> Result<K::Value>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        // Actual UTF-8 strings are not a thing in PHP, but keys are converted
+        // as a convenience. Conversion only fails once the key is
+        // deserialized as text, so that keys can be kept as bytes.
+        let text = match std::str::from_utf8(scratch) {
+            Ok(key) => Ok(Cow::Borrowed(key)),
+            Err(_) if self.de.latin1_fallback => Ok(Cow::Owned(latin1(scratch))),
+            Err(err) => Err(err),
+        };
+
+        if self.de.records_keys() {
+            self.key = Some(match &text {
+                Ok(key) => key.clone().into_owned(),
+                Err(_) => String::from_utf8_lossy(scratch).into_owned(),
+            });
+        }
+        let text = text.map(|key| match key {
+            Cow::Borrowed(key) => Cow::Borrowed(self.de.match_field(key, self.fields)),
+            Cow::Owned(key) => Cow::Owned(self.de.match_field(&key, self.fields).to_owned()),
+        });
+
+        seed.deserialize(StringKey { raw: scratch, text })
+    }
+
+    /// Read keys into `scratch` until one is selected by [`keeps`], skipping
+    /// the values of all others, and pass it on to `seed`.
+    ///
+    /// [`keeps`]: ArrayMapping::keeps
+    fn selected_key<'de, K>(&mut self, scratch: &mut Vec<u8>, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let integer = loop {
+            if self.index == self.num_elements {
+                return Ok(None);
+            }
+
+            let offset = self.de.position();
+            let integer = match self.de.input.read_type()? {
+                b'i' => true,
+                b's' => false,
+                c => return Err(ErrorKind::UnsupportedArrayKeyType(char::from(c)).into()),
+            };
+            self.de.input.expect(b':')?;
+
+            if integer {
+                scratch.clear();
+                scratch.extend_from_slice(self.de.input.read_int_text()?.as_bytes());
+            } else {
+                self.de.input.read_key_string_into(scratch)?;
+            }
+
+            if !self.keeps(integer, scratch, offset) {
+                self.de.skip_value()?;
+            } else if !self.de.skip_object(&String::from_utf8_lossy(scratch))? {
+                break integer;
+            }
+
+            self.index += 1;
+        };
+
+        if !integer {
+            return self.visit_string_key(scratch, seed).map(Some);
+        }
+
+        if self.de.records_keys() {
+            self.key = Some(String::from_utf8_lossy(scratch).into_owned());
+        }
+
+        if self.de.positional_structs && !self.fields.is_empty() {
+            let key = field_at(self.fields, scratch).map_or_else(
+                || String::from_utf8_lossy(scratch).into_owned(),
+                str::to_owned,
+            );
+            return seed.deserialize(key.into_deserializer()).map(Some);
+        }
+
+        seed.deserialize(IntKey(parse_bytes(scratch)?)).map(Some)
+    }
+
+    /// Check whether the entry with `key`, found at `offset`, is passed on.
+    ///
+    /// Map entries are subject to the key filter. Struct entries not
+    /// matching any field are recorded as unknown, and those repeating a
+    /// field as duplicates, if enabled.
+    fn keeps(&mut self, integer: bool, key: &[u8], offset: u64) -> bool {
+        if let Some(filter) = &self.filter {
+            return filter.matches(key);
+        }
+
+        if self.fields.is_empty()
+            || (self.de.unknown_keys.is_none() && self.de.duplicate_fields.is_none())
+        {
+            return true;
+        }
+
+        let field = if integer {
+            field_at(self.fields, key).filter(|_| self.de.positional_structs)
+        } else {
+            let matched = match std::str::from_utf8(key) {
+                Ok(key) => Cow::Borrowed(self.de.match_field(key, self.fields)),
+                Err(_) if self.de.latin1_fallback => {
+                    let key = latin1(key);
+                    Cow::Owned(self.de.match_field(&key, self.fields).to_owned())
+                }
+                // Passed on, to fail unless deserialized as bytes.
+                Err(_) => return true,
+            };
+            self.fields.iter().find(|field| **field == matched).copied()
+        };
+
+        let field = match field {
+            Some(field) => field,
+            None if self.de.unknown_keys.is_some() => {
+                self.de.record_unknown_key(key, offset);
+                return false;
+            }
+            None => return true,
+        };
+
+        if self.de.duplicate_fields.is_some() {
+            if let Some(&(_, used_offset)) = self.seen.iter().find(|(seen, _)| *seen == field) {
+                self.de.record_duplicate_field(key, offset, used_offset);
+                return false;
+            }
+            self.seen.push((field, offset));
+        }
+        true
+    }
+}
+
+/// Deserializer for a string key.
+///
+/// Keys are passed on as text, matched against struct fields, except to
+/// byte containers such as `Vec<u8>`, which receive the raw bytes, and to
+/// integers, which accept keys holding decimal integers.
+struct StringKey<'k> {
+    raw: &'k [u8],
+    /// The key as text, if it could be converted.
+    text: std::result::Result<Cow<'k, str>, std::str::Utf8Error>,
+}
+
+impl<'k> StringKey<'k> {
+    fn text(self) -> Result<Cow<'k, str>> {
+        self.text.map_err(|err| ErrorKind::Utf8Error(err).into())
+    }
+}
+
+/// Integer deserialization of string keys holding decimal integers, such as
+/// `"42"`. Other keys are rejected as strings.
+macro_rules! deserialize_key_integer {
+    ($($method:ident => $ty:ty, $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self.raw_integer::<$ty>() {
+                    Some(n) => visitor.$visit(n),
+                    None => self.visit_text(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl StringKey<'_> {
+    /// The key as an integer, if it holds one in decimal notation.
+    ///
+    /// Like PHP, only keys written the way PHP writes integers qualify, so
+    /// `"007"` and `"-0"` remain strings.
+    fn raw_integer<T: std::str::FromStr>(&self) -> Option<T> {
+        if !lex::is_canonical_int(self.raw) {
+            return None;
+        }
+        std::str::from_utf8(self.raw).ok()?.parse().ok()
+    }
+
+    /// Passes the key on as text, failing if it is not valid UTF-8.
+    fn visit_text<'de, V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_str(&self.text()?)
+    }
+}
+
+impl<'de, 'k> Deserializer<'de> for StringKey<'k> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.text {
+            Ok(text) => visitor.visit_str(&text),
+            Err(_) => visitor.visit_bytes(self.raw),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_text(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_text(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_text(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_bytes(self.raw)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.raw.to_vec())
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(SeqDeserializer::<_, Error>::new(self.raw.iter().copied()))
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let text = self.text()?;
+        IntoDeserializer::<Error>::into_deserializer(text.as_ref())
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    deserialize_key_integer! {
+        deserialize_i8 => i8, visit_i8,
+        deserialize_i16 => i16, visit_i16,
+        deserialize_i32 => i32, visit_i32,
+        deserialize_i64 => i64, visit_i64,
+        deserialize_i128 => i128, visit_i128,
+        deserialize_u8 => u8, visit_u8,
+        deserialize_u16 => u16, visit_u16,
+        deserialize_u32 => u32, visit_u32,
+        deserialize_u64 => u64, visit_u64,
+        deserialize_u128 => u128, visit_u128,
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char option unit unit_struct tuple tuple_struct map
+        struct ignored_any
+    }
+}
+
+/// Deserializer for an integer key.
+///
+/// Keys are passed on as integers, except to strings and byte containers,
+/// which receive the decimal text. PHP itself does not distinguish the two
+/// for keys.
+struct IntKey(i64);
+
+impl<'de> Deserializer<'de> for IntKey {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i64(self.0)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.0.to_string())
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.0.to_string())
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.0.to_string().into_bytes())
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.0.to_string().into_bytes())
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char option
+        unit unit_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+/// Field referred to by an integer key, given as decimal text.
+fn field_at(fields: &'static [&'static str], key: &[u8]) -> Option<&'static str> {
+    let idx: usize = std::str::from_utf8(key).ok()?.parse().ok()?;
+    fields.get(idx).copied()
+}
+
+impl<'a, 'de, R> MapAccess<'de> for ArrayMapping<'a, R>
+where
+    R: Input,
+{
+    type Error = Error;
+
+    fn size_hint(&self) -> Option<usize> {
+        // Most entries may be filtered out, so the count is no useful hint.
+        if self.filter.is_some() || self.de.object_policy == ObjectPolicy::Skip {
+            return None;
+        }
+
+        // See `ArraySequence::size_hint`.
+        self.de
+            .input
+            .input_len
+            .map(|_| self.num_elements - self.index)
+    }
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        // We are keeping count, so no need to check for end delimiting symbols.
+        if self.index == self.num_elements {
+            return Ok(None);
+        }
+
+        if self.filter.is_some()
+            || self.de.unknown_keys.is_some()
+            || self.de.duplicate_fields.is_some()
+            || self.de.object_policy == ObjectPolicy::Skip
+        {
+            let mut scratch = self.de.take_scratch();
+            let result = self.selected_key(&mut scratch, seed);
+            self.de.set_scratch(scratch);
+            return result;
+        }
+
+        // Keys can be integers or strings.
+        if let Some(b'i') = self.de.input.peek_type()? {
+            if self.de.positional_structs && !self.fields.is_empty() {
+                // Numeric keys refer to fields by position.
+                let idx = i64::deserialize(&mut *self.de)?;
+                if self.de.input.strict {
+                    self.key = Some(idx.to_string());
+                }
+                let key = usize::try_from(idx)
+                    .ok()
+                    .and_then(|idx| self.fields.get(idx))
+                    .map_or_else(|| idx.to_string(), |field| (*field).to_owned());
+                return seed.deserialize(key.into_deserializer()).map(Some);
+            }
+
+            let idx = i64::deserialize(&mut *self.de)?;
+            if self.de.input.strict {
+                self.key = Some(idx.to_string());
+            }
+            return seed.deserialize(IntKey(idx)).map(Some);
+        }
+
+        // We need to hint that we are deserializing a string, since PHP
+        // strings are not fit to be keys. For this reason, we perform the
+        // deserialization here, reusing the scratch buffer:
+        let mut scratch = self.de.take_scratch();
+        let result = self.string_key(&mut scratch, seed);
+        self.de.set_scratch(scratch);
+        result.map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.index += 1;
+
+        let tracked = self.de.tracks_path();
+        if tracked {
+            self.de.path.push(self.key.clone().unwrap_or_default());
+        }
+        let result = seed.deserialize(&mut *self.de);
+        if tracked {
+            self.de.path.pop();
+        }
+
+        let value = match result {
+            Ok(value) => value,
+            Err(err) => {
+                let key = self.key.take().unwrap_or_default();
+                return Err(self.de.annotate(err, &key));
+            }
+        };
+        self.de.values += 1;
+        Ok(value)
+    }
+}
+
+/// Helper to deserialize a PHP array where the keys might be out of order.
+///
+/// ## Caveat
+///
+/// Holes in the array will not be filled in.  The following PHP array
+///
+/// ```php
+/// $arr = array();
+/// $arr[0] = "zero";
+/// $arr[2] = "two";
+/// $arr[1] = "one";
+/// $arr[6] = "six";
+/// ```
+///
+/// will be deserialized to a Rust `Vec` with the four elements
+/// "zero", "one", "two", and "six".
+pub fn deserialize_unordered_array<'de, T, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    // Serialize into a map and return a Vec ordered by the keys.
+    let v = BTreeMap::<usize, T>::deserialize(deserializer)?;
+    Ok(v.into_values().collect())
+}
+
+/// Largest number of missing entries `deserialize_filled_array` fills in.
+const MAX_FILLED_HOLES: usize = 64 * 1024;
+
+/// Helper to deserialize a PHP array where the keys might be out of order,
+/// filling holes with `T::default()`.
+///
+/// Unlike [`deserialize_unordered_array`], every value ends up at the
+/// position given by its key, which suits fixed-slot data such as weekday
+/// or hourly buckets. The resulting `Vec` extends up to the largest key. The
+/// following PHP array
+///
+/// ```php
+/// $arr = array();
+/// $arr[3] = 30;
+/// $arr[0] = 0;
+/// ```
+///
+/// is deserialized to `vec![0, 0, 0, 30]`. To guard against bogus keys, at
+/// most 65536 missing entries are filled in; more result in an error.
+pub fn deserialize_filled_array<'de, T, D>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    let v = BTreeMap::<usize, T>::deserialize(deserializer)?;
+
+    let len = v.keys().next_back().map_or(0, |max| max + 1);
+    if len - v.len() > MAX_FILLED_HOLES {
+        return Err(serde::de::Error::custom(format!(
+            "array with {} entries has too many holes up to key {}",
+            v.len(),
+            len - 1
+        )));
+    }
+
+    let mut filled = Vec::with_capacity(len);
+    for (idx, value) in v {
+        filled.resize_with(idx, T::default);
+        filled.push(value);
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        deserialize_filled_array, deserialize_unordered_array, from_bytes, from_reader, KeyFilter,
+        KeyMatching, ObjectPolicy, PhpDeserializer, PhpDeserializerBuilder, Preset, Profile,
+        ReferencePolicy,
+    };
+    use crate::error::ErrorKind;
+    use crate::value::PhpValue;
+    use crate::version::FormatVersion;
+    use serde::de::IgnoredAny;
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    macro_rules! assert_deserializes {
+        ($ty:ty, $input:expr, $expected:expr) => {
+            // TODO: It's not feasible to infer the type here, compare
+            //       `deserialize_php_string` and `deserialize_string`.gi
+            let actual: $ty = from_bytes($input).expect("deserialization failed");
+            assert_eq!(actual, $expected);
+        };
+    }
+
+    #[test]
+    fn deserialize_bool() {
+        assert_deserializes!(bool, b"b:0;", false);
+        assert_deserializes!(bool, b"b:1;", true);
+    }
+
+    #[test]
+    fn deserialize_bool_invalid() {
+        let err = from_bytes::<bool>(b"b:2;").unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidBooleanValue('2') => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(err.offset(), None);
+        assert_eq!(err.path(), None);
+    }
+
+    #[test]
+    fn deserialize_integer() {
+        assert_deserializes!(i64, b"i:-1;", -1);
+        assert_deserializes!(i64, b"i:0;", 0);
+        assert_deserializes!(i64, b"i:1;", 1);
+        assert_deserializes!(i64, b"i:123;", 123);
+    }
+
+    #[test]
+    fn deserialize_float() {
+        assert_deserializes!(f64, b"d:-1;", -1.0);
+        assert_deserializes!(f64, b"d:0;", 0.0);
+        assert_deserializes!(f64, b"d:1;", 1.0);
+        assert_deserializes!(f64, b"d:-1.9;", -1.9);
+        assert_deserializes!(f64, b"d:0.9;", 0.9);
+        assert_deserializes!(f64, b"d:1.9;", 1.9);
+        assert_deserializes!(f64, b"d:1.0E+25;", 1e25);
+        assert_deserializes!(f64, b"d:-1.5E-7;", -1.5e-7);
+        assert_deserializes!(f64, b"d:INF;", f64::INFINITY);
+        assert_deserializes!(f64, b"d:-INF;", f64::NEG_INFINITY);
+        assert!(from_bytes::<f64>(b"d:NAN;").unwrap().is_nan());
+    }
+
+    #[test]
+    fn deserialize_f32() {
+        // Written by PHP or this crate, and as the binary value widened to `f64`.
+        assert_deserializes!(f32, b"d:0.3;", 0.3);
+        assert_deserializes!(f32, b"d:0.30000001192092896;", 0.3);
+        assert_deserializes!(f32, b"d:3.4028235E+38;", f32::MAX);
+        assert_deserializes!(f32, b"d:3.4028234663852886E+38;", f32::MAX);
+
+        let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+        assert_eq!(strict.deserialize::<f32>(b"d:0.1;").unwrap(), 0.1);
+        assert_eq!(
+            strict
+                .deserialize::<f32>(b"d:0.10000000149011612;")
+                .unwrap(),
+            0.1
+        );
+    }
+
+    #[test]
+    fn deserialize_char() {
+        assert_deserializes!(char, b"i:97;", 'a');
+        assert_deserializes!(char, b"i:128512;", '\u{1F600}');
+        assert_deserializes!(char, "s:4:\"\u{1F600}\";".as_bytes(), '\u{1F600}');
+        assert_deserializes!(char, b"s:1:\"\n\";", '\n');
+
+        let input = b"a:2:{i:0;i:97;i:1;i:55296;}";
+        let err = from_bytes::<Vec<char>>(input).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidCodepoint(0xD800)));
+        assert_eq!(err.offset(), Some(18));
+        assert_eq!(
+            err.to_string(),
+            "Code point 55296 (`0xD800`) is not a valid Unicode scalar value. (at offset 18)"
+        );
+
+        let err = from_bytes::<char>(b"i:4294967296;").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::InvalidCodepoint(0x1_0000_0000)
+        ));
+
+        let err = from_bytes::<char>(br#"s:2:"ab";"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotASingleChar(2)));
+        assert_eq!(err.offset(), Some(0));
+        let err = from_bytes::<char>(br#"s:0:"";"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotASingleChar(0)));
+        assert!(from_bytes::<char>(b"s:1:\"\xff\";").is_err());
+    }
+
+    #[test]
+    fn deserialize_php_string() {
+        assert_deserializes!(
+            Vec<u8>,
+            br#"s:14:"single quote '";"#,
+            b"single quote '".to_owned()
+        );
+    }
+
+    #[test]
+    fn deserialize_string_length_exceeds_input() {
+        // Must fail quickly, without attempting to allocate 4 GB first.
+        let err = from_bytes::<Vec<u8>>(br#"s:4000000000:"short";"#).unwrap_err();
+        match err.kind() {
+            ErrorKind::UnexpectedEof => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let err = from_bytes::<String>(br#"s:70000:"short";"#).unwrap_err();
+        match err.kind() {
+            ErrorKind::UnexpectedEof => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserialize_long_string() {
+        let payload = "x".repeat(3 * 64 * 1024 + 17);
+        let input = format!(r#"s:{}:"{}";"#, payload.len(), payload);
+        assert_deserializes!(String, input.as_bytes(), payload);
+    }
+
+    #[test]
+    fn deserialize_string() {
+        assert_deserializes!(
+            String,
+            br#"s:14:"single quote '";"#,
+            "single quote '".to_owned()
+        );
+    }
+
+    #[test]
+    fn deserialize_escaped_strings() {
+        assert_deserializes!(String, br#"S:5:"a\0a\22b\5C";"#, "a\n\"b\\".to_owned());
+        assert_deserializes!(String, br#"S:0:"";"#, String::new());
+
+        // As keys, behind references and as raw bytes.
+        let input = br#"a:2:{S:3:"\6Bey";S:2:"\ff\00";S:1:"k";R:2;}"#;
+        let map: HashMap<String, serde_bytes::ByteBuf> = from_bytes(input).unwrap();
+        assert_eq!(map["key"].as_slice(), b"\xff\x00");
+        assert_eq!(map["k"].as_slice(), b"\xff\x00");
+
+        let err = from_bytes::<String>(br#"S:2:"a\0g";"#).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::ExpectedDigit { actual: 'g' }
+        ));
+        assert_eq!(err.offset(), Some(8));
+        assert!(from_bytes::<String>(br#"S:2:"a\0";"#).is_err());
+        assert!(from_bytes::<String>(br#"S:1:"ab";"#).is_err());
+
+        // PHP no longer writes them.
+        let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+        assert!(strict.deserialize::<String>(br#"S:1:"a";"#).is_err());
+
+        let value = PhpValue::parse(br#"a:1:{i:0;S:2:"\ffx";}"#).unwrap();
+        assert_eq!(value[0].as_bytes(), Some(&b"\xffx"[..]));
+    }
+
+    #[test]
+    fn deserialize_array() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct SubData();
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Data(Vec<u8>, Vec<u8>, SubData);
+
+        assert_deserializes!(
+            Data,
+            br#"a:3:{i:0;s:4:"user";i:1;s:0:"";i:2;a:0:{}}"#,
+            Data(b"user".to_vec(), b"".to_vec(), SubData())
+        );
+    }
+
+    #[test]
+    fn deserialize_array_unordered() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<f64>);
+
+        let expected = Data(vec![1.1, 2.2, 3.3, 4.4]);
+
+        assert_deserializes!(
+            Data,
+            br#"a:4:{i:1;d:2.2;i:0;d:1.1;i:3;d:4.4;i:2;d:3.3;}"#,
+            expected
+        );
+    }
+
+    #[test]
+    fn deserialize_array_unordered_with_holes() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<String>);
+
+        let expected = Data(vec![
+            "zero".to_string(),
+            "one".to_string(),
+            "two".to_string(),
+            "six".to_string(),
+        ]);
+
+        assert_deserializes!(
+            Data,
+            br#"a:4:{i:0;s:4:"zero";i:2;s:3:"two";i:1;s:3:"one";i:6;s:3:"six";}"#,
+            expected
+        );
+    }
+
+    #[test]
+    fn deserialize_reorder_arrays() {
+        let builder = PhpDeserializerBuilder::new().reorder_arrays(true);
+
+        let input = br#"a:4:{i:0;s:4:"zero";i:2;s:3:"two";i:1;s:3:"one";i:6;s:3:"six";}"#;
+        assert_eq!(
+            builder.deserialize::<Vec<String>>(input).unwrap(),
+            ["zero", "one", "two", "six"]
+        );
+        assert!(matches!(
+            from_bytes::<Vec<String>>(input).unwrap_err().kind(),
+            ErrorKind::IndexMismatch {
+                expected: 1,
+                actual: 2
+            }
+        ));
+
+        // References count values in the order they were written.
+        let input = b"a:2:{i:0;a:2:{i:1;i:5;i:0;i:6;}i:1;R:3;}";
+        assert_eq!(
+            builder.deserialize::<(Vec<i32>, i32)>(input).unwrap(),
+            (vec![6, 5], 5)
+        );
+
+        // Errors point into the input, or at the element if they carry no
+        // offset themselves.
+        let strict = builder
+            .clone()
+            .profile(Profile::Strict)
+            .reorder_arrays(true);
+        let input = b"a:2:{i:1;a:1:{i:0;i:2;}i:0;a:1:{i:0;i:300;}}";
+        let err = strict.deserialize::<Vec<Vec<u8>>>(input).unwrap_err();
+        assert_eq!(err.offset(), Some(36));
+        assert_eq!(err.path(), Some("0.0"));
+        let err = strict.deserialize::<Vec<Vec<bool>>>(input).unwrap_err();
+        assert_eq!(err.offset(), Some(27));
+    }
+
+    #[test]
+    fn deserialize_array_filled_holes() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data(#[serde(deserialize_with = "deserialize_filled_array")] Vec<u32>);
+
+        assert_deserializes!(
+            Data,
+            br#"a:3:{i:6;i:60;i:0;i:1;i:2;i:20;}"#,
+            Data(vec![1, 0, 20, 0, 0, 0, 60])
+        );
+        assert_deserializes!(Data, b"a:0:{}", Data(vec![]));
+
+        let err = from_bytes::<Data>(b"a:1:{i:100000000;i:1;}").unwrap_err();
+        assert!(err.to_string().contains("too many holes"));
+    }
+
+    #[test]
+    fn deserialize_array_count_exceeds_input() {
+        let err = from_bytes::<Vec<u8>>(br#"a:1000000:{i:0;i:1;}"#).unwrap_err();
+        match err.kind() {
+            ErrorKind::ElementCountExceedsInput {
+                declared: 1_000_000,
+                remaining: 9,
+            } => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(err.offset(), Some(2));
+
+        // Tightest possible fit is still accepted.
+        assert_deserializes!(Vec<Option<u8>>, br#"a:2:{i:0;N;i:1;N;}"#, vec![None, None]);
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        // PHP equiv:
+        //
+        // array("foo" => true,
+        //       "bar" => "xyz",
+        //       "sub" => array("x" => 42))
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Outer {
+            foo: bool,
+            bar: String,
+            sub: Inner,
+        }
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Inner {
+            x: i64,
+        }
+
+        assert_deserializes!(
+            Outer,
+            br#"a:3:{s:3:"foo";b:1;s:3:"bar";s:3:"xyz";s:3:"sub";a:1:{s:1:"x";i:42;}}"#,
+            Outer {
+                foo: true,
+                bar: "xyz".to_owned(),
+                sub: Inner { x: 42 },
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_struct_with_optional() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Location {
+            province: Option<String>,
+            postalcode: Option<String>,
+            country: Option<String>,
+        }
+
+        assert_deserializes!(
+            Location,
+            br#"a:0:{}"#,
+            Location {
+                province: None,
+                postalcode: None,
+                country: None,
+            }
+        );
+        assert_deserializes!(
+            Location,
+            br#"a:1:{s:8:"province";s:29:"Newfoundland and Labrador, CA";}"#,
+            Location {
+                province: Some("Newfoundland and Labrador, CA".to_owned()),
+                postalcode: None,
+                country: None,
+            }
+        );
+        assert_deserializes!(
+            Location,
+            br#"a:2:{s:10:"postalcode";s:5:"90002";s:7:"country";s:24:"United States of America";}"#,
+            Location {
+            province: None,
+            postalcode: Some("90002".to_owned()),
+            country: Some("United States of America".to_owned()),
+        }
+        );
+    }
+
+    #[test]
+    fn deserialize_nested() {
+        // PHP: array("x" => array("inner" => 1), "y" => array("inner" => 2))
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Outer {
+            x: Inner,
+            y: Inner,
+        }
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Inner {
+            inner: u8,
+        }
+
+        assert_deserializes!(
+            Outer,
+            br#"a:2:{s:1:"x";a:1:{s:5:"inner";i:1;}s:1:"y";a:1:{s:5:"inner";i:2;}}"#,
+            Outer {
+                x: Inner { inner: 1 },
+                y: Inner { inner: 2 },
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_variable_length() {
+        // PHP: array(1.1, 2.2, 3.3, 4.4)
+        assert_deserializes!(
+            Vec<f64>,
+            br#"a:4:{i:0;d:1.1;i:1;d:2.2;i:2;d:3.3;i:3;d:4.4;}"#,
+            vec![1.1, 2.2, 3.3, 4.4]
+        );
+    }
+
+    #[test]
+    fn deserialize_hashmap() {
+        // PHP: array("foo" => 1, "bar" => 2)
+        let mut expected = HashMap::new();
+        expected.insert("foo".to_owned(), 1);
+        expected.insert("bar".to_owned(), 2);
+
+        assert_deserializes!(HashMap<String, u16>, br#"a:2:{s:3:"foo";i:1;s:3:"bar";i:2;}"#, expected);
+    }
+
+    #[test]
+    fn deserialize_str() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Named {
+            name: Box<str>,
+        }
+
+        assert_deserializes!(
+            Named,
+            br#"a:1:{s:4:"name";s:3:"bob";}"#,
+            Named { name: "bob".into() }
+        );
+    }
+
+    #[test]
+    fn from_reader_consumes_exactly() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Frame {
+            id: u32,
+            name: String,
+            score: Option<f64>,
+        }
+
+        let frame = br#"a:3:{s:2:"id";i:1;s:4:"name";s:2:"ab";s:5:"score";d:0.5;}"#;
+        let mut input = std::io::Cursor::new(
+            [
+                &frame[..],
+                b"N;",
+                b"s:3:\"xyz\";",
+                b"i:-7;",
+                b"b:1;",
+                b"d:1.5E+3;",
+                b"|",
+            ]
+            .concat(),
+        );
+
+        let value: Frame = from_reader(&mut input).unwrap();
+        assert_eq!(value.name, "ab");
+        assert_eq!(input.position(), frame.len() as u64);
+
+        assert_eq!(from_reader::<_, Option<u8>>(&mut input).unwrap(), None);
+        assert_eq!(from_reader::<_, String>(&mut input).unwrap(), "xyz");
+        assert_eq!(from_reader::<_, i64>(&mut input).unwrap(), -7);
+
+        let mut des = PhpDeserializer::from_reader(&mut input);
+        assert!(bool::deserialize(&mut des).unwrap());
+        assert_eq!(des.lookahead(), None);
+        assert_eq!(f64::deserialize(&mut des).unwrap(), 1500.0);
+
+        // Peeking at the framing takes it from the reader, but hands it back.
+        assert_eq!(des.peek().unwrap(), Some(b'|'));
+        assert_eq!(des.lookahead(), Some(b'|'));
+        let (reader, lookahead) = des.into_inner();
+        assert_eq!(lookahead, Some(b'|'));
+        assert_eq!(reader.position(), reader.get_ref().len() as u64);
+    }
+
+    #[test]
+    fn deserialize_key_filter() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Options {
+            version: u8,
+            options: HashMap<String, i64>,
+        }
+
+        let builder = PhpDeserializerBuilder::new().key_filter(KeyFilter::prefix("wp_"));
+        let input = br#"a:2:{s:7:"version";i:2;s:7:"options";a:4:{s:5:"wp_ab";i:1;s:4:"blog";a:1:{i:0;N;}i:5;s:1:"x";s:5:"wp_cd";i:2;}}"#;
+        let options: Options = builder.deserialize(input).unwrap();
+
+        // Struct fields are not filtered, skipped values need not match the map type.
+        assert_eq!(options.version, 2);
+        assert_eq!(options.options.len(), 2);
+        assert_eq!(options.options["wp_cd"], 2);
+
+        // Integer keys are matched by their decimal representation.
+        let builder = PhpDeserializerBuilder::new().key_filter(KeyFilter::new(|key| key.len() > 1));
+        let input = br#"a:4:{i:1;s:1:"a";i:10;s:1:"b";i:-2;s:1:"c";s:1:"x";s:1:"d";}"#;
+        let map: BTreeMap<i64, String> = builder.deserialize(input).unwrap();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [(-2, "c".to_owned()), (10, "b".to_owned())]
+        );
+
+        // Skipped values are still validated.
+        let input = br#"a:2:{s:1:"x";a:1:{i:0;x:1;}s:5:"wp_ab";i:1;}"#;
+        let builder = PhpDeserializerBuilder::new().key_filter(KeyFilter::prefix("wp_"));
+        assert!(builder.deserialize::<HashMap<String, i64>>(input).is_err());
+    }
+
+    #[test]
+    fn deserialize_byte_keys() {
+        // Keys with a quote, a NUL and invalid UTF-8.
+        let input = b"a:3:{s:3:\"a\"b\";i:1;s:2:\"\x00x\";i:2;s:1:\"\xff\";i:3;}";
+
+        let map: HashMap<Vec<u8>, u8> = from_bytes(input).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&b"a\"b"[..]], 1);
+        assert_eq!(map[&b"\x00x"[..]], 2);
+        assert_eq!(map[&b"\xff"[..]], 3);
+
+        let map: BTreeMap<Vec<u8>, u8> = from_bytes(input).unwrap();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [
+                (b"\x00x".to_vec(), 2),
+                (b"a\"b".to_vec(), 1),
+                (b"\xff".to_vec(), 3)
+            ]
+        );
+
+        let err = from_bytes::<HashMap<String, u8>>(input).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Utf8Error(_)));
+
+        let bytes: HashMap<serde_bytes::ByteBuf, u8> = from_bytes(input).unwrap();
+        assert_eq!(bytes[&serde_bytes::ByteBuf::from(b"\xff".to_vec())], 3);
+
+        // Integer keys as their decimal text.
+        let bytes: HashMap<serde_bytes::ByteBuf, u8> = from_bytes(b"a:1:{i:5;i:1;}").unwrap();
+        assert_eq!(bytes[&serde_bytes::ByteBuf::from(b"5".to_vec())], 1);
+    }
+
+    #[test]
+    fn deserialize_key_coercion() {
+        // Integer keys as text.
+        let input = br#"a:2:{i:7;s:1:"a";i:-1;s:1:"b";}"#;
+        let map: BTreeMap<String, String> = from_bytes(input).unwrap();
+        assert_eq!(map["7"], "a");
+        assert_eq!(map["-1"], "b");
+
+        // String keys holding integers as integers.
+        let input = br#"a:3:{s:2:"42";i:1;i:7;i:2;s:2:"-3";i:3;}"#;
+        let map: HashMap<i64, u8> = from_bytes(input).unwrap();
+        assert_eq!(map[&42], 1);
+        assert_eq!(map[&7], 2);
+        assert_eq!(map[&-3], 3);
+        assert!(from_bytes::<HashMap<u64, u8>>(input).is_err());
+        assert!(from_bytes::<HashMap<i64, u8>>(br#"a:1:{s:3:"4 2";i:1;}"#).is_err());
+        assert!(from_bytes::<HashMap<i64, u8>>(br#"a:1:{s:2:"+4";i:1;}"#).is_err());
+        assert!(from_bytes::<HashMap<i64, u8>>(br#"a:2:{s:3:"007";i:1;i:7;i:2;}"#).is_err());
+        assert!(from_bytes::<HashMap<i64, u8>>(br#"a:1:{s:2:"-0";i:1;}"#).is_err());
+
+        let input = br#"a:2:{s:2:"42";i:1;i:7;i:2;}"#;
+        let map: HashMap<u64, u8> = from_bytes(input).unwrap();
+        assert_eq!(map[&42], 1);
+
+        // Also while collecting unknown keys, which reads keys differently.
+        let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+        let (map, unknown) = strict
+            .deserialize_with_unknown_keys::<BTreeMap<String, u8>>(input)
+            .unwrap();
+        assert_eq!(map["7"], 2);
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn deserialize_with_unknown_keys() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(deny_unknown_fields)]
+        struct User {
+            name: String,
+            tags: HashMap<String, Tag>,
+            friends: Vec<User>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(deny_unknown_fields)]
+        struct Tag {
+            label: String,
+        }
+
+        let input = br#"a:4:{s:4:"name";s:1:"a";s:4:"tags";a:1:{s:1:"x";a:2:{s:5:"label";s:1:"y";s:5:"color";N;}}i:3;b:1;s:7:"friends";a:1:{i:0;a:4:{s:4:"name";s:1:"b";s:4:"tags";a:0:{}s:7:"friends";a:0:{}s:3:"age";i:9;}}}"#;
+        assert!(from_bytes::<User>(input).is_err());
+
+        let (user, unknown) = PhpDeserializerBuilder::new()
+            .deserialize_with_unknown_keys::<User>(input)
+            .unwrap();
+        assert_eq!(user.tags["x"].label, "y");
+        assert_eq!(user.friends[0].name, "b");
+        assert_eq!(
+            unknown
+                .iter()
+                .map(|key| (key.path(), key.offset()))
+                .collect::<Vec<_>>(),
+            [("tags.x.color", 73), ("3", 89), ("friends.0.age", 181)]
+        );
+        assert!(input[181..].starts_with(br#"s:3:"age";"#));
+
+        // Integer keys refer to fields when deserializing positionally.
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(deny_unknown_fields)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        let builder = PhpDeserializerBuilder::new().positional_structs(true);
+        let (point, unknown) = builder
+            .deserialize_with_unknown_keys::<Point>(b"a:3:{i:0;i:1;i:1;i:2;i:2;i:3;}")
+            .unwrap();
+        assert_eq!(point, Point { x: 1, y: 2 });
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].to_string(), "2 at offset 21");
+    }
+
+    #[test]
+    fn deserialize_batches() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Row {
+            id: u32,
+            tags: Vec<String>,
+        }
+
+        let rows: Vec<Vec<u8>> = (0..50)
+            .map(|id| match id % 10 {
+                3 => b"a:1:{s:2:\"id\";}".to_vec(),
+                _ => format!(
+                    r#"A: 2:{{s:2:"id";i:{};s:4:"tags";a:1:{{i:0;s:{}:"{}";}}}}"#,
+                    id,
+                    id.to_string().len(),
+                    id
+                )
+                .into_bytes(),
+            })
+            .collect();
+        let inputs: Vec<&[u8]> = rows.iter().map(Vec::as_slice).collect();
+
+        // Rows are deserialized with the options of the builder.
+        assert!(super::from_bytes_batch::<Row, _>(inputs.clone())
+            .iter()
+            .all(Result::is_err));
+
+        let builder = PhpDeserializerBuilder::new().tolerant(true);
+        let sequential = builder.deserialize_batch::<Row, _>(inputs.clone());
+        assert_eq!(sequential.len(), 50);
+        assert!(sequential[3].is_err());
+        assert_eq!(
+            sequential[11].as_ref().unwrap(),
+            &Row {
+                id: 11,
+                tags: vec!["11".to_owned()]
+            }
+        );
+
+        for threads in &[0, 1, 3, 64] {
+            let parallel = builder.deserialize_batch_parallel::<Row>(&inputs, *threads);
+            assert_eq!(parallel.len(), sequential.len());
+            for (a, b) in parallel.iter().zip(&sequential) {
+                assert_eq!(a.as_ref().ok(), b.as_ref().ok());
+            }
+        }
+        assert!(builder.deserialize_batch_parallel::<Row>(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn deserialize_with_duplicate_fields() {
+        #[derive(Debug, Deserialize)]
+        struct Outer {
+            user: User,
+            id: u8,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct User {
+            name: String,
+        }
+
+        let input = br#"a:3:{s:4:"user";a:2:{s:4:"name";s:1:"a";s:4:"NAME";s:1:"b";}s:2:"id";i:1;s:2:"id";i:2;}"#;
+        assert!(from_bytes::<Outer>(input).is_err());
+
+        let builder = PhpDeserializerBuilder::new().key_matching(KeyMatching::IgnoreCase);
+        let (outer, duplicates) = builder
+            .deserialize_with_duplicate_fields::<Outer>(input)
+            .unwrap();
+        assert_eq!(outer.user.name, "a");
+        assert_eq!(outer.id, 1);
+        assert_eq!(
+            duplicates
+                .iter()
+                .map(|field| (field.path(), field.offset(), field.used_offset()))
+                .collect::<Vec<_>>(),
+            [("user.NAME", 40, 21), ("id", 73, 60)]
+        );
+
+        // Without matching, the key is just unknown.
+        let (_, duplicates) = PhpDeserializerBuilder::new()
+            .deserialize_with_duplicate_fields::<Outer>(input)
+            .unwrap();
+        assert_eq!(duplicates.len(), 1);
+
+        // Keys of maps are passed on, the last entry wins.
+        let input = br#"a:2:{s:1:"x";i:1;s:1:"x";i:2;}"#;
+        let (map, duplicates) = PhpDeserializerBuilder::new()
+            .deserialize_with_duplicate_fields::<HashMap<String, u8>>(input)
+            .unwrap();
+        assert_eq!(map["x"], 2);
+        assert!(duplicates.is_empty());
+    }
+
+    #[test]
+    fn deserialize_object_policy() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Row {
+            id: u32,
+            tags: Vec<String>,
+            owner: Option<BTreeMap<String, String>>,
+        }
+
+        let input = br#"a:3:{s:2:"id";i:7;s:4:"tags";a:3:{i:0;s:1:"a";i:1;O:3:"Tag":0:{}i:2;s:1:"b";}s:5:"owner";O:4:"User":1:{s:4:"name";s:3:"bob";}}"#;
+        assert!(from_bytes::<Row>(input).is_err());
+
+        let skip = PhpDeserializerBuilder::new().object_policy(ObjectPolicy::Skip);
+        let mut des = skip.build(input);
+        let row = Row::deserialize(&mut des).unwrap();
+        assert_eq!(
+            row,
+            Row {
+                id: 7,
+                tags: vec!["a".to_owned(), "b".to_owned()],
+                owner: None,
+            }
+        );
+        let skipped = des.skipped_objects();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(
+            (skipped[0].path(), skipped[0].class(), skipped[0].offset()),
+            ("tags.1", "Tag", 50)
+        );
+        assert_eq!(skipped[1].path(), "owner");
+        assert!(input[skipped[1].offset() as usize..].starts_with(b"O:4:\"User\""));
+
+        // Objects outside of arrays cannot be dropped.
+        assert!(skip.deserialize::<Row>(br#"O:3:"Row":0:{}"#).is_err());
+
+        let as_map = PhpDeserializerBuilder::new().object_policy(ObjectPolicy::AsMap);
+        let input = br#"a:3:{s:2:"id";i:7;s:4:"tags";a:0:{}s:5:"owner";O:4:"User":1:{s:4:"name";s:3:"bob";}}"#;
+        let row: Row = as_map.deserialize(input).unwrap();
+        assert_eq!(row.owner.unwrap()["name"], "bob");
+        let row: Row = as_map
+            .deserialize(br#"O:3:"Row":2:{s:2:"id";i:1;s:4:"tags";a:0:{}}"#)
+            .unwrap();
+        assert_eq!(row.id, 1);
+    }
+
+    #[test]
+    fn deserialize_references() {
+        let input = b"a:2:{i:0;a:1:{i:0;i:1;}i:1;R:2;}";
+        assert_eq!(
+            from_bytes::<Vec<Vec<i64>>>(input).unwrap(),
+            vec![vec![1], vec![1]]
+        );
+        assert_eq!(
+            from_reader::<_, Vec<Vec<i64>>>(&input[..]).unwrap(),
+            vec![vec![1], vec![1]]
+        );
+
+        // `r:` is numbered itself, `R:` is not.
+        let input = br#"a:4:{i:0;s:1:"x";i:1;R:2;i:2;s:1:"y";i:3;R:3;}"#;
+        assert_eq!(
+            from_bytes::<Vec<String>>(input).unwrap(),
+            ["x", "x", "y", "y"]
+        );
+        let input = br#"a:4:{i:0;s:1:"x";i:1;r:2;i:2;s:1:"y";i:3;R:4;}"#;
+        assert_eq!(
+            from_bytes::<Vec<String>>(input).unwrap(),
+            ["x", "x", "y", "y"]
+        );
+
+        // References inside referenced values are resolved as well.
+        let input = br#"a:3:{i:0;a:1:{i:0;s:1:"x";}i:1;a:1:{i:0;R:3;}i:2;R:4;}"#;
+        assert_eq!(
+            from_bytes::<Vec<Vec<String>>>(input).unwrap(),
+            vec![vec!["x"]; 3]
+        );
+
+        let input = br#"a:2:{i:0;O:8:"stdClass":1:{s:1:"a";i:1;}i:1;r:2;}"#;
+        let maps: Vec<BTreeMap<String, i64>> = PhpDeserializerBuilder::new()
+            .std_class_as_map(true)
+            .deserialize(input)
+            .unwrap();
+        assert_eq!(maps[0], maps[1]);
+
+        assert_eq!(
+            from_bytes::<Vec<Option<i64>>>(b"a:2:{i:0;N;i:1;R:2;}").unwrap(),
+            [None, None]
+        );
+        let value = PhpValue::parse(b"a:2:{i:0;b:1;i:1;R:2;}").unwrap();
+        assert_eq!(value[1], PhpValue::Bool(true));
+    }
+
+    #[test]
+    fn deserialize_invalid_references() {
+        for (input, index) in [(&b"a:1:{i:0;R:5;}"[..], 5), (b"a:1:{i:0;R:0;}", 0)] {
+            let err = from_bytes::<Vec<Vec<i64>>>(input).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::InvalidReference(i) if *i == index));
+            assert_eq!(err.offset(), Some(9));
+        }
+
+        let builder = PhpDeserializerBuilder::new().references(ReferencePolicy::Error);
+        let err = builder
+            .deserialize::<Vec<i64>>(b"a:2:{i:0;i:1;i:1;r:2;}")
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnsupportedPhpType {
+                type_indicator: 'r'
+            }
+        ));
+        assert_eq!(err.offset(), Some(17));
+        assert_eq!(builder.options().references, ReferencePolicy::Error);
+
+        // Each level doubles the size of the previous one.
+        let mut input = String::from("a:2:{i:0;i:1;i:1;i:1;}");
+        let levels = 40;
+        for level in 0..levels {
+            let slot = if level == 0 { 2 } else { level + 4 };
+            input += &format!("i:{};a:2:{{i:0;R:{};i:1;R:{};}}", level + 1, slot, slot);
+        }
+        let input = format!("a:{}:{{i:0;{}}}", levels + 1, input);
+        let err = from_bytes::<IgnoredAny>(input.as_bytes()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ReferenceLimitExceeded(_)));
+    }
+
+    #[test]
+    fn deserialize_reference_cycles() {
+        let cycle = |err: &crate::Error| match err.kind() {
+            ErrorKind::ReferenceCycle { offset } => Some(*offset),
+            _ => None,
+        };
+
+        let err = from_bytes::<Vec<IgnoredAny>>(b"a:1:{i:0;R:1;}").unwrap_err();
+        assert_eq!((cycle(&err), err.offset()), (Some(0), Some(9)));
+
+        let input = b"a:2:{i:0;i:1;i:1;a:1:{i:0;R:3;}}";
+        let err = from_bytes::<IgnoredAny>(input).unwrap_err();
+        assert_eq!((cycle(&err), err.offset()), (Some(17), Some(26)));
+        let err = PhpValue::parse(input).unwrap_err();
+        assert_eq!(cycle(&err), Some(17));
 
-        Ok(())
+        // $object->self = $object;
+        let input = br#"O:8:"stdClass":1:{s:4:"self";r:1;}"#;
+        let err = PhpDeserializerBuilder::new()
+            .std_class_as_map(true)
+            .deserialize::<BTreeMap<String, IgnoredAny>>(input)
+            .unwrap_err();
+        assert_eq!((cycle(&err), err.offset()), (Some(0), Some(29)));
     }
 
-    /// Read a `-` or `+` sign into a buffer, if present.
-    fn collect_sign(&mut self, buf: &mut SmallVec<[u8; 32]>) -> Result<()> {
-        match self.peek()? {
-            Some(c @ b'+') | Some(c @ b'-') => {
-                buf.push(c);
-                self.expect(c)?;
-            }
-            _ => (),
+    #[test]
+    fn deserialize_map_limits() {
+        #[derive(Debug, Deserialize)]
+        struct User {
+            name: String,
         }
 
-        Ok(())
-    }
-
-    /// Read raw PHP bytestring from input.
-    fn read_raw_string(&mut self) -> Result<Vec<u8>> {
-        // Thankfully, PHP strings are length-delimited, even though
-        // they strangely enough include quotes as well.
-        let mut buf = SmallVec::new();
-        self.collect_unsigned(&mut buf)?;
-        let length: usize = parse_bytes(buf)?;
+        let builder = PhpDeserializerBuilder::new()
+            .max_map_entries(2)
+            .max_key_length(4);
 
-        // Delim and opening quote:
-        self.expect(b':')?;
-        self.expect(b'"')?;
+        let input = br#"a:2:{s:4:"name";s:3:"bob";s:4:"tags";a:2:{i:0;i:1;i:1;i:2;}}"#;
+        assert_eq!(builder.deserialize::<User>(input).unwrap().name, "bob");
 
-        // Inner string data. Note that this code will happily allocate
-        // up to 4 GB of RAM on the heap.
-        let mut data = vec![0; length];
-        self.read_exact(&mut data)?;
-        debug_assert!(data.len() == length);
+        // Limits apply to nested and skipped arrays, whatever they are deserialized as.
+        let input = br#"a:2:{s:4:"name";s:3:"bob";s:4:"tags";a:3:{i:0;i:1;i:1;i:2;i:2;i:3;}}"#;
+        let err = builder.deserialize::<User>(input).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::TooManyEntries {
+                declared: 3,
+                limit: 2
+            }
+        ));
+        assert!(builder
+            .deserialize::<Vec<u8>>(b"a:3:{i:0;i:1;i:1;i:2;i:2;i:3;}")
+            .is_err());
 
-        // Closing quote.
-        self.expect(b'"')?;
-        self.expect(b';')?;
+        let input = br#"a:1:{s:5:"email";s:0:"";}"#;
+        for err in &[
+            builder.deserialize::<User>(input).unwrap_err(),
+            builder
+                .deserialize::<HashMap<String, String>>(input)
+                .unwrap_err(),
+            builder
+                .deserialize::<(String,)>(br#"a:1:{s:5:"00000";i:0;}"#)
+                .unwrap_err(),
+        ] {
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::KeyTooLong {
+                    length: 5,
+                    limit: 4
+                }
+            ));
+        }
 
-        Ok(data)
+        // Values are not affected, and profiles keep the limits.
+        let strict = builder.profile(Profile::Strict);
+        assert_eq!(
+            strict.deserialize::<String>(br#"s:5:"value";"#).unwrap(),
+            "value"
+        );
+        assert!(strict
+            .deserialize::<Vec<u8>>(b"a:3:{i:0;i:1;i:1;i:2;i:2;i:3;}")
+            .is_err());
     }
 
-    /// Read an array header that follows after the `b"a:"` part.
-    fn read_array_header(&mut self) -> Result<usize> {
-        // Read number of elements.
-        let mut buf = SmallVec::new();
-        self.collect_unsigned(&mut buf)?;
-        let num_elements = parse_bytes(buf)?;
-
-        // Read opening part of array.
-        self.expect(b':')?;
-        self.expect(b'{')?;
+    #[test]
+    fn deserialize_hardened_profile() {
+        #[derive(Debug, Deserialize)]
+        #[serde(untagged)]
+        enum Tree {
+            Leaf(i64),
+            Node(Vec<Tree>),
+        }
 
-        Ok(num_elements)
-    }
+        let hardened = PhpDeserializerBuilder::new().profile(Profile::Hardened);
 
-    /// Read exactly defined number of bytes.
-    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
-        // Bail early on zero-length strings.
-        if buf.is_empty() {
-            return Ok(());
+        // Deep nesting is rejected before it can overflow the stack.
+        let bomb = "a:1:{i:0;".repeat(100_000);
+        for err in &[
+            hardened
+                .deserialize::<serde::de::IgnoredAny>(bomb.as_bytes())
+                .unwrap_err(),
+            hardened.deserialize::<Tree>(bomb.as_bytes()).unwrap_err(),
+            hardened
+                .deserialize::<Vec<Tree>>(bomb.as_bytes())
+                .unwrap_err(),
+        ] {
+            assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(128)));
         }
 
-        // If we have buffered a character, move it to buf.
-        if let Some(c) = self.buffer.take() {
-            buf[0] = c;
-            buf = &mut buf[1..];
+        let nested = format!("{}i:1;{}", "a:1:{i:0;".repeat(128), "}".repeat(128));
+        let mut tree = hardened.deserialize::<Tree>(nested.as_bytes()).unwrap();
+        for _ in 0..128 {
+            tree = match tree {
+                Tree::Node(mut children) => children.remove(0),
+                Tree::Leaf(_) => panic!("too shallow"),
+            };
         }
+        assert!(matches!(tree, Tree::Leaf(1)));
 
-        // We can now read the remainder.
-        self.reader.read_exact(buf).map_err(Error::ReadSerialized)
-    }
-}
+        // Nested serialized data counts towards the depth.
+        let nesting = hardened.clone().nested_unserialize(true).max_depth(2);
+        assert_eq!(
+            nesting
+                .deserialize::<Vec<Vec<u8>>>(br#"a:1:{i:0;s:14:"a:1:{i:0;i:5;}";}"#)
+                .unwrap(),
+            vec![vec![5]]
+        );
+        let err = nesting
+            .deserialize::<Vec<Vec<Vec<u8>>>>(br#"a:1:{i:0;s:24:"a:1:{i:0;a:1:{i:0;i:5;}}";}"#)
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(2)));
 
-/// PHP deserializer.
-///
-/// Deserializes the format used by PHP's `serialize` function.
-#[derive(Debug)]
-pub struct PhpDeserializer<R> {
-    input: Lookahead1<R>,
-}
+        // Lower limits are kept.
+        let lower = PhpDeserializerBuilder::new()
+            .max_depth(4)
+            .profile(Profile::Hardened)
+            .profile(Profile::Hardened);
+        let err = lower.deserialize::<Tree>(bomb.as_bytes()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(4)));
 
-impl<R> PhpDeserializer<R>
-where
-    R: BufRead,
-{
-    fn new(input: R) -> PhpDeserializer<R> {
-        PhpDeserializer {
-            input: Lookahead1::new(input),
+        // Without a known input length, declared sizes do not cause allocations.
+        for input in &[
+            &b"a:65536:{"[..],
+            b"a:1024:{i:0;a:1024:{i:0;a:1024:{",
+            b"s:4294967295:\"",
+            br#"a:1:{s:1024:""#,
+        ] {
+            let mut des = hardened.build_reader(*input);
+            let err = Tree::deserialize(&mut des).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::UnexpectedEof), "{:?}", err);
         }
     }
 
-    fn peek(&mut self) -> Result<Option<u8>> {
-        self.input.peek()
-    }
-}
+    #[test]
+    fn deserialize_tolerant_floats() {
+        let tolerant = PhpDeserializerBuilder::new().tolerant_floats(true);
 
-/// Parse a byte string using any `FromStr` function.
-fn parse_bytes<E, T: std::str::FromStr<Err = E>, B: AsRef<[u8]>>(buf: B) -> Result<T>
-where
-    E: std::fmt::Display + std::error::Error + Send + Sync + 'static,
-{
-    let s = std::str::from_utf8(buf.as_ref()).map_err(Error::Utf8Error)?;
-    s.parse()
-        .map_err(|e: E| Error::NotAValidNumber(Box::new(e)))
-}
+        for &(input, expected) in &[
+            (&b"d:0,5;"[..], 0.5),
+            (b"d:-1,25E+3;", -1250.0),
+            (b"d:,5;", 0.5),
+            (b"d:3,;", 3.0),
+            (b"d:0.10000000000000001;", 0.1),
+            (
+                b"d:3.14159265358979323846264338327950288419716939937510;",
+                std::f64::consts::PI,
+            ),
+        ] {
+            assert_eq!(tolerant.deserialize::<f64>(input).unwrap(), expected);
+        }
 
-impl<'a, 'de, R> Deserializer<'de> for &'a mut PhpDeserializer<R>
-where
-    R: BufRead,
-{
-    type Error = Error;
+        // Over-long mantissas are valid PHP, commas are not.
+        assert_eq!(from_bytes::<f64>(b"d:0.10000000000000001;").unwrap(), 0.1);
+        assert!(from_bytes::<f64>(b"d:0,5;").is_err());
+        assert!(tolerant.deserialize::<f64>(b"d:,;").is_err());
+        assert!(tolerant.deserialize::<f64>(b"d:0,5,1;").is_err());
 
-    #[inline]
-    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        // All fields start with a type, followed by a colon.
-        let sym = self.input.read1()?;
+        let lenient = PhpDeserializerBuilder::new().profile(Profile::Lenient);
+        let values: Vec<f64> = lenient.deserialize(b"a:2:{i:0;d:1,5;i:1;D:2,5;}").unwrap();
+        assert_eq!(values, vec![1.5, 2.5]);
+    }
 
-        if sym == b'N' {
-            // `null` is a special case, since it is not followed by a colon.
-            self.input.expect(b';')?;
-            return visitor.visit_unit();
+    #[test]
+    fn deserialize_reject_floats() {
+        #[derive(Debug, Deserialize)]
+        struct Payment {
+            #[allow(dead_code)]
+            cents: i64,
         }
 
-        self.input.expect(b':')?;
+        let strict = PhpDeserializerBuilder::new()
+            .profile(Profile::Strict)
+            .reject_floats(true);
+        let input = br#"a:1:{i:0;a:2:{s:5:"cents";i:5;s:4:"rate";d:0.5;}}"#;
+        assert!(from_bytes::<Vec<Payment>>(input).is_ok());
+        let err = strict.deserialize::<Vec<Payment>>(input).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::FloatRejected));
+        assert_eq!(err.path(), Some("0.rate"));
+        assert_eq!(err.offset(), Some(41));
 
-        // See https://stackoverflow.com/questions/14297926/structure-of-a-serialized-php-string
-        match sym {
-            b'b' => {
-                let val = self.input.read1()?;
-                self.input.expect(b';')?;
-
-                // Boolean.
-                match val {
-                    b'0' => visitor.visit_bool(false),
-                    b'1' => visitor.visit_bool(true),
-                    c => Err(Error::InvalidBooleanValue(char::from(c))),
-                }
-            }
-            b'i' => {
-                // Integer.
-                let mut buf = SmallVec::new();
+        let builder = PhpDeserializerBuilder::new().reject_floats(true);
+        assert!(builder.deserialize::<IgnoredAny>(b"d:1;").is_err());
+        assert!(builder.deserialize::<i64>(b"i:1;").is_ok());
+        let err = builder
+            .tolerant(true)
+            .deserialize::<IgnoredAny>(b"a:1:{i:0;D:1;}")
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::FloatRejected));
 
-                // Collect a potential sign, followed by the unsigned digits.
-                self.input.collect_sign(&mut buf)?;
-                self.input.collect_unsigned(&mut buf)?;
+        // Profiles leave the option alone.
+        let lenient = PhpDeserializerBuilder::new()
+            .reject_floats(true)
+            .profile(Profile::Lenient);
+        let err = lenient.deserialize::<i64>(b"d:1;").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::FloatRejected));
+    }
 
-                // Terminating semicolon.
-                self.input.expect(b';')?;
+    #[test]
+    fn deserialize_tolerant_tokens() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Record {
+            flag: bool,
+            count: i32,
+            ratio: f64,
+            tags: Vec<String>,
+        }
 
-                // Finally, pass to visitor.
-                visitor.visit_i64(parse_bytes(buf)?)
-            }
-            b'd' => {
-                // Float.
-                let mut buf = SmallVec::new();
+        let input = br#"A:4:{s:4:"flag";B:1;s:5:"count";I: -3;s:5:"ratio";D:	0.5;s:4:"tags";A:1:{I:0;s: 1:"x";}}"#;
+        let expected = Record {
+            flag: true,
+            count: -3,
+            ratio: 0.5,
+            tags: vec!["x".to_owned()],
+        };
 
-                // Same as integer:
-                self.input.collect_sign(&mut buf)?;
-                self.input.collect_unsigned(&mut buf)?;
+        let tolerant = PhpDeserializerBuilder::new().tolerant(true);
+        assert_eq!(tolerant.deserialize::<Record>(input).unwrap(), expected);
+        assert!(from_bytes::<Record>(input).is_err());
 
-                // PHP omits decimal dots when serializing `.0` values.
-                let dot = self.input.peek()?;
+        // Spaces inside string data are preserved.
+        let spaced: String = tolerant.deserialize(br#"s:3:" a ";"#).unwrap();
+        assert_eq!(spaced, " a ");
 
-                if let Some(b'.') = dot {
-                    buf.push(b'.');
-                    self.input.expect(b'.')?;
+        // `S` is not an alias for `s`, but denotes escaped strings.
+        let escaped: String = tolerant.deserialize(br#"S:1:"\78";"#).unwrap();
+        assert_eq!(escaped, "x");
+    }
 
-                    // The remainder is another digit string without sign.
-                    self.input.collect_unsigned(&mut buf)?;
-                }
+    #[test]
+    fn deserialize_profiles() {
+        let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+        let php = PhpDeserializerBuilder::new().profile(Profile::Php);
+        let lenient = PhpDeserializerBuilder::new().profile(Profile::Lenient);
 
-                self.input.expect(b';')?;
+        // Canonical input is accepted by all profiles.
+        for input in &[
+            &b"d:0.5;"[..],
+            b"d:-0;",
+            b"d:1.0E+25;",
+            b"d:1.0E-5;",
+            b"d:-INF;",
+        ] {
+            assert!(strict.deserialize::<f64>(input).is_ok());
+            assert!(php.deserialize::<f64>(input).is_ok());
+        }
+        assert_eq!(strict.deserialize::<i64>(b"i:-10;").unwrap(), -10);
+        assert_eq!(
+            strict
+                .deserialize::<Vec<String>>(br#"a:1:{i:0;s:0:"";}"#)
+                .unwrap(),
+            vec![String::new()]
+        );
 
-                visitor.visit_f64(parse_bytes(buf)?)
-            }
-            b's' => {
-                // PHP String.
+        // PHP accepts, but never produces these.
+        for input in &[
+            &b"d:.5;"[..],
+            b"d:5.;",
+            b"d:1e25;",
+            b"d:1.0E25;",
+            b"d:+1;",
+            b"d:01;",
+        ] {
+            assert!(php.deserialize::<f64>(input).is_ok());
+            assert!(matches!(
+                strict.deserialize::<f64>(input).unwrap_err().kind(),
+                ErrorKind::NonCanonical(_)
+            ));
+        }
+        for input in &[&b"i:+1;"[..], b"i:007;", b"i:-0;"] {
+            assert!(php.deserialize::<i64>(input).is_ok());
+            assert!(strict.deserialize::<i64>(input).is_err());
+        }
+        assert!(strict.deserialize::<String>(br#"s:01:"x";"#).is_err());
+        assert!(strict.deserialize::<Vec<i32>>(b"a:00:{}").is_err());
+        assert_eq!(php.deserialize::<f64>(b"d:.5;").unwrap(), 0.5);
+        assert_eq!(php.deserialize::<f64>(b"d:5.;").unwrap(), 5.0);
+        assert!(php.deserialize::<f64>(b"d:.;").is_err());
 
-                let data = self.input.read_raw_string()?;
+        assert!(php.deserialize::<bool>(b"B:1;").is_err());
+        assert!(lenient.deserialize::<bool>(b"B:1;").unwrap());
+    }
 
-                // We now have the complete bytestring, no further parsing required.
-                visitor.visit_seq(serde::de::value::SeqDeserializer::new(data.into_iter()))
-            }
-            b'a' => {
-                // Array.
-                let num_elements = self.input.read_array_header()?;
+    #[test]
+    fn deserialize_key_matching() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct User {
+            user_id: u32,
+            name: String,
+        }
 
-                // We support two ways of array deserialization: tuple and struct.
-                //
-                // Numeric arrays are deserialized as tuples and assumed to
-                // contain no missing keys.
-                //
-                // Associative arrays must contain only string keys and are
-                // serialized as mappings.
-                //
-                // Other variants are currently not supported and would require
-                // hashmaps and variant types.
+        let input = br#"a:2:{s:7:"USER_ID";i:1;s:4:"Name";s:1:"x";}"#;
+        let camel = br#"a:2:{s:6:"userId";i:1;s:4:"name";s:1:"x";}"#;
+        let expected = User {
+            user_id: 1,
+            name: "x".to_owned(),
+        };
 
-                let rval = match self.input.peek()? {
-                    Some(b'i') | Some(b'}') => {
-                        // Numeric or empty array.
-                        visitor.visit_seq(ArraySequence::new(&mut self, num_elements))
-                    }
-                    Some(b's') => {
-                        // Associative array.
-                        visitor.visit_map(ArrayMapping::new(&mut self, num_elements))
-                    }
-                    Some(c) => Err(Error::UnsupportedArrayKeyType(char::from(c))),
-                    None => return Err(Error::UnexpectedEof),
-                };
-                self.input.expect(b'}')?;
-                rval
-            }
-            b'O' => {
-                // Object.
-                Err(Error::MissingFeature(
-                    "Object deserialization is not implemented, sorry.",
-                ))
-            }
-            // Unknown character, not valid.
-            c => Err(Error::InvalidTypeIndicator(char::from(c))),
-        }
+        let ignore_case = PhpDeserializerBuilder::new().key_matching(KeyMatching::IgnoreCase);
+        let normalize = PhpDeserializerBuilder::new().key_matching(KeyMatching::Normalize);
+
+        assert!(from_bytes::<User>(input).is_err());
+        assert_eq!(ignore_case.deserialize::<User>(input).unwrap(), expected);
+        assert!(ignore_case.deserialize::<User>(camel).is_err());
+        assert_eq!(normalize.deserialize::<User>(camel).unwrap(), expected);
+
+        // Map keys are left alone.
+        let map: HashMap<String, i32> = normalize
+            .deserialize(br#"a:1:{s:6:"UserID";i:1;}"#)
+            .unwrap();
+        assert_eq!(map["UserID"], 1);
     }
 
-    #[inline]
-    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        // Characters are serialized as 32 bit numbers values.
-        self.input.expect(b'i')?;
-        self.input.expect(b':')?;
+    #[test]
+    fn deserialize_positional_structs() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Entry {
+            id: u32,
+            label: String,
+            #[serde(default)]
+            note: Option<String>,
+        }
 
-        let mut buf = SmallVec::new();
-        self.input.collect_unsigned(&mut buf)?;
-        // No sign.
+        let positional = PhpDeserializerBuilder::new().positional_structs(true);
+        let expected = Entry {
+            id: 5,
+            label: "x".to_owned(),
+            note: None,
+        };
 
-        self.input.expect(b';')?;
+        let list = br#"a:2:{i:0;i:5;i:1;s:1:"x";}"#;
+        assert!(from_bytes::<Entry>(list).is_err());
+        assert_eq!(positional.deserialize::<Entry>(list).unwrap(), expected);
 
-        // We parse to a 32 bit unsigned value.
-        let raw: u32 = parse_bytes(&buf)?;
-        visitor.visit_char(char::try_from(raw).map_err(Error::CharConversionFailed)?)
-    }
+        // Associative arrays still work and extra elements are ignored.
+        let assoc = br#"a:2:{s:2:"id";i:5;s:5:"label";s:1:"x";}"#;
+        assert_eq!(positional.deserialize::<Entry>(assoc).unwrap(), expected);
+        let long = br#"a:4:{i:0;i:5;i:1;s:1:"x";i:2;N;i:3;b:1;}"#;
+        assert_eq!(positional.deserialize::<Entry>(long).unwrap(), expected);
 
-    #[inline]
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        self.input.expect(b's')?;
-        self.input.expect(b':')?;
-        // Actual UTF-8 strings are not a thing in PHP, but we offer this conversion
-        // as a convenience.
-        let raw = self.input.read_raw_string()?;
-        visitor.visit_string(String::from_utf8(raw).map_err(|e| Error::Utf8Error(e.utf8_error()))?)
-    }
+        // Negative keys match no field, on either path.
+        let negative = br#"a:3:{i:-1;b:1;i:0;i:5;i:1;s:1:"x";}"#;
+        assert_eq!(positional.deserialize::<Entry>(negative).unwrap(), expected);
+        let (entry, unknown) = positional
+            .deserialize_with_unknown_keys::<Entry>(negative)
+            .unwrap();
+        assert_eq!(entry, expected);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].path(), "-1");
 
-    #[inline]
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        // A `null` value indicates our `None` here.
-        if let Some(b'N') = self.input.peek()? {
-            self.input.expect(b'N')?;
-            self.input.expect(b';')?;
-            visitor.visit_none()
-        } else {
-            // Otherwise, we can parse the actual value.
-            visitor.visit_some(self)
-        }
+        // Sequences are unaffected.
+        assert_eq!(
+            positional
+                .deserialize::<Vec<i32>>(b"a:1:{i:0;i:1;}")
+                .unwrap(),
+            vec![1]
+        );
     }
 
-    #[inline]
-    fn deserialize_struct<V>(self, _name: &str, _fields: &[&str], visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        // We need to explicitly implement struct deserialization to be able
-        // to distinguish between empty numeric arrays and empty associative
-        // arrays.
-        self.deserialize_map(visitor)
-    }
+    #[test]
+    fn deserialize_tuples_with_string_keys() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Pair(String, i32);
 
-    #[inline]
-    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        // Similar to `deserialize_struct`, we need to cover the case of the empty map.
-        self.input.expect(b'a')?;
-        self.input.expect(b':')?;
-        let num_elements = self.input.read_array_header()?;
-        let rval = visitor.visit_map(ArrayMapping::new(&mut self, num_elements));
-        self.input.expect(b'}')?;
+        let input = br#"a:2:{s:1:"0";s:1:"a";s:1:"1";i:2;}"#;
+        assert_eq!(
+            from_bytes::<(String, i32)>(input).unwrap(),
+            ("a".to_owned(), 2)
+        );
+        assert_eq!(from_bytes::<Pair>(input).unwrap(), Pair("a".to_owned(), 2));
+        assert_eq!(
+            from_bytes::<Pair>(br#"a:2:{i:0;s:1:"a";i:1;i:2;}"#).unwrap(),
+            Pair("a".to_owned(), 2)
+        );
 
-        rval
+        assert!(from_bytes::<(i32, i32)>(br#"a:2:{s:1:"1";i:1;s:1:"0";i:2;}"#).is_err());
+        assert!(from_bytes::<(i32, i32)>(br#"a:2:{s:1:"a";i:1;s:1:"b";i:2;}"#).is_err());
     }
 
-    #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_newtype_struct(self)
-    }
+    #[test]
+    fn deserialize_tuples_with_missing_options() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Call(String, Option<i32>, Option<bool>);
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 str
-        bytes byte_buf unit unit_struct seq tuple
-        enum identifier ignored_any tuple_struct
+        let input = br#"a:2:{i:0;s:4:"sort";i:1;i:3;}"#;
+        assert_eq!(
+            from_bytes::<Call>(input).unwrap(),
+            Call("sort".to_owned(), Some(3), None)
+        );
+        let err = from_bytes::<(String, Option<i32>)>(b"a:0:{}").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingElement(0)));
+        assert_eq!(
+            from_bytes::<(Option<u8>, Option<u8>)>(b"a:0:{}").unwrap(),
+            (None, None)
+        );
+
+        // Only `Option`s may be missing.
+        let err = from_bytes::<(String, i32)>(br#"a:1:{i:0;s:1:"a";}"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingElement(1)));
+        // Sequences and arrays that are too long are unaffected.
+        assert_eq!(from_bytes::<Vec<Option<u8>>>(b"a:0:{}").unwrap(), vec![]);
+        assert!(from_bytes::<(Option<u8>,)>(b"a:2:{i:0;N;i:1;N;}").is_err());
     }
-}
 
-/// Numeric array sequence helper.
-#[derive(Debug)]
-struct ArraySequence<'a, R> {
-    de: &'a mut PhpDeserializer<R>,
-    num_elements: usize,
-    index: usize,
-}
+    #[test]
+    fn deserialize_with_aliases() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Post {
+            title: String,
+            author: Author,
+        }
 
-impl<'a, R> ArraySequence<'a, R> {
-    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
-        ArraySequence {
-            de,
-            num_elements,
-            index: 0,
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Author {
+            name: String,
         }
-    }
-}
 
-impl<'a, 'de, R> SeqAccess<'de> for ArraySequence<'a, R>
-where
-    R: BufRead,
-{
-    type Error = Error;
+        let mut aliases = HashMap::new();
+        aliases.insert("post_title", "title");
+        aliases.insert("display_name", "name");
+        // Ignored for structs without a `body` field.
+        aliases.insert("author", "body");
 
-    fn size_hint(&self) -> Option<usize> {
-        Some(self.num_elements - self.index)
+        let builder = PhpDeserializerBuilder::new().aliases(aliases);
+        let input = br#"a:2:{s:10:"post_title";s:2:"Hi";s:6:"author";a:1:{s:12:"display_name";s:3:"Bob";}}"#;
+        assert_eq!(
+            builder.deserialize::<Post>(input).unwrap(),
+            Post {
+                title: "Hi".to_owned(),
+                author: Author {
+                    name: "Bob".to_owned()
+                },
+            }
+        );
+        assert!(from_bytes::<Post>(input).is_err());
+
+        // Aliases also apply after switching profiles.
+        let lenient = builder.profile(Profile::Lenient);
+        assert!(lenient.deserialize::<Post>(input).is_ok());
     }
 
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        if self.num_elements == self.index {
-            return Ok(None);
-        }
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn deserialize_indexmap_preserves_order() {
+        use indexmap::IndexMap;
 
-        // Get the index; we are assuming to have a PHP array in regular
-        // "array style", that is with only numerical keys stored in order.
-        //
-        // TODO: Possibly change this behavior to handle arrays with out-of-order keys.
-        let idx = usize::deserialize(&mut *self.de)?;
-        if idx != self.index {
-            return Err(Error::IndexMismatch {
-                expected: self.index,
-                actual: idx,
-            });
-        }
-        debug_assert_eq!(idx, self.index);
-        self.index += 1;
+        let input = br#"a:3:{s:1:"c";a:2:{i:5;s:1:"x";i:2;s:1:"y";}s:1:"a";a:0:{}s:1:"b";a:1:{i:0;s:1:"z";}}"#;
+        let map: IndexMap<String, IndexMap<i64, String>> = from_bytes(input).unwrap();
 
-        // We can now deserialize the actual value.
-        seed.deserialize(&mut *self.de).map(Some)
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec!["c", "a", "b"]);
+        assert_eq!(map["c"].keys().collect::<Vec<_>>(), vec![&5, &2]);
+        assert!(map["a"].is_empty());
+        assert_eq!(crate::to_vec(&map).unwrap(), input.to_vec());
     }
-}
 
-/// Associative array helper.
-#[derive(Debug)]
-struct ArrayMapping<'a, R> {
-    de: &'a mut PhpDeserializer<R>,
-    num_elements: usize,
-    index: usize,
-}
+    #[test]
+    fn deserialize_null_as_default() {
+        #[derive(Debug, Default, Deserialize, PartialEq)]
+        #[serde(default)]
+        struct Inner {
+            flag: bool,
+        }
 
-impl<'a, R> ArrayMapping<'a, R> {
-    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
-        ArrayMapping {
-            de,
-            num_elements,
-            index: 0,
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Record {
+            flag: bool,
+            count: i64,
+            small: u8,
+            ratio: f32,
+            name: String,
+            bytes: crate::PhpString,
+            list: Vec<i32>,
+            pair: (i32, i32),
+            map: HashMap<String, i32>,
+            inner: Inner,
+            opt: Option<i32>,
         }
-    }
-}
 
-impl<'a, 'de, R> MapAccess<'de> for ArrayMapping<'a, R>
-where
-    R: BufRead,
-{
-    type Error = Error;
+        let input = br#"a:11:{s:4:"flag";N;s:5:"count";N;s:5:"small";N;s:5:"ratio";N;s:4:"name";N;s:5:"bytes";N;s:4:"list";N;s:4:"pair";a:2:{i:0;i:1;i:1;i:2;}s:3:"map";N;s:5:"inner";N;s:3:"opt";N;}"#;
+        assert!(from_bytes::<Record>(input).is_err());
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
-    where
-        K: DeserializeSeed<'de>,
-    {
-        // We are keeping count, so no need to check for end delimiting symbols.
-        if self.index == self.num_elements {
-            return Ok(None);
+        let builder = PhpDeserializerBuilder::new().null_as_default(true);
+        assert_eq!(
+            builder.deserialize::<Record>(input).unwrap(),
+            Record {
+                flag: false,
+                count: 0,
+                small: 0,
+                ratio: 0.0,
+                name: String::new(),
+                bytes: crate::PhpString::new(),
+                list: vec![],
+                pair: (1, 2),
+                map: HashMap::new(),
+                inner: Inner::default(),
+                opt: None,
+            }
+        );
+
+        // Tuples of fixed length cannot be empty.
+        assert!(builder.deserialize::<(i32, i32)>(b"N;").is_err());
+    }
+
+    #[test]
+    fn deserialize_std_class_as_map() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct User {
+            name: String,
+            address: Address,
         }
 
-        // Keys can be integers or strings.
-        if let Some(b'i') = self.de.peek()? {
-            return seed.deserialize(&mut *self.de).map(Some);
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Address {
+            city: String,
         }
 
-        // We need to hint that we are deserializing a string, since PHP
-        // strings are not fit to be keys. For this reason, we perform the
-        // deserialization here:
-        let key = String::deserialize(&mut *self.de)?;
+        let input = br#"O:8:"stdClass":3:{s:4:"name";s:3:"Bob";s:7:"address";O:8:"stdClass":1:{s:4:"city";s:4:"Oslo";}s:5:"extra";O:8:"stdClass":0:{}}"#;
+        let expected = User {
+            name: "Bob".to_owned(),
+            address: Address {
+                city: "Oslo".to_owned(),
+            },
+        };
 
-        // Pass the already deserialized string on.
-        seed.deserialize(key.into_deserializer()).map(Some)
-    }
+        match from_bytes::<User>(input).unwrap_err().kind() {
+            ErrorKind::UnsupportedPhpType { type_indicator } => assert_eq!(*type_indicator, 'O'),
+            other => panic!("unexpected error: {:?}", other),
+        }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
-    where
-        V: DeserializeSeed<'de>,
-    {
-        self.index += 1;
-        seed.deserialize(&mut *self.de)
+        let builder = PhpDeserializerBuilder::new().std_class_as_map(true);
+        assert_eq!(builder.deserialize::<User>(input).unwrap(), expected);
+
+        // Other classes are still rejected.
+        let other = br#"O:4:"User":1:{s:4:"city";s:4:"Oslo";}"#;
+        assert!(builder.deserialize::<Address>(other).is_err());
     }
-}
 
-/// Helper to deserialize a PHP array where the keys might be out of order.
-///
-/// ## Caveat
-///
-/// Holes in the array will not be filled in.  The following PHP array
-///
-/// ```php
-/// $arr = array();
-/// $arr[0] = "zero";
-/// $arr[2] = "two";
-/// $arr[1] = "one";
-/// $arr[6] = "six";
-/// ```
-///
-/// will be deserialized to a Rust `Vec` with the four elements
-/// "zero", "one", "two", and "six".
-pub fn deserialize_unordered_array<'de, T, D>(
-    deserializer: D,
-) -> std::result::Result<Vec<T>, D::Error>
-where
-    D: Deserializer<'de>,
-    T: Deserialize<'de>,
-{
-    // Serialize into a map and return a Vec ordered by the keys.
-    let v = BTreeMap::<usize, T>::deserialize(deserializer)?;
-    Ok(v.into_iter().map(|(_, v)| v).collect())
-}
+    #[test]
+    fn deserialize_enum_cases() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Suit {
+            Hearts,
+            Spades,
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::{deserialize_unordered_array, from_bytes};
-    use serde::Deserialize;
-    use std::collections::HashMap;
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Hand {
+            trump: Suit,
+            lead: Suit,
+            extra: IgnoredAny,
+        }
 
-    macro_rules! assert_deserializes {
-        ($ty:ty, $input:expr, $expected:expr) => {
-            // TODO: It's not feasible to infer the type here, compare
-            //       `deserialize_php_string` and `deserialize_string`.gi
-            let actual: $ty = from_bytes($input).expect("deserialization failed");
-            assert_eq!(actual, $expected);
-        };
-    }
+        let input = br#"a:3:{s:5:"trump";E:11:"Suit:Hearts";s:4:"lead";r:2;s:5:"extra";E:11:"Suit:Spades";}"#;
+        let err = from_bytes::<Hand>(input).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnsupportedPhpType {
+                type_indicator: 'E'
+            }
+        ));
 
-    #[test]
-    fn deserialize_bool() {
-        assert_deserializes!(bool, b"b:0;", false);
-        assert_deserializes!(bool, b"b:1;", true);
-    }
+        let php81 = PhpDeserializerBuilder::new().format_version(FormatVersion::Php81);
+        let hand: Hand = php81.deserialize(input).unwrap();
+        assert_eq!((hand.trump, hand.lead), (Suit::Hearts, Suit::Hearts));
 
-    #[test]
-    fn deserialize_integer() {
-        assert_deserializes!(i64, b"i:-1;", -1);
-        assert_deserializes!(i64, b"i:0;", 0);
-        assert_deserializes!(i64, b"i:1;", 1);
-        assert_deserializes!(i64, b"i:123;", 123);
-    }
+        // Self-describing types see the enum and case name.
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Any {
+            Text(String),
+        }
+        let cases: Vec<Any> = php81
+            .deserialize(br#"a:1:{i:0;E:11:"Suit:Hearts";}"#)
+            .unwrap();
+        assert_eq!(cases, [Any::Text("Suit:Hearts".to_owned())]);
 
-    #[test]
-    fn deserialize_float() {
-        assert_deserializes!(f64, b"d:-1;", -1.0);
-        assert_deserializes!(f64, b"d:0;", 0.0);
-        assert_deserializes!(f64, b"d:1;", 1.0);
-        assert_deserializes!(f64, b"d:-1.9;", -1.9);
-        assert_deserializes!(f64, b"d:0.9;", 0.9);
-        assert_deserializes!(f64, b"d:1.9;", 1.9);
+        let err = php81.deserialize::<Suit>(br#"E:6:"Hearts";"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidEnumCase(name) if name == "Hearts"));
+        assert_eq!(err.offset(), Some(2));
     }
 
     #[test]
-    fn deserialize_php_string() {
-        assert_deserializes!(
-            Vec<u8>,
-            br#"s:14:"single quote '";"#,
-            b"single quote '".to_owned()
+    fn deserialize_unit_structs() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Marker;
+
+        assert_eq!(from_bytes::<Marker>(b"N;").unwrap(), Marker);
+        assert_eq!(from_bytes::<Marker>(b"a:0:{}").unwrap(), Marker);
+        assert_eq!(
+            from_bytes::<Vec<Marker>>(b"a:2:{i:0;N;i:1;a:0:{}}").unwrap(),
+            vec![Marker, Marker]
         );
+        assert!(from_bytes::<Marker>(b"a:1:{i:0;N;}").is_err());
+        assert!(from_bytes::<Marker>(b"i:0;").is_err());
     }
 
     #[test]
-    fn deserialize_string() {
-        assert_deserializes!(
-            String,
-            br#"s:14:"single quote '";"#,
-            "single quote '".to_owned()
+    fn deserialize_unit_variants() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Status {
+            Open,
+            #[serde(rename = "closed")]
+            Closed,
+            Held(u32),
+        }
+
+        let input = br#"a:3:{i:0;s:4:"Open";i:1;s:6:"closed";i:2;N;}"#;
+        assert_eq!(
+            from_bytes::<Vec<Option<Status>>>(input).unwrap(),
+            [Some(Status::Open), Some(Status::Closed), None]
         );
+
+        assert!(from_bytes::<Status>(br#"s:6:"Closed";"#).is_err());
+        // Only unit variants can be given by name.
+        assert!(from_bytes::<Status>(br#"s:4:"Held";"#).is_err());
+        assert!(from_bytes::<Status>(b"i:0;").is_err());
     }
 
     #[test]
-    fn deserialize_array() {
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct SubData();
+    fn deserialize_externally_tagged_variants() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        enum Shape {
+            Dot,
+            Circle(f64),
+            Line(i32, i32),
+            Rect { w: u32, h: u32 },
+        }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Data(Vec<u8>, Vec<u8>, SubData);
+        let shapes = vec![
+            Shape::Dot,
+            Shape::Circle(0.5),
+            Shape::Line(1, 2),
+            Shape::Rect { w: 3, h: 4 },
+        ];
+        let data = crate::to_vec(&shapes).unwrap();
+        assert_eq!(from_bytes::<Vec<Shape>>(&data).unwrap(), shapes);
 
-        assert_deserializes!(
-            Data,
-            br#"a:3:{i:0;s:4:"user";i:1;s:0:"";i:2;a:0:{}}"#,
-            Data(b"user".to_vec(), b"".to_vec(), SubData())
+        // Unit variants may also be given as an array, with a `null` payload.
+        assert_eq!(
+            from_bytes::<Shape>(br#"a:1:{s:3:"Dot";N;}"#).unwrap(),
+            Shape::Dot
+        );
+        // Fields of struct variants may be in any order.
+        assert_eq!(
+            from_bytes::<Shape>(br#"a:1:{s:4:"Rect";a:2:{s:1:"h";i:4;s:1:"w";i:3;}}"#).unwrap(),
+            Shape::Rect { w: 3, h: 4 }
         );
+
+        assert!(from_bytes::<Shape>(br#"a:0:{}"#).is_err());
+        assert!(from_bytes::<Shape>(br#"a:2:{s:3:"Dot";N;s:3:"Dot";N;}"#).is_err());
+        assert!(from_bytes::<Shape>(br#"a:1:{s:4:"Cube";i:1;}"#).is_err());
+        assert!(from_bytes::<Shape>(br#"a:1:{s:6:"Circle";d:0.5;i:0;}"#).is_err());
     }
 
     #[test]
-    fn deserialize_array_unordered() {
-        #[derive(Debug, Deserialize, PartialEq)]
-        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<f64>);
+    fn deserialize_tagged_variants() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
 
-        let expected = Data(vec![1.1, 2.2, 3.3, 4.4]);
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        #[serde(tag = "type")]
+        enum Shape {
+            Empty,
+            Dot(Point),
+            Circle { radius: u32, label: String },
+        }
 
-        assert_deserializes!(
-            Data,
-            br#"a:4:{i:1;d:2.2;i:0;d:1.1;i:3;d:4.4;i:2;d:3.3;}"#,
-            expected
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        #[serde(tag = "t", content = "c")]
+        enum Message {
+            Ping,
+            Move(i64, i64),
+            Text(String),
+        }
+
+        // The tag may appear anywhere among the fields.
+        let input = br#"a:3:{i:0;a:1:{s:4:"type";s:5:"Empty";}i:1;a:3:{s:1:"x";i:1;s:4:"type";s:3:"Dot";s:1:"y";i:2;}i:2;a:3:{s:4:"type";s:6:"Circle";s:6:"radius";i:3;s:5:"label";s:1:"a";}}"#;
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Dot(Point { x: 1, y: 2 }),
+            Shape::Circle {
+                radius: 3,
+                label: "a".to_owned(),
+            },
+        ];
+        // Serde buffers the fields of internally tagged variants, strings
+        // among them are passed on as text.
+        assert_eq!(from_bytes::<Vec<Shape>>(input).unwrap(), shapes);
+        let builder = PhpDeserializerBuilder::new().byte_strings(true);
+        assert_eq!(builder.deserialize::<Vec<Shape>>(input).unwrap(), shapes);
+        assert_eq!(
+            from_bytes::<Shape>(br#"a:3:{s:1:"x";i:1;s:1:"y";i:2;s:4:"type";s:3:"Dot";}"#).unwrap(),
+            Shape::Dot(Point { x: 1, y: 2 })
+        );
+        assert!(from_bytes::<Shape>(br#"a:1:{s:4:"type";s:4:"Cube";}"#).is_err());
+        assert!(from_bytes::<Shape>(br#"a:1:{s:1:"x";i:1;}"#).is_err());
+
+        let messages = vec![
+            Message::Ping,
+            Message::Move(1, -1),
+            Message::Text("hi".to_owned()),
+        ];
+        let data = crate::to_vec(&messages).unwrap();
+        assert_eq!(from_bytes::<Vec<Message>>(&data).unwrap(), messages);
+        assert_eq!(
+            from_bytes::<Message>(br#"a:2:{s:1:"c";a:2:{i:0;i:3;i:1;i:4;}s:1:"t";s:4:"Move";}"#)
+                .unwrap(),
+            Message::Move(3, 4)
         );
     }
 
     #[test]
-    fn deserialize_array_unordered_with_holes() {
+    fn deserialize_registered_classes() {
         #[derive(Debug, Deserialize, PartialEq)]
-        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<String>);
+        struct Point {
+            x: i32,
+            y: i32,
+        }
 
-        let expected = Data(vec![
-            "zero".to_string(),
-            "one".to_string(),
-            "two".to_string(),
-            "six".to_string(),
-        ]);
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape {
+            Circle { radius: u32 },
+            Dot(Point),
+            Empty,
+        }
 
-        assert_deserializes!(
-            Data,
-            br#"a:4:{i:0;s:4:"zero";i:2;s:3:"two";i:1;s:3:"one";i:6;s:3:"six";}"#,
-            expected
+        let builder = PhpDeserializerBuilder::new()
+            .register_class("Circle", "Circle")
+            .register_class("Dot", "Dot")
+            .register_class("Point", "Dot")
+            .register_class("EmptyShape", "Empty");
+
+        let input = br#"a:3:{i:0;O:6:"Circle":1:{s:6:"radius";i:5;}i:1;O:3:"Dot":2:{s:1:"y";i:2;s:1:"x";i:1;}i:2;O:10:"EmptyShape":1:{s:4:"name";N;}}"#;
+        let shapes: Vec<Shape> = builder.deserialize(input).unwrap();
+        assert_eq!(
+            shapes,
+            [
+                Shape::Circle { radius: 5 },
+                Shape::Dot(Point { x: 1, y: 2 }),
+                Shape::Empty
+            ]
         );
+
+        // Registered classes are accepted in place of arrays.
+        let point: Point = builder
+            .deserialize(br#"O:5:"Point":2:{s:1:"x";i:3;s:1:"y";i:4;}"#)
+            .unwrap();
+        assert_eq!(point, Point { x: 3, y: 4 });
+
+        let err = builder
+            .deserialize::<Shape>(br#"O:6:"Square":0:{}"#)
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ClassNotAllowed(class) if class == "Square"));
+        assert!(builder
+            .deserialize::<Point>(br#"O:6:"Square":0:{}"#)
+            .is_err());
+        assert!(from_bytes::<Shape>(br#"O:6:"Circle":1:{s:6:"radius";i:5;}"#).is_err());
     }
 
     #[test]
-    fn deserialize_struct() {
-        // PHP equiv:
-        //
-        // array("foo" => true,
-        //       "bar" => "xyz",
-        //       "sub" => array("x" => 42))
-
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Outer {
-            foo: bool,
-            bar: String,
-            sub: Inner,
+    fn deserialize_magento_preset() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Item {
+            sku: String,
+            options: Vec<Option_>,
         }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Inner {
-            x: i64,
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Option_ {
+            label: String,
         }
 
-        assert_deserializes!(
-            Outer,
-            br#"a:3:{s:3:"foo";b:1;s:3:"bar";s:3:"xyz";s:3:"sub";a:1:{s:1:"x";i:42;}}"#,
-            Outer {
-                foo: true,
-                bar: "xyz".to_owned(),
-                sub: Inner { x: 42 },
-            }
+        // `options` holds a serialized list, `label` is Latin-1 text.
+        let input = b"a:2:{s:3:\"sku\";s:3:\"A-1\";s:7:\"options\";s:39:\"a:1:{i:0;a:1:{s:5:\"label\";s:4:\"Gr\xfcn\";}}\";}";
+        let expected = Item {
+            sku: "A-1".to_owned(),
+            options: vec![Option_ {
+                label: "Gr\u{fc}n".to_owned(),
+            }],
+        };
+
+        assert!(from_bytes::<Item>(input).is_err());
+        let builder = PhpDeserializerBuilder::new().preset(Preset::Magento);
+        assert_eq!(builder.deserialize::<Item>(input).unwrap(), expected);
+
+        // The Latin-1 string was converted to UTF-8 by a migration, breaking its length.
+        let migrated = "a:1:{s:5:\"label\";s:4:\"Grün\";}";
+        assert_eq!(
+            builder.deserialize::<Option_>(migrated.as_bytes()).unwrap(),
+            expected.options[0]
         );
+
+        // Keys fall back to Latin-1 as well.
+        let map: HashMap<String, i64> = builder.deserialize(b"a:1:{s:1:\"\xe9\";i:1;}").unwrap();
+        assert_eq!(map["\u{e9}"], 1);
+
+        // Nested data must be a single value.
+        assert!(builder
+            .deserialize::<Vec<i64>>(br#"s:10:"a:0:{}N;";"#)
+            .is_err());
     }
 
     #[test]
-    fn deserialize_struct_with_optional() {
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Location {
-            province: Option<String>,
-            postalcode: Option<String>,
-            country: Option<String>,
+    fn deserialize_drupal_fixtures() {
+        #[derive(Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
+        #[serde(untagged)]
+        enum Key {
+            Int(i64),
+            Text(String),
         }
 
-        assert_deserializes!(
-            Location,
-            br#"a:0:{}"#,
-            Location {
-                province: None,
-                postalcode: None,
-                country: None,
-            }
-        );
-        assert_deserializes!(
-            Location,
-            br#"a:1:{s:8:"province";s:29:"Newfoundland and Labrador, CA";}"#,
-            Location {
-                province: Some("Newfoundland and Labrador, CA".to_owned()),
-                postalcode: None,
-                country: None,
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Node {
+            Null,
+            Bool(bool),
+            Int(i64),
+            Float(f64),
+            Text(String),
+            Binary(serde_bytes::ByteBuf),
+            Array(BTreeMap<Key, Node>),
+        }
+
+        let builder = PhpDeserializerBuilder::new().preset(Preset::Drupal);
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/drupal");
+        let mut count = 0;
+
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let input = std::fs::read(&path).unwrap();
+            if let Err(err) = builder.deserialize::<Node>(&input) {
+                panic!("{}: {}", path.display(), err);
             }
-        );
-        assert_deserializes!(
-            Location,
-            br#"a:2:{s:10:"postalcode";s:5:"90002";s:7:"country";s:24:"United States of America";}"#,
-            Location {
-            province: None,
-            postalcode: Some("90002".to_owned()),
-            country: Some("United States of America".to_owned()),
+            count += 1;
+        }
+        assert_eq!(count, 8);
+
+        #[derive(Debug, Deserialize)]
+        struct CacheRow<T> {
+            cid: String,
+            data: T,
+            created: u32,
+            expire: i32,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Page {
+            title: String,
+            body: Vec<u8>,
+            page_compressed: bool,
         }
+
+        let input = include_bytes!("../tests/drupal/cache_page.serialized");
+        assert!(from_bytes::<CacheRow<Page>>(input).is_err());
+        let row: CacheRow<Page> = builder.deserialize(input).unwrap();
+        assert_eq!(row.cid, "http://example.com/node/1");
+        assert_eq!(row.data.title, "Hello");
+        assert_eq!(row.data.body[..2], [0x1f, 0x8b]);
+        assert!(row.data.page_compressed);
+        assert_eq!(row.expire, -1);
+
+        // `created` was stored as a float.
+        let input = include_bytes!("../tests/drupal/cache_field_info.serialized");
+        let row: CacheRow<HashMap<String, HashMap<String, Node>>> =
+            builder.deserialize(input).unwrap();
+        assert_eq!(row.created, 1_700_000_123);
+        assert_eq!(
+            row.data["body"]["type"],
+            Node::Text("text_with_summary".to_owned())
         );
+
+        let node: Node = builder
+            .deserialize(include_bytes!(
+                "../tests/drupal/variable_update_last_check.serialized"
+            ))
+            .unwrap();
+        assert_eq!(node, Node::Float(1_699_999_999.0));
+        assert!(builder.deserialize::<u32>(b"d:1.5;").is_err());
+        assert!(builder.deserialize::<u8>(b"d:256;").is_err());
     }
 
     #[test]
-    fn deserialize_nested() {
-        // PHP: array("x" => array("inner" => 1), "y" => array("inner" => 2))
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Outer {
-            x: Inner,
-            y: Inner,
+    fn deserialize_strict_range_diagnostics() {
+        #[derive(Debug, Deserialize)]
+        struct Row {
+            #[allow(dead_code)]
+            id: u32,
+            #[allow(dead_code)]
+            scores: Vec<u8>,
         }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Inner {
-            inner: u8,
-        }
+        let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+        let input = br#"a:2:{i:0;a:2:{s:2:"id";i:1;s:6:"scores";a:1:{i:0;i:7;}}i:1;a:2:{s:2:"id";i:2;s:6:"scores";a:2:{i:0;i:1;i:1;i:-3;}}}"#;
 
-        assert_deserializes!(
-            Outer,
-            br#"a:2:{s:1:"x";a:1:{s:5:"inner";i:1;}s:1:"y";a:1:{s:5:"inner";i:2;}}"#,
-            Outer {
-                x: Inner { inner: 1 },
-                y: Inner { inner: 2 },
+        let err = strict.deserialize::<Vec<Row>>(input).unwrap_err();
+        match err.kind() {
+            ErrorKind::OutOfRange { value, type_name } => {
+                assert_eq!(value, "-3");
+                assert_eq!(*type_name, "u8");
             }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(err.path(), Some("1.scores.1"));
+        assert_eq!(err.offset(), Some(107));
+        assert_eq!(&input[107..112], b"i:-3;");
+        assert_eq!(
+            err.to_string(),
+            "Value `-3` is out of range for `u8`. (at `1.scores.1`) (at offset 107)"
         );
+
+        // Integer keys and overflowing values are covered as well.
+        let err = strict
+            .deserialize::<HashMap<i64, i32>>(b"a:1:{i:7;i:3000000000;}")
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::OutOfRange {
+                type_name: "i32",
+                ..
+            }
+        ));
+        assert_eq!(err.path(), Some("7"));
+
+        let err = strict
+            .deserialize::<Vec<f32>>(b"a:1:{i:0;d:1.0E+300;}")
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::OutOfRange {
+                type_name: "f32",
+                ..
+            }
+        ));
+
+        // Other profiles report no path.
+        let err = from_bytes::<Vec<Row>>(input).unwrap_err();
+        assert_eq!(err.path(), None);
     }
 
     #[test]
-    fn deserialize_variable_length() {
-        // PHP: array(1.1, 2.2, 3.3, 4.4)
+    fn deserialize_ignored_fields() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Partial {
+            b: bool,
+        }
+
         assert_deserializes!(
-            Vec<f64>,
-            br#"a:4:{i:0;d:1.1;i:1;d:2.2;i:2;d:3.3;i:3;d:4.4;}"#,
-            vec![1.1, 2.2, 3.3, 4.4]
+            Partial,
+            br#"a:3:{s:1:"a";a:2:{i:0;s:2:"x;";i:1;d:0.5;}s:1:"b";b:1;s:1:"c";N;}"#,
+            Partial { b: true }
         );
     }
 
     #[test]
-    fn deserialize_hashmap() {
-        // PHP: array("foo" => 1, "bar" => 2)
-        let mut expected = HashMap::new();
-        expected.insert("foo".to_owned(), 1);
-        expected.insert("bar".to_owned(), 2);
+    fn deserialize_with_serde_ignored() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct User {
+            name: String,
+            tags: Vec<String>,
+            parent: Option<Box<User>>,
+            scores: HashMap<u32, Score>,
+        }
 
-        assert_deserializes!(HashMap<String, u16>, br#"a:2:{s:3:"foo";i:1;s:3:"bar";i:2;}"#, expected);
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Score {
+            value: i64,
+        }
+
+        let input = br#"a:6:{s:4:"name";s:3:"bob";s:4:"tags";a:1:{i:0;s:1:"x";}s:5:"color";s:4:"blue";s:6:"parent";a:4:{s:4:"name";s:5:"alice";s:4:"tags";a:0:{}s:4:"meta";a:2:{i:0;d:1.5;i:1;a:1:{s:1:"k";N;}}s:6:"scores";a:0:{}}s:3:"age";i:42;s:6:"scores";a:1:{i:7;a:2:{s:5:"value";i:3;s:4:"note";s:2:"ok";}}}"#;
+
+        let mut des = PhpDeserializer::from_slice(input);
+        let mut ignored = Vec::new();
+        let user: User =
+            serde_ignored::deserialize(&mut des, |path| ignored.push(path.to_string()))
+                .expect("deserialization failed");
+
+        assert_eq!(user.name, "bob");
+        assert_eq!(user.parent.unwrap().name, "alice");
+        assert_eq!(user.scores[&7], Score { value: 3 });
+        assert_eq!(
+            ignored,
+            vec!["color", "parent.?.meta", "age", "scores.7.note"]
+        );
     }
 }
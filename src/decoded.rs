@@ -0,0 +1,301 @@
+//! Parsed view over serialized data, for repeated queries.
+
+use crate::de::PhpDeserializerBuilder;
+use crate::error::{Error, ErrorKind, Result};
+use crate::lex::Lookahead1;
+use serde::Deserialize;
+use std::ops::Range;
+
+/// A value parsed once into an index over its input, which can be queried
+/// and deserialized repeatedly.
+///
+/// Building the view checks the whole input and records the byte range of
+/// every value, along with the keys of all arrays and objects. Lookups walk
+/// this index instead of the input, and deserializing a value only touches
+/// its own bytes. This suits services that route payloads by inspecting a
+/// field first and then deserialize the whole value into the matching type:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_php::Decoded;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Refund {
+///     order: u32,
+///     amount: u32,
+/// }
+///
+/// let input = br#"a:2:{s:4:"kind";s:6:"refund";s:4:"data";a:2:{s:5:"order";i:7;s:6:"amount";i:250;}}"#;
+/// let decoded = Decoded::new(input).unwrap();
+///
+/// let kind: Option<String> = decoded.get("kind").unwrap();
+/// let refund: Refund = match kind.as_deref() {
+///     Some("refund") => decoded.get("data").unwrap().unwrap(),
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(refund.amount, 250);
+/// assert_eq!(decoded.get::<u32>("data.order").unwrap(), Some(7));
+/// ```
+///
+/// Paths are keys separated by dots, so keys containing dots cannot be
+/// looked up. The empty path refers to the complete value. Integer keys are
+/// matched by their decimal representation and objects of any class are
+/// indexed like arrays. If a key occurs more than once, the last entry wins,
+/// as in PHP. Finding a key takes time linear in the number of entries of the
+/// array holding it.
+#[derive(Clone, Debug)]
+pub struct Decoded<'a> {
+    input: &'a [u8],
+    /// All values, the top-level value first.
+    nodes: Vec<Node>,
+    /// Keys of all arrays and the nodes of their values; the entries of an
+    /// array are contiguous.
+    entries: Vec<(Vec<u8>, usize)>,
+}
+
+/// A single value of a [`Decoded`] view.
+#[derive(Clone, Debug)]
+struct Node {
+    /// Byte range of the serialized value.
+    range: Range<usize>,
+    /// Range of its entries in `entries`, if it is an array or object.
+    children: Option<Range<usize>>,
+}
+
+impl<'a> Decoded<'a> {
+    /// Parse `input`, which must contain exactly one value.
+    pub fn new(input: &'a [u8]) -> Result<Self> {
+        let mut lexer = Lookahead1::new(input);
+        lexer.set_input_len(input.len() as u64);
+
+        let mut decoded = Decoded {
+            input,
+            nodes: Vec::new(),
+            entries: Vec::new(),
+        };
+        decoded
+            .scan(&mut lexer)
+            .map_err(|err| err.with_offset(lexer.position()))?;
+
+        if lexer.peek()?.is_some() {
+            return Err(Error::from(ErrorKind::TrailingData).with_offset(lexer.position()));
+        }
+
+        Ok(decoded)
+    }
+
+    /// The complete input.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Returns whether a value is stored at `path`.
+    pub fn contains(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    /// Serialized value stored at `path`.
+    pub fn raw(&self, path: &str) -> Option<&'a [u8]> {
+        self.find(path)
+            .map(|node| &self.input[self.nodes[node].range.clone()])
+    }
+
+    /// Keys of the array or object stored at `path`, in input order.
+    ///
+    /// Returns `None` if there is no value at `path` or it is not an array.
+    pub fn keys(&self, path: &str) -> Option<impl Iterator<Item = &[u8]>> {
+        let children = self.nodes[self.find(path)?].children.clone()?;
+        Some(self.entries[children].iter().map(|(key, _)| key.as_slice()))
+    }
+
+    /// Deserialize the complete value.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        self.deserialize_with(&PhpDeserializerBuilder::default())
+    }
+
+    /// Deserialize the complete value using the options of `builder`.
+    pub fn deserialize_with<T>(&self, builder: &PhpDeserializerBuilder) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        builder.deserialize(self.input)
+    }
+
+    /// Deserialize the value stored at `path`.
+    ///
+    /// Returns `None` if there is no value at `path`. Errors carry the path.
+    pub fn get<T>(&self, path: &str) -> Result<Option<T>>
+    where
+        T: Deserialize<'a>,
+    {
+        self.get_with(&PhpDeserializerBuilder::default(), path)
+    }
+
+    /// Deserialize the value stored at `path` using the options of
+    /// `builder`.
+    pub fn get_with<T>(&self, builder: &PhpDeserializerBuilder, path: &str) -> Result<Option<T>>
+    where
+        T: Deserialize<'a>,
+    {
+        match self.raw(path) {
+            Some(raw) => builder.deserialize(raw).map(Some).map_err(|err| {
+                if path.is_empty() {
+                    err
+                } else {
+                    err.with_path_segment(path)
+                }
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Node of the value stored at `path`.
+    fn find(&self, path: &str) -> Option<usize> {
+        if path.is_empty() {
+            return Some(0);
+        }
+
+        path.split('.').try_fold(0, |node, key| {
+            let children = self.nodes[node].children.clone()?;
+            self.entries[children]
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key.as_bytes())
+                .map(|&(_, child)| child)
+        })
+    }
+
+    /// Read a complete value, recording it and all values it contains.
+    ///
+    /// Returns the index of its node.
+    fn scan(&mut self, lexer: &mut Lookahead1<&[u8]>) -> Result<usize> {
+        let start = lexer.position() as usize;
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            range: start..start,
+            children: None,
+        });
+
+        let sym = lexer.read_type()?;
+        if sym == b'N' {
+            lexer.expect(b';')?;
+        } else {
+            lexer.expect(b':')?;
+
+            let num_entries = match sym {
+                b'b' => lexer.read_bool().map(|_| None)?,
+                b'i' => lexer.read_int_text().map(|_| None)?,
+                b'd' => lexer.read_float().map(|_| None)?,
+                b's' => lexer.read_raw_string().map(|_| None)?,
+                b'a' => Some(lexer.read_array_header()?),
+                b'O' => Some(lexer.read_object_header()?.1),
+                c => return Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+            };
+
+            if let Some(num_entries) = num_entries {
+                // Reserve the entries up front, as nested arrays add their own.
+                // The array header has checked the count against the input.
+                let first = self.entries.len();
+                self.entries
+                    .resize_with(first + num_entries, || (Vec::new(), 0));
+
+                for idx in first..first + num_entries {
+                    let key = read_key(lexer)?;
+                    let child = self.scan(lexer)?;
+                    self.entries[idx] = (key, child);
+                }
+                lexer.read_array_end()?;
+
+                self.nodes[node].children = Some(first..first + num_entries);
+            }
+        }
+
+        self.nodes[node].range.end = lexer.position() as usize;
+        Ok(node)
+    }
+}
+
+/// Read an array key, returning integer keys in their decimal representation.
+fn read_key(lexer: &mut Lookahead1<&[u8]>) -> Result<Vec<u8>> {
+    match lexer.read_type()? {
+        b'i' => {
+            lexer.expect(b':')?;
+            Ok(lexer.read_int_text()?.into_bytes())
+        }
+        b's' => {
+            lexer.expect(b':')?;
+            lexer.read_key_string()
+        }
+        c => Err(ErrorKind::UnsupportedArrayKeyType(char::from(c)).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoded;
+    use crate::error::ErrorKind;
+    use serde::de::IgnoredAny;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[test]
+    fn queries_nested_values() {
+        let input = br#"a:3:{s:4:"user";O:4:"User":2:{s:2:"id";i:5;s:5:"roles";a:2:{i:0;s:5:"admin";i:1;s:3:"dev";}}i:7;N;s:4:"user";a:1:{s:2:"id";i:6;}}"#;
+        let decoded = Decoded::new(input).unwrap();
+
+        // The last duplicate wins.
+        assert_eq!(decoded.get::<u32>("user.id").unwrap(), Some(6));
+        assert!(!decoded.contains("user.roles"));
+        assert!(decoded.contains("7"));
+        assert_eq!(decoded.get::<Option<u8>>("7").unwrap(), Some(None));
+        assert_eq!(decoded.raw("user"), Some(&br#"a:1:{s:2:"id";i:6;}"#[..]));
+        assert_eq!(decoded.get::<u8>("user.id.x").unwrap(), None);
+        assert_eq!(decoded.get::<u8>("missing").unwrap(), None);
+
+        assert_eq!(
+            decoded.keys("").unwrap().collect::<Vec<_>>(),
+            [&b"user"[..], b"7", b"user"]
+        );
+        assert!(decoded.keys("7").is_none());
+        assert_eq!(decoded.keys("user").unwrap().collect::<Vec<_>>(), [b"id"]);
+        assert_eq!(decoded.as_bytes(), &input[..]);
+    }
+
+    #[test]
+    fn deserializes_repeatedly() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Event {
+            kind: String,
+        }
+
+        let input = br#"a:2:{s:4:"kind";s:5:"login";s:2:"at";i:100;}"#;
+        let decoded = Decoded::new(input).unwrap();
+
+        assert_eq!(
+            decoded.deserialize::<Event>().unwrap(),
+            Event {
+                kind: "login".to_owned()
+            }
+        );
+        let map: HashMap<String, IgnoredAny> = decoded.deserialize().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(decoded.get::<f64>("at").unwrap(), Some(100.0));
+
+        let err = decoded.get::<u8>("kind").unwrap_err();
+        assert_eq!(err.path(), Some("kind"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let err = Decoded::new(br#"a:1:{s:1:"a";i:1;}i:2;"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TrailingData));
+        assert_eq!(err.offset(), Some(18));
+
+        assert!(Decoded::new(br#"a:2:{s:1:"a";i:1;}"#).is_err());
+        assert!(Decoded::new(br#"a:1:{d:1.5;i:1;}"#).is_err());
+        assert!(Decoded::new(b"x:1;").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Long-lived deserializer for decoding many payloads.
+
+use crate::de::{PhpDeserializer, PhpDeserializerBuilder};
+use crate::error::Result;
+use serde::Deserialize;
+
+/// Scratch buffers growing beyond this size are not kept between payloads.
+const MAX_RETAINED_SCRATCH: usize = 64 * 1024;
+
+/// Reusable deserializer, keeping its configuration and buffers across calls.
+///
+/// Every call to [`from_bytes`](crate::from_bytes) or
+/// [`PhpDeserializerBuilder::deserialize`] sets up a fresh deserializer,
+/// including a read buffer and a buffer for array keys. When decoding large
+/// numbers of small payloads, this setup can make up a noticeable part of the
+/// total cost. A `Decoder` reads directly from the input slice and keeps its
+/// key buffer, so that after warming up, decoding a payload only allocates
+/// for the values being produced.
+///
+/// ```rust
+/// use serde_php::{Decoder, PhpDeserializerBuilder, Profile};
+///
+/// let mut decoder = Decoder::with_builder(PhpDeserializerBuilder::new().profile(Profile::Strict));
+///
+/// for payload in &[&b"i:1;"[..], b"i:2;", b"i:3;"] {
+///     let n: i64 = decoder.deserialize(payload).unwrap();
+///     assert!((1..=3).contains(&n));
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    /// Options applied to every payload.
+    builder: PhpDeserializerBuilder,
+    /// Buffer for reading keys, handed to each deserializer.
+    scratch: Vec<u8>,
+}
+
+impl Decoder {
+    /// Create a decoder with default options, matching [`from_bytes`](crate::from_bytes).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a decoder using the options of `builder`.
+    pub fn with_builder(builder: PhpDeserializerBuilder) -> Self {
+        Decoder {
+            builder,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Options used by this decoder.
+    pub fn builder(&self) -> &PhpDeserializerBuilder {
+        &self.builder
+    }
+
+    /// Deserialize a single payload.
+    ///
+    /// Behaves like [`PhpDeserializerBuilder::deserialize`] with the
+    /// decoder's options. A failed payload does not affect later calls.
+    pub fn deserialize<'de, T>(&mut self, input: &'de [u8]) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut des = PhpDeserializer::from_slice_unbuffered(input);
+        self.builder.configure(&mut des);
+        des.set_scratch(std::mem::take(&mut self.scratch));
+
+        let result = T::deserialize(&mut des);
+
+        // Keep the buffer, unless a single huge key made it grow out of proportion.
+        let mut scratch = des.take_scratch();
+        if scratch.capacity() > MAX_RETAINED_SCRATCH {
+            scratch = Vec::new();
+        }
+        self.scratch = scratch;
+
+        result.or_else(|err| self.builder.retry_repaired(input, err))
+    }
+}
+
+impl From<PhpDeserializerBuilder> for Decoder {
+    fn from(builder: PhpDeserializerBuilder) -> Self {
+        Decoder::with_builder(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+    use crate::de::{KeyMatching, PhpDeserializerBuilder};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Entry {
+        id: u32,
+        name: String,
+    }
+
+    #[test]
+    fn decodes_many_payloads() {
+        let mut decoder = Decoder::new();
+
+        for id in 0..100u32 {
+            let name = format!("entry-{}", id);
+            let input = format!(
+                r#"a:2:{{s:2:"id";i:{};s:4:"name";s:{}:"{}";}}"#,
+                id,
+                name.len(),
+                name
+            );
+            let entry: Entry = decoder.deserialize(input.as_bytes()).unwrap();
+            assert_eq!(entry, Entry { id, name });
+        }
+    }
+
+    #[test]
+    fn recovers_after_error() {
+        let mut decoder = Decoder::new();
+
+        assert!(decoder
+            .deserialize::<Entry>(br#"a:2:{s:2:"id";i:1;s:4:"na"#)
+            .is_err());
+
+        let entry: Entry = decoder
+            .deserialize(br#"a:2:{s:2:"id";i:2;s:4:"name";s:1:"b";}"#)
+            .unwrap();
+        assert_eq!(entry.id, 2);
+        assert_eq!(entry.name, "b");
+    }
+
+    #[test]
+    fn applies_options() {
+        let mut decoder: Decoder = PhpDeserializerBuilder::new()
+            .key_matching(KeyMatching::IgnoreCase)
+            .into();
+
+        let entry: Entry = decoder
+            .deserialize(br#"a:2:{s:2:"ID";i:3;s:4:"Name";s:1:"c";}"#)
+            .unwrap();
+        assert_eq!(
+            entry,
+            Entry {
+                id: 3,
+                name: "c".to_owned()
+            }
+        );
+        assert_eq!(decoder.builder().deserialize::<i64>(b"i:1;").unwrap(), 1);
+    }
+
+    #[test]
+    fn drops_oversized_scratch() {
+        let mut decoder = Decoder::new();
+        let key = "k".repeat(super::MAX_RETAINED_SCRATCH + 1);
+        let input = format!(r#"a:1:{{s:{}:"{}";i:1;}}"#, key.len(), key);
+
+        let map: BTreeMap<String, i64> = decoder.deserialize(input.as_bytes()).unwrap();
+        assert_eq!(map[&key], 1);
+        assert_eq!(decoder.scratch.capacity(), 0);
+    }
+}
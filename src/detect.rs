@@ -0,0 +1,280 @@
+//! Detection of serialized data.
+//!
+//! Columns in real-world databases frequently hold a mix of PHP-serialized
+//! values, JSON and plain strings, depending on which version of an
+//! application wrote them. The functions in this module sniff the payload and
+//! pick the right way to decode it.
+
+use crate::de::from_bytes;
+use crate::error::{Error, Result};
+use crate::value::PhpValue;
+use serde::de::value::BytesDeserializer;
+use serde::Deserialize;
+
+/// Check whether data looks like a serialized value.
+///
+/// Replicates the heuristics of WordPress' `is_serialized` (in strict mode):
+/// only the overall shape of the data is checked, not whether it is actually
+/// valid. Surrounding whitespace is ignored.
+pub fn is_serialized(data: &[u8]) -> bool {
+    let data = trim(data);
+
+    if data == b"N;" {
+        return true;
+    }
+
+    if data.len() < 4 || data[1] != b':' {
+        return false;
+    }
+
+    if !matches!(data.last(), Some(b';') | Some(b'}')) {
+        return false;
+    }
+
+    let rest = &data[2..];
+    match data[0] {
+        b's' if data[data.len() - 2] != b'"' => false,
+        // `<token>:<digits>:`
+        b's' | b'a' | b'O' | b'E' => {
+            let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+            digits > 0 && rest.get(digits) == Some(&b':')
+        }
+        // `<token>:<number>;` with nothing following.
+        b'b' | b'i' | b'd' => {
+            let number = rest
+                .iter()
+                .take_while(|c| matches!(c, b'0'..=b'9' | b'.' | b'E' | b'+' | b'-'))
+                .count();
+            number > 0 && &rest[number..] == b";"
+        }
+        _ => false,
+    }
+}
+
+/// Deserialize data if it looks serialized, otherwise treat it as a plain
+/// string.
+///
+/// Mirrors WordPress' `maybe_unserialize`, which is used for values such as
+/// those in `wp_options` or `postmeta` that may or may not be serialized.
+/// Data that passes [`is_serialized`] is deserialized (ignoring surrounding
+/// whitespace) and errors are reported. Any other data is deserialized as a
+/// string, unchanged.
+///
+/// ```rust
+/// let plain: String = serde_php::maybe_from_bytes(b"hello").unwrap();
+/// assert_eq!(plain, "hello");
+///
+/// let serialized: String = serde_php::maybe_from_bytes(br#"s:5:"hello";"#).unwrap();
+/// assert_eq!(serialized, "hello");
+/// ```
+pub fn maybe_from_bytes<'de, T>(data: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    if is_serialized(data) {
+        from_bytes(trim(data))
+    } else {
+        T::deserialize(BytesDeserializer::<Error>::new(data))
+    }
+}
+
+impl PhpValue {
+    /// Parse data if it looks serialized, otherwise return it as a string.
+    ///
+    /// See [`maybe_from_bytes`] for details.
+    pub fn maybe_parse(data: &[u8]) -> Result<PhpValue> {
+        if is_serialized(data) {
+            PhpValue::parse(trim(data))
+        } else {
+            Ok(PhpValue::String(data.to_vec()))
+        }
+    }
+}
+
+/// Format of a payload, as determined by [`Format::detect`].
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// PHP-serialized data, see [`is_serialized`].
+    Php,
+    /// A JSON object or array.
+    Json,
+    /// Anything else, treated as a plain string.
+    Plain,
+}
+
+#[cfg(feature = "json")]
+impl Format {
+    /// Determine the format of a payload.
+    ///
+    /// PHP-serialized data is recognized by shape only, while JSON is only
+    /// detected if the payload is a valid JSON object or array. Bare JSON
+    /// scalars such as `42` or `"text"` are indistinguishable from plain
+    /// strings and are reported as [`Format::Plain`].
+    pub fn detect(data: &[u8]) -> Format {
+        if is_serialized(data) {
+            return Format::Php;
+        }
+
+        let is_json = matches!(trim(data).first(), Some(b'{') | Some(b'['))
+            && serde_json::from_slice::<serde::de::IgnoredAny>(data).is_ok();
+        if is_json {
+            Format::Json
+        } else {
+            Format::Plain
+        }
+    }
+}
+
+/// Deserialize a PHP-serialized, JSON or plain string payload.
+///
+/// The format is determined using [`Format::detect`], after which the
+/// payload is deserialized using the matching backend. Plain strings are
+/// deserialized as a string, unchanged.
+///
+/// Requires the `json` feature.
+///
+/// ```rust
+/// use std::collections::BTreeMap;
+///
+/// let from_php: BTreeMap<String, i64> =
+///     serde_php::from_any_bytes(br#"a:1:{s:1:"a";i:1;}"#).unwrap();
+/// let from_json: BTreeMap<String, i64> = serde_php::from_any_bytes(br#"{"a": 1}"#).unwrap();
+/// assert_eq!(from_php, from_json);
+/// ```
+#[cfg(feature = "json")]
+pub fn from_any_bytes<'de, T>(data: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    match Format::detect(data) {
+        Format::Php => from_bytes(trim(data)),
+        Format::Json => serde_json::from_slice(data).map_err(json_error),
+        Format::Plain => T::deserialize(BytesDeserializer::<Error>::new(data)),
+    }
+}
+
+#[cfg(feature = "json")]
+impl PhpValue {
+    /// Parse a PHP-serialized, JSON or plain string payload.
+    ///
+    /// See [`from_any_bytes`] for details. JSON is converted as described in
+    /// the `From<serde_json::Value>` implementation.
+    pub fn parse_any(data: &[u8]) -> Result<PhpValue> {
+        match Format::detect(data) {
+            Format::Php => PhpValue::parse(trim(data)),
+            Format::Json => serde_json::from_slice::<serde_json::Value>(data)
+                .map(PhpValue::from)
+                .map_err(json_error),
+            Format::Plain => Ok(PhpValue::String(data.to_vec())),
+        }
+    }
+}
+
+/// Convert a JSON error into a deserialization error.
+#[cfg(feature = "json")]
+fn json_error(err: serde_json::Error) -> Error {
+    crate::error::ErrorKind::DeserializationFailed(err.to_string()).into()
+}
+
+/// Remove surrounding ASCII whitespace, like PHP's `trim`.
+pub(crate) fn trim(data: &[u8]) -> &[u8] {
+    let is_space = |c: &u8| matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b'\0' | b'\x0b');
+    let start = data.iter().position(|c| !is_space(c)).unwrap_or(data.len());
+    let end = data
+        .iter()
+        .rposition(|c| !is_space(c))
+        .map_or(start, |pos| pos + 1);
+    &data[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "json")]
+    use super::{from_any_bytes, Format};
+    use super::{is_serialized, maybe_from_bytes};
+    use crate::value::PhpValue;
+
+    #[test]
+    fn detects_serialized() {
+        let serialized: &[&[u8]] = &[
+            b"N;",
+            b" N;\n",
+            b"b:1;",
+            b"i:-42;",
+            b"d:1.0E+25;",
+            br#"s:3:"abc";"#,
+            b"a:0:{}",
+            br#"O:8:"stdClass":0:{}"#,
+            // Only the shape is checked, not the content.
+            br#"s:9:"abc";"#,
+        ];
+        for data in serialized {
+            assert!(is_serialized(data), "{:?}", String::from_utf8_lossy(data));
+        }
+
+        let plain: &[&[u8]] = &[
+            b"",
+            b"hello",
+            b"N",
+            b"i:1",
+            b"i:1;x",
+            b"x:1;",
+            br#"s:3:"abc""#,
+            b"a:x:{}",
+            b"b:;",
+        ];
+        for data in plain {
+            assert!(!is_serialized(data), "{:?}", String::from_utf8_lossy(data));
+        }
+    }
+
+    #[test]
+    fn maybe_deserialize() {
+        assert_eq!(
+            maybe_from_bytes::<Vec<i32>>(b"a:1:{i:0;i:5;}\n").unwrap(),
+            vec![5]
+        );
+        assert_eq!(maybe_from_bytes::<String>(b"a:1:{").unwrap(), "a:1:{");
+        assert!(maybe_from_bytes::<String>(br#"s:9:"abc";"#).is_err());
+
+        assert_eq!(
+            PhpValue::maybe_parse(b"yes").unwrap(),
+            PhpValue::from("yes")
+        );
+        assert_eq!(
+            PhpValue::maybe_parse(b"b:0;").unwrap(),
+            PhpValue::from(false)
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn detect_format() {
+        assert_eq!(Format::detect(b"a:0:{}"), Format::Php);
+        assert_eq!(Format::detect(b" {\"a\": [1, 2]}\n"), Format::Json);
+        assert_eq!(Format::detect(b"[1, 2]"), Format::Json);
+        assert_eq!(Format::detect(b"[draft] Title"), Format::Plain);
+        assert_eq!(Format::detect(b"42"), Format::Plain);
+        assert_eq!(Format::detect(b""), Format::Plain);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn deserialize_any_format() {
+        let php: Vec<String> = from_any_bytes(br#"a:1:{i:0;s:1:"x";}"#).unwrap();
+        let json: Vec<String> = from_any_bytes(br#"["x"]"#).unwrap();
+        assert_eq!(php, json);
+
+        let plain: String = from_any_bytes(b"[x]").unwrap();
+        assert_eq!(plain, "[x]");
+
+        assert!(from_any_bytes::<Vec<i32>>(br#"["x"]"#).is_err());
+
+        assert_eq!(
+            PhpValue::parse_any(br#"{"a":true}"#).unwrap(),
+            PhpValue::parse(br#"a:1:{s:1:"a";b:1;}"#).unwrap()
+        );
+        assert_eq!(PhpValue::parse_any(b"42").unwrap(), PhpValue::from("42"));
+    }
+}
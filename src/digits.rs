@@ -0,0 +1,146 @@
+//! Fast parsing of decimal digits.
+//!
+//! Lengths, counts and integer values make up the bulk of numeric payloads.
+//! Instead of going through `str::parse`, digits are converted eight at a time
+//! using SIMD-within-a-register arithmetic on a `u64`.
+
+use std::convert::TryFrom;
+
+/// Eight ASCII zeros, used for padding and to strip the ASCII offset.
+const ZEROS: u64 = 0x3030_3030_3030_3030;
+
+/// Powers of ten up to `10^8`, indexed by exponent.
+const POW10: [u64; 9] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+];
+
+/// Check whether all eight bytes of `chunk` are ASCII digits.
+#[inline]
+fn all_digits(chunk: u64) -> bool {
+    // Digits are `0x30..=0x39`; adding 6 moves anything above `0x39` out of
+    // the `0x3_` range.
+    let high = chunk & 0xF0F0_F0F0_F0F0_F0F0;
+    let overflow = chunk.wrapping_add(0x0606_0606_0606_0606) & 0xF0F0_F0F0_F0F0_F0F0;
+    high == ZEROS && overflow == ZEROS
+}
+
+/// Convert eight ASCII digits, loaded in little-endian order, to their value.
+#[inline]
+fn parse_eight(chunk: u64) -> u64 {
+    // Combine pairs of digits, then pairs of pairs, then the two halves.
+    let chunk = (chunk & 0x0F0F_0F0F_0F0F_0F0F).wrapping_mul(2561) >> 8;
+    let chunk = (chunk & 0x00FF_00FF_00FF_00FF).wrapping_mul(6_553_601) >> 16;
+    (chunk & 0x0000_FFFF_0000_FFFF).wrapping_mul(42_949_672_960_001) >> 32
+}
+
+/// Parse ASCII digits into a `u64`.
+///
+/// Returns `None` if `digits` is empty, contains anything but digits or
+/// overflows.
+pub(crate) fn parse_u64(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut acc: u64 = 0;
+    let mut chunks = digits.chunks_exact(8);
+
+    for chunk in &mut chunks {
+        let chunk = u64::from_le_bytes(<[u8; 8]>::try_from(chunk).expect("chunk of eight"));
+        if !all_digits(chunk) {
+            return None;
+        }
+        acc = acc.checked_mul(POW10[8])?.checked_add(parse_eight(chunk))?;
+    }
+
+    // Left-pad the rest with zeros, so it can be converted in one go as well.
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let mut padded = ZEROS.to_le_bytes();
+        padded[8 - rest.len()..].copy_from_slice(rest);
+        let chunk = u64::from_le_bytes(padded);
+        if !all_digits(chunk) {
+            return None;
+        }
+        acc = acc
+            .checked_mul(POW10[rest.len()])?
+            .checked_add(parse_eight(chunk))?;
+    }
+
+    Some(acc)
+}
+
+/// Parse an optionally signed integer into an `i64`.
+///
+/// Returns `None` under the same conditions as [`parse_u64`].
+pub(crate) fn parse_i64(text: &[u8]) -> Option<i64> {
+    match text {
+        [b'-', digits @ ..] => {
+            let magnitude = parse_u64(digits)?;
+            if magnitude == 1 << 63 {
+                Some(i64::MIN)
+            } else {
+                i64::try_from(magnitude).ok().map(|n| -n)
+            }
+        }
+        [b'+', digits @ ..] | digits => i64::try_from(parse_u64(digits)?).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_i64, parse_u64};
+
+    #[test]
+    fn matches_std_parsing() {
+        let inputs = [
+            "0",
+            "7",
+            "42",
+            "1234567",
+            "12345678",
+            "123456789",
+            "0000000000000000001",
+            "9999999999999999",
+            "18446744073709551615",
+        ];
+
+        for input in &inputs {
+            assert_eq!(parse_u64(input.as_bytes()), input.parse().ok(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_digits() {
+        for input in &[
+            "",
+            "18446744073709551616",
+            "1234567a",
+            "12345678/",
+            "1:",
+            "-1",
+        ] {
+            assert_eq!(parse_u64(input.as_bytes()), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn signed() {
+        assert_eq!(parse_i64(b"-9223372036854775808"), Some(i64::MIN));
+        assert_eq!(parse_i64(b"9223372036854775807"), Some(i64::MAX));
+        assert_eq!(parse_i64(b"+15"), Some(15));
+        assert_eq!(parse_i64(b"-15"), Some(-15));
+        assert_eq!(parse_i64(b"9223372036854775808"), None);
+        assert_eq!(parse_i64(b"-9223372036854775809"), None);
+        assert_eq!(parse_i64(b"-"), None);
+        assert_eq!(parse_i64(b"--1"), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! Custom encoders for Rust types serialized by the application.
+
+use crate::error::Result;
+use crate::ser::PhpSerializerBuilder;
+use crate::value::PhpValue;
+use serde::Serialize;
+use std::any;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Function rewriting the serialized form of a value.
+type EncodeFn = dyn Fn(PhpValue) -> PhpValue + Send + Sync;
+
+/// Registry of encoders, overriding how values of specific Rust types are
+/// serialized.
+///
+/// Types from other crates come with their own `Serialize` implementation,
+/// which may not produce what PHP code expects. Instead of wrapping every
+/// occurrence in a newtype, an encoder can be registered for the type once.
+/// It receives the value as serialized by its own implementation and returns
+/// the value to write instead.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_php::{Encoders, PhpValue};
+/// use std::time::Duration;
+///
+/// #[derive(Serialize)]
+/// struct Job {
+///     name: &'static str,
+///     timeout: Duration,
+///     retry_after: Option<Duration>,
+/// }
+///
+/// // `Duration` is a struct of `secs` and `nanos` by default, PHP expects seconds.
+/// let encoders = Encoders::new().register::<Duration, _>(|value| {
+///     let secs = value["secs"].as_f64().unwrap_or_default();
+///     let nanos = value["nanos"].as_f64().unwrap_or_default();
+///     PhpValue::from(secs + nanos / 1e9)
+/// });
+///
+/// let job = Job {
+///     name: "sync",
+///     timeout: Duration::from_millis(1500),
+///     retry_after: None,
+/// };
+/// assert_eq!(
+///     encoders.to_vec(&job).unwrap(),
+///     &br#"a:3:{s:4:"name";s:4:"sync";s:7:"timeout";d:1.5;s:11:"retry_after";N;}"#[..]
+/// );
+/// ```
+///
+/// Types are identified using [`std::any::type_name`], so an encoder applies
+/// to all instances of a generic type that only differ in lifetimes, as well
+/// as to references to the type. Encoders apply to values, including
+/// top-level values, elements and values inside `Option`s or newtypes, but not
+/// to map keys. Values are passed to encoders as [`PhpValue`]s, including
+/// objects, so values serialized as custom objects (`C:`) or enum cases
+/// (`E:`), which `PhpValue` cannot hold, fail with
+/// [`ErrorKind::UnsupportedPhpType`](crate::ErrorKind::UnsupportedPhpType).
+#[derive(Clone, Default)]
+pub struct Encoders {
+    by_type: Arc<HashMap<&'static str, Arc<EncodeFn>>>,
+}
+
+impl Encoders {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `encode` for values of type `T`, replacing any encoder
+    /// registered for `T` before.
+    pub fn register<T, F>(mut self, encode: F) -> Self
+    where
+        T: ?Sized,
+        F: Fn(PhpValue) -> PhpValue + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.by_type).insert(type_key::<T>(), Arc::new(encode));
+        self
+    }
+
+    /// Returns whether no encoders are registered.
+    pub fn is_empty(&self) -> bool {
+        self.by_type.is_empty()
+    }
+
+    /// Encoder registered for `T`, if any.
+    pub(crate) fn get<T: ?Sized>(&self) -> Option<&EncodeFn> {
+        if self.by_type.is_empty() {
+            return None;
+        }
+        self.by_type.get(type_key::<T>()).map(Arc::as_ref)
+    }
+
+    /// Write out serialization of value, applying the registered encoders.
+    ///
+    /// Uses the default options, see
+    /// [`PhpSerializerBuilder::encoders`](crate::PhpSerializerBuilder::encoders)
+    /// for others.
+    pub fn to_writer<W, T>(&self, writer: W, value: &T) -> Result<()>
+    where
+        W: Write,
+        T: Serialize + ?Sized,
+    {
+        PhpSerializerBuilder::new()
+            .encoders(self.clone())
+            .to_writer(writer, value)
+    }
+
+    /// Write serialization of value into byte vector, applying the
+    /// registered encoders.
+    pub fn to_vec<T>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut buf = Vec::new();
+        self.to_writer(&mut buf, value)?;
+        Ok(buf)
+    }
+}
+
+/// Key of `T` in the registry.
+///
+/// Collections hand out references to their elements, so references are
+/// looked up as the type they refer to.
+pub(crate) fn type_key<T: ?Sized>() -> &'static str {
+    let mut name = any::type_name::<T>();
+    while let Some(rest) = name.strip_prefix('&') {
+        name = rest.strip_prefix("mut ").unwrap_or(rest);
+    }
+    name
+}
+
+impl fmt::Debug for Encoders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.by_type.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoders;
+    use crate::value::PhpValue;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Network {
+        addr: [u8; 4],
+        prefix: u8,
+    }
+
+    fn cidr() -> Encoders {
+        Encoders::new().register::<Network, _>(|value| {
+            let octets: Vec<String> = (0..4i64)
+                .map(|idx| value["addr"][idx].to_string())
+                .collect();
+            PhpValue::from(format!("{}/{}", octets.join("."), value["prefix"]))
+        })
+    }
+
+    #[test]
+    fn applies_to_nested_values() {
+        #[derive(Serialize)]
+        struct Rule {
+            source: Network,
+            targets: Vec<Network>,
+            fallback: Option<Network>,
+        }
+
+        let rule = Rule {
+            source: Network {
+                addr: [10, 0, 0, 0],
+                prefix: 8,
+            },
+            targets: vec![Network {
+                addr: [192, 168, 1, 0],
+                prefix: 24,
+            }],
+            fallback: None,
+        };
+
+        assert_eq!(
+            cidr().to_vec(&rule).unwrap(),
+            &br#"a:3:{s:6:"source";s:10:"10.0.0.0/8";s:7:"targets";a:1:{i:0;s:14:"192.168.1.0/24";}s:8:"fallback";N;}"#[..]
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert("lan", Some(rule.targets));
+        assert_eq!(
+            cidr().to_vec(&map).unwrap(),
+            &br#"a:1:{s:3:"lan";a:1:{i:0;s:14:"192.168.1.0/24";}}"#[..]
+        );
+    }
+
+    #[test]
+    fn applies_to_top_level_values() {
+        let network = Network {
+            addr: [127, 0, 0, 1],
+            prefix: 32,
+        };
+        assert_eq!(
+            cidr().to_vec(&network).unwrap(),
+            &br#"s:12:"127.0.0.1/32";"#[..]
+        );
+        assert_eq!(
+            Encoders::new().to_vec(&network).unwrap(),
+            crate::to_vec(&network).unwrap()
+        );
+    }
+
+    #[test]
+    fn replaces_encoders() {
+        let encoders = Encoders::new()
+            .register::<u8, _>(|_| PhpValue::Null)
+            .register::<u8, _>(|value| PhpValue::from(value.as_i64().unwrap() * 2));
+
+        assert!(!encoders.is_empty());
+        assert_eq!(
+            encoders.to_vec(&vec![1u8, 2]).unwrap(),
+            b"a:2:{i:0;i:2;i:1;i:4;}"
+        );
+        // Other integer types are not affected.
+        assert_eq!(encoders.to_vec(&3u16).unwrap(), b"i:3;");
+    }
+
+    #[test]
+    fn passes_objects() {
+        use crate::error::ErrorKind;
+        use crate::version::FormatVersion;
+        use crate::PhpSerializerBuilder;
+
+        #[derive(Serialize)]
+        struct Order {
+            id: u32,
+            cart: PhpValue,
+        }
+
+        let cart = PhpValue::parse_objects(br#"O:4:"Cart":1:{s:5:"total";d:9.5;}"#).unwrap();
+        let encoders = Encoders::new().register::<Order, _>(|mut value| {
+            if let PhpValue::Array(entries) = &mut value {
+                entries.retain(|(key, _)| key.as_bytes() != Some(&b"id"[..]));
+            }
+            value
+        });
+        let order = Order { id: 7, cart };
+        assert_eq!(
+            encoders.to_vec(&order).unwrap(),
+            &br#"a:1:{s:4:"cart";O:4:"Cart":1:{s:5:"total";d:9.5;}}"#[..]
+        );
+
+        // Builder options apply along with the encoders.
+        let builder = PhpSerializerBuilder::new()
+            .encoders(encoders.clone())
+            .reject_floats(true);
+        assert!(matches!(
+            builder.to_vec(&order).unwrap_err().kind(),
+            ErrorKind::FloatRejected
+        ));
+
+        #[derive(Serialize)]
+        enum Suit {
+            Hearts,
+        }
+
+        #[derive(Serialize)]
+        struct Card {
+            suit: Suit,
+        }
+
+        let php81 = PhpSerializerBuilder::new()
+            .format_version(FormatVersion::Php81)
+            .encoders(Encoders::new().register::<Card, _>(|value| value));
+        let err = php81.to_vec(&Card { suit: Suit::Hearts }).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnsupportedPhpType {
+                type_indicator: 'E'
+            }
+        ));
+    }
+}
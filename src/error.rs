@@ -7,8 +7,106 @@ use std::{fmt, io};
 pub type Result<T> = ::core::result::Result<T, Error>;
 
 /// PHP serialization/deserialization error.
+///
+/// Carries an [`ErrorKind`] describing what went wrong, along with optional
+/// location information.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<u64>,
+    path: Option<String>,
+}
+
+impl Error {
+    /// Returns the kind of error.
+    #[inline]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Consumes the error, returning its kind.
+    #[inline]
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Byte offset into the input at which the error occurred, if known.
+    #[inline]
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Path of keys and indices leading to the value that caused the error, if known.
+    ///
+    /// Segments are separated by dots, e.g. `users.3.age`. Paths are only
+    /// recorded when deserializing with [`Profile::Strict`](crate::Profile::Strict).
+    #[inline]
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Attaches a byte offset, unless one is already present.
+    #[inline]
+    pub(crate) fn with_offset(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    /// Shifts the offset by `base`, for errors in data taken from the input
+    /// at `base`. Errors without an offset are placed at `base`.
+    #[inline]
+    pub(crate) fn with_base_offset(mut self, base: u64) -> Self {
+        self.offset = Some(self.offset.map_or(base, |offset| base + offset));
+        self
+    }
+}
+
+impl Error {
+    /// Prepends a segment to the path, as the error propagates outwards.
+    pub(crate) fn with_path_segment(mut self, segment: &str) -> Self {
+        self.path = Some(match self.path.take() {
+            Some(path) => format!("{}.{}", segment, path),
+            None => segment.to_owned(),
+        });
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    #[inline]
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            offset: None,
+            path: None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.kind, f)?;
+
+        if let Some(ref path) = self.path {
+            write!(f, " (at `{}`)", path)?;
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, " (at offset {})", offset)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Kind of PHP serialization/deserialization error.
+///
+/// New kinds may be added in future releases, so matching on this type
+/// requires a wildcard arm.
 #[derive(Debug, Display)]
-pub enum Error {
+#[ignore_extra_doc_attributes]
+#[non_exhaustive]
+pub enum ErrorKind {
     /// Error writing serializated value: {0}
     WriteSerialized(io::Error),
     /// Error reading serializing value: {0}
@@ -29,8 +127,10 @@ pub enum Error {
     },
     /// Deserialized bytestring is not valid UTF: {0}
     Utf8Error(std::str::Utf8Error),
-    /// Could not convert into char from decimal value: {0}
-    CharConversionFailed(std::char::CharTryFromError),
+    /// Code point {0} (`{0:#X}`) is not a valid Unicode scalar value.
+    InvalidCodepoint(u64),
+    /// Expected a single character, but got a string of {0} characters.
+    NotASingleChar(usize),
     /// Not a valid number or incorrect number type: {0}
     NotAValidNumber(Box<dyn std::error::Error + Send + Sync>),
     /// Not a valid value for boolean: {0}
@@ -39,8 +139,19 @@ pub enum Error {
     UnsupportedArrayKeyType(char),
     /// Invalid type indicator on value: {0}
     InvalidTypeIndicator(char),
-    /// Feature not implemented by `serde_php`: {0}
-    MissingFeature(&'static str),
+    /// Map keys of type `{type_name}` serialize as {found}, but PHP array keys
+    /// must be integers or strings.
+    UnsupportedMapKey {
+        /// Name of the Rust key type.
+        type_name: &'static str,
+        /// What the key serialized as, e.g. `a sequence`.
+        found: &'static str,
+    },
+    /// Deserializing PHP values of type `{type_indicator}` is not supported.
+    UnsupportedPhpType {
+        /// Type indicator found in the input, e.g. `O` for objects.
+        type_indicator: char,
+    },
     /// Array-index mismatch (must be in-order and numeric), expected {expected}
     /// but got {actual}
     IndexMismatch {
@@ -49,6 +160,65 @@ pub enum Error {
         /// Actual index found.
         actual: usize,
     },
+    /// Array element {0} is missing, only trailing `Option`s of tuples may be absent
+    MissingElement(usize),
+    /// Array declares {declared} elements, but only {remaining} bytes of input remain.
+    ElementCountExceedsInput {
+        /// Number of elements declared in the array header.
+        declared: usize,
+        /// Number of bytes left in the input after the header.
+        remaining: u64,
+    },
+    /// Value `{value}` is out of range for `{type_name}`.
+    OutOfRange {
+        /// Value found in the input.
+        value: String,
+        /// Name of the Rust type it was to be deserialized into.
+        type_name: &'static str,
+    },
+    /// Array with {declared} entries exceeds the limit of {limit} entries.
+    TooManyEntries {
+        /// Number of entries declared in the array header.
+        declared: usize,
+        /// Configured limit.
+        limit: usize,
+    },
+    /// Array key of {length} bytes exceeds the limit of {limit} bytes.
+    KeyTooLong {
+        /// Length of the key.
+        length: usize,
+        /// Configured limit.
+        limit: usize,
+    },
+    /// Nesting depth exceeds the limit of {0}.
+    DepthLimitExceeded(usize),
+    /// Reference to value {0}, which does not exist.
+    InvalidReference(i64),
+    /// Reference to the value at offset {offset}, which contains the reference.
+    ReferenceCycle {
+        /// Offset of the referenced value.
+        offset: u64,
+    },
+    /// Resolving references expands the input beyond the limit of {0} bytes.
+    ReferenceLimitExceeded(usize),
+    /// References cannot be written verbatim, as they would point at other values.
+    VerbatimReference,
+    /// Transcoding failed: {0}
+    TranscodeFailed(String),
+    /// Float found, but floats are rejected.
+    FloatRejected,
+    /// Enum case `{0}` is not of the form `Enum:Case`.
+    InvalidEnumCase(String),
+    /// Objects of class `{0}` are not allowed.
+    ClassNotAllowed(String),
+    /// Session variable name `{0}` cannot be encoded.
+    InvalidSessionKey(String),
+    /// `{0}` is not in the form PHP itself would produce.
+    NonCanonical(String),
+    /// Unexpected trailing data after value.
+    TrailingData,
+    /// Invalid envelope around serialized data: {0}
+    InvalidEnvelope(String),
     /// Attempted to serialize sequence of unknown length.
     ///
     /// PHP requires all collections to be length prefixed. Serializing
@@ -65,12 +235,11 @@ pub enum Error {
 //       `NotAValidNumber` errors cannot be constructed `Send`.
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            Error::WriteSerialized(ref err) => Some(err),
-            Error::ReadSerialized(ref err) => Some(err),
-            Error::Utf8Error(ref err) => Some(err),
-            Error::CharConversionFailed(ref err) => Some(err),
-            Error::NotAValidNumber(ref err) => Some(err.as_ref()),
+        match self.kind {
+            ErrorKind::WriteSerialized(ref err) => Some(err),
+            ErrorKind::ReadSerialized(ref err) => Some(err),
+            ErrorKind::Utf8Error(ref err) => Some(err),
+            ErrorKind::NotAValidNumber(ref err) => Some(err.as_ref()),
             _ => None,
         }
     }
@@ -82,7 +251,7 @@ impl serde::ser::Error for Error {
     where
         T: fmt::Display,
     {
-        Error::SerializationFailed(msg.to_string())
+        ErrorKind::SerializationFailed(msg.to_string()).into()
     }
 }
 
@@ -92,6 +261,6 @@ impl serde::de::Error for Error {
     where
         T: fmt::Display,
     {
-        Error::DeserializationFailed(msg.to_string())
+        ErrorKind::DeserializationFailed(msg.to_string()).into()
     }
 }
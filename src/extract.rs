@@ -0,0 +1,74 @@
+//! Extraction of single fields.
+
+use crate::de::PhpDeserializer;
+use crate::error::Result;
+use serde::Deserialize;
+
+/// Deserialize the value of a single key of a top-level array.
+///
+/// The array is scanned for `key` and only the matching value is
+/// deserialized, while all other entries are skipped without building up any
+/// values. This is much cheaper than deserializing the whole array when only a
+/// single field is of interest. Integer keys are matched by their decimal
+/// representation, so `"5"` finds the key `i:5;`.
+///
+/// Returns `None` if the key is not present. Entries following the matching
+/// one are not examined, so malformed data after it goes unnoticed.
+///
+/// ```rust
+/// let input = br#"a:2:{s:2:"ct";i:3;s:2:"wt";i:420;}"#;
+/// let wt: Option<u64> = serde_php::get_field(input, "wt").unwrap();
+/// assert_eq!(wt, Some(420));
+/// assert_eq!(serde_php::get_field::<u64>(input, "cpu").unwrap(), None);
+/// ```
+pub fn get_field<'de, T>(input: &'de [u8], key: &str) -> Result<Option<T>>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = PhpDeserializer::from_slice(input);
+    let num_elements = des.read_array_start()?;
+
+    for _ in 0..num_elements {
+        if des.read_key()? == key.as_bytes() {
+            return T::deserialize(&mut des).map(Some);
+        }
+        des.skip_value()?;
+    }
+
+    des.read_array_end()?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_field;
+    use serde::Deserialize;
+
+    #[test]
+    fn finds_nested_values() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Stats {
+            ct: u32,
+        }
+
+        let input = br#"a:3:{s:4:"skip";a:1:{s:2:"wt";i:1;}i:7;s:4:"main";s:5:"stats";a:1:{s:2:"ct";i:9;}}"#;
+
+        assert_eq!(
+            get_field::<Stats>(input, "stats").unwrap(),
+            Some(Stats { ct: 9 })
+        );
+        assert_eq!(
+            get_field::<String>(input, "7").unwrap(),
+            Some("main".to_owned())
+        );
+        // Only top-level keys are considered.
+        assert_eq!(get_field::<u32>(input, "wt").unwrap(), None);
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert!(get_field::<u32>(b"i:1;", "a").is_err());
+        assert!(get_field::<u32>(br#"a:1:{s:1:"a";s:1:"x";}"#, "a").is_err());
+        assert!(get_field::<u32>(br#"a:2:{s:1:"b";i:1;"#, "a").is_err());
+    }
+}
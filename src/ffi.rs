@@ -0,0 +1,287 @@
+//! C foreign function interface.
+//!
+//! Exposes conversion between PHP-serialized data and JSON to non-Rust code.
+//! The corresponding C declarations can be found in `include/serde_php.h`.
+//! To build a shared or static library, enable the `ffi` feature and select
+//! the crate type explicitly, e.g.
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! All functions return one of the `SERDE_PHP_*` status codes. Buffers
+//! returned through output parameters are owned by the caller and must be
+//! released using `serde_php_free_string` or `serde_php_free_bytes`. Error
+//! messages are optionally returned through an `error` output parameter, which
+//! may be `NULL` if the caller is not interested in them.
+
+use crate::value::PhpValue;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::{ptr, slice};
+
+/// Operation succeeded.
+pub const SERDE_PHP_OK: c_int = 0;
+/// A required pointer argument was `NULL`.
+pub const SERDE_PHP_ERR_NULL_ARGUMENT: c_int = 1;
+/// The PHP-serialized input is not valid.
+pub const SERDE_PHP_ERR_INVALID_PHP: c_int = 2;
+/// The JSON input is not valid.
+pub const SERDE_PHP_ERR_INVALID_JSON: c_int = 3;
+
+/// Store an error message in `error`, if it is not `NULL`.
+unsafe fn set_error(error: *mut *mut c_char, msg: String) {
+    if !error.is_null() {
+        // Error messages never contain NUL bytes, but be defensive anyway.
+        let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+        *error = msg.into_raw();
+    }
+}
+
+/// Convert PHP-serialized input into a NUL-terminated JSON string.
+///
+/// On success, `*output` is set to a newly allocated string. Input nested
+/// more than 128 levels deep is rejected as invalid, as are keys that are not
+/// valid UTF-8, which JSON cannot represent.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes, `output` must be valid
+/// for writes. `error` must either be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn serde_php_to_json(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    if input.is_null() || output.is_null() {
+        return SERDE_PHP_ERR_NULL_ARGUMENT;
+    }
+
+    let value = match PhpValue::parse_bounded(slice::from_raw_parts(input, input_len)) {
+        Ok(value) => value,
+        Err(err) => {
+            set_error(error, err.to_string());
+            return SERDE_PHP_ERR_INVALID_PHP;
+        }
+    };
+
+    // Keys that are not valid UTF-8 cannot be represented in JSON.
+    let json = match serde_json::to_string(&value) {
+        Ok(json) => json,
+        Err(err) => {
+            set_error(error, err.to_string());
+            return SERDE_PHP_ERR_INVALID_PHP;
+        }
+    };
+    // JSON never contains raw NUL bytes.
+    *output = CString::new(json).unwrap_or_default().into_raw();
+
+    SERDE_PHP_OK
+}
+
+/// Check whether input is a single valid PHP-serialized value.
+///
+/// Returns `SERDE_PHP_OK` if it is, `SERDE_PHP_ERR_INVALID_PHP` otherwise,
+/// including for input nested more than 128 levels deep.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes. `error` must either be
+/// `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn serde_php_validate(
+    input: *const u8,
+    input_len: usize,
+    error: *mut *mut c_char,
+) -> c_int {
+    if input.is_null() {
+        return SERDE_PHP_ERR_NULL_ARGUMENT;
+    }
+
+    match PhpValue::parse_bounded(slice::from_raw_parts(input, input_len)) {
+        Ok(_) => SERDE_PHP_OK,
+        Err(err) => {
+            set_error(error, err.to_string());
+            SERDE_PHP_ERR_INVALID_PHP
+        }
+    }
+}
+
+/// Convert a NUL-terminated JSON string into PHP-serialized data.
+///
+/// On success, `*output` is set to a newly allocated buffer of `*output_len`
+/// bytes. The output is not NUL-terminated, as PHP-serialized data may
+/// contain NUL bytes.
+///
+/// # Safety
+///
+/// `json` must be a valid NUL-terminated string, `output` and `output_len`
+/// must be valid for writes. `error` must either be `NULL` or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn serde_php_from_json(
+    json: *const c_char,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+    error: *mut *mut c_char,
+) -> c_int {
+    if json.is_null() || output.is_null() || output_len.is_null() {
+        return SERDE_PHP_ERR_NULL_ARGUMENT;
+    }
+
+    let parsed: serde_json::Value = match serde_json::from_slice(CStr::from_ptr(json).to_bytes()) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            set_error(error, err.to_string());
+            return SERDE_PHP_ERR_INVALID_JSON;
+        }
+    };
+
+    let data = PhpValue::from(parsed).to_vec().into_boxed_slice();
+    *output_len = data.len();
+    *output = Box::into_raw(data) as *mut u8;
+
+    SERDE_PHP_OK
+}
+
+/// Release a string returned by this library. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `s` must have been returned by this library and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn serde_php_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Release a buffer returned by `serde_php_from_json`. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `data` and `len` must have been returned by this library and the buffer
+/// must not have been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn serde_php_free_bytes(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_json_and_back() {
+        let input = br#"a:2:{s:1:"a";i:1;s:1:"b";a:1:{i:0;b:1;}}"#;
+
+        unsafe {
+            assert_eq!(
+                serde_php_validate(input.as_ptr(), input.len(), ptr::null_mut()),
+                SERDE_PHP_OK
+            );
+
+            let mut json = ptr::null_mut();
+            assert_eq!(
+                serde_php_to_json(input.as_ptr(), input.len(), &mut json, ptr::null_mut()),
+                SERDE_PHP_OK
+            );
+            assert_eq!(
+                CStr::from_ptr(json).to_str().unwrap(),
+                r#"{"a":1,"b":[true]}"#
+            );
+
+            let mut output = ptr::null_mut();
+            let mut output_len = 0;
+            assert_eq!(
+                serde_php_from_json(json, &mut output, &mut output_len, ptr::null_mut()),
+                SERDE_PHP_OK
+            );
+            assert_eq!(slice::from_raw_parts(output, output_len), &input[..]);
+
+            serde_php_free_string(json);
+            serde_php_free_bytes(output, output_len);
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        let input = b"i:1";
+
+        unsafe {
+            let mut error = ptr::null_mut();
+            assert_eq!(
+                serde_php_validate(input.as_ptr(), input.len(), &mut error),
+                SERDE_PHP_ERR_INVALID_PHP
+            );
+            assert!(!error.is_null());
+            serde_php_free_string(error);
+
+            let mut output = ptr::null_mut();
+            let mut output_len = 0;
+            let json = CString::new("{").unwrap();
+            assert_eq!(
+                serde_php_from_json(json.as_ptr(), &mut output, &mut output_len, ptr::null_mut()),
+                SERDE_PHP_ERR_INVALID_JSON
+            );
+            assert!(output.is_null());
+
+            assert_eq!(
+                serde_php_to_json(ptr::null(), 0, &mut ptr::null_mut(), ptr::null_mut()),
+                SERDE_PHP_ERR_NULL_ARGUMENT
+            );
+        }
+    }
+
+    #[test]
+    fn reports_unrepresentable_keys() {
+        let input = b"a:1:{s:1:\"\xff\";i:1;}";
+
+        unsafe {
+            let mut json = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            assert_eq!(
+                serde_php_to_json(input.as_ptr(), input.len(), &mut json, &mut error),
+                SERDE_PHP_ERR_INVALID_PHP
+            );
+            assert!(json.is_null());
+            assert!(!error.is_null());
+            serde_php_free_string(error);
+        }
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let nested = |depth| {
+            let mut input = b"a:1:{i:0;".repeat(depth);
+            input.extend_from_slice(b"N;");
+            input.extend_from_slice(&b"}".repeat(depth));
+            input
+        };
+
+        unsafe {
+            let input = nested(128);
+            assert_eq!(
+                serde_php_validate(input.as_ptr(), input.len(), ptr::null_mut()),
+                SERDE_PHP_OK
+            );
+
+            // Deep enough to overflow the stack without a limit.
+            let input = nested(1_000_000);
+            assert_eq!(
+                serde_php_validate(input.as_ptr(), input.len(), ptr::null_mut()),
+                SERDE_PHP_ERR_INVALID_PHP
+            );
+            let mut json = ptr::null_mut();
+            assert_eq!(
+                serde_php_to_json(input.as_ptr(), input.len(), &mut json, ptr::null_mut()),
+                SERDE_PHP_ERR_INVALID_PHP
+            );
+            assert!(json.is_null());
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Streaming aggregation over arrays.
+
+use crate::de::{PhpDeserializer, ReferencePolicy};
+use crate::error::{ErrorKind, Result};
+use serde::de::DeserializeOwned;
+use std::io::{BufReader, Read};
+
+/// Fold over the entries of a top-level array, one entry at a time.
+///
+/// Each key-value pair is deserialized and handed to `f` together with the
+/// accumulator, after which it is dropped. At no point is more than a single
+/// entry held in memory, allowing aggregation over exports far larger than
+/// the available memory. For the same reason, references (`R:` and `r:`)
+/// are not resolved, but result in an error.
+///
+/// Keys are deserialized as `K`, which must accept the key types present in
+/// the input (`i64` for integer keys, `String` for string keys). Input that
+/// is not exactly one array, including input with trailing data, results in
+/// an error.
+///
+/// ```rust
+/// let input = &br#"a:3:{s:1:"a";i:1;s:1:"b";i:2;s:1:"c";i:3;}"#[..];
+/// let sum = serde_php::fold_array(input, 0, |acc, _key: String, value: i64| acc + value)
+///     .unwrap();
+/// assert_eq!(sum, 6);
+/// ```
+pub fn fold_array<R, K, V, A, F>(reader: R, init: A, mut f: F) -> Result<A>
+where
+    R: Read,
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    F: FnMut(A, K, V) -> A,
+{
+    let mut des = PhpDeserializer::new(BufReader::new(reader));
+    des.set_references(ReferencePolicy::Error);
+    let num_elements = des.read_array_start()?;
+
+    let mut acc = init;
+    for _ in 0..num_elements {
+        let key = K::deserialize(&mut des)?;
+        let value = V::deserialize(&mut des)?;
+        acc = f(acc, key, value);
+    }
+
+    des.read_array_end()?;
+    match des.peek()? {
+        Some(_) => Err(ErrorKind::TrailingData.into()),
+        None => Ok(acc),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fold_array;
+    use crate::error::ErrorKind;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize)]
+    struct Row {
+        status: String,
+    }
+
+    #[test]
+    fn histogram() {
+        let input = br#"a:3:{i:0;a:1:{s:6:"status";s:2:"ok";}i:1;a:1:{s:6:"status";s:4:"fail";}i:2;a:1:{s:6:"status";s:2:"ok";}}"#;
+
+        let counts = fold_array(
+            &input[..],
+            BTreeMap::new(),
+            |mut counts, _idx: u64, row: Row| {
+                *counts.entry(row.status).or_insert(0) += 1;
+                counts
+            },
+        )
+        .unwrap();
+
+        assert_eq!(counts["ok"], 2);
+        assert_eq!(counts["fail"], 1);
+    }
+
+    #[test]
+    fn empty_and_invalid() {
+        let count = |input: &[u8]| fold_array(input, 0, |n, _: u64, _: i64| n + 1);
+
+        assert_eq!(count(b"a:0:{}").unwrap(), 0);
+        assert!(count(b"i:1;").is_err());
+        assert!(count(b"a:2:{i:0;i:1;}").is_err());
+        assert!(matches!(
+            count(b"a:0:{}N;").unwrap_err().kind(),
+            ErrorKind::TrailingData
+        ));
+    }
+}
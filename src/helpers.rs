@@ -0,0 +1,789 @@
+//! Field helpers for use with `#[serde(with = "...")]`.
+
+/// Lists stored as a single comma-separated string, e.g. `"a,b,c"`.
+///
+/// Items can be of any type implementing `Display` and `FromStr`. An empty
+/// string is an empty list. Items are not trimmed and may not contain the
+/// delimiter themselves. For other delimiters, see [`delimited`].
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Post {
+///     #[serde(with = "serde_php::helpers::comma_separated")]
+///     tags: Vec<String>,
+/// }
+///
+/// let input = br#"a:1:{s:4:"tags";s:10:"rust,serde";}"#;
+/// let post: Post = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(post.tags, vec!["rust", "serde"]);
+/// assert_eq!(serde_php::to_vec(&post).unwrap(), input.to_vec());
+/// ```
+pub mod comma_separated {
+    use serde::{Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    /// Serialize items as a comma-separated string.
+    pub fn serialize<T, S>(items: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        super::delimited::serialize::<',', T, S>(items, serializer)
+    }
+
+    /// Deserialize items from a comma-separated string.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        super::delimited::deserialize::<',', T, D>(deserializer)
+    }
+}
+
+/// Lists stored as a single string with a configurable delimiter.
+///
+/// The delimiter is passed as a const generic parameter:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Config {
+///     #[serde(
+///         serialize_with = "serde_php::helpers::delimited::serialize::<'|', _, _>",
+///         deserialize_with = "serde_php::helpers::delimited::deserialize::<'|', _, _>"
+///     )]
+///     ports: Vec<u16>,
+/// }
+///
+/// let input = br#"a:1:{s:5:"ports";s:7:"80|8080";}"#;
+/// let config: Config = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(config.ports, vec![80, 8080]);
+/// assert_eq!(serde_php::to_vec(&config).unwrap(), input.to_vec());
+/// ```
+pub mod delimited {
+    use serde::de::{Deserialize, Deserializer, Error};
+    use serde::Serializer;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    /// Serialize items as a string separated by `DELIM`.
+    pub fn serialize<const DELIM: char, T, S>(items: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        let mut joined = String::new();
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                joined.push(DELIM);
+            }
+            joined.push_str(&item.to_string());
+        }
+
+        serializer.serialize_str(&joined)
+    }
+
+    /// Deserialize items from a string separated by `DELIM`.
+    pub fn deserialize<'de, const DELIM: char, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let joined = String::deserialize(deserializer)?;
+
+        if joined.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        joined
+            .split(DELIM)
+            .map(|item| {
+                item.parse()
+                    .map_err(|err| D::Error::custom(format!("invalid item `{}`: {}", item, err)))
+            })
+            .collect()
+    }
+}
+
+/// Numeric arrays as `Vec<(i64, T)>`, keeping the original indices.
+///
+/// Unlike [`deserialize_unordered_array`](crate::deserialize_unordered_array),
+/// neither gaps nor the order of entries are changed, which is useful when
+/// indices carry meaning, e.g. user IDs used as keys. Serializing writes the
+/// pairs back in the same order.
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Scores {
+///     #[serde(with = "serde_php::helpers::indexed_pairs")]
+///     by_user: Vec<(i64, u32)>,
+/// }
+///
+/// let input = br#"a:1:{s:7:"by_user";a:2:{i:42;i:7;i:3;i:9;}}"#;
+/// let scores: Scores = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(scores.by_user, vec![(42, 7), (3, 9)]);
+/// assert_eq!(serde_php::to_vec(&scores).unwrap(), input.to_vec());
+/// ```
+pub mod indexed_pairs {
+    use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+    use serde::{Serialize, Serializer};
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    /// Serialize pairs as an array, using the first element as key.
+    pub fn serialize<T, S>(pairs: &[(i64, T)], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_map(pairs.iter().map(|(idx, value)| (idx, value)))
+    }
+
+    /// Deserialize an array into pairs of index and value, in input order.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<(i64, T)>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(PairsVisitor(PhantomData))
+    }
+
+    /// Visitor collecting map entries in order.
+    struct PairsVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for PairsVisitor<T>
+    where
+        T: Deserialize<'de>,
+    {
+        type Value = Vec<(i64, T)>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array with integer keys")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut pairs = Vec::with_capacity(map.size_hint().unwrap_or(0).min(1024));
+            while let Some(pair) = map.next_entry()? {
+                pairs.push(pair);
+            }
+            Ok(pairs)
+        }
+    }
+}
+
+/// Sets stored as arrays whose keys do not matter.
+///
+/// Sets such as `HashSet` and `BTreeSet` are written as numeric arrays and
+/// can be read back from arrays with consecutive keys without this helper.
+/// PHP functions like `array_unique` and `array_filter` leave holes in the
+/// keys though. Serde requests sets exactly like a `Vec`, whose positions
+/// matter, so without this helper such arrays are rejected with an
+/// [`IndexMismatch`](crate::ErrorKind::IndexMismatch) error for sets as well.
+/// This helper ignores the keys altogether, accepting any array. Any
+/// collection implementing `Default` and `Extend` can be used:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use std::collections::BTreeSet;
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Post {
+///     #[serde(with = "serde_php::helpers::set")]
+///     tags: BTreeSet<String>,
+/// }
+///
+/// // array_unique(["php", "rust", "php", "go"])
+/// let input = br#"a:1:{s:4:"tags";a:3:{i:0;s:3:"php";i:1;s:4:"rust";i:3;s:2:"go";}}"#;
+/// let post: Post = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(post.tags.len(), 3);
+///
+/// let output = serde_php::to_vec(&post).unwrap();
+/// assert_eq!(output, &br#"a:1:{s:4:"tags";a:3:{i:0;s:2:"go";i:1;s:3:"php";i:2;s:4:"rust";}}"#[..]);
+/// ```
+pub mod set {
+    use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+    use serde::{Serialize, Serializer};
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    /// Serialize the items as an array with consecutive keys.
+    pub fn serialize<C, T, S>(set: &C, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        for<'a> &'a C: IntoIterator<Item = &'a T>,
+        T: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(set)
+    }
+
+    /// Deserialize the values of an array, whatever their keys.
+    pub fn deserialize<'de, C, T, D>(deserializer: D) -> Result<C, D::Error>
+    where
+        C: Default + Extend<T> + IntoIterator<Item = T>,
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ValuesVisitor(PhantomData))
+    }
+
+    /// Visitor collecting map values, discarding the keys.
+    struct ValuesVisitor<C, T>(PhantomData<(C, T)>);
+
+    impl<'de, C, T> Visitor<'de> for ValuesVisitor<C, T>
+    where
+        C: Default + Extend<T> + IntoIterator<Item = T>,
+        T: Deserialize<'de>,
+    {
+        type Value = C;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut set = C::default();
+            while let Some((IgnoredAny, value)) = map.next_entry()? {
+                set.extend(Some(value));
+            }
+            Ok(set)
+        }
+    }
+}
+
+/// Values that may occur more than once, such as `Rc`s and `Arc`s.
+///
+/// With [`PhpSerializerBuilder::backreferences`](crate::PhpSerializerBuilder::backreferences)
+/// enabled, repeated occurrences of the same value, as identified by its
+/// address, are written as references to the first one. Otherwise, and for
+/// other formats, values are serialized unchanged. To use this for elements
+/// of collections, wrap the element type in a newtype using this helper.
+///
+/// Deserializing resolves references to copies of the referenced value, so
+/// deserialized values are never shared.
+pub mod shared {
+    use crate::ser::SHARED_TOKEN;
+    use serde::de::{Deserialize, Deserializer};
+    use serde::{Serialize, Serializer};
+    use std::ops::Deref;
+
+    /// Serialize the value pointed to, or a reference to an earlier
+    /// occurrence.
+    pub fn serialize<P, T, S>(value: &P, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        P: Deref<Target = T>,
+        T: ?Sized + Serialize,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(SHARED_TOKEN, &**value)
+    }
+
+    /// Deserialize a value, wrapping it in a new pointer.
+    pub fn deserialize<'de, P, T, D>(deserializer: D) -> Result<P, D::Error>
+    where
+        P: Deref<Target = T> + From<T>,
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(P::from)
+    }
+}
+
+/// Maps whose keys are written using their `Display` implementation.
+///
+/// PHP array keys must be integers or strings. Integer and string keys,
+/// newtypes around them and unit enum variants (written as their name) are
+/// supported as is; serializing maps with other key types fails, naming the
+/// key type. Keys that implement `Display` and `FromStr` can be stored as
+/// strings with this helper instead:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use std::collections::BTreeMap;
+/// use std::net::Ipv4Addr;
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Hosts {
+///     #[serde(with = "serde_php::helpers::display_keys")]
+///     names: BTreeMap<Ipv4Addr, String>,
+/// }
+///
+/// let input = br#"a:1:{s:5:"names";a:1:{s:9:"127.0.0.1";s:9:"localhost";}}"#;
+/// let hosts: Hosts = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(hosts.names[&Ipv4Addr::LOCALHOST], "localhost");
+/// assert_eq!(serde_php::to_vec(&hosts).unwrap(), input.to_vec());
+/// ```
+pub mod display_keys {
+    use serde::de::{Deserialize, Deserializer, Error, MapAccess, Visitor};
+    use serde::{Serialize, Serializer};
+    use std::fmt::{self, Display};
+    use std::iter::FromIterator;
+    use std::marker::PhantomData;
+    use std::str::FromStr;
+
+    /// Serialize a map, writing keys as strings.
+    pub fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Display + 'a,
+        V: Serialize + 'a,
+        S: Serializer,
+    {
+        serializer.collect_map(map.into_iter().map(|(key, value)| (key.to_string(), value)))
+    }
+
+    /// Deserialize a map, parsing keys from strings.
+    pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: FromIterator<(K, V)>,
+        K: FromStr,
+        K::Err: Display,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(KeysVisitor(PhantomData))
+    }
+
+    /// Visitor parsing the keys of map entries.
+    struct KeysVisitor<M, K, V>(PhantomData<(M, K, V)>);
+
+    impl<'de, M, K, V> Visitor<'de> for KeysVisitor<M, K, V>
+    where
+        M: FromIterator<(K, V)>,
+        K: FromStr,
+        K::Err: Display,
+        V: Deserialize<'de>,
+    {
+        type Value = M;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(1024));
+            while let Some((KeyText(key), value)) = map.next_entry::<KeyText, V>()? {
+                let key = key
+                    .parse()
+                    .map_err(|err| A::Error::custom(format!("invalid key `{}`: {}", key, err)))?;
+                entries.push((key, value));
+            }
+            Ok(entries.into_iter().collect())
+        }
+    }
+
+    /// Text of a key, with integer keys in their decimal representation.
+    struct KeyText(String);
+
+    impl<'de> Deserialize<'de> for KeyText {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct KeyTextVisitor;
+
+            impl<'de> Visitor<'de> for KeyTextVisitor {
+                type Value = KeyText;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    formatter.write_str("an array key")
+                }
+
+                fn visit_i64<E: Error>(self, v: i64) -> Result<KeyText, E> {
+                    Ok(KeyText(v.to_string()))
+                }
+
+                fn visit_u64<E: Error>(self, v: u64) -> Result<KeyText, E> {
+                    Ok(KeyText(v.to_string()))
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<KeyText, E> {
+                    Ok(KeyText(v.to_owned()))
+                }
+            }
+
+            deserializer.deserialize_any(KeyTextVisitor)
+        }
+    }
+}
+
+/// Fields that deserialize `null` as their default value.
+///
+/// Useful for fields that are occasionally `null` in the input, without
+/// making them `Option`s. Values are serialized unchanged. To apply this to
+/// all fields, see
+/// [`PhpDeserializerBuilder::null_as_default`](crate::PhpDeserializerBuilder::null_as_default).
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, Serialize)]
+/// struct Post {
+///     #[serde(with = "serde_php::helpers::null_as_default")]
+///     tags: Vec<String>,
+/// }
+///
+/// let post: Post = serde_php::from_bytes(br#"a:1:{s:4:"tags";N;}"#).unwrap();
+/// assert!(post.tags.is_empty());
+/// ```
+pub mod null_as_default {
+    use serde::de::{Deserialize, Deserializer};
+    use serde::{Serialize, Serializer};
+
+    /// Serialize the value unchanged.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        value.serialize(serializer)
+    }
+
+    /// Deserialize a value, using its default for `null`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + Default,
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+    }
+}
+
+/// Money value objects stored as `['amount' => '1999', 'currency' => 'EUR']`.
+///
+/// This is the shape used by `moneyphp/money` and many hand-written value
+/// objects. Amounts are integers in minor units of the currency, e.g. cents,
+/// and may be stored as either integers or numeric strings. Serializing
+/// writes them as strings. Other keys of the array are ignored.
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_php::helpers::money::Money;
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Order {
+///     #[serde(with = "serde_php::helpers::money")]
+///     total: Money,
+/// }
+///
+/// let input = br#"a:1:{s:5:"total";a:2:{s:6:"amount";i:1999;s:8:"currency";s:3:"EUR";}}"#;
+/// let order: Order = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(order.total, Money::new(1999, "EUR"));
+/// assert_eq!(
+///     serde_php::to_vec(&order).unwrap(),
+///     &br#"a:1:{s:5:"total";a:2:{s:6:"amount";s:4:"1999";s:8:"currency";s:3:"EUR";}}"#[..]
+/// );
+/// ```
+pub mod money {
+    use serde::de::{self, Deserializer, IgnoredAny, MapAccess, Unexpected, Visitor};
+    use serde::ser::{SerializeStruct, Serializer};
+    use std::convert::TryFrom;
+    use std::fmt;
+
+    /// An amount of money in minor units of a currency.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    pub struct Money {
+        /// Amount in minor units, e.g. cents.
+        pub amount: i64,
+        /// Currency code, e.g. `EUR`.
+        pub currency: String,
+    }
+
+    impl Money {
+        /// Create a new amount of money.
+        pub fn new(amount: i64, currency: impl Into<String>) -> Self {
+            Money {
+                amount,
+                currency: currency.into(),
+            }
+        }
+    }
+
+    /// Serialize money as array of a string amount and currency.
+    pub fn serialize<S>(money: &Money, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Money", 2)?;
+        state.serialize_field("amount", &money.amount.to_string())?;
+        state.serialize_field("currency", &money.currency)?;
+        state.end()
+    }
+
+    /// Deserialize money from an array of amount and currency.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Money, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("Money", &["amount", "currency"], MoneyVisitor)
+    }
+
+    struct MoneyVisitor;
+
+    impl<'de> Visitor<'de> for MoneyVisitor {
+        type Value = Money;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of amount and currency")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Money, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut amount = None;
+            let mut currency = None;
+
+            while let Some(key) = map.next_key::<String>()? {
+                match key.as_str() {
+                    "amount" => amount = Some(map.next_value::<Amount>()?.0),
+                    "currency" => currency = Some(map.next_value()?),
+                    _ => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+            }
+
+            Ok(Money {
+                amount: amount.ok_or_else(|| de::Error::missing_field("amount"))?,
+                currency: currency.ok_or_else(|| de::Error::missing_field("currency"))?,
+            })
+        }
+    }
+
+    /// Amount given as either integer or numeric string.
+    struct Amount(i64);
+
+    impl<'de> de::Deserialize<'de> for Amount {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct AmountVisitor;
+
+            impl<'de> Visitor<'de> for AmountVisitor {
+                type Value = Amount;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    formatter.write_str("an integer amount")
+                }
+
+                fn visit_i64<E: de::Error>(self, v: i64) -> Result<Amount, E> {
+                    Ok(Amount(v))
+                }
+
+                fn visit_u64<E: de::Error>(self, v: u64) -> Result<Amount, E> {
+                    i64::try_from(v)
+                        .map(Amount)
+                        .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+                }
+
+                fn visit_str<E: de::Error>(self, v: &str) -> Result<Amount, E> {
+                    v.parse()
+                        .map(Amount)
+                        .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+                }
+
+                fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Amount, E> {
+                    match std::str::from_utf8(v) {
+                        Ok(s) => self.visit_str(s),
+                        Err(_) => Err(E::invalid_value(Unexpected::Bytes(v), &self)),
+                    }
+                }
+            }
+
+            // Strings are handed out as a whole, integers are passed on as such.
+            deserializer.deserialize_byte_buf(AmountVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, to_vec, ErrorKind};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Lists {
+        #[serde(with = "super::comma_separated")]
+        names: Vec<String>,
+        #[serde(
+            serialize_with = "super::delimited::serialize::<';', _, _>",
+            deserialize_with = "super::delimited::deserialize::<';', _, _>"
+        )]
+        ids: Vec<u32>,
+    }
+
+    #[test]
+    fn roundtrip_lists() {
+        let input = br#"a:2:{s:5:"names";s:5:"a,,b ";s:3:"ids";s:0:"";}"#;
+        let lists: Lists = from_bytes(input).unwrap();
+
+        assert_eq!(
+            lists,
+            Lists {
+                names: vec!["a".to_owned(), String::new(), "b ".to_owned()],
+                ids: vec![],
+            }
+        );
+        assert_eq!(to_vec(&lists).unwrap(), input.to_vec());
+    }
+
+    #[test]
+    fn indexed_pairs_keep_gaps_and_order() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Slots(#[serde(with = "super::indexed_pairs")] Vec<(i64, Option<String>)>);
+
+        let input = br#"a:3:{i:5;s:1:"a";i:-1;N;i:0;s:1:"b";}"#;
+        let slots: Slots = from_bytes(input).unwrap();
+        assert_eq!(
+            slots.0,
+            vec![
+                (5, Some("a".to_owned())),
+                (-1, None),
+                (0, Some("b".to_owned()))
+            ]
+        );
+        assert_eq!(to_vec(&slots).unwrap(), input.to_vec());
+
+        assert_eq!(from_bytes::<Slots>(b"a:0:{}").unwrap(), Slots(vec![]));
+        assert!(from_bytes::<Slots>(br#"a:1:{s:1:"x";i:1;}"#).is_err());
+    }
+
+    #[test]
+    fn sets_roundtrip() {
+        use std::collections::{BTreeSet, HashSet};
+
+        // Consecutive keys, as written for sets, need no helper.
+        let ids: HashSet<u32> = [7, 3].iter().copied().collect();
+        assert_eq!(
+            from_bytes::<HashSet<u32>>(&to_vec(&ids).unwrap()).unwrap(),
+            ids
+        );
+        let tags: BTreeSet<String> = vec!["b".to_owned(), "a".to_owned()].into_iter().collect();
+        let output = to_vec(&tags).unwrap();
+        assert_eq!(output, br#"a:2:{i:0;s:1:"a";i:1;s:1:"b";}"#.to_vec());
+        assert_eq!(from_bytes::<BTreeSet<String>>(&output).unwrap(), tags);
+        assert!(from_bytes::<BTreeSet<u32>>(b"a:2:{i:0;i:1;i:2;i:2;}").is_err());
+
+        // Holes are rejected as for a `Vec`, the helper accepts them.
+        let holes = b"a:2:{i:0;i:5;i:3;i:7;}";
+        let err = from_bytes::<HashSet<u32>>(holes).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::IndexMismatch { .. }));
+        let err = from_bytes::<BTreeSet<u32>>(holes).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::IndexMismatch { .. }));
+        #[derive(Debug, Deserialize)]
+        struct Ids(#[serde(with = "super::set")] BTreeSet<u32>);
+        let ids: Ids = from_bytes(holes).unwrap();
+        assert_eq!(ids.0, [5, 7].iter().copied().collect());
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Groups {
+            #[serde(with = "super::set")]
+            ids: HashSet<u32>,
+            #[serde(with = "super::set")]
+            names: BTreeSet<String>,
+        }
+
+        // Holes, shuffled keys, string keys and duplicates.
+        let input =
+            br#"a:2:{s:3:"ids";a:4:{i:0;i:1;i:2;i:2;i:9;i:1;s:1:"x";i:5;}s:5:"names";a:0:{}}"#;
+        let groups: Groups = from_bytes(input).unwrap();
+        assert_eq!(groups.ids, [1, 2, 5].iter().copied().collect());
+        assert!(groups.names.is_empty());
+        assert_eq!(
+            from_bytes::<Groups>(&to_vec(&groups).unwrap()).unwrap(),
+            groups
+        );
+    }
+
+    #[test]
+    fn null_as_default_field() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Counter {
+            #[serde(with = "super::null_as_default")]
+            count: u32,
+        }
+
+        assert_eq!(
+            from_bytes::<Counter>(br#"a:1:{s:5:"count";N;}"#).unwrap(),
+            Counter { count: 0 }
+        );
+        assert_eq!(
+            from_bytes::<Counter>(br#"a:1:{s:5:"count";i:3;}"#).unwrap(),
+            Counter { count: 3 }
+        );
+        assert_eq!(
+            to_vec(&Counter { count: 0 }).unwrap(),
+            br#"a:1:{s:5:"count";i:0;}"#.to_vec()
+        );
+    }
+
+    #[test]
+    fn money_amounts() {
+        use super::money::Money;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Price(#[serde(with = "super::money")] Money);
+
+        let input = br#"a:3:{s:8:"currency";s:3:"USD";s:6:"amount";s:4:"-250";s:5:"scale";i:2;}"#;
+        assert_eq!(
+            from_bytes::<Price>(input).unwrap(),
+            Price(Money::new(-250, "USD"))
+        );
+        assert_eq!(
+            to_vec(&Price(Money::new(-250, "USD"))).unwrap(),
+            br#"a:2:{s:6:"amount";s:4:"-250";s:8:"currency";s:3:"USD";}"#.to_vec()
+        );
+
+        assert!(from_bytes::<Price>(
+            br#"a:2:{s:6:"amount";s:5:"19.99";s:8:"currency";s:3:"EUR";}"#
+        )
+        .is_err());
+        assert!(from_bytes::<Price>(br#"a:1:{s:6:"amount";i:1;}"#).is_err());
+    }
+
+    #[test]
+    fn display_keys_roundtrip() {
+        use std::collections::HashMap;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Limits(#[serde(with = "super::display_keys")] HashMap<u8, u32>);
+
+        // Integer keys are parsed from their decimal representation as well.
+        let input = br#"a:2:{i:1;i:10;s:1:"2";i:20;}"#;
+        let limits: Limits = from_bytes(input).unwrap();
+        assert_eq!(limits.0.len(), 2);
+        assert_eq!(limits.0[&2], 20);
+
+        let single = Limits(std::iter::once((5, 50)).collect());
+        assert_eq!(to_vec(&single).unwrap(), br#"a:1:{s:1:"5";i:50;}"#.to_vec());
+
+        let err = from_bytes::<Limits>(br#"a:1:{s:3:"300";i:1;}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid key `300`"));
+    }
+
+    #[test]
+    fn invalid_item() {
+        let input = br#"a:2:{s:5:"names";s:0:"";s:3:"ids";s:3:"1;x";}"#;
+        let err = from_bytes::<Lists>(input).unwrap_err();
+        assert!(err.to_string().contains("invalid item `x`"));
+    }
+}
@@ -0,0 +1,209 @@
+//! Index of the entries of a top-level array.
+
+use crate::de::{PhpDeserializer, PhpDeserializerBuilder, ReferencePolicy};
+use crate::error::{Error, ErrorKind, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Index of a top-level array, mapping keys onto the byte ranges of their
+/// values.
+///
+/// Building the index scans the input once, validating its structure without
+/// building up any values. Afterwards, individual entries are deserialized on
+/// demand, touching only the bytes of their value. For large arrays that are
+/// accessed repeatedly, e.g. session or options blobs, this is cheaper than
+/// deserializing everything up front or scanning the input for every access
+/// using [`get_field`](crate::get_field).
+///
+/// Integer keys are matched by their decimal representation, so `"5"` finds
+/// the key `i:5;`. If a key occurs more than once, the last entry wins, as in
+/// PHP.
+///
+/// ```rust
+/// use serde_php::ArrayIndex;
+///
+/// let input = br#"a:3:{s:4:"user";s:3:"bob";s:4:"cart";a:1:{i:0;i:42;}i:7;b:1;}"#;
+/// let index = ArrayIndex::new(input).unwrap();
+///
+/// assert_eq!(index.len(), 3);
+/// assert_eq!(index.get::<Vec<u32>>("cart").unwrap(), Some(vec![42]));
+/// assert_eq!(index.get::<bool>("7").unwrap(), Some(true));
+/// assert_eq!(index.raw("user"), Some(&br#"s:3:"bob";"#[..]));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ArrayIndex<'a> {
+    input: &'a [u8],
+    /// Keys and value ranges, in input order.
+    entries: Vec<(Vec<u8>, Range<usize>)>,
+    /// Position in `entries` of the last entry for each key.
+    positions: HashMap<Vec<u8>, usize>,
+}
+
+impl<'a> ArrayIndex<'a> {
+    /// Index the array contained in `input`.
+    ///
+    /// Fails if `input` is not a single, well-formed array.
+    pub fn new(input: &'a [u8]) -> Result<Self> {
+        let mut des = PhpDeserializer::from_slice_unbuffered(input);
+        // Values are deserialized on their own later, so references between
+        // them cannot be resolved.
+        des.set_references(ReferencePolicy::Error);
+        let entries = scan(&mut des).map_err(|err| err.with_offset(des.position()))?;
+
+        let positions = entries
+            .iter()
+            .enumerate()
+            .map(|(pos, (key, _))| (key.clone(), pos))
+            .collect();
+
+        Ok(ArrayIndex {
+            input,
+            entries,
+            positions,
+        })
+    }
+
+    /// Number of entries in the array, including duplicate keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the array is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Keys of all entries, in input order.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries.iter().map(|(key, _)| key.as_slice())
+    }
+
+    /// Returns whether `key` is present.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.positions.contains_key(key.as_bytes())
+    }
+
+    /// Byte range of the value stored under `key`.
+    pub fn range(&self, key: &str) -> Option<Range<usize>> {
+        let pos = *self.positions.get(key.as_bytes())?;
+        Some(self.entries[pos].1.clone())
+    }
+
+    /// Serialized value stored under `key`.
+    pub fn raw(&self, key: &str) -> Option<&'a [u8]> {
+        self.range(key).map(|range| &self.input[range])
+    }
+
+    /// Deserialize the value stored under `key`.
+    ///
+    /// Returns `None` if the key is not present. Errors carry the key as
+    /// their path.
+    pub fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: Deserialize<'a>,
+    {
+        self.get_with(&PhpDeserializerBuilder::default(), key)
+    }
+
+    /// Deserialize the value stored under `key` using the options of
+    /// `builder`.
+    pub fn get_with<T>(&self, builder: &PhpDeserializerBuilder, key: &str) -> Result<Option<T>>
+    where
+        T: Deserialize<'a>,
+    {
+        match self.raw(key) {
+            Some(raw) => builder
+                .deserialize(raw)
+                .map(Some)
+                .map_err(|err| err.with_path_segment(key)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Read all entries of an array, recording the byte range of each value.
+fn scan(des: &mut PhpDeserializer<&[u8]>) -> Result<Vec<(Vec<u8>, Range<usize>)>> {
+    let num_elements = des.read_array_start()?;
+    let mut entries = Vec::with_capacity(num_elements.min(1024));
+
+    for _ in 0..num_elements {
+        let key = des.read_key()?;
+        let start = des.position() as usize;
+        des.skip_value()?;
+        entries.push((key, start..des.position() as usize));
+    }
+
+    des.read_array_end()?;
+    if des.peek()?.is_some() {
+        return Err(Error::from(ErrorKind::TrailingData));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArrayIndex;
+    use crate::de::{PhpDeserializerBuilder, Profile};
+    use crate::error::ErrorKind;
+    use serde::Deserialize;
+
+    #[test]
+    fn indexes_entries() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Cart {
+            items: Vec<u32>,
+        }
+
+        let input = br#"a:4:{s:4:"cart";a:1:{s:5:"items";a:2:{i:0;i:1;i:1;i:2;}}i:3;N;s:1:"x";i:1;s:1:"x";i:2;}"#;
+        let index = ArrayIndex::new(input).unwrap();
+
+        assert_eq!(index.len(), 4);
+        assert!(!index.is_empty());
+        assert_eq!(
+            index.keys().collect::<Vec<_>>(),
+            vec![&b"cart"[..], b"3", b"x", b"x"]
+        );
+
+        assert_eq!(
+            index.get::<Cart>("cart").unwrap(),
+            Some(Cart { items: vec![1, 2] })
+        );
+        assert_eq!(index.get::<Option<u8>>("3").unwrap(), Some(None));
+        assert!(index.contains_key("3"));
+        assert_eq!(index.get::<u8>("missing").unwrap(), None);
+
+        // The last duplicate wins.
+        assert_eq!(index.get::<u8>("x").unwrap(), Some(2));
+        let range = index.range("x").unwrap();
+        assert_eq!(&input[range], b"i:2;");
+
+        // Values can be read repeatedly and as different types.
+        assert_eq!(index.get::<i64>("x").unwrap(), Some(2));
+        assert_eq!(index.get::<f64>("x").unwrap(), Some(2.0));
+    }
+
+    #[test]
+    fn applies_options() {
+        let input = br#"a:1:{s:1:"n";i:+5;}"#;
+        let index = ArrayIndex::new(input).unwrap();
+        let strict = PhpDeserializerBuilder::new().profile(Profile::Strict);
+
+        assert_eq!(index.get::<u8>("n").unwrap(), Some(5));
+        let err = index.get_with::<u8>(&strict, "n").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NonCanonical(_)));
+        assert_eq!(err.path(), Some("n"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let err = ArrayIndex::new(br#"a:1:{s:1:"a";i:1;}i:2;"#).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TrailingData));
+        assert_eq!(err.offset(), Some(18));
+
+        assert!(ArrayIndex::new(b"i:1;").is_err());
+        assert!(ArrayIndex::new(br#"a:2:{s:1:"a";i:1;}"#).is_err());
+        assert!(ArrayIndex::new(br#"a:1:{s:1:"a";x:1;}"#).is_err());
+    }
+}
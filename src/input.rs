@@ -0,0 +1,130 @@
+//! Byte sources for the deserializer.
+
+use crate::error::{ErrorKind, Result};
+use std::io::{self, Read};
+
+/// Source of serialized data.
+///
+/// The deserializer reads single bytes while lexing and whole runs of bytes
+/// for string data, nothing else. Implementing this trait allows reading
+/// from sources other than [`Read`]ers, e.g. ring buffers or chained chunks
+/// of data received by an async task, without an adapter copying the data
+/// through an intermediate buffer. All readers implement it.
+///
+/// ```rust
+/// use serde_php::{ErrorKind, Input, PhpDeserializer, Result};
+/// use serde::Deserialize;
+///
+/// /// Data received in several chunks.
+/// struct Chunks<'a>(Vec<&'a [u8]>);
+///
+/// impl Input for Chunks<'_> {
+///     fn read_byte(&mut self) -> Result<Option<u8>> {
+///         while let Some(chunk) = self.0.first_mut() {
+///             if let Some((&c, rest)) = chunk.split_first() {
+///                 *chunk = rest;
+///                 return Ok(Some(c));
+///             }
+///             self.0.remove(0);
+///         }
+///         Ok(None)
+///     }
+///
+///     fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+///         for c in buf {
+///             *c = self.read_byte()?.ok_or(ErrorKind::UnexpectedEof)?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let input = Chunks(vec![b"a:1:{s:4:\"na", b"me\";s:3:\"b", b"ob\";}"]);
+/// let mut des = PhpDeserializer::from_input(input);
+/// let map: std::collections::HashMap<String, String> = Deserialize::deserialize(&mut des).unwrap();
+/// assert_eq!(map["name"], "bob");
+/// ```
+pub trait Input {
+    /// Read the next byte, returning `None` at the end of the input.
+    fn read_byte(&mut self) -> Result<Option<u8>>;
+
+    /// Read exactly enough bytes to fill `buf`.
+    ///
+    /// Fails with [`ErrorKind::UnexpectedEof`] if the input ends before.
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()>;
+}
+
+impl<R: Read + ?Sized> Input for R {
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut buf = [0];
+        let length = self.read(&mut buf).map_err(ErrorKind::ReadSerialized)?;
+        Ok(if length == 0 { None } else { Some(buf[0]) })
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.read_exact(buf).map_err(|err| {
+            match err.kind() {
+                io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+                _ => ErrorKind::ReadSerialized(err),
+            }
+            .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Input;
+    use crate::de::PhpDeserializer;
+    use crate::error::{ErrorKind, Result};
+    use serde::Deserialize;
+    use std::collections::VecDeque;
+
+    /// Ring buffer handing out bytes one at a time.
+    struct Ring(VecDeque<u8>);
+
+    impl Input for Ring {
+        fn read_byte(&mut self) -> Result<Option<u8>> {
+            Ok(self.0.pop_front())
+        }
+
+        fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+            if buf.len() > self.0.len() {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            let len = buf.len();
+            for (c, b) in buf.iter_mut().zip(self.0.drain(..len)) {
+                *c = b;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_custom_input() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Job {
+            id: u32,
+            tags: Vec<String>,
+        }
+
+        let data = br#"a:2:{s:2:"id";i:7;s:4:"tags";a:1:{i:0;s:3:"new";}}i:1;"#;
+        let mut des = PhpDeserializer::from_input(Ring(data.iter().copied().collect()));
+        let job = Job::deserialize(&mut des).unwrap();
+        assert_eq!(
+            job,
+            Job {
+                id: 7,
+                tags: vec!["new".to_owned()]
+            }
+        );
+
+        // The input is left right after the value.
+        let (rest, lookahead) = des.into_inner();
+        assert_eq!(lookahead, None);
+        assert_eq!(rest.0, b"i:1;");
+
+        let mut des = PhpDeserializer::from_input(Ring(br#"s:5:"ab"#.iter().copied().collect()));
+        let err = String::deserialize(&mut des).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnexpectedEof));
+    }
+}
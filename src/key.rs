@@ -0,0 +1,208 @@
+//! Array key type.
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::{fmt, str};
+
+/// A PHP array key, either an integer or a string.
+///
+/// PHP arrays can mix both kinds of keys, which typed maps such as
+/// `HashMap<String, V>` reject. Maps keyed by `PhpKey` accept any array and
+/// write it back with the same keys:
+///
+/// ```rust
+/// use serde_php::PhpKey;
+/// use std::collections::BTreeMap;
+///
+/// let input = br#"a:2:{i:0;s:3:"foo";s:4:"name";s:3:"bar";}"#;
+/// let map: BTreeMap<PhpKey, String> = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(map[&PhpKey::Int(0)], "foo");
+/// assert_eq!(map[&PhpKey::from("name")], "bar");
+/// assert_eq!(serde_php::to_vec(&map).unwrap(), &input[..]);
+/// ```
+///
+/// PHP itself turns strings holding a decimal integer, such as `"5"`, into
+/// integer keys. `PhpKey` keeps keys the way they are written, so `"5"` and
+/// `5` are different keys.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum PhpKey {
+    /// An integer key, `i:5;`.
+    Int(i64),
+    /// A string key, `s:4:"name";`.
+    Bytes(Vec<u8>),
+}
+
+impl PhpKey {
+    /// Returns the integer, if this is an integer key.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            PhpKey::Int(n) => Some(*n),
+            PhpKey::Bytes(_) => None,
+        }
+    }
+
+    /// Returns the string data, if this is a string key.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            PhpKey::Int(_) => None,
+            PhpKey::Bytes(bytes) => Some(bytes),
+        }
+    }
+
+    /// Returns the string as `&str`, if this is a string key holding valid
+    /// UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|bytes| str::from_utf8(bytes).ok())
+    }
+}
+
+impl From<i64> for PhpKey {
+    fn from(n: i64) -> Self {
+        PhpKey::Int(n)
+    }
+}
+
+impl From<Vec<u8>> for PhpKey {
+    fn from(v: Vec<u8>) -> Self {
+        PhpKey::Bytes(v)
+    }
+}
+
+impl From<&[u8]> for PhpKey {
+    fn from(v: &[u8]) -> Self {
+        PhpKey::Bytes(v.to_vec())
+    }
+}
+
+impl From<String> for PhpKey {
+    fn from(v: String) -> Self {
+        PhpKey::Bytes(v.into_bytes())
+    }
+}
+
+impl From<&str> for PhpKey {
+    fn from(v: &str) -> Self {
+        PhpKey::Bytes(v.as_bytes().to_vec())
+    }
+}
+
+/// Displays integers as decimals and strings as UTF-8, replacing invalid
+/// sequences with `U+FFFD`.
+impl fmt::Display for PhpKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhpKey::Int(n) => fmt::Display::fmt(n, f),
+            PhpKey::Bytes(bytes) => fmt::Display::fmt(&String::from_utf8_lossy(bytes), f),
+        }
+    }
+}
+
+impl Serialize for PhpKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PhpKey::Int(n) => serializer.serialize_i64(*n),
+            // Text where possible, as other formats may only accept strings
+            // as map keys.
+            PhpKey::Bytes(bytes) => match str::from_utf8(bytes) {
+                Ok(s) => serializer.serialize_str(s),
+                Err(_) => serializer.serialize_bytes(bytes),
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PhpKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Keys and values are handed out as integers, text or, if not valid
+        // UTF-8, bytes.
+        deserializer.deserialize_any(PhpKeyVisitor)
+    }
+}
+
+/// Visitor accepting integers and anything string-like.
+struct PhpKeyVisitor;
+
+impl<'de> Visitor<'de> for PhpKeyVisitor {
+    type Value = PhpKey;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("an integer or a string")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<PhpKey, E> {
+        Ok(PhpKey::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<PhpKey, E> {
+        i64::try_from(v)
+            .map(PhpKey::Int)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<PhpKey, E> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<PhpKey, E> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<PhpKey, E> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<PhpKey, E> {
+        Ok(PhpKey::Bytes(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<PhpKey, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        // PHP strings outside of keys that are not valid UTF-8 are passed on
+        // as sequences of bytes.
+        let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(c) = seq.next_element()? {
+            data.push(c);
+        }
+        Ok(PhpKey::Bytes(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhpKey;
+    use crate::{from_bytes, to_vec};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn roundtrips_mixed_keys() {
+        let input = b"a:3:{i:-1;i:1;s:1:\"a\";i:2;s:2:\"\xff0\";i:3;}";
+        let map: HashMap<PhpKey, i64> = from_bytes(input).unwrap();
+        assert_eq!(map[&PhpKey::Int(-1)], 1);
+        assert_eq!(map[&PhpKey::from("a")], 2);
+        assert_eq!(map[&PhpKey::from(&b"\xff0"[..])], 3);
+
+        let ordered: BTreeMap<PhpKey, i64> = from_bytes(input).unwrap();
+        assert_eq!(to_vec(&ordered).unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn reads_keys_as_values() {
+        let keys: Vec<PhpKey> = from_bytes(b"a:2:{i:0;i:7;i:1;s:1:\"x\";}").unwrap();
+        assert_eq!(keys, vec![PhpKey::Int(7), PhpKey::from("x")]);
+        assert_eq!(to_vec(&keys).unwrap(), &b"a:2:{i:0;i:7;i:1;s:1:\"x\";}"[..]);
+        assert!(from_bytes::<PhpKey>(b"d:0.5;").is_err());
+
+        assert_eq!(PhpKey::Int(7).to_string(), "7");
+        assert_eq!(PhpKey::from("x").as_str(), Some("x"));
+        assert_eq!(PhpKey::Int(7).as_bytes(), None);
+    }
+}
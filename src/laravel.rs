@@ -0,0 +1,229 @@
+//! Laravel cache and session payloads.
+//!
+//! Laravel wraps serialized values in different envelopes depending on the
+//! storage backend. The functions in this module unwrap these and deserialize
+//! the contained value in a single step:
+//!
+//! | Backend                      | Stored as                                    |
+//! |------------------------------|----------------------------------------------|
+//! | cache: database              | `serialize($value)`                          |
+//! | cache: database (PostgreSQL) | `base64_encode(serialize($value))`           |
+//! | cache: redis, memcached      | `serialize($value)`, numbers as plain text   |
+//! | cache: file                  | 10 digit expiry timestamp + `serialize(...)` |
+//! | session: file                | `serialize($attributes)`                     |
+//! | session: database            | `base64_encode(serialize($attributes))`      |
+//! | session: cache-backed        | `serialize(serialize($attributes))`          |
+//!
+//! Encrypted payloads are detected and rejected. With the `laravel-crypt`
+//! feature, `Encrypter` verifies and decrypts them using the application key,
+//! including encrypted cookies and sessions.
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! // A session stored by the database session handler.
+//! let payload = b"YToxOntzOjY6Il90b2tlbiI7czozOiJhYmMiO30=";
+//! let session: HashMap<String, String> = serde_php::laravel::decode_session(payload).unwrap();
+//! assert_eq!(session["_token"], "abc");
+//! ```
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::from_bytes;
+use crate::value::PhpValue;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+
+#[cfg(feature = "laravel-crypt")]
+mod encryption;
+
+#[cfg(feature = "laravel-crypt")]
+pub use encryption::{Cipher, Encrypter};
+
+/// Length of the expiry timestamp prefix used by the file cache store.
+const FILE_EXPIRY_LEN: usize = 10;
+
+/// Base64 encoding of `{"iv":`, the start of Laravel's encryption envelope.
+const ENCRYPTED_PREFIX: &[u8] = b"eyJpdiI6";
+
+/// Entry of the file cache store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileCacheEntry<T> {
+    /// Unix timestamp after which the entry is expired.
+    pub expires_at: u64,
+    /// Cached value.
+    pub value: T,
+}
+
+/// Decode a cache value as stored by the database, redis or memcached stores.
+///
+/// Numeric values, which are stored without serialization by some stores,
+/// are deserialized as integers or floats.
+pub fn decode_cache<T: DeserializeOwned>(raw: &[u8]) -> Result<T> {
+    let raw = trim(raw);
+
+    if let Some(number) = numeric(raw) {
+        return from_bytes(&number);
+    }
+
+    from_bytes(&unwrap_base64(raw)?)
+}
+
+/// Decode an entry of the file cache store, including its expiry timestamp.
+pub fn decode_file_cache<T: DeserializeOwned>(raw: &[u8]) -> Result<FileCacheEntry<T>> {
+    let (expiry, rest) = if raw.len() >= FILE_EXPIRY_LEN {
+        raw.split_at(FILE_EXPIRY_LEN)
+    } else {
+        return Err(envelope_error("missing expiry timestamp"));
+    };
+
+    let expires_at = std::str::from_utf8(expiry)
+        .ok()
+        .filter(|digits| digits.bytes().all(|c| c.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok())
+        .ok_or_else(|| envelope_error("invalid expiry timestamp"))?;
+
+    Ok(FileCacheEntry {
+        expires_at,
+        value: from_bytes(rest)?,
+    })
+}
+
+/// Decode session attributes, as stored by the file, database or cache-backed
+/// session handlers.
+pub fn decode_session<T: DeserializeOwned>(raw: &[u8]) -> Result<T> {
+    let data = unwrap_base64(trim(raw))?;
+
+    // Cache-backed sessions are serialized twice.
+    if data.starts_with(b"s:") {
+        if let PhpValue::String(inner) = PhpValue::parse(&data)? {
+            return from_bytes(&inner);
+        }
+    }
+
+    from_bytes(&data)
+}
+
+/// Decode base64 if the input is not already serialized data.
+fn unwrap_base64(raw: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if raw.starts_with(ENCRYPTED_PREFIX) {
+        return Err(envelope_error("payload is encrypted"));
+    }
+
+    if looks_serialized(raw) {
+        return Ok(Cow::Borrowed(raw));
+    }
+
+    STANDARD
+        .decode(raw)
+        .map(Cow::Owned)
+        .map_err(|err| envelope_error(&format!("invalid base64: {}", err)))
+}
+
+/// Check whether input starts like a serialized value.
+fn looks_serialized(raw: &[u8]) -> bool {
+    match raw {
+        [b'N', b';', ..] => true,
+        [c, b':', ..] => b"abdisOC".contains(c),
+        _ => false,
+    }
+}
+
+/// Turn plain numbers into their serialized representation.
+fn numeric(raw: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(raw).ok()?;
+
+    if text.parse::<i64>().is_ok() {
+        Some(format!("i:{};", text).into_bytes())
+    } else if !text.is_empty() && text.parse::<f64>().is_ok_and(f64::is_finite) {
+        Some(format!("d:{};", text).into_bytes())
+    } else {
+        None
+    }
+}
+
+/// Remove surrounding whitespace, e.g. a trailing newline.
+fn trim(raw: &[u8]) -> &[u8] {
+    let start = raw
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(raw.len());
+    let end = raw
+        .iter()
+        .rposition(|c| !c.is_ascii_whitespace())
+        .map_or(start, |pos| pos + 1);
+    &raw[start..end]
+}
+
+fn envelope_error(reason: &str) -> Error {
+    ErrorKind::InvalidEnvelope(reason.to_owned()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_cache, decode_file_cache, decode_session, FileCacheEntry};
+    use crate::error::ErrorKind;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        id: u32,
+        name: String,
+    }
+
+    #[test]
+    fn cache_values() {
+        let serialized = br#"a:2:{s:2:"id";i:7;s:4:"name";s:3:"bob";}"#;
+        let expected = User {
+            id: 7,
+            name: "bob".to_owned(),
+        };
+
+        assert_eq!(decode_cache::<User>(serialized).unwrap(), expected);
+        assert_eq!(
+            decode_cache::<User>(b"YToyOntzOjI6ImlkIjtpOjc7czo0OiJuYW1lIjtzOjM6ImJvYiI7fQ==\n")
+                .unwrap(),
+            expected
+        );
+        assert_eq!(decode_cache::<i64>(b"42").unwrap(), 42);
+        assert_eq!(decode_cache::<f64>(b"-1.5").unwrap(), -1.5);
+        assert_eq!(decode_cache::<String>(br#"s:2:"42";"#).unwrap(), "42");
+    }
+
+    #[test]
+    fn file_cache() {
+        let entry: FileCacheEntry<bool> = decode_file_cache(b"1700000000b:1;").unwrap();
+        assert_eq!(
+            entry,
+            FileCacheEntry {
+                expires_at: 1_700_000_000,
+                value: true
+            }
+        );
+
+        assert!(decode_file_cache::<bool>(b"b:1;").is_err());
+        assert!(decode_file_cache::<bool>(b"17000000x0b:1;").is_err());
+    }
+
+    #[test]
+    fn sessions() {
+        let attributes = br#"a:1:{s:6:"_token";s:3:"abc";}"#;
+        let double = br#"s:29:"a:1:{s:6:"_token";s:3:"abc";}";"#;
+
+        for raw in &[&attributes[..], &double[..]] {
+            let session: HashMap<String, String> = decode_session(raw).unwrap();
+            assert_eq!(session["_token"], "abc");
+        }
+    }
+
+    #[test]
+    fn rejects_encrypted() {
+        let err = decode_session::<HashMap<String, String>>(b"eyJpdiI6IjEyMyJ9").unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidEnvelope(reason) => assert_eq!(reason, "payload is encrypted"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! Decryption of payloads encrypted by Laravel's `Illuminate\Encryption\Encrypter`.
+
+use super::{decode_session, envelope_error, trim};
+use crate::error::Result;
+use crate::from_bytes;
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, KeyIvInit};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use sha1::Sha1;
+use sha2::Sha256;
+
+/// Cipher configured as `cipher` in Laravel's `config/app.php`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Cipher {
+    /// `AES-128-CBC`, using a 16 byte key.
+    Aes128Cbc,
+    /// `AES-256-CBC`, using a 32 byte key. This is Laravel's default.
+    Aes256Cbc,
+}
+
+impl Cipher {
+    /// Key length required by the cipher, in bytes.
+    fn key_len(self) -> usize {
+        match self {
+            Cipher::Aes128Cbc => 16,
+            Cipher::Aes256Cbc => 32,
+        }
+    }
+}
+
+/// Decrypter for payloads, cookies and sessions encrypted by Laravel.
+///
+/// Laravel encrypts values into a base64-encoded JSON envelope holding the
+/// IV, the ciphertext and an HMAC-SHA256 over both. The MAC is verified
+/// before anything is decrypted.
+///
+/// ```rust
+/// use serde_php::laravel::Encrypter;
+///
+/// // `APP_KEY` from Laravel's `.env` file.
+/// let encrypter = Encrypter::from_app_key("base64:MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=").unwrap();
+///
+/// // Result of `Crypt::encrypt("bob")`.
+/// let payload = b"eyJpdiI6IkVCRVNFeFFWRmhjWUdSb2JIQjBlSHc9PSIsInZhbHVlIjoiNy9uM09KZ3JhK1BaVDE2VVBIeHRYQT09IiwibWFjIjoiMDM2NjI3ZmYwNmZiMDczMjRhZTY3YWU1YTFlYmFhZGJmYjI4Zjg4ZGEwNTQ1NjAxNGQ2ZDhkNDIwZmQzZDViZSIsInRhZyI6IiJ9";
+/// let name: String = encrypter.decrypt_value(payload).unwrap();
+/// assert_eq!(name, "bob");
+/// ```
+#[derive(Clone)]
+pub struct Encrypter {
+    key: Vec<u8>,
+    cipher: Cipher,
+}
+
+impl std::fmt::Debug for Encrypter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the key.
+        f.debug_struct("Encrypter")
+            .field("cipher", &self.cipher)
+            .finish()
+    }
+}
+
+/// Envelope around an encrypted value.
+struct Envelope {
+    /// Base64-encoded IV, as found in the envelope.
+    iv: String,
+    /// Base64-encoded ciphertext, as found in the envelope.
+    value: String,
+    /// Hex-encoded MAC.
+    mac: String,
+}
+
+impl Encrypter {
+    /// Create an encrypter using a raw key.
+    ///
+    /// Fails if the key length does not match the cipher.
+    pub fn new(key: &[u8], cipher: Cipher) -> Result<Self> {
+        if key.len() != cipher.key_len() {
+            return Err(envelope_error(&format!(
+                "{:?} requires a {} byte key, got {} bytes",
+                cipher,
+                cipher.key_len(),
+                key.len()
+            )));
+        }
+
+        Ok(Encrypter {
+            key: key.to_vec(),
+            cipher,
+        })
+    }
+
+    /// Create an encrypter from Laravel's `APP_KEY` setting.
+    ///
+    /// Keys prefixed with `base64:` are decoded first. The cipher is chosen
+    /// according to the key length.
+    pub fn from_app_key(app_key: &str) -> Result<Self> {
+        let key = match app_key.strip_prefix("base64:") {
+            Some(encoded) => STANDARD
+                .decode(encoded)
+                .map_err(|err| envelope_error(&format!("invalid base64 in key: {}", err)))?,
+            None => app_key.as_bytes().to_vec(),
+        };
+
+        let cipher = if key.len() == Cipher::Aes128Cbc.key_len() {
+            Cipher::Aes128Cbc
+        } else {
+            Cipher::Aes256Cbc
+        };
+        Encrypter::new(&key, cipher)
+    }
+
+    /// Verify and decrypt a payload, returning the raw plaintext.
+    pub fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let envelope = parse_envelope(trim(payload))?;
+
+        // Laravel computes the MAC over the base64 text of IV and value.
+        let mut mac = <Hmac<Sha256>>::new_from_slice(&self.key).expect("HMAC accepts any key");
+        mac.update(envelope.iv.as_bytes());
+        mac.update(envelope.value.as_bytes());
+        let expected = decode_hex(&envelope.mac).ok_or_else(|| envelope_error("invalid MAC"))?;
+        mac.verify_slice(&expected)
+            .map_err(|_| envelope_error("invalid MAC"))?;
+
+        let iv = decode_base64(&envelope.iv)?;
+        let mut ciphertext = decode_base64(&envelope.value)?;
+        if iv.len() != 16 {
+            return Err(envelope_error("invalid IV length"));
+        }
+
+        let plaintext = match self.cipher {
+            Cipher::Aes128Cbc => cbc::Decryptor::<aes::Aes128>::new_from_slices(&self.key, &iv)
+                .expect("key and IV lengths were checked")
+                .decrypt_padded_mut::<Pkcs7>(&mut ciphertext)
+                .map(<[u8]>::len),
+            Cipher::Aes256Cbc => cbc::Decryptor::<aes::Aes256>::new_from_slices(&self.key, &iv)
+                .expect("key and IV lengths were checked")
+                .decrypt_padded_mut::<Pkcs7>(&mut ciphertext)
+                .map(<[u8]>::len),
+        };
+        let len = plaintext.map_err(|_| envelope_error("decryption failed"))?;
+        ciphertext.truncate(len);
+
+        Ok(ciphertext)
+    }
+
+    /// Decrypt a value encrypted with `Crypt::encrypt` and deserialize it.
+    pub fn decrypt_value<T: DeserializeOwned>(&self, payload: &[u8]) -> Result<T> {
+        from_bytes(&self.decrypt(payload)?)
+    }
+
+    /// Decrypt a cookie set through Laravel's `EncryptCookies` middleware.
+    ///
+    /// The value may still be URL-encoded, as found in a `Cookie` header.
+    /// Since Laravel 5.5.42, the plaintext is prefixed with an HMAC of the
+    /// cookie name, which is checked and removed. For the session cookie, the
+    /// result is the session ID, used to look up the session in its store.
+    pub fn decrypt_cookie(&self, name: &str, value: &[u8]) -> Result<Vec<u8>> {
+        let plaintext = self.decrypt(&percent_decode(value))?;
+
+        let mut mac = <Hmac<Sha1>>::new_from_slice(&self.key).expect("HMAC accepts any key");
+        mac.update(name.as_bytes());
+        mac.update(b"v2");
+        let prefix = encode_hex(&mac.finalize().into_bytes());
+
+        match plaintext.strip_prefix(prefix.as_bytes()) {
+            Some([b'|', rest @ ..]) => Ok(rest.to_vec()),
+            _ => Err(envelope_error("cookie prefix does not match its name")),
+        }
+    }
+
+    /// Decrypt and decode session attributes, as stored by Laravel when
+    /// session encryption is enabled.
+    ///
+    /// See [`decode_session`](super::decode_session).
+    pub fn decode_session<T: DeserializeOwned>(&self, payload: &[u8]) -> Result<T> {
+        decode_session(&self.decrypt(payload)?)
+    }
+}
+
+/// Parse the base64-encoded JSON envelope.
+fn parse_envelope(payload: &[u8]) -> Result<Envelope> {
+    let json = STANDARD
+        .decode(payload)
+        .map_err(|err| envelope_error(&format!("invalid base64: {}", err)))?;
+    let json: serde_json::Value = serde_json::from_slice(&json)
+        .map_err(|err| envelope_error(&format!("invalid JSON: {}", err)))?;
+
+    let field = |name: &str| -> Result<String> {
+        json.get(name)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| envelope_error(&format!("missing `{}`", name)))
+    };
+
+    // AEAD ciphers are not supported, these would carry a tag.
+    if json
+        .get("tag")
+        .and_then(serde_json::Value::as_str)
+        .is_some_and(|tag| !tag.is_empty())
+    {
+        return Err(envelope_error("unsupported cipher (payload carries a tag)"));
+    }
+
+    Ok(Envelope {
+        iv: field("iv")?,
+        value: field("value")?,
+        mac: field("mac")?,
+    })
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(text)
+        .map_err(|err| envelope_error(&format!("invalid base64: {}", err)))
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => Some((hex_digit(*hi)? << 4) | hex_digit(*lo)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    char::from(c).to_digit(16).map(|d| d as u8)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Undo URL encoding of cookie values. Invalid escapes are kept as-is.
+fn percent_decode(value: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(value.len());
+    let mut idx = 0;
+
+    while idx < value.len() {
+        let escaped = value
+            .get(idx + 1..idx + 3)
+            .filter(|_| value[idx] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(c) => {
+                out.push(c);
+                idx += 3;
+            }
+            None => {
+                out.push(value[idx]);
+                idx += 1;
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, Cipher, Encrypter};
+    use crate::error::ErrorKind;
+    use std::collections::HashMap;
+
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    /// `encrypt(serialize(serialize(['_token' => 'abc'])))`, as stored by an
+    /// encrypted session store.
+    const SESSION: &[u8] = b"eyJpdiI6IkFBRUNBd1FGQmdjSUNRb0xEQTBPRHc9PSIsInZhbHVlIjoiVitsVkdZdkhhdHQzbWdVQURyWHEwU3ZQR1VRbWN6N1dHcmFlZmlmM215ZGtBUHhyVHNOcEVCRDdaR3hMcWpkVSIsIm1hYyI6ImIyNzdmNzAwOTQ2ZTQxZGFmYTc0MzViYzBiNjIyMTk5MWNiZWFlMzUzZjZlMjc0NDNhYTgwMDU5YjlhODVjNjQiLCJ0YWciOiIifQ==";
+
+    /// Encrypted `laravel_session` cookie, URL-encoded.
+    const COOKIE: &[u8] = b"eyJpdiI6IklDRWlJeVFsSmljb0tTb3JMQzB1THc9PSIsInZhbHVlIjoia0RYMWFhc2NPanZySTVDVG5BM3J3aVpZbmYxU3pEUWtZd05JaXcvdDgycW5KcjdhT0w2NGx5WjhzejF6TU9vc2ZpNW1jYTU5WkROcFVaZmFjVU55RWkwTnBuaSsxckhURmZ5NytIMTMzTld1aEw0elc2WWV4TzNEV1BaZjJLY1QiLCJtYWMiOiI2ODFkNmUyMmEzYzkxZDU3MWFjYzNiZDIzZDYyNzkwOWFlYzhiODU1MzQ3ODE3M2JjMDNjOWY4YjczNWU1M2MyIiwidGFnIjoiIn0%3D";
+
+    #[test]
+    fn decrypts_session() {
+        let encrypter = Encrypter::new(KEY, Cipher::Aes256Cbc).unwrap();
+        let session: HashMap<String, String> = encrypter.decode_session(SESSION).unwrap();
+        assert_eq!(session["_token"], "abc");
+    }
+
+    #[test]
+    fn decrypts_cookie() {
+        let encrypter = Encrypter::new(KEY, Cipher::Aes256Cbc).unwrap();
+        assert_eq!(
+            encrypter.decrypt_cookie("laravel_session", COOKIE).unwrap(),
+            b"abcdefghijklmnopqrstuvwxyz0123456789ABCD".to_vec()
+        );
+
+        let err = encrypter.decrypt_cookie("XSRF-TOKEN", COOKIE).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidEnvelope(_)));
+    }
+
+    #[test]
+    fn rejects_wrong_key() {
+        let encrypter = Encrypter::new(&[b'x'; 32], Cipher::Aes256Cbc).unwrap();
+        let err = encrypter.decrypt(SESSION).unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidEnvelope(reason) => assert_eq!(reason, "invalid MAC"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        assert!(Encrypter::new(&[0; 16], Cipher::Aes256Cbc).is_err());
+        assert!(Encrypter::from_app_key("base64:AAAA").is_err());
+    }
+
+    #[test]
+    fn url_decoding() {
+        assert_eq!(percent_decode(b"a%3Db%2F%zz%4"), b"a=b/%zz%4".to_vec());
+    }
+}
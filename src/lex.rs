@@ -0,0 +1,1194 @@
+//! Lexer for PHP's serialization format.
+//!
+//! [`Lexer`] splits serialized data into [`Token`]s, along with the range of
+//! bytes each token occupies, without building up any values:
+//!
+//! ```rust
+//! use serde_php::lex::{Lexer, Token};
+//!
+//! let input = br#"a:1:{s:4:"name";O:4:"User":0:{}}"#;
+//! let tokens: Vec<_> = Lexer::new(input).collect::<Result<_, _>>().unwrap();
+//! assert_eq!(
+//!     tokens,
+//!     vec![
+//!         (Token::Array(1), 0..5),
+//!         (Token::String(b"name"), 5..16),
+//!         (Token::Object { class: b"User", len: 0 }, 16..30),
+//!         (Token::End, 30..31),
+//!         (Token::End, 31..32),
+//!     ]
+//! );
+//! ```
+//!
+//! Tokens are not checked against each other, e.g. whether an array holds
+//! as many entries as declared, so the lexer also serves tools working on
+//! damaged data, such as syntax highlighters and repair scripts. The
+//! deserializer and the other parsers of this crate are built on the same
+//! code.
+
+use crate::de::ReferencePolicy;
+use crate::digits;
+use crate::error::{Error, ErrorKind, Result};
+use crate::input::Input;
+use crate::refs::{self, Refs};
+use smallvec::SmallVec;
+use std::convert::TryFrom;
+use std::mem;
+use std::ops::Range;
+
+/// A single token of serialized data.
+///
+/// Array keys are tokens like any other value. Strings and names borrow
+/// from the input.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Token<'a> {
+    /// `N;`
+    Null,
+    /// `b:1;`
+    Bool(bool),
+    /// `i:5;`
+    Int(i64),
+    /// `d:0.5;`
+    Float(f64),
+    /// `s:3:"abc";`
+    String(&'a [u8]),
+    /// `S:3:"a\00b";`, a string in which bytes may be written as a backslash
+    /// followed by two hex digits. Holds the data as written, with escapes.
+    EscapedString(&'a [u8]),
+    /// `a:2:{`, the start of an array with the given number of entries.
+    Array(usize),
+    /// `O:4:"User":2:{`, the start of an object with the given number of
+    /// properties.
+    Object {
+        /// Name of the class.
+        class: &'a [u8],
+        /// Number of properties.
+        len: usize,
+    },
+    /// `}`, the end of an array or object.
+    End,
+    /// `C:4:"List":3:{...}`, a custom serialized object.
+    Custom {
+        /// Name of the class.
+        class: &'a [u8],
+        /// Data written by the class' `serialize` method.
+        data: &'a [u8],
+    },
+    /// `E:11:"Suit:Hearts";`, an enum case.
+    EnumCase {
+        /// Name of the enum.
+        name: &'a [u8],
+        /// Name of the case.
+        case: &'a [u8],
+    },
+    /// `R:2;`, a reference to value 2.
+    Reference(i64),
+    /// `r:2;`, an object reference to value 2.
+    ObjectReference(i64),
+}
+
+/// Iterator over the tokens of serialized data.
+///
+/// Yields every token along with the range of bytes it occupies in the
+/// input. Errors carry the offset at which they occurred, after which the
+/// iterator ends.
+#[derive(Debug)]
+pub struct Lexer<'a> {
+    input: &'a [u8],
+    lexer: Lookahead1<&'a [u8]>,
+    /// Number of arrays and objects currently open.
+    open: usize,
+    /// Buffer for string data, reused across tokens.
+    scratch: Vec<u8>,
+    failed: bool,
+}
+
+impl<'a> Lexer<'a> {
+    /// Create a lexer for the given input.
+    pub fn new(input: &'a [u8]) -> Self {
+        Lexer {
+            input,
+            lexer: Lookahead1::new(input),
+            open: 0,
+            scratch: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Accept common deviations from the format, like
+    /// [`PhpDeserializerBuilder::tolerant`](crate::PhpDeserializerBuilder::tolerant).
+    pub fn tolerant(mut self, enable: bool) -> Self {
+        self.lexer.set_tolerant(enable);
+        self
+    }
+
+    /// Reject numbers PHP would not write the same way, like
+    /// [`Profile::Strict`](crate::Profile::Strict).
+    pub fn strict(mut self, enable: bool) -> Self {
+        self.lexer.set_strict(enable);
+        self
+    }
+
+    /// Offset of the next token.
+    pub fn offset(&self) -> usize {
+        self.lexer.position() as usize
+    }
+
+    /// Read the next token, or `None` at the end of the input.
+    pub fn next_token(&mut self) -> Result<Option<Token<'a>>> {
+        let offset = self.lexer.position();
+        self.read_token().map_err(|err| err.with_offset(offset))
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token<'a>>> {
+        let start = self.offset();
+        let sym = match self.lexer.peek()? {
+            Some(b'}') if self.open > 0 => {
+                self.lexer.read_array_end()?;
+                self.open -= 1;
+                return Ok(Some(Token::End));
+            }
+            Some(_) => self.lexer.read_type()?,
+            None => return Ok(None),
+        };
+
+        if sym == b'N' {
+            self.lexer.expect(b';')?;
+            return Ok(Some(Token::Null));
+        }
+
+        if !b"bidsaOCERr".contains(&sym) {
+            return Err(ErrorKind::InvalidTypeIndicator(char::from(sym)).into());
+        }
+        self.lexer.expect(b':')?;
+        let token = match sym {
+            b'b' => Token::Bool(self.lexer.read_bool()?),
+            b'i' => Token::Int(self.lexer.read_int()?),
+            b'd' => Token::Float(self.lexer.read_float()?),
+            b's' if self.input[start] == b'S' => {
+                self.lexer.read_raw_string_into(&mut self.scratch)?;
+                // Between the opening quote and the closing `";`.
+                let end = self.offset() - 2;
+                let quote = self.input[start..end].iter().position(|&c| c == b'"');
+                let begin = start + quote.expect("string data follows a quote") + 1;
+                Token::EscapedString(&self.input[begin..end])
+            }
+            b's' => {
+                self.lexer.read_raw_string_into(&mut self.scratch)?;
+                // Followed by `";`.
+                Token::String(self.before(2, self.scratch.len()))
+            }
+            b'a' => {
+                let len = self.lexer.read_array_header()?;
+                self.open += 1;
+                Token::Array(len)
+            }
+            b'O' => {
+                let class = self.read_class_name()?;
+                let len = self.lexer.read_array_header()?;
+                self.open += 1;
+                Token::Object { class, len }
+            }
+            b'C' => {
+                let class = self.read_class_name()?;
+                let data = self.lexer.read_custom_data()?;
+                // Followed by `}`.
+                let data = self.before(1, data.len());
+                Token::Custom { class, data }
+            }
+            b'E' => {
+                self.lexer.read_raw_string_into(&mut self.scratch)?;
+                let text = self.before(2, self.scratch.len());
+                match text.iter().position(|&c| c == b':') {
+                    Some(colon) => Token::EnumCase {
+                        name: &text[..colon],
+                        case: &text[colon + 1..],
+                    },
+                    None => {
+                        let text = String::from_utf8_lossy(text).into_owned();
+                        return Err(ErrorKind::InvalidEnumCase(text).into());
+                    }
+                }
+            }
+            b'R' => Token::Reference(self.lexer.read_int()?),
+            b'r' => Token::ObjectReference(self.lexer.read_int()?),
+            _ => unreachable!("type indicator is checked above"),
+        };
+        Ok(Some(token))
+    }
+
+    /// Read a class name, returning it as part of the input.
+    fn read_class_name(&mut self) -> Result<&'a [u8]> {
+        let len = self.lexer.read_class_name()?.len();
+        // Followed by `":`.
+        Ok(self.before(2, len))
+    }
+
+    /// The `len` bytes of input read last, ignoring the last `skip` bytes.
+    fn before(&self, skip: usize, len: usize) -> &'a [u8] {
+        let end = self.offset() - skip;
+        &self.input[end - len..end]
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<(Token<'a>, Range<usize>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let start = self.offset();
+        match self.next_token() {
+            Ok(token) => token.map(|token| Ok((token, start..self.offset()))),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Largest number of bytes allocated at once when reading string data.
+const STRING_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Smallest number of bytes a single array element can occupy.
+///
+/// The shortest possible key is `i:0;`, the shortest possible value `N;`.
+const MIN_ELEMENT_SIZE: u64 = 6;
+
+/// Lookahead buffer with integrated lexer.
+///
+/// Supports peeking ahead a single byte.
+#[derive(Debug)]
+pub(crate) struct Lookahead1<R> {
+    reader: R,
+    buffer: Option<u8>,
+    /// Number of bytes consumed so far, not counting a buffered byte.
+    position: u64,
+    /// Total length of the input, if known in advance.
+    pub(crate) input_len: Option<u64>,
+    /// Whether to accept common deviations from the format, see
+    /// [`PhpDeserializerBuilder::tolerant`](crate::PhpDeserializerBuilder::tolerant).
+    tolerant: bool,
+    /// Whether to reject anything PHP would not produce, see
+    /// [`Profile::Strict`](crate::Profile::Strict).
+    pub(crate) strict: bool,
+    /// Whether to accept comma decimals in floats, see
+    /// [`PhpDeserializerBuilder::tolerant_floats`](crate::PhpDeserializerBuilder::tolerant_floats).
+    tolerant_floats: bool,
+    /// Whether to fail on floats, see
+    /// [`PhpDeserializerBuilder::reject_floats`](crate::PhpDeserializerBuilder::reject_floats).
+    reject_floats: bool,
+    /// Largest number of entries allowed in a single array or object.
+    max_entries: Option<usize>,
+    /// Largest length of string keys allowed, in bytes.
+    max_key_length: Option<usize>,
+    /// Largest number of arrays and objects allowed to be nested.
+    max_depth: Option<usize>,
+    /// Number of arrays and objects currently open.
+    depth: usize,
+    /// Handling of references to earlier values.
+    refs: Refs,
+    /// Whether `buffer` holds a byte of a resolved reference rather than of
+    /// the input.
+    spliced: bool,
+    /// Bytes consumed since capturing started, with references replaced by
+    /// the copies read in their place.
+    capture: Option<Vec<u8>>,
+    /// Whether the last type indicator read was `S`, for a string with
+    /// escaped bytes.
+    escaped: bool,
+}
+
+impl<R: Input> Lookahead1<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Lookahead1 {
+            reader,
+            buffer: None,
+            position: 0,
+            input_len: None,
+            tolerant: false,
+            strict: false,
+            tolerant_floats: false,
+            reject_floats: false,
+            max_entries: None,
+            max_key_length: None,
+            max_depth: None,
+            depth: 0,
+            refs: Refs::Ignore,
+            spliced: false,
+            capture: None,
+            escaped: false,
+        }
+    }
+
+    /// Take over the options of `outer`, for nested serialized data read
+    /// from a string of `outer`.
+    pub(crate) fn nest_in<S>(&mut self, outer: &Lookahead1<S>) {
+        self.tolerant = outer.tolerant;
+        self.strict = outer.strict;
+        self.tolerant_floats = outer.tolerant_floats;
+        self.reject_floats = outer.reject_floats;
+        self.max_entries = outer.max_entries;
+        self.max_key_length = outer.max_key_length;
+        self.max_depth = outer.max_depth;
+        // Nested data counts towards the depth of the string holding it.
+        self.depth = outer.depth;
+        // References are numbered separately for nested data.
+        self.refs = outer.refs.fresh();
+    }
+
+    /// Set the total length of the input, enabling sanity checks.
+    pub(crate) fn set_input_len(&mut self, input_len: u64) {
+        self.input_len = Some(input_len);
+    }
+
+    /// Enable or disable tolerant lexing.
+    pub(crate) fn set_tolerant(&mut self, tolerant: bool) {
+        self.tolerant = tolerant;
+    }
+
+    /// Enable or disable rejection of non-canonical tokens.
+    pub(crate) fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Enable or disable acceptance of comma decimals.
+    pub(crate) fn set_tolerant_floats(&mut self, tolerant_floats: bool) {
+        self.tolerant_floats = tolerant_floats;
+    }
+
+    /// Enable or disable rejection of floats.
+    pub(crate) fn set_reject_floats(&mut self, reject_floats: bool) {
+        self.reject_floats = reject_floats;
+    }
+
+    /// Set the limits on the number of entries and key length of arrays.
+    pub(crate) fn set_map_limits(
+        &mut self,
+        max_entries: Option<usize>,
+        max_key_length: Option<usize>,
+    ) {
+        self.max_entries = max_entries;
+        self.max_key_length = max_key_length;
+    }
+
+    /// Set the limit on the nesting depth of arrays and objects.
+    pub(crate) fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    /// Set how references (`R:` and `r:`) are handled.
+    ///
+    /// Lexers ignore references by default, leaving them to the caller.
+    pub(crate) fn set_references(&mut self, policy: ReferencePolicy) {
+        self.refs = Refs::new(policy);
+    }
+
+    /// Remove the last `len` bytes consumed from the values references can
+    /// refer to, e.g. the name of a session variable.
+    pub(crate) fn forget(&mut self, len: usize) {
+        if let Refs::Resolve(refs) = &mut self.refs {
+            refs.forget(len);
+        }
+    }
+
+    /// Start recording consumed bytes.
+    pub(crate) fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    /// Stop recording consumed bytes, returning those recorded.
+    pub(crate) fn end_capture(&mut self) -> Vec<u8> {
+        self.capture.take().unwrap_or_default()
+    }
+
+    /// Number of bytes consumed so far.
+    ///
+    /// Copies of referenced values are not counted, so positions always
+    /// refer to the input.
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Byte read from the reader but not consumed yet, if any.
+    pub(crate) fn buffered(&self) -> Option<u8> {
+        self.buffer.filter(|_| !self.spliced)
+    }
+
+    /// Return the reader, along with a byte read from it but not consumed.
+    pub(crate) fn into_parts(self) -> (R, Option<u8>) {
+        let buffered = self.buffered();
+        (self.reader, buffered)
+    }
+
+    /// Number of bytes left to read, if the input length is known.
+    fn remaining(&self) -> Option<u64> {
+        let spliced = match &self.refs {
+            Refs::Resolve(refs) => refs.spliced_len() as u64 + u64::from(self.spliced),
+            _ => 0,
+        };
+        self.input_len
+            .map(|len| len.saturating_sub(self.position) + spliced)
+    }
+
+    /// Record consumed input, if references are resolved.
+    #[inline]
+    fn log(&mut self, data: &[u8]) {
+        if let Refs::Resolve(refs) = &mut self.refs {
+            refs.log(data);
+        }
+    }
+
+    /// Fill `buffer` with the next byte if there is one.
+    ///
+    /// Has no effect if `buffer` is already full. The copy of a resolved
+    /// reference is read before the rest of the input.
+    fn fill(&mut self) -> Result<()> {
+        if self.buffer.is_none() {
+            if let Refs::Resolve(refs) = &mut self.refs {
+                self.buffer = refs.next_spliced();
+                self.spliced = self.buffer.is_some();
+            }
+            if !self.spliced {
+                self.buffer = self.reader.read_byte()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Peek at the next byte, without removing it. Returns `None` on EOF.
+    pub(crate) fn peek(&mut self) -> Result<Option<u8>> {
+        self.fill()?;
+        Ok(self.buffer)
+    }
+
+    /// Reed a single byte, returning an error on EOF.
+    pub(crate) fn read1(&mut self) -> Result<u8> {
+        self.fill()?;
+
+        let c = self
+            .buffer
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+        if !mem::take(&mut self.spliced) {
+            self.position += 1;
+            self.log(&[c]);
+        }
+        if let Some(capture) = &mut self.capture {
+            capture.push(c);
+        }
+        Ok(c)
+    }
+
+    /// Expect a specific character.
+    ///
+    /// When lexing tolerantly, spaces following a colon are skipped.
+    pub(crate) fn expect(&mut self, expected: u8) -> Result<()> {
+        let actual = self.read1()?;
+        check_expected(expected, actual)?;
+
+        if self.tolerant && expected == b':' {
+            while let Some(b' ') | Some(b'\t') = self.peek()? {
+                self.read1()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Map a type indicator onto its canonical form.
+    ///
+    /// When lexing tolerantly, uppercase variants of `a`, `b`, `d` and `i` are
+    /// accepted. `S` denotes strings in PHP whose bytes may be escaped as `\xx`
+    /// with two hex digits. Unless lexing strictly, as PHP no longer writes
+    /// them, they are read as strings with the escapes decoded, the declared
+    /// length counting the decoded bytes.
+    fn normalize_type(&self, c: u8) -> u8 {
+        match c {
+            b'A' | b'B' | b'D' | b'I' if self.tolerant => c.to_ascii_lowercase(),
+            b'S' if !self.strict => b's',
+            c => c,
+        }
+    }
+
+    /// Read a type indicator.
+    pub(crate) fn read_type(&mut self) -> Result<u8> {
+        if self.reject_floats && self.peek_type()? == Some(b'd') {
+            return Err(Error::from(ErrorKind::FloatRejected).with_offset(self.position));
+        }
+        self.peek_type()?;
+        let c = self.read1()?;
+        let sym = self.normalize_type(c);
+        self.escaped = c == b'S' && sym == b's';
+        Ok(sym)
+    }
+
+    /// Peek at the next type indicator, without removing it.
+    ///
+    /// If the next value is a reference to be resolved, the type of the
+    /// referenced value is returned instead.
+    pub(crate) fn peek_type(&mut self) -> Result<Option<u8>> {
+        let mut c = self.peek()?;
+        if let Some(sym @ b'R') | Some(sym @ b'r') = c {
+            if !self.spliced && self.take_reference(sym)? {
+                c = self.peek()?;
+            }
+        }
+        Ok(c.map(|c| self.normalize_type(c)))
+    }
+
+    /// Consume a reference, arranging for a copy of the referenced value to
+    /// be read next.
+    ///
+    /// Returns `false` if references are ignored.
+    #[cold]
+    fn take_reference(&mut self, sym: u8) -> Result<bool> {
+        let offset = self.position;
+        let log_len = match &self.refs {
+            Refs::Ignore => return Ok(false),
+            Refs::Reject => {
+                let type_indicator = char::from(sym);
+                let err = Error::from(ErrorKind::UnsupportedPhpType { type_indicator });
+                return Err(err.with_offset(offset));
+            }
+            Refs::Resolve(refs) => refs.log_len(),
+        };
+
+        let dialect = refs::Dialect {
+            tolerant: self.tolerant,
+            tolerant_floats: self.tolerant_floats,
+        };
+        let resolve = |lexer: &mut Self| {
+            lexer.read1()?;
+            lexer.expect(b':')?;
+            let index = lexer.read_int()?;
+            match &mut lexer.refs {
+                Refs::Resolve(refs) => refs.resolve(index, log_len, dialect),
+                _ => unreachable!("references are resolved"),
+            }
+        };
+        // Only the copy is part of captured input, not the reference.
+        let capture = self.capture.take();
+        let result = resolve(self);
+        self.capture = capture;
+        result.map_err(|err| err.with_offset(offset))?;
+        Ok(true)
+    }
+
+    /// Expect a specific type indicator.
+    pub(crate) fn expect_type(&mut self, expected: u8) -> Result<()> {
+        let actual = self.read_type()?;
+        check_expected(expected, actual)
+    }
+
+    /// Reads an unsigned integer, fails on EOF and non-digit, but stops on
+    /// the first invalid character after at least one digit has been read.
+    pub(crate) fn collect_unsigned(&mut self, buf: &mut SmallVec<[u8; 32]>) -> Result<()> {
+        // Read the first character and ensure it is a digit.
+        let c = self.read1()?;
+        if !c.is_ascii_digit() {
+            return Err(ErrorKind::ExpectedDigit {
+                actual: char::from(c),
+            }
+            .into());
+        }
+        buf.push(c);
+
+        // Keep reading digits until we hit EOF or a non-digit.
+        while let Some(c) = self.peek()? {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            self.read1()?;
+            buf.push(c);
+        }
+
+        Ok(())
+    }
+
+    /// Reject `text` as non-canonical when lexing strictly, unless `canonical`.
+    fn check_canonical(&self, text: &[u8], canonical: fn(&[u8]) -> bool) -> Result<()> {
+        if self.strict && !canonical(text) {
+            return Err(ErrorKind::NonCanonical(String::from_utf8_lossy(text).into_owned()).into());
+        }
+        Ok(())
+    }
+
+    /// Read a `-` or `+` sign into a buffer, if present.
+    fn collect_sign(&mut self, buf: &mut SmallVec<[u8; 32]>) -> Result<()> {
+        match self.peek()? {
+            Some(c @ b'+') | Some(c @ b'-') => {
+                buf.push(c);
+                self.expect(c)?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Read raw PHP bytestring from input.
+    pub(crate) fn read_raw_string(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.read_raw_string_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read raw PHP bytestring from input into `data`, replacing its contents.
+    pub(crate) fn read_raw_string_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        let length = self.read_string_length()?;
+        self.read_string_data(length, data)
+    }
+
+    /// Read a string used as array key, enforcing the key length limit.
+    pub(crate) fn read_key_string(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.read_key_string_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read a string used as array key into `data`, replacing its contents.
+    pub(crate) fn read_key_string_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        let offset = self.position;
+        let length = self.read_string_length()?;
+
+        if let Some(limit) = self.max_key_length.filter(|&limit| length > limit) {
+            return Err(Error::from(ErrorKind::KeyTooLong { length, limit }).with_offset(offset));
+        }
+
+        self.read_string_data(length, data)
+    }
+
+    /// Read the length of a string, up to and including the opening quote.
+    fn read_string_length(&mut self) -> Result<usize> {
+        // Thankfully, PHP strings are length-delimited, even though
+        // they strangely enough include quotes as well.
+        let mut buf = SmallVec::new();
+        self.collect_unsigned(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_digits)?;
+        let length = parse_length(&buf)?;
+
+        // Delim and opening quote:
+        self.expect(b':')?;
+        self.expect(b'"')?;
+
+        Ok(length)
+    }
+
+    /// Read `length` bytes of string data into `data`, followed by the
+    /// closing quote and semicolon.
+    fn read_string_data(&mut self, length: usize, data: &mut Vec<u8>) -> Result<()> {
+        // Inner string data. The declared length is not trusted for
+        // allocation, see `read_chunked`.
+        data.clear();
+        data.reserve(length.min(STRING_CHUNK_SIZE));
+        if mem::take(&mut self.escaped) {
+            self.read_escaped_into(length, data)?;
+        } else {
+            self.read_chunked_into(length, data)?;
+        }
+        debug_assert!(data.len() == length);
+
+        // Closing quote.
+        self.expect(b'"')?;
+        self.expect(b';')?;
+
+        Ok(())
+    }
+
+    /// Read `length` bytes of `S:` string data, in which any byte may be
+    /// written as a backslash followed by two hex digits, e.g. `\00`.
+    fn read_escaped_into(&mut self, length: usize, data: &mut Vec<u8>) -> Result<()> {
+        for _ in 0..length {
+            let c = match self.read1()? {
+                b'\\' => self.read_hex_digit()? << 4 | self.read_hex_digit()?,
+                c => c,
+            };
+            data.push(c);
+        }
+        Ok(())
+    }
+
+    /// Read a single hex digit, returning its value.
+    fn read_hex_digit(&mut self) -> Result<u8> {
+        let offset = self.position;
+        let c = self.read1()?;
+        match char::from(c).to_digit(16) {
+            Some(digit) => Ok(digit as u8),
+            None => Err(Error::from(ErrorKind::ExpectedDigit {
+                actual: char::from(c),
+            })
+            .with_offset(offset)),
+        }
+    }
+
+    /// Read a boolean that follows after the `b"b:"` part.
+    pub(crate) fn read_bool(&mut self) -> Result<bool> {
+        let val = self.read1()?;
+        self.expect(b';')?;
+
+        match val {
+            b'0' => Ok(false),
+            b'1' => Ok(true),
+            c => Err(ErrorKind::InvalidBooleanValue(char::from(c)).into()),
+        }
+    }
+
+    /// Read an integer that follows after the `b"i:"` part.
+    pub(crate) fn read_int(&mut self) -> Result<i64> {
+        let mut buf = SmallVec::new();
+
+        // Collect a potential sign, followed by the unsigned digits.
+        self.collect_sign(&mut buf)?;
+        self.collect_unsigned(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_int)?;
+
+        // Terminating semicolon.
+        self.expect(b';')?;
+
+        parse_int(&buf)
+    }
+
+    /// Read an integer that follows after the `b"i:"` part as text.
+    ///
+    /// Unlike `read_int`, this does not fail for integers out of range.
+    pub(crate) fn read_int_text(&mut self) -> Result<String> {
+        let buf = self.read_int_bytes()?;
+        Ok(String::from_utf8(buf.to_vec()).expect("sign and digits are ASCII"))
+    }
+
+    /// Read an integer that follows after the `b"i:"` part as raw bytes.
+    ///
+    /// Like `read_int_text`, but does not allocate.
+    pub(crate) fn read_int_bytes(&mut self) -> Result<SmallVec<[u8; 32]>> {
+        let mut buf = SmallVec::new();
+        self.collect_sign(&mut buf)?;
+        self.collect_unsigned(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_int)?;
+        self.expect(b';')?;
+
+        Ok(buf)
+    }
+
+    /// Read a float that follows after the `b"d:"` part.
+    pub(crate) fn read_float(&mut self) -> Result<f64> {
+        let mut buf = SmallVec::new();
+        self.collect_float(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_float)?;
+        self.expect(b';')?;
+
+        // `FromStr` also takes care of `INF`, `-INF` and `NAN`.
+        parse_bytes(buf)
+    }
+
+    /// Read the textual representation of a float into a buffer.
+    fn collect_float(&mut self, buf: &mut SmallVec<[u8; 32]>) -> Result<()> {
+        self.collect_sign(buf)?;
+
+        // Special values are written as keywords.
+        let keyword: &[u8] = match self.peek()? {
+            Some(b'I') => b"INF",
+            Some(b'N') => b"NAN",
+            _ => b"",
+        };
+        if !keyword.is_empty() {
+            for &c in keyword {
+                self.expect(c)?;
+                buf.push(c);
+            }
+            return Ok(());
+        }
+
+        // PHP omits decimal dots when serializing `.0` values. It also
+        // accepts either side of the dot being empty (`.5`, `5.`), but not both.
+        // Setups with a broken locale may have written a comma instead, which
+        // is normalized to a dot.
+        let comma = self.tolerant_floats;
+        let is_dot = |c: Option<u8>| c == Some(b'.') || (comma && c == Some(b','));
+        let has_int_part = !is_dot(self.peek()?);
+        if has_int_part {
+            self.collect_unsigned(buf)?;
+        }
+
+        if let Some(c) = self.peek()?.filter(|&c| is_dot(Some(c))) {
+            buf.push(b'.');
+            self.expect(c)?;
+
+            // The remainder is another digit string without sign.
+            match self.peek()? {
+                Some(c) if has_int_part && !c.is_ascii_digit() => (),
+                _ => self.collect_unsigned(buf)?,
+            }
+        }
+
+        // Very large and small values use exponential notation, e.g. `1.0E+25`.
+        if let Some(c @ b'E') | Some(c @ b'e') = self.peek()? {
+            buf.push(c);
+            self.expect(c)?;
+            self.collect_sign(buf)?;
+            self.collect_unsigned(buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read an array header that follows after the `b"a:"` part.
+    pub(crate) fn read_array_header(&mut self) -> Result<usize> {
+        let offset = self.position;
+
+        // Read number of elements.
+        let mut buf = SmallVec::new();
+        self.collect_unsigned(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_digits)?;
+        let num_elements = parse_length(&buf)?;
+
+        // Read opening part of array.
+        self.expect(b':')?;
+        self.expect(b'{')?;
+
+        self.depth += 1;
+        if let Some(limit) = self.max_depth.filter(|&limit| self.depth > limit) {
+            return Err(Error::from(ErrorKind::DepthLimitExceeded(limit)).with_offset(offset));
+        }
+
+        if let Some(limit) = self.max_entries.filter(|&limit| num_elements > limit) {
+            return Err(Error::from(ErrorKind::TooManyEntries {
+                declared: num_elements,
+                limit,
+            })
+            .with_offset(offset));
+        }
+
+        // If we know how much input is left, we can reject element counts
+        // that cannot possibly fit (every array is closed by a `}` as well).
+        if let Some(remaining) = self.remaining() {
+            let required = (num_elements as u64)
+                .saturating_mul(MIN_ELEMENT_SIZE)
+                .saturating_add(1);
+
+            if required > remaining {
+                return Err(Error::from(ErrorKind::ElementCountExceedsInput {
+                    declared: num_elements,
+                    remaining,
+                })
+                .with_offset(offset));
+            }
+        }
+
+        Ok(num_elements)
+    }
+
+    /// Read the closing brace of an array or object.
+    pub(crate) fn read_array_end(&mut self) -> Result<()> {
+        self.expect(b'}')?;
+        self.depth = self.depth.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Read an object header that follows after the `b"O:"` part, up to and
+    /// including the opening brace.
+    ///
+    /// Returns the class name and the number of properties.
+    pub(crate) fn read_object_header(&mut self) -> Result<(Vec<u8>, usize)> {
+        let class = self.read_class_name()?;
+        let num_properties = self.read_array_header()?;
+        Ok((class, num_properties))
+    }
+
+    /// Read an enum case that follows after the `b"E:"` part.
+    ///
+    /// Returns the name of the enum and of the case.
+    pub(crate) fn read_enum_case(&mut self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let offset = self.position;
+        let mut name = self.read_raw_string()?;
+        match name.iter().position(|&c| c == b':') {
+            Some(colon) => {
+                let case = name.split_off(colon + 1);
+                name.pop();
+                Ok((name, case))
+            }
+            None => {
+                let name = String::from_utf8_lossy(&name).into_owned();
+                Err(Error::from(ErrorKind::InvalidEnumCase(name)).with_offset(offset))
+            }
+        }
+    }
+
+    /// Read a custom serialized object that follows after the `b"C:"` part,
+    /// including the closing brace.
+    ///
+    /// Returns the class name and the data written by its `serialize`
+    /// method.
+    pub(crate) fn read_custom(&mut self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let class = self.read_class_name()?;
+        let data = self.read_custom_data()?;
+        Ok((class, data))
+    }
+
+    /// Read the data of a custom serialized object that follows after its
+    /// class name, including the closing brace.
+    fn read_custom_data(&mut self) -> Result<Vec<u8>> {
+        let mut buf = SmallVec::new();
+        self.collect_unsigned(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_digits)?;
+        let length = parse_length(&buf)?;
+
+        self.expect(b':')?;
+        self.expect(b'{')?;
+        let data = self.read_chunked(length)?;
+        self.expect(b'}')?;
+        Ok(data)
+    }
+
+    /// Read the length-prefixed, quoted class name of an object, followed by
+    /// a colon.
+    fn read_class_name(&mut self) -> Result<Vec<u8>> {
+        let mut buf = SmallVec::new();
+        self.collect_unsigned(&mut buf)?;
+        self.check_canonical(&buf, is_canonical_digits)?;
+        let length = parse_length(&buf)?;
+
+        self.expect(b':')?;
+        self.expect(b'"')?;
+        let class = self.read_chunked(length)?;
+        self.expect(b'"')?;
+        self.expect(b':')?;
+        Ok(class)
+    }
+
+    /// Read exactly `length` bytes into a newly allocated buffer.
+    ///
+    /// Data is read in chunks of at most `STRING_CHUNK_SIZE` bytes, growing
+    /// the buffer only as data actually arrives. A bogus length far exceeding
+    /// the available input thus results in an `UnexpectedEof` error instead
+    /// of a huge up-front allocation.
+    fn read_chunked(&mut self, length: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(length.min(STRING_CHUNK_SIZE));
+        self.read_chunked_into(length, &mut data)?;
+        Ok(data)
+    }
+
+    /// Read exactly `length` bytes, appending them to `data`.
+    ///
+    /// Like `read_chunked`, but reuses the allocation of `data`.
+    fn read_chunked_into(&mut self, length: usize, data: &mut Vec<u8>) -> Result<()> {
+        let begin = data.len();
+        let end = begin + length;
+
+        // If we have buffered a character, move it to the output.
+        if length > 0 {
+            if let Some(c) = self.buffer.take() {
+                data.push(c);
+                if !mem::take(&mut self.spliced) {
+                    self.position += 1;
+                    self.log(&[c]);
+                }
+            }
+        }
+
+        // The rest of a resolved reference comes before the input.
+        if let Refs::Resolve(refs) = &mut self.refs {
+            refs.take_spliced(end - data.len(), data);
+        }
+
+        while data.len() < end {
+            let start = data.len();
+            let chunk = (end - start).min(STRING_CHUNK_SIZE);
+            data.resize(start + chunk, 0);
+
+            self.reader.read_bytes(&mut data[start..])?;
+            self.position += chunk as u64;
+            self.log(&data[start..]);
+        }
+
+        if let Some(capture) = &mut self.capture {
+            capture.extend_from_slice(&data[begin..]);
+        }
+
+        Ok(())
+    }
+}
+
+/// Check whether digits are free of superfluous leading zeros.
+fn is_canonical_digits(digits: &[u8]) -> bool {
+    !digits.is_empty()
+        && digits.iter().all(u8::is_ascii_digit)
+        && (digits.len() == 1 || digits[0] != b'0')
+}
+
+/// Check whether an integer is written the way PHP writes it.
+pub(crate) fn is_canonical_int(text: &[u8]) -> bool {
+    match text {
+        [b'-', b'0'] => false,
+        [b'-', digits @ ..] => is_canonical_digits(digits),
+        digits => is_canonical_digits(digits),
+    }
+}
+
+/// Check whether a float is written the way PHP writes it, see `write_float`.
+fn is_canonical_float(text: &[u8]) -> bool {
+    if matches!(text, b"INF" | b"-INF" | b"NAN") {
+        return true;
+    }
+
+    let unsigned = text.strip_prefix(b"-").unwrap_or(text);
+    let (mantissa, exponent) = match unsigned.iter().position(|&c| c == b'E' || c == b'e') {
+        Some(pos) => unsigned.split_at(pos),
+        None => (unsigned, &b""[..]),
+    };
+    let (int_part, frac_part) = match mantissa.iter().position(|&c| c == b'.') {
+        Some(pos) => (&mantissa[..pos], Some(&mantissa[pos + 1..])),
+        None => (mantissa, None),
+    };
+
+    is_canonical_digits(int_part)
+        && frac_part.is_none_or(|frac| !frac.is_empty())
+        && match exponent {
+            [] => true,
+            [b'E', b'+' | b'-', digits @ ..] => is_canonical_digits(digits),
+            _ => false,
+        }
+}
+
+/// Check that an expected character was found.
+pub(crate) fn check_expected(expected: u8, actual: u8) -> Result<()> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ErrorKind::Unexpected {
+            expected: char::from(expected),
+            actual: char::from(actual),
+        }
+        .into())
+    }
+}
+
+/// Parse a byte string using any `FromStr` function.
+pub(crate) fn parse_bytes<E, T: std::str::FromStr<Err = E>, B: AsRef<[u8]>>(buf: B) -> Result<T>
+where
+    E: std::fmt::Display + std::error::Error + Send + Sync + 'static,
+{
+    let s = std::str::from_utf8(buf.as_ref()).map_err(ErrorKind::Utf8Error)?;
+    s.parse()
+        .map_err(|e: E| ErrorKind::NotAValidNumber(Box::new(e)).into())
+}
+
+/// Parse a length or count, collected by `collect_unsigned`.
+///
+/// Takes the fast path through [`digits::parse_u64`], falling back to
+/// `parse_bytes` for a proper error.
+fn parse_length(buf: &[u8]) -> Result<usize> {
+    match digits::parse_u64(buf).and_then(|n| usize::try_from(n).ok()) {
+        Some(n) => Ok(n),
+        None => parse_bytes(buf),
+    }
+}
+
+/// Parse an integer value, see `parse_length`.
+fn parse_int(buf: &[u8]) -> Result<i64> {
+    match digits::parse_i64(buf) {
+        Some(n) => Ok(n),
+        None => parse_bytes(buf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lexer, Token};
+    use crate::error::ErrorKind;
+
+    fn tokens(input: &[u8]) -> Vec<Token<'_>> {
+        Lexer::new(input).map(|token| token.unwrap().0).collect()
+    }
+
+    #[test]
+    fn lexes_all_token_types() {
+        let input = br#"a:10:{i:0;N;i:1;b:1;i:2;d:-0.5;i:3;s:2:"a}";i:4;C:4:"List":2:{x;}i:5;E:11:"Suit:Hearts";i:6;R:2;i:7;r:2;i:8;O:8:"stdClass":0:{}i:9;S:3:"a\00b";}"#;
+        assert_eq!(
+            tokens(input),
+            vec![
+                Token::Array(10),
+                Token::Int(0),
+                Token::Null,
+                Token::Int(1),
+                Token::Bool(true),
+                Token::Int(2),
+                Token::Float(-0.5),
+                Token::Int(3),
+                Token::String(b"a}"),
+                Token::Int(4),
+                Token::Custom {
+                    class: b"List",
+                    data: b"x;"
+                },
+                Token::Int(5),
+                Token::EnumCase {
+                    name: b"Suit",
+                    case: b"Hearts"
+                },
+                Token::Int(6),
+                Token::Reference(2),
+                Token::Int(7),
+                Token::ObjectReference(2),
+                Token::Int(8),
+                Token::Object {
+                    class: b"stdClass",
+                    len: 0
+                },
+                Token::End,
+                Token::Int(9),
+                Token::EscapedString(br"a\00b"),
+                Token::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_spans() {
+        let input = br#"s:0:"";i:12;a:0:{}"#;
+        let spans: Vec<_> = Lexer::new(input).map(|token| token.unwrap().1).collect();
+        assert_eq!(spans, vec![0..7, 7..12, 12..17, 17..18]);
+    }
+
+    #[test]
+    fn ignores_structure() {
+        // Entry counts are not checked, which allows inspecting damaged data.
+        assert_eq!(
+            tokens(b"a:2:{i:0;}"),
+            vec![Token::Array(2), Token::Int(0), Token::End]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_offsets() {
+        let mut lexer = Lexer::new(b"i:1;b:2;i:3;");
+        assert_eq!(lexer.next().unwrap().unwrap().0, Token::Int(1));
+        let err = lexer.next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidBooleanValue('2')));
+        assert_eq!(err.offset(), Some(4));
+        assert!(lexer.next().is_none());
+
+        // Braces only close arrays and objects.
+        let err = Lexer::new(b"N;}").nth(1).unwrap().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidTypeIndicator('}')));
+        assert_eq!(err.offset(), Some(2));
+
+        let err = Lexer::new(br#"E:4:"Suit";"#).next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidEnumCase(_)));
+        assert_eq!(err.offset(), Some(0));
+    }
+
+    #[test]
+    fn lexes_tolerantly() {
+        assert!(Lexer::new(b"I: 5;").next().unwrap().is_err());
+        let mut lexer = Lexer::new(b"I: 5;").tolerant(true);
+        assert_eq!(lexer.next().unwrap().unwrap(), (Token::Int(5), 0..5));
+
+        assert!(Lexer::new(b"i:05;").strict(true).next().unwrap().is_err());
+    }
+}
@@ -28,6 +28,14 @@
 //!   | array (associative)     | regular `struct`s or `HashMap<_, _>`                  |
 //!
 //! * Rust `String`s are transparently UTF8-converted to PHP bytestrings.
+//! * Strings written as `S:`, with bytes escaped as a backslash followed by two
+//!   hex digits, are read like other strings, except with
+//!   [`Profile::Strict`].
+//! * Enums are externally tagged: unit variants are written as strings holding
+//!   the variant name, other variants as arrays with a single entry holding
+//!   their payload under the variant name, e.g. `a:1:{s:6:"Circle";d:0.5;}`.
+//!   Internally and adjacently tagged enums (`#[serde(tag = "type")]`) are
+//!   written as associative arrays holding the variant name under the tag.
 //!
 //! ### Out-of-order arrays
 //!
@@ -45,8 +53,10 @@
 //! results in an array that would be equivalent to ["zero", "one", "two", "three"],
 //! at least when iterated over.
 //!
-//! Because deserialization does not buffer values, these arrays cannot be directly
-//! serialized into a `Vec`. Instead they should be deserialized into a map, which
+//! By default, deserialization does not buffer values, so these arrays cannot be
+//! directly deserialized into a `Vec`. Enabling
+//! [`PhpDeserializerBuilder::reorder_arrays`] buffers and sorts the entries of
+//! such arrays instead. Alternatively they can be deserialized into a map, which
 //! can then be turned into a `Vec` if desired.
 //!
 //! A second concern are "holes" in the array, e.g. if the entry with key `1` is
@@ -55,14 +65,206 @@
 //! The helper function `deserialize_unordered_array` can be used with serde's
 //! `deserialize_with` decorator to automatically buffer and order things, as well
 //! as plugging holes by closing any gaps.
+//! Where indices carry meaning, [`helpers::indexed_pairs`] keeps them instead.
+//! [`deserialize_filled_array`] puts every value at the position given by its
+//! key and fills holes with default values. [`Sparse`] does the same with
+//! `None` for holes, and writes the array back with its holes.
+//!
+//! ### Preserving order
+//!
+//! PHP arrays are ordered, while `HashMap`s are not. With the `indexmap`
+//! feature enabled, `indexmap::IndexMap` is the recommended container for
+//! associative arrays whose order matters. Entries keep the order they were
+//! written in, both when deserializing and when serializing:
+//!
+//! ```rust
+//! # #[cfg(feature = "indexmap")]
+//! # {
+//! use indexmap::IndexMap;
+//!
+//! let input = br#"a:2:{s:1:"z";i:1;s:1:"a";i:2;}"#;
+//! let map: IndexMap<String, i64> = serde_php::from_bytes(input).unwrap();
+//! assert_eq!(map.keys().collect::<Vec<_>>(), vec!["z", "a"]);
+//! assert_eq!(serde_php::to_vec(&map).unwrap(), input.to_vec());
+//! # }
+//! ```
+//!
+//! ### Positional records
+//!
+//! Some PHP code writes records as numeric arrays, e.g. a point as
+//! `a:2:{i:0;i:3;i:1;i:4;}`. [`PhpDeserializerBuilder::positional_structs`]
+//! assigns their elements to the fields of regular structs in declaration
+//! order, while associative arrays keep working.
+//!
+//! ### Delimited strings
+//!
+//! Lists stored as a single string such as `"a,b,c"` can be (de)serialized as
+//! `Vec`s using the [`helpers::comma_separated`] and [`helpers::delimited`]
+//! field helpers.
+//!
+//! ### Values of unknown structure
+//!
+//! [`PhpValue`] can represent any supported value and is useful when the
+//! structure of the input is not known in advance. With the `json` feature
+//! enabled, it can be converted from and to JSON. Sensitive entries can be
+//! redacted before sharing payloads, see [`PhpValue::redact_keys`].
+//! [`PhpValue::parse_objects`] keeps objects of any class, writing them back
+//! byte for byte.
+//!
+//! ### Custom serialized objects
+//!
+//! Objects of classes implementing PHP's `Serializable` interface are written
+//! as `C:` records holding data in a format of the class' choosing.
+//! [`PhpCustom`] keeps their class name and data, and writes them back
+//! unchanged.
+//!
+//! ### Raw values
+//!
+//! [`PhpRawValue`] captures a value of any type as serialized data instead
+//! of decoding it, and writes it back unchanged. Other formats see it as a
+//! string. [`PhpFragment`] writes already serialized data that is only
+//! checked for framing.
+//!
+//! ### References
+//!
+//! References to earlier values (`R:` and `r:`), as written by PHP for
+//! repeated objects and PHP references, are resolved to copies of the
+//! referenced value. See [`ReferencePolicy`] to reject them instead.
+//!
+//! ### PHP versions
+//!
+//! [`FormatVersion`] pins the format to that of a PHP version, e.g. to write
+//! floats with the 17 digits of PHP 5 or to read and write the enum cases
+//! (`E:`) of PHP 8.1. See [`PhpSerializerBuilder::format_version`] and
+//! [`PhpDeserializerBuilder::format_version`].
+//!
+//! ### Corrupted streams
+//!
+//! [`Recovering`] reads concatenated values, skipping over damaged records
+//! and resuming at the next value that can be read.
+//!
+//! ### Schema validation
+//!
+//! The [`schema`] module checks input against a description of its expected
+//! keys and types, reporting every violation with its path instead of
+//! stopping at the first one.
+//!
+//! ### Untrusted input
+//!
+//! [`Profile::Hardened`] enables limits on nesting depth, array sizes and key
+//! lengths, bounding the memory and time spent on hostile payloads. The
+//! `fuzz` directory contains a `cargo fuzz` target exercising this profile.
+//!
+//! ### Tokens
+//!
+//! The [`lex`] module splits serialized data into tokens along with their
+//! byte ranges, for tools such as syntax highlighters that work below the
+//! level of values.
+//!
+//! ### Command line tool
+//!
+//! Enabling the `cli` feature builds the `php-serde` binary, which can
+//! inspect, validate, tokenize and repair serialized data, as well as
+//! convert it from and to JSON.
+//!
+//! ### C interface
+//!
+//! The `ffi` feature exposes conversion from and to JSON through a C
+//! interface, see the `ffi` module for details.
+//!
+//! ### WebAssembly
+//!
+//! The `wasm` feature exports the same conversions through `wasm_bindgen`,
+//! for use in client-side tools. See the `wasm` module.
+//!
+//! ### Values from SQL dumps
+//!
+//! Serialized values extracted from SQL dumps are typically quoted and
+//! backslash-escaped. The [`sql`] module contains helpers to undo this before
+//! deserializing.
+//!
+//! ### Laravel
+//!
+//! The `laravel` feature adds the `laravel` module, which unwraps the
+//! envelopes used by Laravel's cache and session storage. The `laravel-crypt`
+//! feature additionally decrypts encrypted payloads, cookies and sessions.
+//!
+//! ### Transcoding
+//!
+//! The `transcode` feature adds the `transcode` module, which re-encodes
+//! serialized data into JSON (with the `json` feature) or MessagePack (with
+//! the `msgpack` feature) without building up intermediate values.
+//!
+//! ### Sessions
+//!
+//! The [`session`] module reads and writes `$_SESSION` data in the formats of
+//! PHP's session serialize handlers, for sharing sessions with PHP
+//! applications.
+//!
+//! ### Arena allocation
+//!
+//! The `arena` feature adds the `arena` module, which parses arbitrary values
+//! into a `bumpalo` arena. All memory of a parsed value is released at once
+//! when the arena is reset, avoiding per-value allocations for request-scoped
+//! parsing.
+//!
+//! ### Arbitrary-precision numbers
+//!
+//! The `bigint` feature adds the `bigint` module, which maps PHP's `GMP`
+//! objects and BCMath strings onto `num_bigint::BigInt`.
+//!
+//! ### Metrics
+//!
+//! [`from_bytes_observed`] and [`to_writer_observed`] report the number of
+//! bytes and values processed, as well as the time taken, to an observer. See
+//! the [`metrics`] module.
+//!
+//! ### Regression corpus
+//!
+//! The `corpus` feature provides a harness for golden-file tests, checking a
+//! directory of serialized payloads against their expected JSON conversion.
+//! See the `corpus` module for the directory format.
+//!
+//! ### Reporting ignored fields
+//!
+//! [`PhpDeserializer`] can be driven manually, which allows wrapping it with
+//! crates such as `serde_ignored` to find out which keys of a payload were
+//! not used:
+//!
+//! ```rust
+//! # use serde::Deserialize;
+//! # #[derive(Deserialize)]
+//! # struct User { name: String }
+//! # fn main() -> Result<(), serde_php::Error> {
+//! let input = br#"a:2:{s:4:"name";s:3:"bob";s:5:"color";s:4:"blue";}"#;
+//! let mut des = serde_php::PhpDeserializer::from_slice(input);
+//! let mut ignored = Vec::new();
+//! let user: User = serde_ignored::deserialize(&mut des, |path| ignored.push(path.to_string()))?;
+//! assert_eq!(ignored, vec!["color"]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To also get the byte offsets of such keys, and to accept them for structs
+//! using `#[serde(deny_unknown_fields)]`, use
+//! [`PhpDeserializerBuilder::deserialize_with_unknown_keys`]. Repeated
+//! struct fields are reported by
+//! [`PhpDeserializerBuilder::deserialize_with_duplicate_fields`].
 //!
 //! ## What is missing?
 //!
-//! * PHP objects
+//! * PHP objects, unless their class is registered using
+//!   [`PhpDeserializerBuilder::register_class`], or they are accepted through
+//!   [`PhpDeserializerBuilder::std_class_as_map`] or [`ObjectPolicy`]. Structs
+//!   are always written as arrays. Object records are only written for
+//!   objects held by a [`PhpValue`] (`O:`), numbers written with
+//!   `bigint::gmp` (`O:3:"GMP"`), [`PhpCustom`] (`C:`) and, with
+//!   [`FormatVersion::Php81`], unit enum variants (`E:`). [`PhpRawValue`] and
+//!   [`PhpFragment`] write whatever data they hold.
 //! * Non-string/numeric array keys, except when deserializing into a `HashMap`
-//! * Mixed arrays. Array keys are assumed to always have the same key type
-//!   (Note: If this is required, consider extending this library with a variant
-//!    type).
+//! * Mixed arrays, except when deserializing into maps keyed by [`PhpKey`],
+//!   which holds either kind of key, or into a [`PhpValue`]. Otherwise, array
+//!   keys are assumed to always have the same key type.
 //!
 //! ## Example use
 //!
@@ -199,17 +401,91 @@
 //! assert_eq!(profile, orig);
 //! ```
 
+#[cfg(feature = "arena")]
+pub mod arena;
+#[cfg(feature = "bigint")]
+pub mod bigint;
+mod copy;
+#[cfg(feature = "corpus")]
+pub mod corpus;
+mod custom;
 mod de;
+mod decoded;
+mod decoder;
+mod detect;
+mod digits;
+mod encoders;
 mod error;
+mod extract;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fold;
+pub mod helpers;
+mod index;
+mod input;
+mod key;
+#[cfg(feature = "laravel")]
+pub mod laravel;
+pub mod lex;
+pub mod metrics;
+mod number;
+mod options;
+mod pretty;
+mod raw;
+mod recover;
+mod refs;
+pub mod repair;
+pub mod schema;
 mod ser;
+pub mod session;
+mod sparse;
+pub mod spl;
+pub mod sql;
+mod string;
+pub mod testing;
+#[cfg(feature = "transcode")]
+pub mod transcode;
+mod value;
+mod version;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use de::{deserialize_unordered_array, from_bytes};
-pub use error::{Error, Result};
-pub use ser::{to_vec, to_writer};
+pub use copy::copy_validate;
+pub use custom::PhpCustom;
+pub use de::{
+    deserialize_filled_array, deserialize_unordered_array, from_bytes, from_bytes_batch,
+    from_bytes_observed, from_reader, DuplicateField, KeyFilter, KeyMatching, ObjectPolicy,
+    PhpDeserializer, PhpDeserializerBuilder, Preset, Profile, ReferencePolicy, SkippedObject,
+    UnknownKey,
+};
+pub use decoded::Decoded;
+pub use decoder::Decoder;
+#[cfg(feature = "json")]
+pub use detect::{from_any_bytes, Format};
+pub use detect::{is_serialized, maybe_from_bytes};
+pub use encoders::Encoders;
+pub use error::{Error, ErrorKind, Result};
+pub use extract::get_field;
+pub use fold::fold_array;
+pub use index::ArrayIndex;
+pub use input::Input;
+pub use key::PhpKey;
+pub use number::Number;
+pub use options::{Limits, Options};
+pub use raw::{PhpFragment, PhpRawValue};
+pub use recover::{Recovered, Recovering};
+pub use ser::{
+    to_vec, to_vec_append, to_vec_pretty, to_writer, to_writer_observed, PhpSerializerBuilder,
+    UnitStructMode,
+};
+pub use sparse::Sparse;
+pub use string::PhpString;
+pub use value::{PhpValue, StringMode, Truncated};
+pub use version::FormatVersion;
 
 #[cfg(test)]
 mod tests {
-    use super::{from_bytes, to_vec};
+    use super::{from_bytes, to_vec, PhpDeserializerBuilder, Profile};
     use proptest::prelude::any;
     use proptest::proptest;
     use serde::{Deserialize, Serialize};
@@ -266,7 +542,7 @@ mod tests {
         }
 
         #[test]
-        fn roundtrip_u64(v in 0..(std::i64::MAX as u64)) {
+        fn roundtrip_u64(v in 0..(i64::MAX as u64)) {
             roundtrip!(u64, v);
         }
 
@@ -330,6 +606,26 @@ mod tests {
             roundtrip!((String, i32), v);
         }
 
+        #[test]
+        fn hardened_survives_arbitrary_input(tokens in proptest::collection::vec(
+            proptest::sample::select(vec![
+                &b"a:1:{"[..], b"a:65536:{", b"i:0;", b"i:", b"s:1:\"x\";", b"s:999999999:\"",
+                b"d:1.5;", b"N;", b"b:1;", b"}", b"O:8:\"stdClass\":1:{", b";", b"\"",
+            ]),
+            0..300,
+        )) {
+            let input = tokens.concat();
+            let hardened = PhpDeserializerBuilder::new()
+                .profile(Profile::Hardened)
+                .max_depth(16)
+                .nested_unserialize(true)
+                .std_class_as_map(true);
+
+            let _ = hardened.deserialize::<serde::de::IgnoredAny>(&input);
+            let _ = hardened.deserialize::<Vec<HashMap<String, Option<i64>>>>(&input);
+            let _ = serde::de::IgnoredAny::deserialize(&mut hardened.build_reader(&input[..]));
+        }
+
         #[test]
         fn roundtrip_string_string_hashmap(v in proptest::collection::hash_map(any::<String>(), any::<String>(), 0..100)) {
             roundtrip!(HashMap<String, String>, v);
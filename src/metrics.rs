@@ -0,0 +1,139 @@
+//! Metrics reporting.
+//!
+//! [`from_bytes_observed`](crate::from_bytes_observed) and
+//! [`to_writer_observed`](crate::to_writer_observed) report statistics about
+//! each run to an [`Observer`], which can be used to feed monitoring systems:
+//!
+//! ```rust
+//! use serde_php::metrics::{Metrics, Operation};
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//!
+//! let bytes_read = AtomicU64::new(0);
+//! let observer = |op: Operation, metrics: &Metrics| {
+//!     if op == Operation::Deserialize {
+//!         bytes_read.fetch_add(metrics.bytes, Ordering::Relaxed);
+//!     }
+//! };
+//!
+//! let value: Vec<i32> = serde_php::from_bytes_observed(b"a:1:{i:0;i:42;}", &observer).unwrap();
+//! assert_eq!(value, vec![42]);
+//! assert_eq!(bytes_read.load(Ordering::Relaxed), 15);
+//! ```
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Kind of operation being reported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operation {
+    /// Serialization into PHP format.
+    Serialize,
+    /// Deserialization from PHP format.
+    Deserialize,
+}
+
+/// Statistics collected during a single serialization or deserialization.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Metrics {
+    /// Number of bytes read or written.
+    pub bytes: u64,
+    /// Number of values processed, including nested values but not array keys.
+    pub values: u64,
+    /// Time spent on the operation.
+    pub duration: Duration,
+    /// Whether the operation failed.
+    pub failed: bool,
+}
+
+/// Receiver of metrics.
+///
+/// Observers are called once at the end of every operation, including failed
+/// ones. Any `Fn(Operation, &Metrics)` is an observer.
+pub trait Observer {
+    /// Record metrics of a completed operation.
+    fn observe(&self, operation: Operation, metrics: &Metrics);
+}
+
+impl<F> Observer for F
+where
+    F: Fn(Operation, &Metrics),
+{
+    fn observe(&self, operation: Operation, metrics: &Metrics) {
+        self(operation, metrics)
+    }
+}
+
+/// Writer wrapper counting the number of bytes written.
+#[derive(Debug)]
+pub(crate) struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+
+    /// Number of bytes written so far.
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metrics, Operation};
+    use crate::{from_bytes_observed, to_writer_observed};
+    use std::cell::RefCell;
+
+    #[test]
+    fn reports_both_directions() {
+        let reports = RefCell::new(Vec::new());
+        let observer = |op: Operation, metrics: &Metrics| reports.borrow_mut().push((op, *metrics));
+
+        let input = br#"a:2:{s:1:"a";a:2:{i:0;i:1;i:1;i:2;}s:1:"b";N;}"#;
+        let value: std::collections::BTreeMap<String, Option<Vec<u8>>> =
+            from_bytes_observed(input, &observer).unwrap();
+
+        let mut output = Vec::new();
+        to_writer_observed(&mut output, &value, &observer).unwrap();
+        assert_eq!(output, input.to_vec());
+
+        let reports = reports.into_inner();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].0, Operation::Deserialize);
+        assert_eq!(reports[1].0, Operation::Serialize);
+
+        for (_, metrics) in reports {
+            assert_eq!(metrics.bytes, input.len() as u64);
+            assert_eq!(metrics.values, 5);
+            assert!(!metrics.failed);
+        }
+    }
+
+    #[test]
+    fn reports_failures() {
+        let reports = RefCell::new(Vec::new());
+        let observer = |_: Operation, metrics: &Metrics| reports.borrow_mut().push(*metrics);
+
+        assert!(from_bytes_observed::<Vec<i32>, _>(b"a:2:{i:0;i:1;i:1;x", &observer).is_err());
+
+        let reports = reports.into_inner();
+        assert!(reports[0].failed);
+        assert_eq!(reports[0].values, 1);
+    }
+}
@@ -0,0 +1,211 @@
+//! Numbers of arbitrary PHP values.
+
+use crate::ser::{widen_f32, write_float};
+use serde::ser::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+
+/// A PHP integer (`i:`) or float (`d:`).
+///
+/// Integers cover the full 64-bit range. Integers that do not fit, which are
+/// produced by some non-PHP serializers, are kept in their original decimal
+/// representation instead of being turned into a float.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Number {
+    n: N,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum N {
+    Int(i64),
+    Float(f64),
+    /// Decimal digits with optional leading `-`, outside of the `i64` range.
+    BigInt(String),
+}
+
+impl Number {
+    /// Parse the text of an integer, which must consist of an optional sign
+    /// followed by at least one decimal digit.
+    pub(crate) fn from_int_text(text: &str) -> Number {
+        match text.parse() {
+            Ok(v) => Number { n: N::Int(v) },
+            Err(_) => {
+                let (negative, digits) = match text.as_bytes()[0] {
+                    b'-' => (true, &text[1..]),
+                    b'+' => (false, &text[1..]),
+                    _ => (false, text),
+                };
+                let digits = digits.trim_start_matches('0');
+                let sign = if negative { "-" } else { "" };
+
+                Number {
+                    n: N::BigInt(format!("{}{}", sign, digits)),
+                }
+            }
+        }
+    }
+
+    /// Returns whether the number is an integer (`i:`), including integers
+    /// out of the `i64` range.
+    pub fn is_int(&self) -> bool {
+        !self.is_float()
+    }
+
+    /// Returns whether the number is a float (`d:`).
+    pub fn is_float(&self) -> bool {
+        matches!(self.n, N::Float(_))
+    }
+
+    /// Returns the integer, if the number is an integer that fits into `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.n {
+            N::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the number as a float, which may lose precision for integers.
+    pub fn as_f64(&self) -> f64 {
+        match self.n {
+            N::Int(v) => v as f64,
+            N::Float(v) => v,
+            N::BigInt(ref digits) => digits.parse().unwrap_or(f64::NAN),
+        }
+    }
+
+    /// Returns the decimal representation of an integer out of the `i64` range.
+    pub fn as_big_int(&self) -> Option<&str> {
+        match self.n {
+            N::BigInt(ref digits) => Some(digits),
+            _ => None,
+        }
+    }
+
+    /// Write serialized representation.
+    pub(crate) fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match self.n {
+            N::Int(v) => write!(out, "i:{};", v),
+            N::BigInt(ref digits) => write!(out, "i:{};", digits),
+            N::Float(v) => {
+                out.write_all(b"d:")?;
+                write_float(out, v)?;
+                out.write_all(b";")
+            }
+        }
+    }
+
+    /// Write as PHP source code.
+    pub(crate) fn export(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.n {
+            // The literal `-9223372036854775808` would be parsed as a float.
+            N::Int(i64::MIN) => write!(f, "{}-1", i64::MIN + 1),
+            N::Int(v) => write!(f, "{}", v),
+            N::BigInt(ref digits) => f.write_str(digits),
+            N::Float(v) if v.is_nan() => f.write_str("NAN"),
+            N::Float(v) if v.is_infinite() && v > 0.0 => f.write_str("INF"),
+            N::Float(v) if v.is_infinite() => f.write_str("-INF"),
+            // `Debug` always includes a decimal point or exponent, keeping the
+            // value a float in PHP.
+            N::Float(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+/// Formats the number as it appears in serialized form, without type prefix.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.n {
+            N::Int(v) => write!(f, "{}", v),
+            N::BigInt(ref digits) => f.write_str(digits),
+            N::Float(v) => {
+                let mut buf = Vec::new();
+                write_float(&mut buf, v).expect("writing to a `Vec` cannot fail");
+                f.write_str(&String::from_utf8_lossy(&buf))
+            }
+        }
+    }
+}
+
+impl Serialize for Number {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.n {
+            N::Int(v) => serializer.serialize_i64(v),
+            N::Float(v) => serializer.serialize_f64(v),
+            // Large positive values may still fit an unsigned integer.
+            N::BigInt(ref digits) => match digits.parse::<u64>() {
+                Ok(v) => serializer.serialize_u64(v),
+                Err(_) => serializer.serialize_f64(self.as_f64()),
+            },
+        }
+    }
+}
+
+macro_rules! from_int {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for Number {
+                fn from(v: $ty) -> Self {
+                    Number { n: N::Int(i64::from(v)) }
+                }
+            }
+        )*
+    };
+}
+
+from_int!(i8 i16 i32 i64 u8 u16 u32);
+
+impl From<u64> for Number {
+    fn from(v: u64) -> Self {
+        match i64::try_from(v) {
+            Ok(v) => Number { n: N::Int(v) },
+            Err(_) => Number {
+                n: N::BigInt(v.to_string()),
+            },
+        }
+    }
+}
+
+impl From<f32> for Number {
+    fn from(v: f32) -> Self {
+        Number {
+            n: N::Float(widen_f32(v)),
+        }
+    }
+}
+
+impl From<f64> for Number {
+    fn from(v: f64) -> Self {
+        Number { n: N::Float(v) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Number;
+
+    #[test]
+    fn big_ints() {
+        let n = Number::from_int_text("-00099999999999999999999");
+        assert!(n.is_int());
+        assert_eq!(n.as_i64(), None);
+        assert_eq!(n.as_big_int(), Some("-99999999999999999999"));
+        assert_eq!(n.to_string(), "-99999999999999999999");
+
+        let n = Number::from(u64::MAX);
+        assert_eq!(n, Number::from_int_text("18446744073709551615"));
+        assert_eq!(n.as_f64(), u64::MAX as f64);
+    }
+
+    #[test]
+    fn ints_and_floats() {
+        assert_eq!(Number::from_int_text("+5"), Number::from(5));
+        assert!(Number::from(5).is_int());
+        assert!(Number::from(5.0).is_float());
+        assert_ne!(Number::from(5), Number::from(5.0));
+        assert_eq!(Number::from(1e25).to_string(), "1.0E+25");
+    }
+}
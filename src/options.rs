@@ -0,0 +1,137 @@
+//! Deserializer options as plain data.
+
+use crate::de::{KeyMatching, ObjectPolicy, ReferencePolicy};
+use crate::version::FormatVersion;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Snapshot of the options of a
+/// [`PhpDeserializerBuilder`](crate::PhpDeserializerBuilder).
+///
+/// Unlike the builder, the options can be serialized and deserialized
+/// themselves, so that parsing policies can be loaded from configuration
+/// files and logged along with the payloads they were applied to. Missing
+/// fields take their default values, unknown fields are rejected.
+///
+/// ```rust
+/// use serde_php::{Options, PhpDeserializerBuilder, Profile};
+///
+/// let options = PhpDeserializerBuilder::new().profile(Profile::Strict).options();
+/// assert!(options.strict);
+///
+/// // E.g. read from a configuration file.
+/// let mut options = Options::default();
+/// options.tolerant = true;
+/// options.limits.max_depth = Some(16);
+///
+/// let builder = PhpDeserializerBuilder::from(options);
+/// assert_eq!(builder.deserialize::<i64>(b"I: 5;").unwrap(), 5);
+/// ```
+///
+/// Key filters and repair strategies are code and therefore not part of the
+/// snapshot. Profiles and presets are recorded as the options they set.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Options {
+    /// See [`PhpDeserializerBuilder::tolerant`](crate::PhpDeserializerBuilder::tolerant).
+    pub tolerant: bool,
+    /// Reject anything PHP would not produce, as set by
+    /// [`Profile::Strict`](crate::Profile::Strict).
+    pub strict: bool,
+    /// See [`PhpDeserializerBuilder::tolerant_floats`](crate::PhpDeserializerBuilder::tolerant_floats).
+    pub tolerant_floats: bool,
+    /// See [`PhpDeserializerBuilder::reject_floats`](crate::PhpDeserializerBuilder::reject_floats).
+    pub reject_floats: bool,
+    /// See [`PhpDeserializerBuilder::key_matching`](crate::PhpDeserializerBuilder::key_matching).
+    pub key_matching: KeyMatching,
+    /// See [`PhpDeserializerBuilder::positional_structs`](crate::PhpDeserializerBuilder::positional_structs).
+    pub positional_structs: bool,
+    /// Alternative keys, mapped onto field names, see
+    /// [`PhpDeserializerBuilder::aliases`](crate::PhpDeserializerBuilder::aliases).
+    pub aliases: BTreeMap<String, String>,
+    /// See [`PhpDeserializerBuilder::null_as_default`](crate::PhpDeserializerBuilder::null_as_default).
+    pub null_as_default: bool,
+    /// See [`PhpDeserializerBuilder::std_class_as_map`](crate::PhpDeserializerBuilder::std_class_as_map).
+    pub std_class_as_map: bool,
+    /// Registered classes, mapped onto enum variants, see
+    /// [`PhpDeserializerBuilder::register_class`](crate::PhpDeserializerBuilder::register_class).
+    pub classes: BTreeMap<String, String>,
+    /// See [`PhpDeserializerBuilder::latin1_fallback`](crate::PhpDeserializerBuilder::latin1_fallback).
+    pub latin1_fallback: bool,
+    /// See [`PhpDeserializerBuilder::nested_unserialize`](crate::PhpDeserializerBuilder::nested_unserialize).
+    pub nested_unserialize: bool,
+    /// See [`PhpDeserializerBuilder::repair_lengths`](crate::PhpDeserializerBuilder::repair_lengths).
+    pub repair_lengths: bool,
+    /// See [`PhpDeserializerBuilder::byte_strings`](crate::PhpDeserializerBuilder::byte_strings).
+    pub byte_strings: bool,
+    /// See [`PhpDeserializerBuilder::integral_floats`](crate::PhpDeserializerBuilder::integral_floats).
+    pub integral_floats: bool,
+    /// See [`PhpDeserializerBuilder::reorder_arrays`](crate::PhpDeserializerBuilder::reorder_arrays).
+    pub reorder_arrays: bool,
+    /// See [`PhpDeserializerBuilder::object_policy`](crate::PhpDeserializerBuilder::object_policy).
+    pub object_policy: ObjectPolicy,
+    /// See [`PhpDeserializerBuilder::references`](crate::PhpDeserializerBuilder::references).
+    pub references: ReferencePolicy,
+    /// See [`PhpDeserializerBuilder::format_version`](crate::PhpDeserializerBuilder::format_version).
+    pub format_version: FormatVersion,
+    /// Resource limits.
+    pub limits: Limits,
+}
+
+/// Resource limits of [`Options`], unlimited if `None`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Limits {
+    /// See [`PhpDeserializerBuilder::max_map_entries`](crate::PhpDeserializerBuilder::max_map_entries).
+    pub max_map_entries: Option<usize>,
+    /// See [`PhpDeserializerBuilder::max_key_length`](crate::PhpDeserializerBuilder::max_key_length).
+    pub max_key_length: Option<usize>,
+    /// See [`PhpDeserializerBuilder::max_depth`](crate::PhpDeserializerBuilder::max_depth).
+    pub max_depth: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::de::{KeyMatching, ObjectPolicy, PhpDeserializerBuilder, Preset};
+
+    #[test]
+    fn roundtrips_builders() {
+        let builder = PhpDeserializerBuilder::new()
+            .preset(Preset::Drupal)
+            .key_matching(KeyMatching::Normalize)
+            .aliases(vec![("post_title", "title")])
+            .register_class("App\\Card", "Card")
+            .object_policy(ObjectPolicy::Skip)
+            .max_key_length(32);
+        let options = builder.options();
+        assert!(options.std_class_as_map && options.byte_strings);
+        assert_eq!(options.limits.max_key_length, Some(32));
+        assert_eq!(options.limits.max_depth, None);
+        assert_eq!(
+            PhpDeserializerBuilder::from(options.clone()).options(),
+            options
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn loads_configuration() {
+        use super::Options;
+
+        let config =
+            r#"{"tolerant": true, "key_matching": "ignore_case", "limits": {"max_depth": 8}}"#;
+        let options: Options = serde_json::from_str(config).unwrap();
+        assert!(options.tolerant);
+        assert!(!options.strict);
+        assert_eq!(options.key_matching, KeyMatching::IgnoreCase);
+        assert_eq!(options.object_policy, ObjectPolicy::Error);
+        assert_eq!(options.limits.max_depth, Some(8));
+
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(serde_json::from_str::<Options>(&json).unwrap(), options);
+
+        assert!(serde_json::from_str::<Options>(r#"{"lenient": true}"#).is_err());
+    }
+}
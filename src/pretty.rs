@@ -0,0 +1,158 @@
+//! Indented rendering of serialized data, for diagnostics.
+
+use crate::error::{ErrorKind, Result};
+use crate::lex::Lookahead1;
+
+/// Width of one level of indentation.
+const INDENT: usize = 4;
+
+/// Spread the arrays and objects of a serialized value over multiple lines.
+///
+/// Every entry is written on a line of its own, indented by its nesting
+/// level, with the key and value next to each other. Empty arrays stay on a
+/// single line. Apart from the added whitespace, the data is unchanged.
+pub(crate) fn indent(input: &[u8]) -> Result<Vec<u8>> {
+    let mut lexer = Lookahead1::new(input);
+    let mut out = Vec::with_capacity(input.len() * 2);
+    write_value(&mut lexer, input, &mut out, 0)?;
+
+    if lexer.peek()?.is_some() {
+        return Err(ErrorKind::TrailingData.into());
+    }
+    out.push(b'\n');
+    Ok(out)
+}
+
+/// Copy a single value, `depth` being the nesting level of its entries.
+fn write_value(
+    lexer: &mut Lookahead1<&[u8]>,
+    input: &[u8],
+    out: &mut Vec<u8>,
+    depth: usize,
+) -> Result<()> {
+    let start = lexer.position() as usize;
+    let sym = lexer.read_type()?;
+
+    let num_entries = if sym == b'N' {
+        lexer.expect(b';').map(|_| None)?
+    } else {
+        lexer.expect(b':')?;
+        match sym {
+            b'b' => lexer.read_bool().map(|_| None)?,
+            b'i' => lexer.read_int_text().map(|_| None)?,
+            b'd' => lexer.read_float().map(|_| None)?,
+            b's' => lexer.read_raw_string().map(|_| None)?,
+            b'C' => lexer.read_custom().map(|_| None)?,
+            b'E' => lexer.read_enum_case().map(|_| None)?,
+            // References are copied as written, they refer to values by
+            // position rather than by offset.
+            b'R' | b'r' => lexer.read_int().map(|_| None)?,
+            b'a' => Some(lexer.read_array_header()?),
+            b'O' => Some(lexer.read_object_header()?.1),
+            c => return Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+        }
+    };
+    out.extend_from_slice(&input[start..lexer.position() as usize]);
+
+    if let Some(num_entries) = num_entries {
+        for _ in 0..num_entries {
+            out.push(b'\n');
+            out.resize(out.len() + (depth + 1) * INDENT, b' ');
+            write_value(lexer, input, out, depth + 1)?;
+            write_value(lexer, input, out, depth + 1)?;
+        }
+        lexer.read_array_end()?;
+
+        if num_entries > 0 {
+            out.push(b'\n');
+            out.resize(out.len() + depth * INDENT, b' ');
+        }
+        out.push(b'}');
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::indent;
+    use crate::to_vec_pretty;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn indents_nested_arrays() {
+        #[derive(Serialize)]
+        struct Post {
+            title: &'static str,
+            tags: Vec<&'static str>,
+            meta: BTreeMap<&'static str, Vec<u8>>,
+            draft: Option<bool>,
+        }
+
+        let mut meta = BTreeMap::new();
+        meta.insert("views", vec![]);
+        let post = Post {
+            title: "{a:1;}",
+            tags: vec!["rust", "php"],
+            meta,
+            draft: None,
+        };
+
+        let expected = r#"a:4:{
+    s:5:"title";s:6:"{a:1;}";
+    s:4:"tags";a:2:{
+        i:0;s:4:"rust";
+        i:1;s:3:"php";
+    }
+    s:4:"meta";a:1:{
+        s:5:"views";a:0:{}
+    }
+    s:5:"draft";N;
+}
+"#;
+        assert_eq!(
+            String::from_utf8(to_vec_pretty(&post).unwrap()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn keeps_scalars_on_one_line() {
+        assert_eq!(to_vec_pretty(&1.5).unwrap(), b"d:1.5;\n");
+        assert_eq!(
+            indent(br#"O:4:"User":1:{s:2:"id";i:-7;}"#).unwrap(),
+            &b"O:4:\"User\":1:{\n    s:2:\"id\";i:-7;\n}\n"[..]
+        );
+        assert!(indent(b"i:1;i:2;").is_err());
+    }
+
+    #[test]
+    fn keeps_references() {
+        use crate::PhpSerializerBuilder;
+        use std::rc::Rc;
+
+        #[derive(Serialize)]
+        struct Page {
+            #[serde(with = "crate::helpers::shared")]
+            author: Rc<String>,
+            #[serde(with = "crate::helpers::shared")]
+            editor: Rc<String>,
+        }
+
+        let name = Rc::new("bob".to_owned());
+        let page = Page {
+            author: name.clone(),
+            editor: name,
+        };
+        let builder = PhpSerializerBuilder::new().backreferences(true);
+        assert_eq!(
+            String::from_utf8(builder.to_vec_pretty(&page).unwrap()).unwrap(),
+            "a:2:{\n    s:6:\"author\";s:3:\"bob\";\n    s:6:\"editor\";R:2;\n}\n"
+        );
+        assert_eq!(
+            indent(b"a:1:{i:0;r:1;}").unwrap(),
+            &b"a:1:{\n    i:0;r:1;\n}\n"[..]
+        );
+    }
+}
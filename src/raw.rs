@@ -0,0 +1,569 @@
+//! Serialized values kept as they appear in the input.
+
+use crate::de::PhpDeserializer;
+use crate::error::{Error, ErrorKind, Result};
+use crate::lex::{Lexer, Token};
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use serde::ser::{Serialize, Serializer};
+use std::hash::{Hash, Hasher};
+use std::str;
+use std::{fmt, marker::PhantomData};
+
+/// Newtype struct name recognized by the serializer and deserializer,
+/// carrying a complete serialized value as bytes.
+pub(crate) const RAW_TOKEN: &str = "$serde_php::private::Raw";
+
+/// A single serialized value, left undecoded.
+///
+/// Deserializing a `PhpRawValue` from PHP data captures the serialization of
+/// the value at its position instead of decoding it, whatever its type.
+/// Serializing it writes the captured data back unchanged. This allows
+/// passing parts of a payload through without knowing or modeling their
+/// structure, similar to `serde_json`'s `RawValue`:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_php::PhpRawValue;
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Envelope {
+///     id: u32,
+///     payload: PhpRawValue,
+/// }
+///
+/// let input = br#"a:2:{s:2:"id";i:7;s:7:"payload";O:4:"Cart":1:{s:5:"items";a:0:{}}}"#;
+/// let envelope: Envelope = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(envelope.payload.as_bytes(), br#"O:4:"Cart":1:{s:5:"items";a:0:{}}"#);
+///
+/// assert_eq!(serde_php::to_vec(&envelope).unwrap(), &input[..]);
+/// ```
+///
+/// Objects of any class and `C:` records are captured as well. References
+/// are replaced by copies of the values they refer to, so the captured data
+/// stands on its own.
+///
+/// Other formats see the serialized value as a string, or as bytes if it is
+/// not valid UTF-8, and accept either back.
+///
+/// Raw values own their data, so they can be sent to other threads and
+/// decoded there. Errors from [`decode`](PhpRawValue::decode) carry offsets
+/// into the original input:
+///
+/// ```rust
+/// use serde_php::PhpRawValue;
+/// use std::collections::HashMap;
+/// use std::thread;
+///
+/// let input = br#"a:2:{s:1:"a";a:1:{i:0;i:1;}s:1:"b";a:1:{i:0;s:1:"x";}}"#;
+/// let jobs: HashMap<String, Box<PhpRawValue>> = serde_php::from_bytes(input).unwrap();
+///
+/// let worker = thread::spawn(move || jobs["b"].decode::<Vec<i32>>());
+/// let err = worker.join().unwrap().unwrap_err();
+/// assert_eq!(err.offset(), Some(35));
+/// ```
+///
+/// Values compare equal if their serializations do, regardless of their
+/// offsets.
+#[derive(Clone, Debug)]
+pub struct PhpRawValue {
+    data: Vec<u8>,
+    offset: u64,
+}
+
+impl PhpRawValue {
+    /// Check that `data` holds exactly one serialized value and wrap it.
+    ///
+    /// References in `data` are replaced by copies of the values they refer
+    /// to.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut des = PhpDeserializer::from_slice_unbuffered(data);
+        let parse = |des: &mut PhpDeserializer<&[u8]>| {
+            let data = des.read_verbatim()?;
+            des.end()?;
+            Ok(PhpRawValue { data, offset: 0 })
+        };
+        parse(&mut des).map_err(|err: Error| err.with_offset(des.position()))
+    }
+
+    /// The serialized value.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the value, returning its serialization.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Byte offset of the value in the input it was deserialized from.
+    ///
+    /// Zero for values created by [`from_bytes`](PhpRawValue::from_bytes) or
+    /// deserialized from other formats.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Deserialize the value, like [`from_bytes`](crate::from_bytes).
+    ///
+    /// Offsets of errors refer to the input the value was deserialized
+    /// from. Errors without an offset of their own are placed at the start
+    /// of the value.
+    pub fn decode<'a, T>(&'a self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        crate::from_bytes(&self.data).map_err(|err| err.with_base_offset(self.offset))
+    }
+}
+
+impl PartialEq for PhpRawValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Eq for PhpRawValue {}
+
+impl Hash for PhpRawValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.data.hash(state);
+    }
+}
+
+impl Serialize for PhpRawValue {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_TOKEN, &Raw(&self.data))
+    }
+}
+
+/// Already serialized data, written verbatim.
+///
+/// Unlike [`PhpRawValue`], a fragment borrows its data and is only checked
+/// for framing: it must consist of exactly one value whose tokens are well
+/// formed and whose braces match. Entry counts are not checked and nothing
+/// is decoded, so cached parts of a payload can be stitched into larger
+/// structures cheaply:
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_php::PhpFragment;
+///
+/// #[derive(Serialize)]
+/// struct Page<'a> {
+///     title: &'a str,
+///     sidebar: PhpFragment<'a>,
+/// }
+///
+/// let cached = br#"a:1:{i:0;s:4:"Home";}"#;
+/// let page = Page {
+///     title: "News",
+///     sidebar: PhpFragment::new(cached).unwrap(),
+/// };
+/// assert_eq!(
+///     serde_php::to_vec(&page).unwrap(),
+///     &br#"a:2:{s:5:"title";s:4:"News";s:7:"sidebar";a:1:{i:0;s:4:"Home";}}"#[..]
+/// );
+///
+/// assert!(PhpFragment::new(br#"a:1:{i:0;s:4:"Home";"#).is_err());
+/// ```
+///
+/// References (`R:` and `r:`) are rejected, as they would point at different
+/// values once the fragment is part of another payload. Other formats see
+/// the data as a string, or as bytes if it is not valid UTF-8.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PhpFragment<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PhpFragment<'a> {
+    /// Check the framing of `data` and wrap it.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let mut lexer = Lexer::new(data);
+        let mut open = 0usize;
+        loop {
+            let offset = lexer.offset() as u64;
+            let token = lexer
+                .next_token()?
+                .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof).with_offset(offset))?;
+            match token {
+                Token::Array(_) | Token::Object { .. } => open += 1,
+                Token::End => open -= 1,
+                Token::Reference(_) | Token::ObjectReference(_) => {
+                    return Err(Error::from(ErrorKind::VerbatimReference).with_offset(offset));
+                }
+                _ => (),
+            }
+            if open == 0 {
+                break;
+            }
+        }
+
+        if lexer.offset() < data.len() {
+            let offset = lexer.offset() as u64;
+            return Err(Error::from(ErrorKind::TrailingData).with_offset(offset));
+        }
+        Ok(PhpFragment { data })
+    }
+
+    /// The serialized data.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl Serialize for PhpFragment<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_TOKEN, &Raw(self.data))
+    }
+}
+
+/// Serialized data, as a string if possible. The PHP serializer writes it
+/// verbatim.
+struct Raw<'a>(&'a [u8]);
+
+impl Serialize for Raw<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match str::from_utf8(self.0) {
+            Ok(s) => serializer.serialize_str(s),
+            Err(_) => serializer.serialize_bytes(self.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PhpRawValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(RAW_TOKEN, RawVisitor)
+    }
+}
+
+struct RawVisitor;
+
+impl<'de> Visitor<'de> for RawVisitor {
+    type Value = PhpRawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a serialized value")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<PhpRawValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<PhpRawValue, E> {
+        PhpRawValue::from_bytes(v).map_err(E::custom)
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<PhpRawValue, E> {
+        self.visit_bytes(&v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<PhpRawValue, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<PhpRawValue, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        // Formats without a bytes type write bytes as a sequence of numbers.
+        let mut data = Vec::new();
+        while let Some(byte) = seq.next_element()? {
+            data.push(byte);
+        }
+        self.visit_bytes(&data)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<PhpRawValue, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        // Captured by the PHP deserializer, so known to be valid.
+        match map.next_entry_seed(PhantomData::<u64>, CapturedSeed)? {
+            Some((offset, data)) => Ok(PhpRawValue { data, offset }),
+            None => Err(de::Error::invalid_length(0, &self)),
+        }
+    }
+}
+
+/// A value captured by the PHP deserializer, handed to [`RawVisitor`] as a
+/// map with a single entry from its offset to its serialization.
+pub(crate) struct Captured {
+    offset: u64,
+    data: Option<Vec<u8>>,
+}
+
+impl Captured {
+    pub(crate) fn new(offset: u64, data: Vec<u8>) -> Self {
+        Captured {
+            offset,
+            data: Some(data),
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for Captured {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.data.is_none() {
+            return Ok(None);
+        }
+        seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.offset))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let data = self
+            .data
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(CapturedData(data))
+    }
+}
+
+/// Serialization of a captured value, handed out as bytes.
+struct CapturedData(Vec<u8>);
+
+impl<'de> Deserializer<'de> for CapturedData {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.0)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Reads the serialization of a captured value.
+struct CapturedSeed;
+
+impl<'de> DeserializeSeed<'de> for CapturedSeed {
+    type Value = Vec<u8>;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CapturedSeed {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("captured data")
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<Vec<u8>, E> {
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhpFragment, PhpRawValue};
+    use crate::error::ErrorKind;
+    use crate::{from_bytes, to_vec, PhpDeserializerBuilder, PhpSerializerBuilder};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Job {
+        id: u32,
+        args: PhpRawValue,
+    }
+
+    #[test]
+    fn roundtrips_any_value() {
+        let values: &[&[u8]] = &[
+            b"N;",
+            b"i:-3;",
+            br#"s:3:"a;}";"#,
+            br#"a:2:{i:0;O:8:"stdClass":0:{}i:1;C:8:"SplQueue":7:{i:4;:N;}}"#,
+            br#"O:4:"Mail":1:{s:2:"to";a:1:{i:0;d:0.5;}}"#,
+        ];
+        for &value in values {
+            let mut input = br#"a:2:{s:2:"id";i:1;s:4:"args";"#.to_vec();
+            input.extend_from_slice(value);
+            input.push(b'}');
+
+            let job: Job = from_bytes(&input).unwrap();
+            assert_eq!(job.args.as_bytes(), value);
+            assert_eq!(to_vec(&job).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn resolves_references() {
+        let input = br#"a:2:{s:2:"id";a:1:{i:0;i:5;}s:4:"args";a:2:{i:0;R:3;i:1;R:2;}}"#;
+
+        #[derive(Deserialize)]
+        struct Loose {
+            args: PhpRawValue,
+        }
+        let loose: Loose = from_bytes(input).unwrap();
+        assert_eq!(loose.args.as_bytes(), b"a:2:{i:0;i:5;i:1;a:1:{i:0;i:5;}}");
+        assert_eq!(
+            loose.args.decode::<(i32, Vec<i32>)>().unwrap(),
+            (5, vec![5])
+        );
+
+        let top = PhpRawValue::from_bytes(b"a:2:{i:0;s:1:\"x\";i:1;R:2;}").unwrap();
+        assert_eq!(top.as_bytes(), b"a:2:{i:0;s:1:\"x\";i:1;s:1:\"x\";}");
+        assert_eq!(top.decode::<Vec<String>>().unwrap(), ["x", "x"]);
+    }
+
+    #[test]
+    fn keeps_offsets() {
+        fn assert_send<T: Send + 'static>(_: &T) {}
+
+        let input = br#"a:2:{s:1:"a";i:1;s:1:"b";a:1:{i:0;d:0.5;}}"#;
+        let entries: HashMap<String, Box<PhpRawValue>> = from_bytes(input).unwrap();
+        assert_send(&entries);
+        assert_eq!(entries["a"].offset(), 13);
+        assert_eq!(entries["b"].offset(), 25);
+        assert_eq!(entries["b"].as_bytes(), b"a:1:{i:0;d:0.5;}");
+
+        let err = entries["b"].decode::<Vec<i32>>().unwrap_err();
+        assert_eq!(err.offset(), Some(25));
+        assert_eq!(
+            entries["a"],
+            Box::new(PhpRawValue::from_bytes(b"i:1;").unwrap())
+        );
+
+        let reordered: Vec<PhpRawValue> = PhpDeserializerBuilder::new()
+            .reorder_arrays(true)
+            .deserialize(b"a:2:{i:1;i:5;i:0;N;}")
+            .unwrap();
+        assert_eq!(reordered[0].offset(), 17);
+        assert_eq!(reordered[1].offset(), 9);
+    }
+
+    #[test]
+    fn counts_values_for_backreferences() {
+        #[derive(Serialize)]
+        struct Batch {
+            args: PhpRawValue,
+            #[serde(with = "crate::helpers::shared")]
+            first: Rc<String>,
+            #[serde(with = "crate::helpers::shared")]
+            second: Rc<String>,
+        }
+
+        let name = Rc::new("x".to_owned());
+        let batch = Batch {
+            args: PhpRawValue::from_bytes(b"a:1:{i:0;a:1:{i:0;N;}}").unwrap(),
+            first: name.clone(),
+            second: name,
+        };
+        let data = PhpSerializerBuilder::new()
+            .backreferences(true)
+            .to_vec(&batch)
+            .unwrap();
+        assert_eq!(
+            data,
+            &br#"a:3:{s:4:"args";a:1:{i:0;a:1:{i:0;N;}}s:5:"first";s:1:"x";s:6:"second";R:5;}"#[..]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(PhpRawValue::from_bytes(b"").is_err());
+        assert!(PhpRawValue::from_bytes(b"a:1:{i:0;").is_err());
+        assert!(PhpRawValue::from_bytes(b"i:1;i:2;").is_err());
+        assert_eq!(
+            PhpRawValue::from_bytes(b"a:1:{i:0;R:1;}")
+                .unwrap_err()
+                .offset(),
+            Some(9)
+        );
+    }
+
+    #[test]
+    fn writes_fragments() {
+        #[derive(Serialize)]
+        struct Batch<'a> {
+            cached: PhpFragment<'a>,
+            #[serde(with = "crate::helpers::shared")]
+            first: Rc<String>,
+            #[serde(with = "crate::helpers::shared")]
+            second: Rc<String>,
+        }
+
+        // Entry counts are not checked, but values are still numbered.
+        let name = Rc::new("x".to_owned());
+        let batch = Batch {
+            cached: PhpFragment::new(br#"O:4:"Cart":1:{i:0;N;i:1;b:1;}"#).unwrap(),
+            first: name.clone(),
+            second: name,
+        };
+        let data = PhpSerializerBuilder::new()
+            .backreferences(true)
+            .to_vec(&batch)
+            .unwrap();
+        assert_eq!(
+            data,
+            &br#"a:3:{s:6:"cached";O:4:"Cart":1:{i:0;N;i:1;b:1;}s:5:"first";s:1:"x";s:6:"second";R:5;}"#[..]
+        );
+    }
+
+    #[test]
+    fn rejects_broken_fragments() {
+        let offset = |data: &[u8]| PhpFragment::new(data).unwrap_err().offset();
+        assert_eq!(offset(b""), Some(0));
+        assert_eq!(offset(b"a:1:{i:0;N;"), Some(11));
+        assert_eq!(offset(b"a:1:{i:0;N;}}"), Some(12));
+        assert_eq!(offset(b"i:1;i:2;"), Some(4));
+        assert_eq!(offset(b"a:1:{i:0;s:3:\"ab\";}"), Some(9));
+
+        let err = PhpFragment::new(b"a:2:{i:0;N;i:1;R:2;}").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::VerbatimReference));
+        assert_eq!(err.offset(), Some(15));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn embeds_as_string() {
+        let job = Job {
+            id: 2,
+            args: PhpRawValue::from_bytes(br#"a:1:{i:0;s:1:"x";}"#).unwrap(),
+        };
+        let json = serde_json::to_string(&job).unwrap();
+        assert_eq!(json, r#"{"id":2,"args":"a:1:{i:0;s:1:\"x\";}"}"#);
+        assert_eq!(serde_json::from_str::<Job>(&json).unwrap(), job);
+        assert!(serde_json::from_str::<Job>(r#"{"id":2,"args":"a:1:{"}"#).is_err());
+    }
+}
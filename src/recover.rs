@@ -0,0 +1,253 @@
+//! Salvaging values from corrupted streams of concatenated payloads.
+
+use crate::de::{PhpDeserializer, PhpDeserializerBuilder};
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Range;
+
+/// Part of a stream, as yielded by [`Recovering`].
+#[derive(Debug)]
+pub enum Recovered<T> {
+    /// A value, along with the range of input it was read from.
+    Value(T, Range<usize>),
+    /// Input that was skipped, along with the error that caused it to be.
+    ///
+    /// The offset of the error is relative to the start of the range.
+    Skipped(Error, Range<usize>),
+}
+
+impl<T> Recovered<T> {
+    /// The value, if any.
+    pub fn ok(self) -> Option<T> {
+        match self {
+            Recovered::Value(value, _) => Some(value),
+            Recovered::Skipped(..) => None,
+        }
+    }
+
+    /// Range of the input covered.
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            Recovered::Value(_, range) | Recovered::Skipped(_, range) => range.clone(),
+        }
+    }
+}
+
+/// Iterator over the values of a stream of concatenated payloads, resuming
+/// after corrupted parts.
+///
+/// Exports and logs frequently hold one serialized value after the other,
+/// possibly separated by whitespace. When a single record is truncated or
+/// otherwise damaged, parsing the rest of the stream would fail as well. This
+/// iterator instead scans forward for the next position where a value of type
+/// `T` can be read, reporting the input skipped along the way:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_php::{Recovered, Recovering};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Order {
+///     id: u32,
+/// }
+///
+/// let stream = b"a:1:{s:2:\"id\";i:1;}\na:1:{s:2:\"id\";i:\na:1:{s:2:\"id\";i:3;}";
+/// let records: Vec<Recovered<Order>> = Recovering::new(stream).collect();
+///
+/// assert_eq!(records.len(), 3);
+/// assert!(matches!(records[1], Recovered::Skipped(..)));
+/// assert_eq!(records[1].range(), 20..37);
+///
+/// let ids: Vec<u32> = records.into_iter().filter_map(Recovered::ok).map(|o| o.id).collect();
+/// assert_eq!(ids, [1, 3]);
+/// ```
+///
+/// Candidate positions are those starting with a type indicator, such as
+/// `a:` or `O:`. Every candidate is tried in turn, so resynchronizing takes
+/// time quadratic in the length of the corrupted part in the worst case.
+/// Values nested inside a corrupted record may be mistaken for records of
+/// their own if they deserialize into `T`, which is less likely the more
+/// specific `T` is.
+#[derive(Debug)]
+pub struct Recovering<'de, T> {
+    /// Options applied to every record.
+    builder: PhpDeserializerBuilder,
+    input: &'de [u8],
+    /// Offset of the next record.
+    pos: usize,
+    /// Value found while resynchronizing, to be yielded after the skipped
+    /// input.
+    pending: Option<(T, Range<usize>)>,
+    /// Buffer for reading keys, handed to each deserializer.
+    scratch: Vec<u8>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'de, T> Recovering<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    /// Iterate over the values of `input`, using default options.
+    pub fn new(input: &'de [u8]) -> Self {
+        Self::with_builder(PhpDeserializerBuilder::default(), input)
+    }
+
+    /// Iterate over the values of `input`, using the options of `builder`.
+    pub fn with_builder(builder: PhpDeserializerBuilder, input: &'de [u8]) -> Self {
+        Recovering {
+            builder,
+            input,
+            pos: 0,
+            pending: None,
+            scratch: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Offset of the input not yet consumed.
+    pub fn position(&self) -> usize {
+        self.pending
+            .as_ref()
+            .map_or(self.pos, |(_, range)| range.start)
+    }
+
+    /// Read a value starting at `start`, returning it along with its end.
+    fn parse_at(&mut self, start: usize) -> Result<(T, usize)> {
+        let mut des = PhpDeserializer::from_slice_unbuffered(&self.input[start..]);
+        self.builder.configure(&mut des);
+        des.set_scratch(mem::take(&mut self.scratch));
+
+        let result = T::deserialize(&mut des);
+        let len = des.position();
+        self.scratch = des.take_scratch();
+        result
+            .map(|value| (value, start + len as usize))
+            .map_err(|err| err.with_offset(len))
+    }
+}
+
+impl<'de, T> Iterator for Recovering<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    type Item = Recovered<T>;
+
+    fn next(&mut self) -> Option<Recovered<T>> {
+        if let Some((value, range)) = self.pending.take() {
+            return Some(Recovered::Value(value, range));
+        }
+
+        // Records may be separated by whitespace, e.g. one per line.
+        self.pos += self.input[self.pos..]
+            .iter()
+            .take_while(|c| c.is_ascii_whitespace())
+            .count();
+        if self.pos == self.input.len() {
+            return None;
+        }
+
+        let start = self.pos;
+        let error = match self.parse_at(start) {
+            Ok((value, end)) => {
+                self.pos = end;
+                return Some(Recovered::Value(value, start..end));
+            }
+            Err(err) => err,
+        };
+
+        for candidate in start + 1..self.input.len() {
+            if !is_candidate(&self.input[candidate..]) {
+                continue;
+            }
+            if let Ok((value, end)) = self.parse_at(candidate) {
+                self.pending = Some((value, candidate..end));
+                self.pos = end;
+                return Some(Recovered::Skipped(error, start..candidate));
+            }
+        }
+
+        self.pos = self.input.len();
+        Some(Recovered::Skipped(error, start..self.pos))
+    }
+}
+
+/// Check whether data plausibly starts with a serialized value.
+fn is_candidate(data: &[u8]) -> bool {
+    match data {
+        [b'N', b';', ..] => true,
+        [b'b', b':', b'0'..=b'1', b';', ..] => true,
+        [b'i', b':', c, ..] | [b'd', b':', c, ..] => c.is_ascii_digit() || *c == b'-',
+        [b's' | b'S' | b'a' | b'O' | b'C' | b'E', b':', c, ..] => c.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recovered, Recovering};
+    use crate::de::PhpDeserializerBuilder;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row {
+        a: i64,
+    }
+
+    #[test]
+    fn resynchronizes_after_errors() {
+        let stream = b"a:1:{s:1:\"a\";i:1;}\na:1:{s:1:\"a\";i:2;\na:1:{s:1:\"a\";i:3;}xxa:1:{s:1:\"a\";i:4;}a:1:{";
+        let mut records = Recovering::<Row>::new(stream);
+
+        match records.next() {
+            Some(Recovered::Value(row, range)) => {
+                assert_eq!(row, Row { a: 1 });
+                assert_eq!(range, 0..18);
+            }
+            other => panic!("expected value, got {:?}", other),
+        }
+        match records.next() {
+            Some(Recovered::Skipped(err, range)) => {
+                // Right after the newline where the closing brace should be.
+                assert_eq!(err.offset(), Some(18));
+                assert_eq!(range, 19..37);
+            }
+            other => panic!("expected skipped input, got {:?}", other),
+        }
+        assert_eq!(records.position(), 37);
+
+        let rest: Vec<_> = records
+            .map(|record| (record.range(), record.ok()))
+            .collect();
+        assert_eq!(
+            rest,
+            vec![
+                (37..55, Some(Row { a: 3 })),
+                (55..57, None),
+                (57..75, Some(Row { a: 4 })),
+                (75..80, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_options() {
+        let stream = b"i:1;I: 2;i:3;";
+        let records = |builder| {
+            Recovering::<i64>::with_builder(builder, stream)
+                .map(|record| (record.range(), record.ok()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            records(PhpDeserializerBuilder::new()),
+            vec![(0..4, Some(1)), (4..9, None), (9..13, Some(3))]
+        );
+        assert_eq!(
+            records(PhpDeserializerBuilder::new().tolerant(true)),
+            vec![(0..4, Some(1)), (4..9, Some(2)), (9..13, Some(3))]
+        );
+        assert!(Recovering::<i64>::new(b" \n").next().is_none());
+    }
+}
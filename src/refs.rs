@@ -0,0 +1,303 @@
+//! Resolution of references (`R:` and `r:`) to earlier values.
+//!
+//! PHP numbers every value it unserializes, starting at 1 for the top-level
+//! value, except for array keys and `R:` references themselves. References
+//! refer to values by these numbers, e.g. in `a:2:{i:0;s:1:"x";i:1;R:2;}`,
+//! `R:2;` refers to the string. Both kinds of references are resolved by
+//! reading a copy of the referenced value in their place.
+//!
+//! To find the referenced value, the lexer keeps a log of the input consumed
+//! so far. The log is only numbered when the first reference is encountered,
+//! and from then on only up to the next reference, so inputs without any
+//! references merely pay for the copy.
+//!
+//! Arrays and objects can only be referred to once they are complete, so
+//! copies never contain themselves. References from within a value to the
+//! value itself, e.g. an object holding itself, are reported as cycles.
+
+use crate::de::ReferencePolicy;
+use crate::error::{ErrorKind, Result};
+use crate::lex::Lookahead1;
+use std::convert::TryFrom;
+
+/// Smallest number of bytes resolved references may expand to in total.
+const MIN_EXPANSION_BUDGET: usize = 1024 * 1024;
+
+/// Number of bytes resolved references may expand to in total, relative to
+/// the size of the input read so far.
+///
+/// Each reference is resolved into a full copy, so references to values
+/// that contain references themselves can expand exponentially.
+const EXPANSION_FACTOR: usize = 16;
+
+/// Handling of references by a lexer.
+#[derive(Debug)]
+pub(crate) enum Refs {
+    /// References are left to the caller, which typically fails on them.
+    Ignore,
+    /// References are rejected.
+    Reject,
+    /// References are resolved.
+    Resolve(Box<References>),
+}
+
+impl Refs {
+    /// Handling according to `policy`.
+    pub(crate) fn new(policy: ReferencePolicy) -> Refs {
+        match policy {
+            ReferencePolicy::Resolve => Refs::Resolve(Box::default()),
+            ReferencePolicy::Error => Refs::Reject,
+        }
+    }
+
+    /// Same handling, for independent input such as nested serialized data.
+    pub(crate) fn fresh(&self) -> Refs {
+        match self {
+            Refs::Ignore => Refs::Ignore,
+            Refs::Reject => Refs::Reject,
+            Refs::Resolve(_) => Refs::Resolve(Box::default()),
+        }
+    }
+}
+
+/// State for resolving references.
+#[derive(Debug, Default)]
+pub(crate) struct References {
+    /// Input consumed so far, excluding resolved references.
+    log: Vec<u8>,
+    /// Numbered values found in `log`, as the range of their serialization.
+    /// The end is `None` for arrays and objects not closed yet.
+    slots: Vec<(usize, Option<usize>)>,
+    /// Length of the part of `log` numbered so far.
+    scanned: usize,
+    /// Arrays and objects open at `scanned`, as their slot and the number of
+    /// keys and values left.
+    open: Vec<(usize, usize)>,
+    /// Copy of the referenced value, read in place of the last reference.
+    splice: Vec<u8>,
+    /// Number of bytes of `splice` read so far.
+    splice_pos: usize,
+    /// Number of bytes copied for references so far.
+    expanded: usize,
+    /// Positions in `log` where input was removed, along with the total
+    /// number of bytes removed up to there.
+    forgotten: Vec<(usize, usize)>,
+}
+
+impl References {
+    /// Record consumed input.
+    #[inline]
+    pub(crate) fn log(&mut self, data: &[u8]) {
+        self.log.extend_from_slice(data);
+    }
+
+    /// Number of bytes consumed so far.
+    #[inline]
+    pub(crate) fn log_len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Remove the last `len` bytes from the log, for input that is not part
+    /// of a value.
+    pub(crate) fn forget(&mut self, len: usize) {
+        let new_len = self.log.len().saturating_sub(len).max(self.scanned);
+        let removed = self.log.len() - new_len;
+        if removed > 0 {
+            let total = self.forgotten.last().map_or(0, |&(_, total)| total);
+            self.forgotten.push((new_len, total + removed));
+            self.log.truncate(new_len);
+        }
+    }
+
+    /// Offset in the input of position `pos` of the log.
+    fn input_offset(&self, pos: usize) -> u64 {
+        let idx = self.forgotten.partition_point(|&(at, _)| at <= pos);
+        let removed = idx.checked_sub(1).map_or(0, |idx| self.forgotten[idx].1);
+        (pos + removed) as u64
+    }
+
+    /// Number of bytes of the current copy left to read.
+    #[inline]
+    pub(crate) fn spliced_len(&self) -> usize {
+        self.splice.len() - self.splice_pos
+    }
+
+    /// Take the next byte of the current copy, if any is left.
+    #[inline]
+    pub(crate) fn next_spliced(&mut self) -> Option<u8> {
+        let c = self.splice.get(self.splice_pos).copied()?;
+        self.splice_pos += 1;
+        Some(c)
+    }
+
+    /// Take up to `len` bytes of the current copy, appending them to `data`.
+    pub(crate) fn take_spliced(&mut self, len: usize, data: &mut Vec<u8>) {
+        let end = self.splice_pos + len.min(self.spliced_len());
+        data.extend_from_slice(&self.splice[self.splice_pos..end]);
+        self.splice_pos = end;
+    }
+
+    /// Resolve a reference to value `index`, which is read from the log up
+    /// to `log_len`, i.e. excluding the reference itself.
+    ///
+    /// The referenced value is read next, see `next_spliced`.
+    pub(crate) fn resolve(&mut self, index: i64, log_len: usize, lexer: Dialect) -> Result<()> {
+        self.scan(log_len, lexer)?;
+
+        let (start, end) = self.slot(index)?;
+        let budget = MIN_EXPANSION_BUDGET.max(self.log.len() * EXPANSION_FACTOR);
+
+        // Copy the value, replacing references inside it by copies of their
+        // own. These point to complete values, so this terminates. An
+        // explicit stack avoids deep recursion for chains of references.
+        self.splice.clear();
+        self.splice_pos = 0;
+        let mut stack = vec![(start, end)];
+        while let Some((pos, end)) = stack.pop() {
+            if pos == end {
+                continue;
+            }
+
+            let (token, len) = next_token(&self.log[pos..end], lexer)?;
+            stack.push((pos + len, end));
+            match token {
+                Token::Ref(index, _) => stack.push(self.slot(index)?),
+                _ => self.splice.extend_from_slice(&self.log[pos..pos + len]),
+            }
+
+            if self.expanded + self.splice.len() > budget {
+                return Err(ErrorKind::ReferenceLimitExceeded(budget).into());
+            }
+        }
+
+        self.expanded += self.splice.len();
+        Ok(())
+    }
+
+    /// Range of the complete value `index`.
+    ///
+    /// Values not complete yet contain the reference to them.
+    fn slot(&self, index: i64) -> Result<(usize, usize)> {
+        let slot = usize::try_from(index)
+            .ok()
+            .and_then(|index| index.checked_sub(1))
+            .and_then(|idx| self.slots.get(idx));
+
+        match slot {
+            Some(&(start, Some(end))) => Ok((start, end)),
+            Some(&(start, None)) => Err(ErrorKind::ReferenceCycle {
+                offset: self.input_offset(start),
+            }
+            .into()),
+            None => Err(ErrorKind::InvalidReference(index).into()),
+        }
+    }
+
+    /// Number the values in the log up to `log_len`.
+    fn scan(&mut self, log_len: usize, lexer: Dialect) -> Result<()> {
+        while self.scanned < log_len {
+            let start = self.scanned;
+            let (token, len) = next_token(&self.log[start..log_len], lexer)?;
+            self.scanned += len;
+
+            // Keys are not numbered, they precede each value.
+            let is_key = match self.open.last_mut() {
+                Some((slot, 0)) => {
+                    let slot = *slot;
+                    self.open.pop();
+                    self.slots[slot].1 = Some(self.scanned);
+                    continue;
+                }
+                Some((_, left)) => {
+                    *left -= 1;
+                    *left % 2 == 1
+                }
+                None => false,
+            };
+            if is_key {
+                continue;
+            }
+
+            match token {
+                Token::Value => self.slots.push((start, Some(self.scanned))),
+                Token::Open(num_entries) => {
+                    self.open.push((self.slots.len(), num_entries * 2));
+                    self.slots.push((start, None));
+                }
+                // Object references are numbered themselves, as the value
+                // they refer to.
+                Token::Ref(index, true) => {
+                    let slot = self.slot(index)?;
+                    self.slots.push((slot.0, Some(slot.1)));
+                }
+                Token::Ref(_, false) | Token::Close => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of values making up serialized data, as numbered by PHP.
+pub(crate) fn count_values(data: &[u8]) -> Result<u64> {
+    let dialect = Dialect {
+        tolerant: true,
+        tolerant_floats: true,
+    };
+    let mut refs = References::default();
+    refs.log(data);
+    refs.scan(data.len(), dialect)?;
+    Ok(refs.slots.len() as u64)
+}
+
+/// Lexer options relevant for reading the log back.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Dialect {
+    pub(crate) tolerant: bool,
+    pub(crate) tolerant_floats: bool,
+}
+
+/// A single token of serialized data.
+enum Token {
+    /// A complete value that is not a reference.
+    Value,
+    /// Header of an array or object with the given number of entries.
+    Open(usize),
+    /// Closing brace of an array or object.
+    Close,
+    /// Reference to a value, `true` for object references (`r:`).
+    Ref(i64, bool),
+}
+
+/// Read the token at the start of `data`, returning it along with its length.
+fn next_token(data: &[u8], dialect: Dialect) -> Result<(Token, usize)> {
+    let mut lexer = Lookahead1::new(data);
+    lexer.set_tolerant(dialect.tolerant);
+    lexer.set_tolerant_floats(dialect.tolerant_floats);
+
+    let sym = lexer.read_type()?;
+    let token = match sym {
+        b'}' => Token::Close,
+        b'N' => {
+            lexer.expect(b';')?;
+            Token::Value
+        }
+        _ => {
+            lexer.expect(b':')?;
+            match sym {
+                b'b' => lexer.read_bool().map(|_| Token::Value)?,
+                b'i' => lexer.read_int_text().map(|_| Token::Value)?,
+                b'd' => lexer.read_float().map(|_| Token::Value)?,
+                b's' => lexer.read_raw_string().map(|_| Token::Value)?,
+                b'C' => lexer.read_custom().map(|_| Token::Value)?,
+                b'E' => lexer.read_enum_case().map(|_| Token::Value)?,
+                b'a' => Token::Open(lexer.read_array_header()?),
+                b'O' => Token::Open(lexer.read_object_header()?.1),
+                b'R' | b'r' => Token::Ref(lexer.read_int()?, sym == b'r'),
+                c => return Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+            }
+        }
+    };
+
+    Ok((token, lexer.position() as usize))
+}
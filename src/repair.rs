@@ -0,0 +1,736 @@
+//! Repair of corrupted serialized data.
+//!
+//! The most common kind of corruption found in the wild are string length
+//! prefixes that no longer match the actual string data, e.g. after a naive
+//! search-and-replace on a database dump or a charset conversion that changed
+//! the byte length of strings. Such payloads are rejected by PHP's
+//! `unserialize()` as well as by this crate.
+//!
+//! ```rust
+//! use serde_php::repair::fix_string_lengths;
+//!
+//! // "http://old" was replaced by "https://new.example" without adjusting the length.
+//! let broken = br#"a:1:{s:3:"url";s:10:"https://new.example";}"#;
+//! let fixed = fix_string_lengths(broken);
+//! assert_eq!(fixed, br#"a:1:{s:3:"url";s:19:"https://new.example";}"#.to_vec());
+//! ```
+//!
+//! To find corrupted rows in bulk before fixing them, e.g. WordPress widget
+//! and option rows after a site migration, [`audit_string_lengths`] reports
+//! the mismatches without changing anything.
+//!
+//! ## Strategies
+//!
+//! Other kinds of corruption call for other fixes. A [`RepairStrategy`]
+//! rewrites input that failed to deserialize and is registered with
+//! [`PhpDeserializerBuilder::repair_strategy`](crate::PhpDeserializerBuilder::repair_strategy).
+//! Strategies are tried in order whenever deserializing fails, each working
+//! on the output of the previous ones, until deserializing succeeds:
+//!
+//! ```rust
+//! use serde_php::repair::{FixCounts, FixLengths};
+//! use serde_php::PhpDeserializerBuilder;
+//!
+//! let builder = PhpDeserializerBuilder::new()
+//!     .repair_strategy(FixLengths)
+//!     .repair_strategy(FixCounts);
+//!
+//! // An entry was appended without adjusting the count, and a string was edited.
+//! let broken = br#"a:1:{i:0;s:3:"a";i:1;s:1:"bc";}"#;
+//! let list: Vec<String> = builder.deserialize(broken).unwrap();
+//! assert_eq!(list, ["a", "bc"]);
+//! ```
+//!
+//! Strategies receive the error, so they can restrict themselves to
+//! specific kinds of errors.
+
+use crate::error::Error;
+use std::fmt;
+
+/// A way of repairing input that failed to deserialize.
+pub trait RepairStrategy: fmt::Debug + Send + Sync {
+    /// Return a repaired copy of `input`, which failed to deserialize with
+    /// `error`, or `None` if the strategy does not apply.
+    ///
+    /// Returning the input unchanged counts as not applying.
+    fn repair(&self, input: &[u8], error: &Error) -> Option<Vec<u8>>;
+}
+
+/// Fixes string lengths using [`fix_string_lengths`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixLengths;
+
+impl RepairStrategy for FixLengths {
+    fn repair(&self, input: &[u8], _error: &Error) -> Option<Vec<u8>> {
+        Some(fix_string_lengths(input))
+    }
+}
+
+/// Fixes element counts of arrays using [`fix_array_counts`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixCounts;
+
+impl RepairStrategy for FixCounts {
+    fn repair(&self, input: &[u8], _error: &Error) -> Option<Vec<u8>> {
+        Some(fix_array_counts(input))
+    }
+}
+
+/// Drops array entries that cannot be parsed using
+/// [`drop_broken_entries`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DropBrokenEntries;
+
+impl RepairStrategy for DropBrokenEntries {
+    fn repair(&self, input: &[u8], _error: &Error) -> Option<Vec<u8>> {
+        Some(drop_broken_entries(input))
+    }
+}
+
+/// Recompute string length prefixes that do not match the string data.
+///
+/// The input is walked token by token. Whenever a string's declared length
+/// does not end on a closing `";` that is followed by a plausible next token,
+/// the nearest position that does is taken as the actual end of the string
+/// and the length prefix is rewritten accordingly.
+///
+/// This is a heuristic: strings containing the sequence `";` followed by
+/// something resembling a serialized value may still be cut short. Input that
+/// does not need fixing is returned unchanged.
+pub fn fix_string_lengths(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut pos = 0;
+
+    while pos < input.len() {
+        match scan_string(input, pos) {
+            Some(ScannedString {
+                data: Some(data),
+                end,
+                ..
+            }) => {
+                output.extend_from_slice(format!("s:{}:\"", data.len()).as_bytes());
+                output.extend_from_slice(data);
+                output.extend_from_slice(b"\";");
+                pos = end;
+            }
+            _ => {
+                // Copy everything up to the start of the next token verbatim.
+                let next = next_token(input, pos);
+                output.extend_from_slice(&input[pos..next]);
+                pos = next;
+            }
+        }
+    }
+
+    output
+}
+
+/// Recompute element counts of arrays and objects that do not match the
+/// number of entries they hold.
+///
+/// Unlike string lengths, counts are only fixed if the input is otherwise
+/// well-formed, as entries are found by parsing them. Input that cannot be
+/// parsed is returned unchanged, as is anything following the first value.
+/// Arrays nested more than 512 levels deep count as unparseable.
+///
+/// ```rust
+/// use serde_php::repair::fix_array_counts;
+///
+/// let fixed = fix_array_counts(br#"a:3:{i:0;a:0:{i:0;N;}i:1;b:1;}"#);
+/// assert_eq!(fixed, br#"a:2:{i:0;a:1:{i:0;N;}i:1;b:1;}"#.to_vec());
+/// ```
+pub fn fix_array_counts(input: &[u8]) -> Vec<u8> {
+    Walker::new(input, false).rewrite()
+}
+
+/// Remove entries of arrays and objects that cannot be parsed, adjusting
+/// the element counts.
+///
+/// When an entry fails to parse, the input is skipped up to the next
+/// position at which a complete entry or the end of the array follows. Arrays
+/// cut short by the end of the input are closed. Like
+/// [`fix_string_lengths`], this is a heuristic: data resembling serialized
+/// entries inside a broken entry may be kept. Input that does not start with
+/// a value is returned unchanged, as is anything following the first value.
+/// Arrays nested more than 512 levels deep count as broken entries.
+///
+/// ```rust
+/// use serde_php::repair::drop_broken_entries;
+///
+/// let fixed = drop_broken_entries(br#"a:3:{i:0;i:1;i:1;x:garbage;i:2;i:3;}"#);
+/// assert_eq!(fixed, br#"a:2:{i:0;i:1;i:2;i:3;}"#.to_vec());
+/// ```
+pub fn drop_broken_entries(input: &[u8]) -> Vec<u8> {
+    Walker::new(input, true).rewrite()
+}
+
+/// Nesting depth up to which arrays and objects are rewritten.
+///
+/// Deeper values are treated as broken, so that walking arbitrary input
+/// cannot overflow the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Parser rewriting a single value with recomputed counts.
+struct Walker<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Whether entries that fail to parse are skipped.
+    drop_broken: bool,
+    /// Number of arrays and objects entered.
+    depth: usize,
+}
+
+impl<'a> Walker<'a> {
+    fn new(input: &'a [u8], drop_broken: bool) -> Self {
+        Walker {
+            input,
+            pos: 0,
+            drop_broken,
+            depth: 0,
+        }
+    }
+
+    /// Rewrite the first value, keeping whatever follows it.
+    fn rewrite(mut self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.input.len());
+        if self.value(&mut output).is_none() {
+            return self.input.to_vec();
+        }
+        output.extend_from_slice(&self.input[self.pos..]);
+        output
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.input[self.pos..]
+    }
+
+    /// Consume `expected` if the input continues with it.
+    fn eat(&mut self, expected: &[u8]) -> Option<()> {
+        if self.rest().starts_with(expected) {
+            self.pos += expected.len();
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consume an unsigned decimal number.
+    fn number(&mut self) -> Option<usize> {
+        let digits = self
+            .rest()
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let number = std::str::from_utf8(&self.rest()[..digits])
+            .ok()?
+            .parse()
+            .ok()?;
+        self.pos += digits;
+        Some(number)
+    }
+
+    /// Consume a value, appending it to `output`.
+    fn value(&mut self, output: &mut Vec<u8>) -> Option<()> {
+        let start = self.pos;
+
+        match self.rest().first()? {
+            b'N' => self.eat(b"N;")?,
+            b'b' | b'i' | b'd' => {
+                // Scalars run up to the next `;`, without any delimiters.
+                self.pos += 1;
+                self.eat(b":")?;
+                let len = self
+                    .rest()
+                    .iter()
+                    .position(|&c| c == b';')
+                    .filter(|&len| len > 0)?;
+                let scalar = &self.rest()[..len];
+                if !scalar
+                    .iter()
+                    .all(|c| c.is_ascii_alphanumeric() || b"+-.".contains(c))
+                {
+                    return None;
+                }
+                self.pos += len + 1;
+            }
+            b's' => {
+                self.eat(b"s:")?;
+                let len = self.number()?;
+                self.eat(b":\"")?;
+                self.pos = self.pos.checked_add(len)?;
+                if self.pos > self.input.len() {
+                    return None;
+                }
+                self.eat(b"\";")?;
+            }
+            b'a' => {
+                self.eat(b"a:")?;
+                self.number()?;
+                self.eat(b":{")?;
+                return self.entries(output, b"a:");
+            }
+            b'O' => {
+                self.eat(b"O:")?;
+                let len = self.number()?;
+                self.eat(b":\"")?;
+                self.pos = self.pos.checked_add(len)?;
+                if self.pos > self.input.len() {
+                    return None;
+                }
+                self.eat(b"\":")?;
+                let header = self.input[start..self.pos].to_vec();
+                self.number()?;
+                self.eat(b":{")?;
+                return self.entries(output, &header);
+            }
+            _ => return None,
+        }
+
+        output.extend_from_slice(&self.input[start..self.pos]);
+        Some(())
+    }
+
+    /// Consume an array key, appending it to `output`.
+    fn key(&mut self, output: &mut Vec<u8>) -> Option<()> {
+        match self.rest().first()? {
+            b'i' | b's' => self.value(output),
+            _ => None,
+        }
+    }
+
+    /// Consume a complete entry, appending it to `output`.
+    fn entry(&mut self, output: &mut Vec<u8>) -> Option<()> {
+        self.key(output)?;
+        self.value(output)
+    }
+
+    /// Consume entries up to and including the closing brace, appending them
+    /// to `output` after `header` and their count.
+    fn entries(&mut self, output: &mut Vec<u8>, header: &[u8]) -> Option<()> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let result = self.nested_entries(output, header);
+        self.depth -= 1;
+        result
+    }
+
+    /// Body of [`entries`](Walker::entries), once the depth is accounted for.
+    fn nested_entries(&mut self, output: &mut Vec<u8>, header: &[u8]) -> Option<()> {
+        let mut entries = Vec::new();
+        let mut count = 0;
+
+        loop {
+            if self.eat(b"}").is_some() {
+                break;
+            }
+            if self.rest().is_empty() && self.drop_broken {
+                break;
+            }
+
+            let start = self.pos;
+            let len = entries.len();
+            if self.entry(&mut entries).is_some() {
+                count += 1;
+                continue;
+            }
+            if !self.drop_broken {
+                return None;
+            }
+
+            // Skip to the next entry or the end of the array.
+            entries.truncate(len);
+            self.pos = start + 1;
+            while !self.rest().is_empty() && !self.rest().starts_with(b"}") {
+                if self.input[self.pos - 1] == b';' && self.probe() {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+
+        output.extend_from_slice(header);
+        output.extend_from_slice(format!("{}:{{", count).as_bytes());
+        output.extend_from_slice(&entries);
+        output.push(b'}');
+        Some(())
+    }
+
+    /// Check whether an entry follows, i.e. a complete key and the start of
+    /// a value.
+    ///
+    /// The value itself is left to parsing the entry, which skips anything
+    /// broken inside it, so that skipping over broken input stays linear.
+    fn probe(&self) -> bool {
+        let mut walker = Walker {
+            input: self.input,
+            pos: self.pos,
+            drop_broken: false,
+            depth: self.depth,
+        };
+        if walker.key(&mut Vec::new()).is_none() {
+            return false;
+        }
+        match walker.rest() {
+            [b'N', b';', ..] => true,
+            [c, b':', ..] => b"abdiOs".contains(c),
+            _ => false,
+        }
+    }
+}
+
+/// A string whose declared length disagrees with its data, see
+/// [`audit_string_lengths`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LengthMismatch {
+    offset: usize,
+    declared: usize,
+    actual: Option<usize>,
+}
+
+impl LengthMismatch {
+    /// Byte offset of the string in the input, pointing at its `s:`.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Length given by the string's prefix.
+    pub fn declared(&self) -> usize {
+        self.declared
+    }
+
+    /// Length of the string data up to its closing `";`, or `None` if no
+    /// plausible end was found, e.g. because the input is truncated.
+    pub fn actual(&self) -> Option<usize> {
+        self.actual
+    }
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "string at offset {} declares {} bytes, ",
+            self.offset, self.declared
+        )?;
+        match self.actual {
+            Some(actual) => write!(f, "but holds {}", actual),
+            None => f.write_str("but has no end"),
+        }
+    }
+}
+
+/// Find all strings whose length prefix does not match the string data.
+///
+/// The input is walked the same way as by [`fix_string_lengths`], which
+/// would rewrite exactly the strings reported here that have an
+/// [`actual`](LengthMismatch::actual) length. Nothing is parsed strictly, so
+/// this never fails, and valid input yields no mismatches.
+///
+/// ```rust
+/// use serde_php::repair::audit_string_lengths;
+///
+/// let row = r#"a:2:{s:5:"title";s:6:"Über uns";s:4:"text";s:3:"Hi";}"#;
+/// let mismatches = audit_string_lengths(row.as_bytes());
+///
+/// assert_eq!(mismatches.len(), 2);
+/// assert_eq!(mismatches[0].offset(), 17);
+/// assert_eq!(mismatches[0].declared(), 6);
+/// assert_eq!(mismatches[0].actual(), Some(9));
+/// ```
+pub fn audit_string_lengths(input: &[u8]) -> Vec<LengthMismatch> {
+    let mut mismatches = Vec::new();
+    let mut pos = 0;
+
+    while pos < input.len() {
+        match scan_string(input, pos) {
+            Some(found) => {
+                let actual = found.data.map(<[u8]>::len);
+                if actual != Some(found.declared) {
+                    mismatches.push(LengthMismatch {
+                        offset: pos,
+                        declared: found.declared,
+                        actual,
+                    });
+                }
+                pos = found.end;
+            }
+            None => pos = next_token(input, pos),
+        }
+    }
+
+    mismatches
+}
+
+/// A string token found while scanning.
+#[derive(Debug)]
+struct ScannedString<'a> {
+    /// Length given by the prefix.
+    declared: usize,
+    /// Actual string data, between the quotes, if its end was found.
+    data: Option<&'a [u8]>,
+    /// Position after the terminating `;`, or after the opening quote if the
+    /// end was not found.
+    end: usize,
+}
+
+/// Try to scan a string token starting at `pos`.
+///
+/// Returns `None` if `pos` does not start a string token.
+fn scan_string(input: &[u8], pos: usize) -> Option<ScannedString<'_>> {
+    let rest = input.get(pos..)?;
+    if !rest.starts_with(b"s:") {
+        return None;
+    }
+
+    let digits = rest[2..].iter().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 || rest.get(2 + digits..4 + digits)? != b":\"" {
+        return None;
+    }
+
+    let declared: usize = std::str::from_utf8(&rest[2..2 + digits])
+        .ok()?
+        .parse()
+        .ok()?;
+    let data_start = pos + 4 + digits;
+
+    // Prefer the declared length, if it checks out.
+    if let Some(declared_end) = data_start.checked_add(declared) {
+        if is_string_end(input, declared_end) {
+            return Some(ScannedString {
+                declared,
+                data: Some(&input[data_start..declared_end]),
+                end: declared_end + 2,
+            });
+        }
+    }
+
+    // Otherwise look for the nearest plausible end.
+    let end = (data_start..input.len()).find(|&candidate| is_string_end(input, candidate));
+    Some(ScannedString {
+        declared,
+        data: end.map(|end| &input[data_start..end]),
+        end: end.map_or(data_start, |end| end + 2),
+    })
+}
+
+/// Check whether a string's closing `";` at `pos` is followed by a plausible next token.
+fn is_string_end(input: &[u8], pos: usize) -> bool {
+    match input.get(pos..) {
+        Some(rest) if rest.starts_with(b"\";") => is_token_start(&rest[2..]),
+        _ => false,
+    }
+}
+
+/// Check whether `rest` is empty or starts with something resembling a token.
+fn is_token_start(rest: &[u8]) -> bool {
+    match rest {
+        [] | [b'}', ..] | [b'N', b';', ..] => true,
+        [c, b':', ..] => b"abdiOsCrR".contains(c),
+        _ => false,
+    }
+}
+
+/// Find the start of the next token after `pos`.
+///
+/// Tokens are always preceded by one of `;`, `{` or `}`, so everything up to
+/// and including the next such delimiter is skipped.
+fn next_token(input: &[u8], pos: usize) -> usize {
+    input[pos..]
+        .iter()
+        .position(|c| matches!(c, b';' | b'{' | b'}'))
+        .map(|idx| pos + idx + 1)
+        .unwrap_or_else(|| input.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{audit_string_lengths, drop_broken_entries, fix_array_counts, fix_string_lengths};
+    use crate::de::PhpDeserializerBuilder;
+
+    #[test]
+    fn leaves_valid_input_unchanged() {
+        let inputs: &[&[u8]] = &[
+            br#"a:2:{i:0;s:5:"a";b:";i:1;d:1.5;}"#,
+            br#"s:0:"";"#,
+            b"N;",
+            b"a:0:{}",
+        ];
+
+        for input in inputs {
+            assert_eq!(fix_string_lengths(input), input.to_vec());
+        }
+    }
+
+    #[test]
+    fn fixes_too_short_and_too_long() {
+        assert_eq!(
+            fix_string_lengths(br#"a:2:{i:0;s:1:"abc";i:1;s:9:"x";}"#),
+            br#"a:2:{i:0;s:3:"abc";i:1;s:1:"x";}"#.to_vec()
+        );
+    }
+
+    #[test]
+    fn fixes_multibyte_conversion() {
+        // Latin-1 "é" (1 byte) converted to UTF-8 (2 bytes).
+        let broken = "s:4:\"café\";".as_bytes();
+        assert_eq!(
+            fix_string_lengths(broken),
+            "s:5:\"café\";".as_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn keeps_embedded_delimiters_with_correct_length() {
+        let input = br#"a:1:{s:4:"html";s:13:"<a href="x";>";}"#;
+        assert_eq!(fix_string_lengths(input), input.to_vec());
+    }
+
+    #[test]
+    fn audits_lengths() {
+        let input = br#"a:3:{i:0;s:1:"abc";i:1;s:4:"x";y";i:2;s:2:"ok";}"#;
+        let mismatches = audit_string_lengths(input);
+
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].offset(), 9);
+        assert_eq!(mismatches[0].declared(), 1);
+        assert_eq!(mismatches[0].actual(), Some(3));
+        assert_eq!(
+            mismatches[0].to_string(),
+            "string at offset 9 declares 1 bytes, but holds 3"
+        );
+
+        assert!(audit_string_lengths(br#"a:1:{i:0;s:5:"a";b:";}"#).is_empty());
+    }
+
+    #[test]
+    fn audits_truncated_strings() {
+        let mismatches = audit_string_lengths(br#"a:1:{i:0;s:10:"abc"#);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].actual(), None);
+        assert_eq!(
+            mismatches[0].to_string(),
+            "string at offset 9 declares 10 bytes, but has no end"
+        );
+
+        // Fixing leaves such strings alone.
+        assert_eq!(
+            fix_string_lengths(br#"a:1:{i:0;s:10:"abc"#),
+            br#"a:1:{i:0;s:10:"abc"#.to_vec()
+        );
+    }
+
+    #[test]
+    fn fixes_counts() {
+        let inputs: &[&[u8]] = &[
+            br#"a:2:{i:0;s:5:"a;}i:";i:1;d:-1.5E+25;}"#,
+            br#"O:8:"stdClass":1:{s:1:"a";N;}"#,
+            b"b:1;",
+        ];
+        for input in inputs {
+            assert_eq!(fix_array_counts(input), input.to_vec());
+        }
+
+        assert_eq!(
+            fix_array_counts(br#"O:4:"User":0:{s:2:"id";i:1;s:4:"tags";a:9:{}}"#),
+            br#"O:4:"User":2:{s:2:"id";i:1;s:4:"tags";a:0:{}}"#.to_vec()
+        );
+        // Broken entries are left to other repairs.
+        let broken = br#"a:1:{i:0;s:9:"x";i:1;N;}"#;
+        assert_eq!(fix_array_counts(broken), broken.to_vec());
+    }
+
+    #[test]
+    fn drops_broken_entries() {
+        assert_eq!(
+            drop_broken_entries(br#"a:3:{s:1:"a";s:20:"x";s:1:"b";a:1:{i:0;i:2;}s:1:"c";N;}"#),
+            br#"a:2:{s:1:"b";a:1:{i:0;i:2;}s:1:"c";N;}"#.to_vec()
+        );
+        // Truncated input is closed.
+        assert_eq!(
+            drop_broken_entries(br#"a:3:{i:0;i:1;i:1;a:2:{i:0;b:1;i:1;s:4:"ab"#),
+            br#"a:2:{i:0;i:1;i:1;a:1:{i:0;b:1;}}"#.to_vec()
+        );
+        assert_eq!(drop_broken_entries(b"x"), b"x".to_vec());
+    }
+
+    #[test]
+    fn limits_depth() {
+        use super::FixCounts;
+        use crate::de::Profile;
+        use crate::error::ErrorKind;
+
+        let nested = |depth| {
+            let mut input = b"a:1:{i:0;".repeat(depth);
+            input.extend_from_slice(b"N;");
+            input.extend_from_slice(&b"}".repeat(depth));
+            input
+        };
+
+        // Deep enough to overflow the stack without a limit.
+        let deep = nested(200_000);
+        assert_eq!(fix_array_counts(&deep), deep);
+        drop_broken_entries(&deep);
+        drop_broken_entries(&deep[..deep.len() / 2]);
+
+        // Counts are still fixed below the limit.
+        let mut wrong = nested(512);
+        wrong[2] = b'2';
+        assert_eq!(fix_array_counts(&wrong), nested(512));
+
+        let builder = PhpDeserializerBuilder::new()
+            .profile(Profile::Hardened)
+            .repair_strategy(FixCounts);
+        let err = builder
+            .deserialize::<serde::de::IgnoredAny>(&deep[..deep.len() / 2])
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(128)));
+    }
+
+    #[test]
+    fn applies_strategies_in_order() {
+        use super::{DropBrokenEntries, FixLengths, RepairStrategy};
+        use crate::error::{Error, ErrorKind};
+
+        /// Turns `T` into `N`, but only where a `T` was found unexpectedly.
+        #[derive(Debug)]
+        struct NullifyT;
+
+        impl RepairStrategy for NullifyT {
+            fn repair(&self, input: &[u8], error: &Error) -> Option<Vec<u8>> {
+                match error.kind() {
+                    ErrorKind::Unexpected { actual: 'T', .. } => Some(
+                        input
+                            .iter()
+                            .map(|&c| if c == b'T' { b'N' } else { c })
+                            .collect(),
+                    ),
+                    _ => None,
+                }
+            }
+        }
+
+        let input = br#"a:2:{i:0;T;i:1;s:3:"a";}"#;
+        let none = PhpDeserializerBuilder::new();
+        assert!(none.deserialize::<Vec<Option<String>>>(input).is_err());
+
+        let nullify = none.clone().repair_strategy(NullifyT);
+        assert!(nullify.deserialize::<Vec<Option<String>>>(input).is_err());
+
+        let both = nullify.repair_strategy(FixLengths);
+        assert_eq!(
+            both.deserialize::<Vec<Option<String>>>(input).unwrap(),
+            [None, Some("a".to_owned())]
+        );
+
+        let drop = none.repair_strategy(DropBrokenEntries);
+        assert_eq!(
+            drop.deserialize::<Vec<(i64, Option<String>)>>(
+                br#"a:2:{i:0;a:2:{i:0;i:1;i:1;N;}i:1;T;}"#
+            )
+            .unwrap(),
+            [(1, None)]
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Structural validation of serialized data.
+//!
+//! A [`Schema`] describes the expected shape of a value: which keys an array
+//! must contain and which types values must have. Unlike deserializing into a
+//! typed structure, which stops at the first mismatch, validation reports all
+//! violations at once, each with the path to the offending value.
+//!
+//! ```rust
+//! use serde_php::schema::{validate_against, ObjectSchema, Schema};
+//!
+//! let schema: Schema = ObjectSchema::new()
+//!     .required("id", Schema::Int)
+//!     .required("tags", Schema::list(Schema::String))
+//!     .optional("email", Schema::String.nullable())
+//!     .into();
+//!
+//! let input = br#"a:2:{s:2:"id";s:1:"7";s:4:"tags";a:2:{i:0;s:1:"a";i:1;i:2;}}"#;
+//! let violations = validate_against(input, &schema).unwrap();
+//!
+//! let report: Vec<String> = violations.iter().map(ToString::to_string).collect();
+//! assert_eq!(report, ["id: expected int, found string", "tags.1: expected string, found int"]);
+//! ```
+
+use crate::error::Result;
+use crate::value::PhpValue;
+use std::fmt;
+
+/// Expected shape of a value.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Schema {
+    /// Any value.
+    Any,
+    /// `null`.
+    Null,
+    /// A boolean.
+    Bool,
+    /// An integer.
+    Int,
+    /// A float.
+    Float,
+    /// An integer or a float.
+    Number,
+    /// A string.
+    String,
+    /// Either `null` or a value matching the inner schema.
+    Nullable(Box<Schema>),
+    /// An array with keys `0..n` in order, all values matching the inner
+    /// schema.
+    List(Box<Schema>),
+    /// An array with arbitrary keys, all values matching the inner schema.
+    Map(Box<Schema>),
+    /// An array with known keys.
+    Object(ObjectSchema),
+}
+
+impl Schema {
+    /// Schema for lists of `element`.
+    pub fn list(element: Schema) -> Self {
+        Schema::List(Box::new(element))
+    }
+
+    /// Schema for maps with values matching `value`.
+    pub fn map(value: Schema) -> Self {
+        Schema::Map(Box::new(value))
+    }
+
+    /// Allow `null` in addition to values matching this schema.
+    pub fn nullable(self) -> Self {
+        match self {
+            Schema::Nullable(_) | Schema::Null | Schema::Any => self,
+            other => Schema::Nullable(Box::new(other)),
+        }
+    }
+
+    /// Validate an already parsed value, returning all violations.
+    ///
+    /// An empty result means the value matches the schema.
+    pub fn validate(&self, value: &PhpValue) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut path = Vec::new();
+        self.check(value, &mut path, &mut violations);
+        violations
+    }
+
+    fn check(&self, value: &PhpValue, path: &mut Vec<String>, out: &mut Vec<Violation>) {
+        let matches = match (self, value) {
+            (Schema::Any, _) => true,
+            (Schema::Null, PhpValue::Null) => true,
+            (Schema::Bool, PhpValue::Bool(_)) => true,
+            (Schema::Int, PhpValue::Number(n)) => n.is_int(),
+            (Schema::Float, PhpValue::Number(n)) => n.is_float(),
+            (Schema::Number, PhpValue::Number(_)) => true,
+            (Schema::String, PhpValue::String(_)) => true,
+            (Schema::Nullable(_), PhpValue::Null) => true,
+            (Schema::Nullable(inner), _) => return inner.check(value, path, out),
+            (Schema::List(element), PhpValue::Array(entries)) if value.is_list() => {
+                for (key, item) in entries {
+                    path.push(key_segment(key));
+                    element.check(item, path, out);
+                    path.pop();
+                }
+                true
+            }
+            (Schema::Map(schema), PhpValue::Array(entries)) => {
+                for (key, item) in entries {
+                    path.push(key_segment(key));
+                    schema.check(item, path, out);
+                    path.pop();
+                }
+                true
+            }
+            (Schema::Object(object), PhpValue::Array(_)) => {
+                object.check(value, path, out);
+                true
+            }
+            _ => false,
+        };
+
+        if !matches {
+            out.push(Violation::new(
+                path,
+                ViolationKind::TypeMismatch {
+                    expected: self.to_string(),
+                    found: type_name(value),
+                },
+            ));
+        }
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schema::Any => f.write_str("any"),
+            Schema::Null => f.write_str("null"),
+            Schema::Bool => f.write_str("bool"),
+            Schema::Int => f.write_str("int"),
+            Schema::Float => f.write_str("float"),
+            Schema::Number => f.write_str("number"),
+            Schema::String => f.write_str("string"),
+            Schema::Nullable(inner) => write!(f, "{} or null", inner),
+            Schema::List(_) => f.write_str("list"),
+            Schema::Map(_) | Schema::Object(_) => f.write_str("array"),
+        }
+    }
+}
+
+impl From<ObjectSchema> for Schema {
+    fn from(object: ObjectSchema) -> Self {
+        Schema::Object(object)
+    }
+}
+
+/// Schema for arrays with known keys, see [`Schema::Object`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectSchema {
+    fields: Vec<Field>,
+    deny_unknown_keys: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Field {
+    key: String,
+    schema: Schema,
+    required: bool,
+}
+
+impl ObjectSchema {
+    /// Create an object schema without any keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require `key` to be present, with a value matching `schema`.
+    pub fn required(self, key: impl Into<String>, schema: Schema) -> Self {
+        self.field(key.into(), schema, true)
+    }
+
+    /// Allow `key` to be absent; if present, its value must match `schema`.
+    pub fn optional(self, key: impl Into<String>, schema: Schema) -> Self {
+        self.field(key.into(), schema, false)
+    }
+
+    /// Report keys not declared in the schema.
+    ///
+    /// By default, additional keys are allowed and not validated.
+    pub fn deny_unknown_keys(mut self, deny: bool) -> Self {
+        self.deny_unknown_keys = deny;
+        self
+    }
+
+    fn field(mut self, key: String, schema: Schema, required: bool) -> Self {
+        self.fields.retain(|field| field.key != key);
+        self.fields.push(Field {
+            key,
+            schema,
+            required,
+        });
+        self
+    }
+
+    fn check(&self, value: &PhpValue, path: &mut Vec<String>, out: &mut Vec<Violation>) {
+        for field in &self.fields {
+            path.push(field.key.clone());
+            match value.get(&field.key) {
+                Some(item) => field.schema.check(item, path, out),
+                None if field.required => out.push(Violation::new(path, ViolationKind::MissingKey)),
+                None => {}
+            }
+            path.pop();
+        }
+
+        if self.deny_unknown_keys {
+            for (key, _) in value.as_array().into_iter().flatten() {
+                let segment = key_segment(key);
+                if !self.fields.iter().any(|field| field.key == segment) {
+                    path.push(segment);
+                    out.push(Violation::new(path, ViolationKind::UnknownKey));
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// A mismatch between a value and a schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    path: String,
+    kind: ViolationKind,
+}
+
+impl Violation {
+    fn new(path: &[String], kind: ViolationKind) -> Self {
+        Violation {
+            path: path.join("."),
+            kind,
+        }
+    }
+
+    /// Path to the offending value, with keys separated by `.`, as in
+    /// [`Error::path`](crate::Error::path).
+    ///
+    /// Empty if the top-level value itself does not match.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Kind of violation.
+    pub fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{}: {}", self.path, self.kind)
+        }
+    }
+}
+
+/// Kind of [`Violation`].
+#[derive(Clone, Debug, displaydoc::Display, PartialEq)]
+#[non_exhaustive]
+pub enum ViolationKind {
+    /// required key is missing
+    MissingKey,
+    /// key is not part of the schema
+    UnknownKey,
+    /// expected {expected}, found {found}
+    TypeMismatch {
+        /// Description of the expected type.
+        expected: String,
+        /// Type of the value found.
+        found: String,
+    },
+}
+
+/// Parse `input` and validate it against `schema`, returning all violations.
+///
+/// Fails only if `input` is not a single, well-formed value. An empty result
+/// means the value matches the schema.
+pub fn validate_against(input: &[u8], schema: &Schema) -> Result<Vec<Violation>> {
+    Ok(schema.validate(&PhpValue::parse(input)?))
+}
+
+/// Path segment of an array key.
+fn key_segment(key: &PhpValue) -> String {
+    match key {
+        PhpValue::String(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        other => other.to_string(),
+    }
+}
+
+/// Name of the type of `value`, as used in violations.
+fn type_name(value: &PhpValue) -> String {
+    let name = match value {
+        PhpValue::Null => "null",
+        PhpValue::Bool(_) => "bool",
+        PhpValue::Number(n) if n.is_float() => "float",
+        PhpValue::Number(_) => "int",
+        PhpValue::String(_) => "string",
+        PhpValue::Array(_) if value.is_list() => "list",
+        PhpValue::Array(_) => "array",
+    };
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_against, ObjectSchema, Schema, ViolationKind};
+
+    fn report(input: &[u8], schema: &Schema) -> Vec<String> {
+        validate_against(input, schema)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn validates_scalars() {
+        assert!(report(b"i:1;", &Schema::Int).is_empty());
+        assert!(report(b"i:1;", &Schema::Number).is_empty());
+        assert!(report(b"d:1.5;", &Schema::Number).is_empty());
+        assert!(report(b"N;", &Schema::Int.nullable()).is_empty());
+        assert!(report(b"b:1;", &Schema::Any).is_empty());
+
+        assert_eq!(report(b"d:1;", &Schema::Int), ["expected int, found float"]);
+        assert_eq!(
+            report(br#"s:1:"x";"#, &Schema::Bool.nullable()),
+            ["expected bool, found string"]
+        );
+        assert_eq!(
+            report(b"a:0:{}", &Schema::String),
+            ["expected string, found list"]
+        );
+    }
+
+    #[test]
+    fn reports_all_violations() {
+        let address: Schema = ObjectSchema::new()
+            .required("city", Schema::String)
+            .optional("zip", Schema::Int)
+            .into();
+        let schema: Schema = ObjectSchema::new()
+            .required("id", Schema::Int)
+            .required("name", Schema::String)
+            .required("addresses", Schema::list(address))
+            .optional("meta", Schema::map(Schema::String))
+            .into();
+
+        let input = br#"a:4:{s:2:"id";i:1;s:9:"addresses";a:2:{i:0;a:1:{s:4:"city";s:1:"A";}i:1;a:2:{s:3:"zip";s:1:"1";s:5:"extra";b:1;}}s:4:"meta";a:2:{s:1:"a";s:1:"b";i:5;N;}s:5:"other";N;}"#;
+        assert_eq!(
+            report(input, &schema),
+            [
+                "name: required key is missing",
+                "addresses.1.city: required key is missing",
+                "addresses.1.zip: expected int, found string",
+                "meta.5: expected string, found null",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_list_keys() {
+        let schema = Schema::list(Schema::Int);
+        assert!(report(b"a:2:{i:0;i:1;i:1;i:2;}", &schema).is_empty());
+        assert_eq!(
+            report(b"a:2:{i:0;i:1;i:2;i:2;}", &schema),
+            ["expected list, found array"]
+        );
+    }
+
+    #[test]
+    fn denies_unknown_keys() {
+        let schema: Schema = ObjectSchema::new()
+            .required("a", Schema::Any)
+            .deny_unknown_keys(true)
+            .into();
+
+        let violations = validate_against(br#"a:2:{s:1:"a";N;i:3;N;}"#, &schema).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path(), "3");
+        assert_eq!(violations[0].kind(), &ViolationKind::UnknownKey);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(validate_against(b"a:1:{", &Schema::Any).is_err());
+        assert!(validate_against(b"i:1;i:2;", &Schema::Any).is_err());
+    }
+}
@@ -1,8 +1,27 @@
-use crate::error::{Error, Result};
+use crate::custom;
+use crate::encoders::{self, Encoders};
+use crate::error::{Error, ErrorKind, Result};
+use crate::metrics::{CountingWriter, Metrics, Observer, Operation};
+use crate::pretty;
+use crate::raw;
+use crate::refs;
+use crate::value::PhpValue;
+use crate::version::FormatVersion;
 use serde::{ser, Serialize};
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::mem;
+use std::time::Instant;
+
+/// Newtype struct name recognized by the serializer, wrapping a value that
+/// may occur more than once. The address of the value identifies it.
+pub(crate) const SHARED_TOKEN: &str = "$serde_php::private::Shared";
 
 /// Write out serialization of value.
+///
+/// May be called repeatedly on the same writer to store multiple records, see
+/// [`to_vec_append`] for framing considerations.
 #[inline]
 pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
 where
@@ -13,6 +32,35 @@ where
     value.serialize(&mut ser)
 }
 
+/// Write out serialization of value, reporting metrics to `observer`.
+pub fn to_writer_observed<W, T, O>(writer: W, value: &T, observer: &O) -> Result<()>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+    O: Observer + ?Sized,
+{
+    let start = Instant::now();
+    let mut ser = Serializer::new(CountingWriter::new(writer));
+    let result = value.serialize(&mut ser);
+
+    if result.is_ok() {
+        // The top-level value itself.
+        ser.values += 1;
+    }
+
+    observer.observe(
+        Operation::Serialize,
+        &Metrics {
+            bytes: ser.output.count(),
+            values: ser.values,
+            duration: start.elapsed(),
+            failed: result.is_err(),
+        },
+    );
+
+    result
+}
+
 /// Write serialization of value into byte vector.
 #[inline]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
@@ -24,26 +72,536 @@ where
     Ok(buf)
 }
 
+/// Write an indented rendering of the serialization of value into a byte
+/// vector, for diagnostics.
+///
+/// Arrays and objects are spread over multiple lines, one entry per line, so
+/// that serialized structures can be compared line by line in tests and code
+/// reviews. The result is not valid serialized data as far as PHP is
+/// concerned, use [`to_vec`] for that.
+///
+/// ```rust
+/// use std::collections::BTreeMap;
+///
+/// let mut map = BTreeMap::new();
+/// map.insert("name", vec!["a", "b"]);
+/// assert_eq!(
+///     String::from_utf8(serde_php::to_vec_pretty(&map).unwrap()).unwrap(),
+///     r#"a:1:{
+///     s:4:"name";a:2:{
+///         i:0;s:1:"a";
+///         i:1;s:1:"b";
+///     }
+/// }
+/// "#
+/// );
+/// ```
+pub fn to_vec_pretty<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    pretty::indent(&to_vec(value)?)
+}
+
+/// Append serialization of value to an existing byte vector.
+///
+/// Allows batch writers to reuse a single buffer instead of allocating a
+/// fresh one for every record. On error, `buf` is truncated back to its
+/// original length, so no partially serialized value is left behind.
+///
+/// ## Framing
+///
+/// Serialized values are self-delimiting, so records written back-to-back
+/// can be told apart by a reader that parses them in order. This offers no
+/// way to skip a record without parsing it and a single corrupted record
+/// makes all following ones unreadable. For record-style storage, prefixing
+/// each record with its length is recommended:
+///
+/// ```rust
+/// let mut buf = Vec::new();
+/// for record in &["first", "second"] {
+///     let start = buf.len();
+///     buf.extend_from_slice(&[0; 4]);
+///     serde_php::to_vec_append(&mut buf, record).unwrap();
+///     let len = (buf.len() - start - 4) as u32;
+///     buf[start..start + 4].copy_from_slice(&len.to_le_bytes());
+/// }
+///
+/// assert_eq!(&buf[..4], &12u32.to_le_bytes());
+/// assert_eq!(&buf[4..16], br#"s:5:"first";"#);
+/// ```
+pub fn to_vec_append<T>(buf: &mut Vec<u8>, value: &T) -> Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    let len = buf.len();
+    let result = to_writer(&mut *buf, value);
+    if result.is_err() {
+        buf.truncate(len);
+    }
+    result
+}
+
+/// Builder for serializers with non-default options.
+///
+/// ```rust
+/// use serde_php::PhpSerializerBuilder;
+///
+/// let builder = PhpSerializerBuilder::new().chars_as_strings(true);
+/// assert_eq!(builder.to_vec(&'é').unwrap(), "s:2:\"é\";".as_bytes());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PhpSerializerBuilder {
+    chars_as_strings: bool,
+    unit_structs: UnitStructMode,
+    backreferences: bool,
+    reject_floats: bool,
+    format_version: FormatVersion,
+    encoders: Encoders,
+}
+
+impl PhpSerializerBuilder {
+    /// Create a builder with all options set to their defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write `char`s as strings holding the UTF-8 encoded character.
+    ///
+    /// By default, chars are written as their code point, e.g. `i:233;` for
+    /// `é`, which PHP code sees as a number. As strings, they can be used
+    /// like any other PHP string and are passed back unchanged by PHP code
+    /// handling them as such. Both forms are accepted when deserializing.
+    /// Disabled by default.
+    pub fn chars_as_strings(mut self, enable: bool) -> Self {
+        self.chars_as_strings = enable;
+        self
+    }
+
+    /// Set how unit structs, such as `struct Marker;`, are written.
+    ///
+    /// PHP has no equivalent of unit structs, so they are written as `null`
+    /// by default, like `()`. Both `null` and empty arrays are accepted when
+    /// deserializing.
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_php::{PhpSerializerBuilder, UnitStructMode};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Marker;
+    ///
+    /// assert_eq!(serde_php::to_vec(&Marker).unwrap(), b"N;");
+    /// let builder = PhpSerializerBuilder::new().unit_structs(UnitStructMode::EmptyArray);
+    /// assert_eq!(builder.to_vec(&Marker).unwrap(), b"a:0:{}");
+    /// ```
+    pub fn unit_structs(mut self, mode: UnitStructMode) -> Self {
+        self.unit_structs = mode;
+        self
+    }
+
+    /// Write values occurring more than once as references to their first
+    /// occurrence.
+    ///
+    /// Only values serialized using [`helpers::shared`](crate::helpers::shared),
+    /// typically `Rc`s and `Arc`s, are considered, and are identified by
+    /// their address. Like PHP, repeated objects are written as `r:` and
+    /// other repeated values as `R:` (PHP references), which keeps the output
+    /// smaller and tells PHP code that the values are one and the same.
+    /// Disabled by default, writing every occurrence in full.
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_php::PhpSerializerBuilder;
+    /// use std::rc::Rc;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Page {
+    ///     #[serde(with = "serde_php::helpers::shared")]
+    ///     author: Rc<String>,
+    ///     #[serde(with = "serde_php::helpers::shared")]
+    ///     editor: Rc<String>,
+    /// }
+    ///
+    /// let name = Rc::new("bob".to_owned());
+    /// let page = Page { author: name.clone(), editor: name };
+    ///
+    /// let builder = PhpSerializerBuilder::new().backreferences(true);
+    /// assert_eq!(
+    ///     builder.to_vec(&page).unwrap(),
+    ///     &br#"a:2:{s:6:"author";s:3:"bob";s:6:"editor";R:2;}"#[..]
+    /// );
+    /// ```
+    pub fn backreferences(mut self, enable: bool) -> Self {
+        self.backreferences = enable;
+        self
+    }
+
+    /// Fail on floats instead of writing them.
+    ///
+    /// Enforces that output meant for integer-only data, e.g. monetary
+    /// amounts kept as integer cents, holds no floats. Disabled by default.
+    ///
+    /// ```rust
+    /// use serde_php::{ErrorKind, PhpSerializerBuilder};
+    ///
+    /// let builder = PhpSerializerBuilder::new().reject_floats(true);
+    /// assert!(builder.to_vec(&(1, 2)).is_ok());
+    /// let err = builder.to_vec(&(1, 2.5)).unwrap_err();
+    /// assert!(matches!(err.kind(), ErrorKind::FloatRejected));
+    /// ```
+    pub fn reject_floats(mut self, enable: bool) -> Self {
+        self.reject_floats = enable;
+        self
+    }
+
+    /// Write the format understood by the given version of PHP.
+    ///
+    /// Older versions write floats with 17 significant digits, while PHP 8.1
+    /// writes unit variants as enum cases named after the Rust enum, which
+    /// PHP reads back into a matching `enum` only:
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_php::{FormatVersion, PhpSerializerBuilder};
+    ///
+    /// #[derive(Serialize)]
+    /// enum Suit {
+    ///     Hearts,
+    /// }
+    ///
+    /// let php81 = PhpSerializerBuilder::new().format_version(FormatVersion::Php81);
+    /// assert_eq!(php81.to_vec(&Suit::Hearts).unwrap(), br#"E:11:"Suit:Hearts";"#);
+    ///
+    /// let php5 = PhpSerializerBuilder::new().format_version(FormatVersion::Php5);
+    /// assert_eq!(php5.to_vec(&0.1).unwrap(), b"d:0.10000000000000001;");
+    /// ```
+    ///
+    /// Defaults to [`FormatVersion::Php74`].
+    pub fn format_version(mut self, version: FormatVersion) -> Self {
+        self.format_version = version;
+        self
+    }
+
+    /// Apply `encoders` to the values written, see [`Encoders`].
+    ///
+    /// ```rust
+    /// use serde_php::{Encoders, PhpSerializerBuilder, PhpValue};
+    ///
+    /// let halve = Encoders::new().register::<f64, _>(|value| {
+    ///     PhpValue::from(value.as_f64().unwrap_or_default() / 2.0)
+    /// });
+    /// let builder = PhpSerializerBuilder::new().encoders(halve).reject_floats(true);
+    /// assert!(builder.to_vec(&1.0).is_err());
+    /// ```
+    pub fn encoders(mut self, encoders: Encoders) -> Self {
+        self.encoders = encoders;
+        self
+    }
+
+    /// Write out serialization of value, like [`to_writer`].
+    pub fn to_writer<W, T>(&self, writer: W, value: &T) -> Result<()>
+    where
+        W: Write,
+        T: Serialize + ?Sized,
+    {
+        let mut ser = Serializer::new(writer);
+        ser.chars_as_strings = self.chars_as_strings;
+        ser.unit_structs = self.unit_structs;
+        ser.reject_floats = self.reject_floats;
+        ser.format_version = self.format_version;
+        if self.backreferences {
+            ser.shared = Some(Box::default());
+        }
+        if !self.encoders.is_empty() {
+            ser.encoders = Some(self.encoders.clone());
+        }
+        ser.serialize_encoded(value)
+    }
+
+    /// Write serialization of value into byte vector, like [`to_vec`].
+    pub fn to_vec<T>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut buf = Vec::new();
+        self.to_writer(&mut buf, value)?;
+        Ok(buf)
+    }
+
+    /// Write an indented rendering of the serialization of value, like
+    /// [`to_vec_pretty`].
+    pub fn to_vec_pretty<T>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        pretty::indent(&self.to_vec(value)?)
+    }
+}
+
+/// Representation of unit structs, see
+/// [`PhpSerializerBuilder::unit_structs`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnitStructMode {
+    /// Write `N;`, the same as `()`. This is the default.
+    #[default]
+    Null,
+    /// Write an empty array, `a:0:{}`, for PHP code expecting an array.
+    EmptyArray,
+}
+
+/// Write a float the same way PHP's `serialize` does.
+///
+/// Uses the shortest representation that round-trips, which matches PHP's
+/// default `serialize_precision` of `-1`. Like PHP, exponential notation (e.g.
+/// `1.0E+25`) is used for very large and very small magnitudes and special
+/// values are written as `INF`, `-INF` and `NAN`.
+pub(crate) fn write_float<W: Write>(out: &mut W, v: f64) -> io::Result<()> {
+    if v.is_nan() {
+        return out.write_all(b"NAN");
+    }
+    if v.is_infinite() {
+        return out.write_all(if v > 0.0 { b"INF" } else { b"-INF" });
+    }
+
+    let sci = format!("{:e}", v);
+    let (mantissa, exp) = sci.split_once('e').expect("exponent is always present");
+    let exp: i32 = exp.parse().expect("exponent is always an integer");
+
+    // Same cut-off points as `php_gcvt` with 17 digits of precision.
+    if !(-4..17).contains(&exp) {
+        let frac = if mantissa.contains('.') { "" } else { ".0" };
+        let sign = if exp < 0 { '-' } else { '+' };
+        write!(out, "{}{}E{}{}", mantissa, frac, sign, exp.abs())
+    } else {
+        write!(out, "{}", v)
+    }
+}
+
+/// Write a float the same way PHP 5 and 7.0 do.
+///
+/// These versions use a `serialize_precision` of 17 significant digits,
+/// which round-trips but shows the binary approximation of most decimals,
+/// e.g. `0.10000000000000001` for `0.1`. Trailing zeros are dropped.
+pub(crate) fn write_float_precise<W: Write>(out: &mut W, v: f64) -> io::Result<()> {
+    if !v.is_finite() {
+        return write_float(out, v);
+    }
+
+    let sci = format!("{:.16e}", v);
+    let (mantissa, exp) = sci.split_once('e').expect("exponent is always present");
+    let exp: i32 = exp.parse().expect("exponent is always an integer");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+    let digits = digits.trim_end_matches('0');
+    let digits = if digits.is_empty() { "0" } else { digits };
+
+    if !(-4..17).contains(&exp) {
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() { "0" } else { rest };
+        let exp_sign = if exp < 0 { '-' } else { '+' };
+        write!(out, "{}{}.{}E{}{}", sign, first, rest, exp_sign, exp.abs())
+    } else if exp < 0 {
+        let zeros = "0".repeat((-exp - 1) as usize);
+        write!(out, "{}0.{}{}", sign, zeros, digits)
+    } else {
+        let int_len = exp as usize + 1;
+        if digits.len() <= int_len {
+            let zeros = "0".repeat(int_len - digits.len());
+            write!(out, "{}{}{}", sign, digits, zeros)
+        } else {
+            let (int, frac) = digits.split_at(int_len);
+            write!(out, "{}{}.{}", sign, int, frac)
+        }
+    }
+}
+
+/// Widen an `f32` to the `f64` with the same shortest representation.
+///
+/// Plain conversion keeps the binary value, which is written as e.g.
+/// `0.30000001192092896` instead of `0.3`. Any decimal number with up to nine
+/// significant digits survives the conversion to `f64` and back, so the
+/// result still reads back as the original `f32`.
+pub(crate) fn widen_f32(v: f32) -> f64 {
+    v.to_string().parse().unwrap_or_else(|_| f64::from(v))
+}
+
 /// Central serializer structure.
 #[derive(Debug)]
 struct Serializer<W> {
     output: W,
+    /// Number of array elements serialized so far.
+    values: u64,
+    /// Custom encoders, if any are registered.
+    encoders: Option<Encoders>,
+    /// Whether chars are written as strings instead of code points.
+    chars_as_strings: bool,
+    /// How unit structs are written.
+    unit_structs: UnitStructMode,
+    /// Whether the next bytes are a complete record, written as is.
+    verbatim: bool,
+    /// Whether floats are rejected.
+    reject_floats: bool,
+    /// Version of PHP the output is meant for.
+    format_version: FormatVersion,
+    /// Shared values written so far, only tracked if backreferences are
+    /// enabled.
+    shared: Option<Box<Shared>>,
+}
+
+/// Values written so far, for writing references to them.
+#[derive(Debug)]
+struct Shared {
+    /// Number of values begun so far, including the current one.
+    ///
+    /// PHP numbers all values except array keys and `R:` references, the
+    /// top-level value being number 1.
+    numbered: u64,
+    /// Numbers of the shared values written so far, by address.
+    numbers: HashMap<usize, u64>,
+    /// Numbers of values written as objects.
+    objects: HashSet<u64>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Shared {
+            numbered: 1,
+            numbers: HashMap::new(),
+            objects: HashSet::new(),
+        }
+    }
 }
 
 impl<W> Serializer<W> {
     /// Create new serializer on writer.
     #[inline]
     fn new(output: W) -> Self {
-        Serializer { output }
+        Serializer {
+            output,
+            values: 0,
+            encoders: None,
+            chars_as_strings: false,
+            unit_structs: UnitStructMode::default(),
+            verbatim: false,
+            reject_floats: false,
+            format_version: FormatVersion::default(),
+            shared: None,
+        }
     }
 }
 
-/// Not implemented helper struct.
-///
-/// "Implements" various traits required for serialization that are not supported. Always fails
-/// with an error message if called.
-#[derive(Debug)]
-struct NotImplemented;
+impl<W> Serializer<W>
+where
+    W: Write,
+{
+    /// Serialize a value, using the encoder registered for its type if there
+    /// is one.
+    #[inline]
+    fn serialize_encoded<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let encode = match self.encoders.as_ref().and_then(Encoders::get::<T>) {
+            Some(encode) => encode,
+            None => return value.serialize(self),
+        };
+
+        // Encoders work on the value as serialized by its own implementation,
+        // with encoders still applying to the values inside.
+        let mut inner = Serializer::new(Vec::new());
+        inner.encoders = self.encoders.clone();
+        inner.chars_as_strings = self.chars_as_strings;
+        inner.unit_structs = self.unit_structs;
+        inner.reject_floats = self.reject_floats;
+        inner.format_version = self.format_version;
+        value.serialize(&mut inner)?;
+        self.values += inner.values;
+
+        let encoded = encode(PhpValue::parse_objects(&inner.output)?);
+        if let Some(shared) = &mut self.shared {
+            if encoded.class_name().is_some() {
+                shared.objects.insert(shared.numbered);
+            }
+            shared.numbered += count_values(&encoded) - 1;
+        }
+        encoded.write_to(&mut self.output)
+    }
+
+    /// Write the start of an externally tagged variant: an array with a
+    /// single entry, up to the key naming the variant. The payload follows.
+    fn begin_variant(&mut self, variant: &str) -> Result<()> {
+        write!(self.output, "a:1:{{s:{}:\"{}\";", variant.len(), variant)
+            .map_err(ErrorKind::WriteSerialized)?;
+        self.begin_value();
+        self.values += 1;
+        Ok(())
+    }
+
+    /// Account for the start of an array element, if values are numbered.
+    #[inline]
+    fn begin_value(&mut self) {
+        if let Some(shared) = &mut self.shared {
+            shared.numbered += 1;
+        }
+    }
+
+    /// Record that the current value is written as an object.
+    fn mark_object(&mut self) {
+        if let Some(shared) = &mut self.shared {
+            shared.objects.insert(shared.numbered);
+        }
+    }
+
+    /// Write a value that may occur more than once, referring back to its
+    /// first occurrence if it has been written before.
+    fn serialize_shared<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let shared = match &mut self.shared {
+            Some(shared) => shared,
+            None => return self.serialize_encoded(value),
+        };
+
+        let address = value as *const T as *const () as usize;
+        let number = match shared.numbers.get(&address) {
+            Some(&number) => number,
+            None => {
+                shared.numbers.insert(address, shared.numbered);
+                return self.serialize_encoded(value);
+            }
+        };
+
+        // Only objects can be referred to by `r:`, which is numbered itself.
+        let sym = if shared.objects.contains(&number) {
+            'r'
+        } else {
+            shared.numbered -= 1;
+            'R'
+        };
+        write!(self.output, "{}:{};", sym, number)
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
+    }
+}
+
+/// Number of values making up `value`, as numbered by PHP.
+fn count_values(value: &PhpValue) -> u64 {
+    match value {
+        PhpValue::Array(entries) => {
+            // Objects hold their class name in an extra entry.
+            let properties = &entries[usize::from(value.class_name().is_some())..];
+            1 + properties
+                .iter()
+                .map(|(_, value)| count_values(value))
+                .sum::<u64>()
+        }
+        _ => 1,
+    }
+}
 
 impl<'a, W> ser::Serializer for &'a mut Serializer<W>
 where
@@ -56,10 +614,10 @@ where
     type SerializeSeq = NumericArraySerializer<'a, W>;
     type SerializeTuple = NumericArraySerializer<'a, W>;
     type SerializeTupleStruct = NumericArraySerializer<'a, W>;
-    type SerializeTupleVariant = NotImplemented;
+    type SerializeTupleVariant = VariantSerializer<NumericArraySerializer<'a, W>>;
     type SerializeMap = Self;
     type SerializeStruct = Self;
-    type SerializeStructVariant = NotImplemented;
+    type SerializeStructVariant = VariantSerializer<Self>;
 
     #[inline]
     fn serialize_bool(self, v: bool) -> Result<()> {
@@ -68,7 +626,7 @@ where
         } else {
             self.output.write_all(b"b:0;")
         }
-        .map_err(Error::WriteSerialized)
+        .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
@@ -90,7 +648,7 @@ where
     fn serialize_i64(self, v: i64) -> Result<()> {
         // We rely on Rust having a "standard" display implementation for
         // `i64` types, which is a reasonable assumption.
-        write!(self.output, "i:{};", v).map_err(Error::WriteSerialized)
+        write!(self.output, "i:{};", v).map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
@@ -110,23 +668,38 @@ where
 
     #[inline]
     fn serialize_u64(self, v: u64) -> Result<()> {
-        write!(self.output, "i:{};", v).map_err(Error::WriteSerialized)
+        write!(self.output, "i:{};", v).map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
     fn serialize_f32(self, v: f32) -> Result<()> {
-        self.serialize_f64(f64::from(v))
+        self.serialize_f64(widen_f32(v))
     }
 
     #[inline]
     fn serialize_f64(self, v: f64) -> Result<()> {
-        // Float representations _should_ match up.
-        // TODO: Verify this prints edges correctly.
-        write!(self.output, "d:{};", v).map_err(Error::WriteSerialized)
+        if self.reject_floats {
+            return Err(ErrorKind::FloatRejected.into());
+        }
+        self.output
+            .write_all(b"d:")
+            .map_err(ErrorKind::WriteSerialized)?;
+        if self.format_version == FormatVersion::Php5 {
+            write_float_precise(&mut self.output, v)
+        } else {
+            write_float(&mut self.output, v)
+        }
+        .map_err(ErrorKind::WriteSerialized)?;
+        self.output
+            .write_all(b";")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
     fn serialize_char(self, v: char) -> Result<()> {
+        if self.chars_as_strings {
+            return self.serialize_str(v.encode_utf8(&mut [0; 4]));
+        }
         self.serialize_u32(u32::from(v))
     }
 
@@ -137,9 +710,23 @@ where
 
     #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        write!(self.output, "s:{}:\"", v.len()).map_err(Error::WriteSerialized)?;
-        self.output.write_all(v).map_err(Error::WriteSerialized)?;
-        write!(self.output, "\";").map_err(Error::WriteSerialized)
+        if mem::take(&mut self.verbatim) {
+            if let Some(shared) = &mut self.shared {
+                if matches!(v.first(), Some(b'O') | Some(b'C') | Some(b'E')) {
+                    shared.objects.insert(shared.numbered);
+                }
+                shared.numbered += refs::count_values(v)? - 1;
+            }
+            return self
+                .output
+                .write_all(v)
+                .map_err(|err| ErrorKind::WriteSerialized(err).into());
+        }
+        write!(self.output, "s:{}:\"", v.len()).map_err(ErrorKind::WriteSerialized)?;
+        self.output
+            .write_all(v)
+            .map_err(ErrorKind::WriteSerialized)?;
+        write!(self.output, "\";").map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
@@ -152,40 +739,59 @@ where
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        self.serialize_encoded(value)
     }
 
     #[inline]
     fn serialize_unit(self) -> Result<()> {
-        self.output.write_all(b"N;").map_err(Error::WriteSerialized)
+        self.output
+            .write_all(b"N;")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of unit structures is not supported.",
-        ))
+        match self.unit_structs {
+            UnitStructMode::Null => self.serialize_unit(),
+            UnitStructMode::EmptyArray => self
+                .output
+                .write_all(b"a:0:{}")
+                .map_err(|err| ErrorKind::WriteSerialized(err).into()),
+        }
     }
 
     #[inline]
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        if self.format_version.has_enums() {
+            // Enum cases are objects, which references can refer to.
+            self.mark_object();
+            let len = name.len() + 1 + variant.len();
+            return write!(self.output, "E:{}:\"{}:{}\";", len, name, variant)
+                .map_err(|err| ErrorKind::WriteSerialized(err).into());
+        }
+        // For C-style enums written as integers instead, look at `serde_repr`.
+        self.serialize_str(variant)
     }
 
     #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        if name == custom::CUSTOM_TOKEN || name == raw::RAW_TOKEN {
+            self.verbatim = true;
+            return value.serialize(self);
+        }
+        if name == SHARED_TOKEN {
+            return self.serialize_shared(value);
+        }
         // We just "unpack" newtypes when deserializing.
-        value.serialize(self)
+        self.serialize_encoded(value)
     }
 
     #[inline]
@@ -193,15 +799,17 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        self.begin_variant(variant)?;
+        self.serialize_encoded(value)?;
+        self.output
+            .write_all(b"}")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 
     #[inline]
@@ -215,10 +823,10 @@ where
         // accident.
         if let Some(n) = len {
             // We can assume sequences are all of the same type.
-            write!(self.output, "a:{}:{{", n).map_err(Error::WriteSerialized)?;
+            write!(self.output, "a:{}:{{", n).map_err(ErrorKind::WriteSerialized)?;
             Ok(NumericArraySerializer::new(self))
         } else {
-            Err(Error::LengthRequired)
+            Err(ErrorKind::LengthRequired.into())
         }
     }
 
@@ -228,11 +836,22 @@ where
     }
 
     #[inline]
+    #[cfg_attr(not(feature = "bigint"), allow(unused_variables))]
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
+        #[cfg(feature = "bigint")]
+        {
+            if name == crate::bigint::GMP_TOKEN {
+                // `GMP` objects carry their properties by position.
+                self.mark_object();
+                write!(self.output, "O:3:\"GMP\":{}:{{", len)
+                    .map_err(ErrorKind::WriteSerialized)?;
+                return Ok(NumericArraySerializer::new(self));
+            }
+        }
         self.serialize_tuple(len)
     }
 
@@ -241,22 +860,23 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        self.begin_variant(variant)?;
+        Ok(VariantSerializer {
+            fields: self.serialize_tuple(len)?,
+        })
     }
 
     #[inline]
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
         if let Some(n) = len {
-            write!(self.output, "a:{}:{{", n).map_err(Error::WriteSerialized)?;
+            write!(self.output, "a:{}:{{", n).map_err(ErrorKind::WriteSerialized)?;
             // No need to count elements, thus no added state.
             Ok(self)
         } else {
-            Err(Error::LengthRequired)
+            Err(ErrorKind::LengthRequired.into())
         }
     }
 
@@ -270,12 +890,13 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        self.begin_variant(variant)?;
+        Ok(VariantSerializer {
+            fields: self.serialize_map(Some(len))?,
+        })
     }
 }
 
@@ -312,7 +933,9 @@ where
     {
         // Output-format is just index directly followed by value.
         self.index.serialize(&mut *self.serializer)?;
-        value.serialize(&mut *self.serializer)?;
+        self.serializer.begin_value();
+        self.serializer.serialize_encoded(value)?;
+        self.serializer.values += 1;
         self.index += 1;
         Ok(())
     }
@@ -321,7 +944,7 @@ where
         self.serializer
             .output
             .write_all(b"}")
-            .map_err(Error::WriteSerialized)
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 }
 
@@ -363,27 +986,41 @@ where
     }
 }
 
-impl ser::SerializeTupleVariant for NotImplemented {
+/// Helper structure for tuple and struct variants, wrapping the serializer
+/// of their fields.
+///
+/// Variants are written in the externally tagged representation, as an
+/// array holding the fields under the name of the variant.
+#[derive(Debug)]
+pub struct VariantSerializer<S> {
+    fields: S,
+}
+
+impl<'a, W> ser::SerializeTupleVariant for VariantSerializer<NumericArraySerializer<'a, W>>
+where
+    W: Write,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        ser::SerializeSeq::serialize_element(&mut self.fields, value)
     }
 
     fn end(self) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        // Closes both the fields and the enclosing array.
+        self.fields
+            .serializer
+            .output
+            .write_all(b"}}")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 }
 
-impl<'a, W> ser::SerializeMap for &'a mut Serializer<W>
+impl<W> ser::SerializeMap for &mut Serializer<W>
 where
     W: Write,
 {
@@ -394,22 +1031,225 @@ where
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        key.serialize(KeySerializer {
+            serializer: &mut **self,
+            type_name: encoders::type_key::<T>(),
+        })
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.begin_value();
+        self.serialize_encoded(value)?;
+        self.values += 1;
+        Ok(())
     }
 
     fn end(self) -> Result<()> {
-        self.output.write_all(b"}").map_err(Error::WriteSerialized)
+        self.output
+            .write_all(b"}")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
+    }
+}
+
+/// Serializer for map keys, which only accepts values that are legal PHP
+/// array keys.
+///
+/// Integers and strings are written as they are, newtypes are unwrapped and
+/// unit variants are written as their name. Everything else fails without
+/// writing the key, naming the offending key type.
+struct KeySerializer<'a, W> {
+    serializer: &'a mut Serializer<W>,
+    /// Name of the key type, for error messages.
+    type_name: &'static str,
+}
+
+impl<'a, W> KeySerializer<'a, W> {
+    /// Error for keys that serialize as `found`.
+    fn unsupported(&self, found: &'static str) -> Error {
+        ErrorKind::UnsupportedMapKey {
+            type_name: self.type_name,
+            found,
+        }
+        .into()
     }
 }
 
-impl<'a, W> ser::SerializeStruct for &'a mut Serializer<W>
+impl<'a, W> ser::Serializer for KeySerializer<'a, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Err(self.unsupported("a boolean"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serializer.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serializer.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serializer.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.serializer.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serializer.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serializer.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serializer.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        // PHP turns larger integers into floats, which are not valid keys.
+        if i64::try_from(v).is_err() {
+            return Err(ErrorKind::OutOfRange {
+                value: v.to_string(),
+                type_name: "i64",
+            }
+            .into());
+        }
+        self.serializer.serialize_u64(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(self.unsupported("a float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(self.unsupported("a float"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serializer.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serializer.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.serializer.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(self.unsupported("null"))
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(self.unsupported("an option"))
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(self.unsupported("null"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(self.unsupported("a unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serializer.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(self.unsupported("a newtype variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(self.unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(self.unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(self.unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(self.unsupported("a tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(self.unsupported("a map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(self.unsupported("a struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(self.unsupported("a struct variant"))
+    }
+}
+
+impl<W> ser::SerializeStruct for &mut Serializer<W>
 where
     W: Write,
 {
@@ -421,38 +1261,47 @@ where
         T: ?Sized + Serialize,
     {
         key.serialize(&mut **self)?;
-        value.serialize(&mut **self)?;
+        self.begin_value();
+        self.serialize_encoded(value)?;
+        self.values += 1;
         Ok(())
     }
 
     fn end(self) -> Result<()> {
-        self.output.write_all(b"}").map_err(Error::WriteSerialized)
+        self.output
+            .write_all(b"}")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 }
 
-impl ser::SerializeStructVariant for NotImplemented {
+impl<W> ser::SerializeStructVariant for VariantSerializer<&mut Serializer<W>>
+where
+    W: Write,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        ser::SerializeStruct::serialize_field(&mut self.fields, key, value)
     }
 
     fn end(self) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        // Closes both the fields and the enclosing array.
+        self.fields
+            .output
+            .write_all(b"}}")
+            .map_err(|err| ErrorKind::WriteSerialized(err).into())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::to_vec;
+    use super::{to_vec, to_vec_append, PhpSerializerBuilder, UnitStructMode};
+    use crate::error::ErrorKind;
+    use crate::version::FormatVersion;
     use serde::Serialize;
     use std::collections::BTreeMap;
 
@@ -496,6 +1345,75 @@ mod tests {
         assert_serializes!(1.9f64, b"d:1.9;");
     }
 
+    #[test]
+    fn serialize_float_php5() {
+        let php5 = PhpSerializerBuilder::new().format_version(FormatVersion::Php5);
+        let cases: &[(f64, &[u8])] = &[
+            (0.1, b"d:0.10000000000000001;"),
+            (-1.9, b"d:-1.8999999999999999;"),
+            (1.5, b"d:1.5;"),
+            (100.0, b"d:100;"),
+            (-0.0, b"d:-0;"),
+            (0.0001, b"d:0.0001;"),
+            (1e22, b"d:1.0E+22;"),
+            (1e25, b"d:1.0000000000000001E+25;"),
+            (1.5e-7, b"d:1.4999999999999999E-7;"),
+            (f64::NEG_INFINITY, b"d:-INF;"),
+        ];
+        for &(v, expected) in cases {
+            assert_eq!(php5.to_vec(&v).unwrap(), expected);
+            assert_eq!(crate::from_bytes::<f64>(expected).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn serialize_f32() {
+        assert_serializes!(0.3f32, b"d:0.3;");
+        assert_serializes!(-1.9f32, b"d:-1.9;");
+        assert_serializes!(16777216f32, b"d:16777216;");
+        assert_serializes!(f32::MAX, b"d:3.4028235E+38;");
+        assert_serializes!(f32::MIN_POSITIVE, b"d:1.1754944E-38;");
+        assert_serializes!(f32::INFINITY, b"d:INF;");
+        assert_serializes!(vec![0.1f32, 2.5], b"a:2:{i:0;d:0.1;i:1;d:2.5;}");
+    }
+
+    #[test]
+    fn serialize_reject_floats() {
+        let builder = PhpSerializerBuilder::new().reject_floats(true);
+        assert_eq!(
+            builder.to_vec(&vec![1, 2]).unwrap(),
+            b"a:2:{i:0;i:1;i:1;i:2;}"
+        );
+        for result in [builder.to_vec(&0.5f64), builder.to_vec(&vec![Some(1.5f32)])] {
+            assert!(matches!(
+                result.unwrap_err().kind(),
+                ErrorKind::FloatRejected
+            ));
+        }
+    }
+
+    #[test]
+    fn serialize_char() {
+        assert_serializes!('a', b"i:97;");
+        assert_serializes!('\u{1F600}', b"i:128512;");
+
+        let builder = PhpSerializerBuilder::new().chars_as_strings(true);
+        let output = builder.to_vec(&('a', '\u{1F600}')).unwrap();
+        assert_eq!(
+            output,
+            "a:2:{i:0;s:1:\"a\";i:1;s:4:\"\u{1F600}\";}".as_bytes()
+        );
+        assert_eq!(
+            crate::from_bytes::<(char, char)>(&output).unwrap(),
+            ('a', '\u{1F600}')
+        );
+
+        // Map keys are written as strings as well.
+        let mut map = BTreeMap::new();
+        map.insert('k', 1);
+        assert_eq!(builder.to_vec(&map).unwrap(), br#"a:1:{s:1:"k";i:1;}"#);
+    }
+
     #[test]
     fn serialize_php_string() {
         assert_serializes!(
@@ -646,6 +1564,269 @@ mod tests {
         assert_serializes!(input, br#"a:2:{s:3:"bar";i:7;s:3:"foo";i:42;}"#);
     }
 
+    #[test]
+    fn serialize_unit_structs() {
+        #[derive(Debug, Serialize)]
+        struct Marker;
+
+        #[derive(Debug, Serialize)]
+        struct Flags {
+            seen: Marker,
+        }
+
+        let flags = Flags { seen: Marker };
+        assert_serializes!(flags, br#"a:1:{s:4:"seen";N;}"#);
+        let builder = PhpSerializerBuilder::new().unit_structs(UnitStructMode::EmptyArray);
+        assert_eq!(
+            builder.to_vec(&flags).unwrap(),
+            br#"a:1:{s:4:"seen";a:0:{}}"#
+        );
+    }
+
+    #[test]
+    fn serialize_unit_variants() {
+        #[derive(Serialize)]
+        enum Status {
+            Open,
+            #[serde(rename = "closed")]
+            Closed,
+        }
+
+        #[derive(Serialize)]
+        struct Ticket {
+            status: Status,
+            history: Vec<Status>,
+        }
+
+        let ticket = Ticket {
+            status: Status::Open,
+            history: vec![Status::Closed],
+        };
+        assert_serializes!(
+            ticket,
+            br#"a:2:{s:6:"status";s:4:"Open";s:7:"history";a:1:{i:0;s:6:"closed";}}"#
+        );
+    }
+
+    #[test]
+    fn serialize_enum_cases() {
+        #[derive(Serialize)]
+        enum Suit {
+            Hearts,
+            Pair(u8),
+        }
+
+        #[derive(Serialize)]
+        struct Hand {
+            #[serde(with = "crate::helpers::shared")]
+            trump: std::rc::Rc<Suit>,
+            #[serde(with = "crate::helpers::shared")]
+            lead: std::rc::Rc<Suit>,
+            pair: Suit,
+        }
+
+        let suit = std::rc::Rc::new(Suit::Hearts);
+        let hand = Hand {
+            trump: suit.clone(),
+            lead: suit,
+            pair: Suit::Pair(2),
+        };
+        let php81 = PhpSerializerBuilder::new()
+            .format_version(FormatVersion::Php81)
+            .backreferences(true);
+        assert_eq!(
+            php81.to_vec(&hand).unwrap(),
+            &br#"a:3:{s:5:"trump";E:11:"Suit:Hearts";s:4:"lead";r:2;s:4:"pair";a:1:{s:4:"Pair";i:2;}}"#[..]
+        );
+        assert_serializes!(Suit::Hearts, br#"s:6:"Hearts";"#);
+    }
+
+    #[test]
+    fn serialize_externally_tagged_variants() {
+        #[derive(Serialize)]
+        enum Shape {
+            Dot,
+            Circle(f64),
+            Line(i32, i32),
+            Rect { w: u32, h: u32 },
+        }
+
+        let shapes = vec![
+            Shape::Dot,
+            Shape::Circle(0.5),
+            Shape::Line(1, 2),
+            Shape::Rect { w: 3, h: 4 },
+        ];
+        assert_serializes!(
+            shapes,
+            br#"a:4:{i:0;s:3:"Dot";i:1;a:1:{s:6:"Circle";d:0.5;}i:2;a:1:{s:4:"Line";a:2:{i:0;i:1;i:1;i:2;}}i:3;a:1:{s:4:"Rect";a:2:{s:1:"w";i:3;s:1:"h";i:4;}}}"#
+        );
+    }
+
+    #[test]
+    fn serialize_tagged_variants() {
+        #[derive(Serialize)]
+        #[serde(tag = "type")]
+        enum Shape {
+            Empty,
+            Circle { radius: u32 },
+        }
+
+        #[derive(Serialize)]
+        #[serde(tag = "t", content = "c")]
+        enum Message {
+            Ping,
+            Move(i64, i64),
+        }
+
+        assert_serializes!(
+            vec![Shape::Empty, Shape::Circle { radius: 3 }],
+            br#"a:2:{i:0;a:1:{s:4:"type";s:5:"Empty";}i:1;a:2:{s:4:"type";s:6:"Circle";s:6:"radius";i:3;}}"#
+        );
+        assert_serializes!(
+            vec![Message::Ping, Message::Move(1, 2)],
+            br#"a:2:{i:0;a:1:{s:1:"t";s:4:"Ping";}i:1;a:2:{s:1:"t";s:4:"Move";s:1:"c";a:2:{i:0;i:1;i:1;i:2;}}}"#
+        );
+    }
+
+    #[test]
+    fn serialize_map_keys() {
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        enum Color {
+            Red,
+            Blue,
+        }
+
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        struct UserId(u32);
+
+        let mut colors = BTreeMap::new();
+        colors.insert(Color::Red, 1);
+        colors.insert(Color::Blue, 2);
+        assert_serializes!(colors, br#"a:2:{s:3:"Red";i:1;s:4:"Blue";i:2;}"#);
+
+        let mut users = BTreeMap::new();
+        users.insert(UserId(7), "bob");
+        assert_serializes!(users, br#"a:1:{i:7;s:3:"bob";}"#);
+
+        let mut pairs = BTreeMap::new();
+        pairs.insert((1, 2), "x");
+        match to_vec(&pairs).unwrap_err().kind() {
+            ErrorKind::UnsupportedMapKey { type_name, found } => {
+                assert_eq!(*type_name, "(i32, i32)");
+                assert_eq!(*found, "a tuple");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let mut flags = BTreeMap::new();
+        flags.insert(true, 1);
+        assert!(to_vec(&flags).is_err());
+
+        let mut large = BTreeMap::new();
+        large.insert(u64::MAX, 1);
+        assert!(matches!(
+            to_vec(&large).unwrap_err().kind(),
+            ErrorKind::OutOfRange { .. }
+        ));
+    }
+
+    #[test]
+    fn append_to_buffer() {
+        struct Failing;
+
+        impl Serialize for Failing {
+            fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("failing"))
+            }
+        }
+
+        let mut buf = Vec::new();
+        to_vec_append(&mut buf, &1).unwrap();
+        to_vec_append(&mut buf, "x").unwrap();
+        assert_eq!(buf, br#"i:1;s:1:"x";"#);
+
+        // Partially written values are removed again.
+        assert!(to_vec_append(&mut buf, &vec![Failing]).is_err());
+        assert_eq!(buf, br#"i:1;s:1:"x";"#);
+    }
+
+    #[test]
+    fn serialize_backreferences() {
+        use crate::PhpCustom;
+        use serde::Deserialize;
+        use std::rc::Rc;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct User {
+            name: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Doc {
+            #[serde(with = "crate::helpers::shared")]
+            owner: Rc<User>,
+            tags: Vec<String>,
+            #[serde(with = "crate::helpers::shared")]
+            editor: Rc<User>,
+            #[serde(with = "crate::helpers::shared")]
+            queue: Rc<PhpCustom>,
+            #[serde(with = "crate::helpers::shared")]
+            queue_copy: Rc<PhpCustom>,
+            #[serde(with = "crate::helpers::shared")]
+            label: Rc<String>,
+            #[serde(with = "crate::helpers::shared")]
+            label_copy: Rc<String>,
+        }
+
+        let user = Rc::new(User {
+            name: "bob".to_owned(),
+        });
+        let queue = Rc::new(PhpCustom::new("SplQueue", ""));
+        let label = Rc::new("x".to_owned());
+        let doc = Doc {
+            owner: user.clone(),
+            tags: vec!["a".to_owned()],
+            editor: user,
+            queue: queue.clone(),
+            queue_copy: queue,
+            label: label.clone(),
+            label_copy: label,
+        };
+
+        // Objects are referred to using `r:`, which takes up a number itself.
+        let output = PhpSerializerBuilder::new()
+            .backreferences(true)
+            .to_vec(&doc)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            concat!(
+                r#"a:7:{s:5:"owner";a:1:{s:4:"name";s:3:"bob";}s:4:"tags";a:1:{i:0;s:1:"a";}"#,
+                r#"s:6:"editor";R:2;s:5:"queue";C:8:"SplQueue":0:{}s:10:"queue_copy";r:6;"#,
+                r#"s:5:"label";s:1:"x";s:10:"label_copy";R:8;}"#
+            )
+        );
+        assert_eq!(crate::from_bytes::<Doc>(&output).unwrap(), doc);
+
+        // Equal values at different addresses are not shared.
+        let copy = Doc {
+            editor: Rc::new(User {
+                name: "bob".to_owned(),
+            }),
+            ..doc
+        };
+        let output = PhpSerializerBuilder::new()
+            .backreferences(true)
+            .to_vec(&copy)
+            .unwrap();
+        assert!(output.windows(4).all(|w| w != b"R:2;"));
+
+        let plain = to_vec(&copy).unwrap();
+        assert!(plain.windows(2).all(|w| w != b"R:" && w != b"r:"));
+        assert_eq!(crate::from_bytes::<Doc>(&plain).unwrap(), copy);
+    }
+
     #[test]
     fn unaffected_by_recursive_type_error() {
         // The following code will not compile, but fail with an infinite type recursion instead,
@@ -0,0 +1,545 @@
+//! PHP session data.
+//!
+//! PHP stores `$_SESSION` using one of several serialize handlers, selected by
+//! the `session.serialize_handler` setting:
+//!
+//! * `php` (the default) writes every session variable as its name, followed
+//!   by `|` and its serialized value, e.g. `user|s:3:"bob";count|i:2;`. This is
+//!   the format of `session_encode()`.
+//! * `php_serialize` writes `serialize($_SESSION)`.
+//!
+//! [`PhpSessionCodec`] abstracts over reading and writing session data, so
+//! that middleware of Rust web frameworks can share sessions with a PHP
+//! application. [`SessionCodec`] implements it for both handlers:
+//!
+//! ```rust
+//! use serde_php::session::{PhpSessionCodec, SessionCodec};
+//!
+//! let codec = SessionCodec::new().allowed_classes(vec!["Cart"]);
+//!
+//! let data = br#"user|s:3:"bob";cart|O:4:"Cart":1:{s:5:"items";i:2;}"#;
+//! let session = codec.decode(data).unwrap();
+//! assert_eq!(session["user"].as_str(), Some("bob"));
+//! assert_eq!(session["cart"]["items"].as_i64(), Some(2));
+//!
+//! let mut session = session;
+//! session["user"] = "alice".into();
+//! assert_eq!(
+//!     codec.encode(&session).unwrap(),
+//!     br#"user|s:5:"alice";cart|O:4:"Cart":1:{s:5:"items";i:2;}"#.to_vec()
+//! );
+//! ```
+//!
+//! Objects of allowed classes are decoded as arrays of their properties,
+//! keeping the class name under [`PhpValue::CLASS_NAME_KEY`], so they are
+//! written back as objects of the same class.
+//!
+//! Sessions written by Symfony can be decoded into their attribute bag, flash
+//! messages and metadata using [`SessionCodec::decode_symfony`].
+
+use crate::de::ReferencePolicy;
+use crate::error::{Error, ErrorKind, Result};
+use crate::from_bytes;
+use crate::lex::Lookahead1;
+use crate::value::{parse_value, ParseOptions, PhpValue};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+
+/// Session key of Symfony's attribute bag.
+const SYMFONY_ATTRIBUTES: &str = "_sf2_attributes";
+/// Session key of Symfony's flash bag.
+const SYMFONY_FLASHES: &str = "_symfony_flashes";
+/// Session key of Symfony's metadata bag.
+const SYMFONY_META: &str = "_sf2_meta";
+
+/// Reading and writing of session data.
+///
+/// Sessions are represented as a [`PhpValue::Array`], mapping the names of
+/// session variables onto their values, like `$_SESSION` does.
+pub trait PhpSessionCodec {
+    /// Decode stored session data.
+    fn decode(&self, data: &[u8]) -> Result<PhpValue>;
+
+    /// Encode a session for storage.
+    fn encode(&self, session: &PhpValue) -> Result<Vec<u8>>;
+}
+
+/// Format of session data, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SessionHandler {
+    /// `session.serialize_handler = php`, the default.
+    #[default]
+    Php,
+    /// `session.serialize_handler = php_serialize`.
+    PhpSerialize,
+}
+
+/// Session data written by Symfony's `NativeSessionStorage`.
+///
+/// Symfony keeps its session bags under fixed keys of `$_SESSION`: attributes
+/// (`$session->get()`/`set()`) under `_sf2_attributes`, flash messages under
+/// `_symfony_flashes` and timestamps under `_sf2_meta`. Decoded using
+/// [`SessionCodec::decode_symfony`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymfonySession<A> {
+    /// Session attributes.
+    pub attributes: A,
+    /// Pending flash messages, by type (e.g. `"notice"`).
+    pub flashes: BTreeMap<String, Vec<PhpValue>>,
+    /// Session metadata, if present.
+    pub meta: Option<SymfonyMetadata>,
+}
+
+/// Contents of Symfony's metadata bag.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SymfonyMetadata {
+    /// Unix timestamp of session creation.
+    pub created: i64,
+    /// Unix timestamp of the last session update.
+    pub updated: i64,
+    /// Cookie lifetime in seconds, `0` for session cookies.
+    pub lifetime: i64,
+}
+
+/// Session codec for PHP's built-in serialize handlers.
+#[derive(Clone, Debug, Default)]
+pub struct SessionCodec {
+    handler: SessionHandler,
+    allowed_classes: Vec<String>,
+    lenient: bool,
+    max_map_entries: Option<usize>,
+    max_key_length: Option<usize>,
+}
+
+impl SessionCodec {
+    /// Create a codec for the default `php` handler, rejecting all objects.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the serialize handler.
+    pub fn handler(mut self, handler: SessionHandler) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// Allow objects of the given classes, decoding them as arrays.
+    ///
+    /// Works like the `allowed_classes` option of PHP's `unserialize`.
+    /// Objects of other classes are rejected with
+    /// [`ErrorKind::ClassNotAllowed`].
+    pub fn allowed_classes<I, S>(mut self, classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_classes = classes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Accept common deviations from the format when decoding.
+    ///
+    /// See [`PhpDeserializerBuilder::tolerant`](crate::PhpDeserializerBuilder::tolerant)
+    /// and [`PhpDeserializerBuilder::tolerant_floats`](crate::PhpDeserializerBuilder::tolerant_floats).
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Limit the number of entries of each array or object.
+    ///
+    /// See [`PhpDeserializerBuilder::max_map_entries`](crate::PhpDeserializerBuilder::max_map_entries).
+    pub fn max_map_entries(mut self, limit: usize) -> Self {
+        self.max_map_entries = Some(limit);
+        self
+    }
+
+    /// Limit the length of array keys and session variable names, in bytes.
+    ///
+    /// See [`PhpDeserializerBuilder::max_key_length`](crate::PhpDeserializerBuilder::max_key_length).
+    pub fn max_key_length(mut self, limit: usize) -> Self {
+        self.max_key_length = Some(limit);
+        self
+    }
+
+    /// Decode a session written by Symfony, deserializing its attributes into `A`.
+    ///
+    /// Works with either serialize handler. Missing bags are treated as empty.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_php::session::{SessionCodec, SessionHandler};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Attributes {
+    ///     _security_main: Option<String>,
+    ///     locale: String,
+    /// }
+    ///
+    /// let data = br#"a:3:{s:15:"_sf2_attributes";a:1:{s:6:"locale";s:2:"de";}s:16:"_symfony_flashes";a:1:{s:6:"notice";a:1:{i:0;s:5:"Saved";}}s:9:"_sf2_meta";a:3:{s:1:"u";i:1700000100;s:1:"c";i:1700000000;s:1:"l";i:0;}}"#;
+    ///
+    /// let codec = SessionCodec::new().handler(SessionHandler::PhpSerialize);
+    /// let session = codec.decode_symfony::<Attributes>(data).unwrap();
+    /// assert_eq!(session.attributes.locale, "de");
+    /// assert_eq!(session.flashes["notice"][0].as_str(), Some("Saved"));
+    /// assert_eq!(session.meta.unwrap().created, 1_700_000_000);
+    /// ```
+    pub fn decode_symfony<A: DeserializeOwned>(&self, data: &[u8]) -> Result<SymfonySession<A>> {
+        let session = self.decode(data)?;
+        let bag = |name: &str| session.get(name).filter(|bag| !bag.is_null());
+
+        let attributes = match bag(SYMFONY_ATTRIBUTES) {
+            Some(attributes) => from_bytes(&attributes.to_vec()),
+            None => from_bytes(b"a:0:{}"),
+        }
+        .map_err(|err| err.with_path_segment(SYMFONY_ATTRIBUTES))?;
+
+        let mut flashes = BTreeMap::new();
+        if let Some(bag) = bag(SYMFONY_FLASHES) {
+            let entries = bag
+                .as_array()
+                .ok_or_else(|| symfony_error(SYMFONY_FLASHES))?;
+            for (kind, messages) in entries {
+                let kind = match kind {
+                    PhpValue::String(kind) => String::from_utf8_lossy(kind).into_owned(),
+                    other => other.to_string(),
+                };
+                let messages = messages
+                    .as_array()
+                    .ok_or_else(|| symfony_error(SYMFONY_FLASHES))?
+                    .iter()
+                    .map(|(_, message)| message.clone())
+                    .collect();
+                flashes.insert(kind, messages);
+            }
+        }
+
+        let meta = match bag(SYMFONY_META) {
+            Some(meta) => {
+                let field = |key| meta.get(key).and_then(PhpValue::as_i64);
+                Some(SymfonyMetadata {
+                    created: field("c").ok_or_else(|| symfony_error(SYMFONY_META))?,
+                    updated: field("u").ok_or_else(|| symfony_error(SYMFONY_META))?,
+                    lifetime: field("l").unwrap_or(0),
+                })
+            }
+            None => None,
+        };
+
+        Ok(SymfonySession {
+            attributes,
+            flashes,
+            meta,
+        })
+    }
+
+    /// Create a lexer configured according to the codec's options.
+    fn lexer<'a>(&self, data: &'a [u8]) -> Lookahead1<&'a [u8]> {
+        let mut lexer = Lookahead1::new(data);
+        lexer.set_input_len(data.len() as u64);
+        lexer.set_tolerant(self.lenient);
+        lexer.set_tolerant_floats(self.lenient);
+        lexer.set_map_limits(self.max_map_entries, self.max_key_length);
+        lexer.set_references(ReferencePolicy::Resolve);
+        lexer
+    }
+
+    fn parse_options(&self) -> ParseOptions<'_> {
+        ParseOptions {
+            allowed_classes: &self.allowed_classes,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Decode data written by the `php` handler.
+    fn decode_php(&self, lexer: &mut Lookahead1<&[u8]>) -> Result<PhpValue> {
+        let options = self.parse_options();
+        let mut variables = Vec::new();
+
+        while lexer.peek()?.is_some() {
+            let start = lexer.position();
+            let mut name = Vec::new();
+            loop {
+                match lexer.read1()? {
+                    b'|' => break,
+                    c => name.push(c),
+                }
+
+                if let Some(limit) = self.max_key_length.filter(|&limit| name.len() > limit) {
+                    let length = name.len();
+                    return Err(
+                        Error::from(ErrorKind::KeyTooLong { length, limit }).with_offset(start)
+                    );
+                }
+            }
+
+            // Variable names are not values, but references are numbered
+            // across all variables.
+            lexer.forget(name.len() + 1);
+            let value = parse_value(lexer, &options)?;
+            variables.push((PhpValue::String(name), value));
+        }
+
+        Ok(PhpValue::Array(variables))
+    }
+
+    /// Decode data written by the `php_serialize` handler.
+    fn decode_php_serialize(&self, lexer: &mut Lookahead1<&[u8]>) -> Result<PhpValue> {
+        let session = parse_value(lexer, &self.parse_options())?;
+        if lexer.peek()?.is_some() {
+            return Err(ErrorKind::TrailingData.into());
+        }
+        if session.as_array().is_none() {
+            return Err(ErrorKind::DeserializationFailed(
+                "session data is not an array".to_owned(),
+            )
+            .into());
+        }
+
+        Ok(session)
+    }
+}
+
+impl PhpSessionCodec for SessionCodec {
+    fn decode(&self, data: &[u8]) -> Result<PhpValue> {
+        // PHP starts out with an empty session for empty data.
+        if data.is_empty() {
+            return Ok(PhpValue::Array(Vec::new()));
+        }
+
+        let mut lexer = self.lexer(data);
+        match self.handler {
+            SessionHandler::Php => self.decode_php(&mut lexer),
+            SessionHandler::PhpSerialize => self.decode_php_serialize(&mut lexer),
+        }
+        .map_err(|err| err.with_offset(lexer.position()))
+    }
+
+    fn encode(&self, session: &PhpValue) -> Result<Vec<u8>> {
+        let variables = session.as_array().ok_or_else(|| {
+            Error::from(ErrorKind::SerializationFailed(
+                "session is not an array".to_owned(),
+            ))
+        })?;
+
+        if self.handler == SessionHandler::PhpSerialize {
+            return Ok(session.to_vec());
+        }
+
+        let mut out = Vec::new();
+        for (name, value) in variables {
+            // Like `session_encode`, names must be strings and cannot contain
+            // the separator.
+            match name {
+                PhpValue::String(name) if !name.contains(&b'|') => {
+                    out.extend_from_slice(name);
+                    out.push(b'|');
+                    value.write_to(&mut out)?;
+                }
+                other => {
+                    let name = match other {
+                        PhpValue::String(name) => String::from_utf8_lossy(name).into_owned(),
+                        other => other.to_string(),
+                    };
+                    return Err(ErrorKind::InvalidSessionKey(name).into());
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+fn symfony_error(bag: &str) -> Error {
+    ErrorKind::DeserializationFailed(format!("malformed Symfony session bag `{}`", bag)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhpSessionCodec, SessionCodec, SessionHandler, SymfonyMetadata};
+    use crate::error::ErrorKind;
+    use crate::value::PhpValue;
+    use std::collections::HashMap;
+
+    #[test]
+    fn php_handler_roundtrip() {
+        let codec = SessionCodec::new();
+        let data = br#"user|s:3:"bob";flags|a:2:{i:0;b:1;i:1;N;}count|i:3;"#;
+
+        let session = codec.decode(data).unwrap();
+        assert_eq!(session["user"].as_str(), Some("bob"));
+        assert_eq!(session["flags"][0], PhpValue::Bool(true));
+        assert_eq!(session["count"].as_i64(), Some(3));
+        assert_eq!(codec.encode(&session).unwrap(), data.to_vec());
+
+        assert_eq!(codec.decode(b"").unwrap(), PhpValue::Array(Vec::new()));
+    }
+
+    #[test]
+    fn php_serialize_handler_roundtrip() {
+        let codec = SessionCodec::new().handler(SessionHandler::PhpSerialize);
+        let data = br#"a:1:{s:4:"user";s:3:"bob";}"#;
+
+        let session = codec.decode(data).unwrap();
+        assert_eq!(session["user"].as_str(), Some("bob"));
+        assert_eq!(codec.encode(&session).unwrap(), data.to_vec());
+
+        assert!(codec.decode(b"i:1;").is_err());
+    }
+
+    #[test]
+    fn class_allowlist() {
+        let data = br#"cart|O:4:"Cart":1:{s:5:"items";i:2;}"#;
+
+        let err = SessionCodec::new().decode(data).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnsupportedPhpType {
+                type_indicator: 'O'
+            }
+        ));
+
+        let err = SessionCodec::new()
+            .allowed_classes(vec!["User"])
+            .decode(data)
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ClassNotAllowed(class) if class == "Cart"));
+
+        let session = SessionCodec::new()
+            .allowed_classes(vec!["User", "Cart"])
+            .decode(data)
+            .unwrap();
+        assert_eq!(session["cart"]["items"].as_i64(), Some(2));
+    }
+
+    #[test]
+    fn resolves_references() {
+        // Values are numbered across variables.
+        let data = br#"user|O:4:"User":1:{s:4:"name";s:3:"bob";}owner|r:1;tags|a:1:{i:0;R:2;}"#;
+        let session = SessionCodec::new()
+            .allowed_classes(vec!["User"])
+            .decode(data)
+            .unwrap();
+        assert_eq!(session["owner"], session["user"]);
+        assert_eq!(session["tags"][0].as_str(), Some("bob"));
+
+        // Offsets account for the variable names.
+        let err = SessionCodec::new()
+            .decode(b"a|i:1;b|a:1:{i:0;R:2;}")
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::ReferenceCycle { offset: 8 }
+        ));
+    }
+
+    #[test]
+    fn lenient_decoding() {
+        let data = b"count|I: 3;";
+
+        assert!(SessionCodec::new().decode(data).is_err());
+        let session = SessionCodec::new().lenient(true).decode(data).unwrap();
+        assert_eq!(session["count"].as_i64(), Some(3));
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let codec = SessionCodec::new().max_map_entries(2).max_key_length(4);
+
+        let session = codec
+            .decode(br#"user|a:2:{s:4:"name";s:3:"bob";i:0;b:1;}"#)
+            .unwrap();
+        assert_eq!(session["user"]["name"].as_str(), Some("bob"));
+
+        let err = codec.decode(b"user|a:3:{i:0;N;i:1;N;i:2;N;}").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::TooManyEntries {
+                declared: 3,
+                limit: 2
+            }
+        ));
+
+        let err = codec.decode(br#"user|a:1:{s:5:"email";N;}"#).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::KeyTooLong {
+                length: 5,
+                limit: 4
+            }
+        ));
+        assert_eq!(err.offset(), Some(12));
+
+        let err = codec.decode(b"username|N;").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::KeyTooLong {
+                length: 5,
+                limit: 4
+            }
+        ));
+        assert_eq!(err.offset(), Some(0));
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let codec = SessionCodec::new();
+
+        let mut session = PhpValue::Array(Vec::new());
+        session["a|b"] = 1.into();
+        let err = codec.encode(&session).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidSessionKey(name) if name == "a|b"));
+
+        session = PhpValue::Array(vec![(PhpValue::from(5), PhpValue::Null)]);
+        assert!(codec.encode(&session).is_err());
+
+        assert!(codec.encode(&PhpValue::Null).is_err());
+        assert!(codec.decode(b"user").is_err());
+    }
+
+    #[test]
+    fn symfony_php_handler() {
+        let data = br#"_sf2_attributes|a:2:{s:7:"user_id";i:42;s:5:"admin";i:0;}_symfony_flashes|a:0:{}_sf2_meta|a:3:{s:1:"u";i:20;s:1:"c";i:10;s:1:"l";i:3600;}"#;
+
+        let session = SessionCodec::new()
+            .decode_symfony::<HashMap<String, i64>>(data)
+            .unwrap();
+        assert_eq!(session.attributes["user_id"], 42);
+        assert!(session.flashes.is_empty());
+        assert_eq!(
+            session.meta,
+            Some(SymfonyMetadata {
+                created: 10,
+                updated: 20,
+                lifetime: 3600
+            })
+        );
+
+        // Sessions without any bags, e.g. new sessions.
+        let session = SessionCodec::new()
+            .decode_symfony::<HashMap<String, i64>>(b"")
+            .unwrap();
+        assert!(session.attributes.is_empty());
+        assert_eq!(session.meta, None);
+    }
+
+    #[test]
+    fn symfony_errors() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Attributes {
+            #[allow(dead_code)]
+            user_id: u8,
+        }
+
+        let data = br#"_sf2_attributes|a:1:{s:7:"user_id";s:3:"bob";}"#;
+        let err = SessionCodec::new()
+            .decode_symfony::<Attributes>(data)
+            .unwrap_err();
+        assert_eq!(err.path(), Some("_sf2_attributes"));
+
+        let data = br#"_sf2_meta|a:1:{s:1:"l";i:0;}"#;
+        assert!(SessionCodec::new()
+            .decode_symfony::<HashMap<String, i64>>(data)
+            .is_err());
+    }
+}
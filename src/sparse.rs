@@ -0,0 +1,123 @@
+//! Numeric arrays with holes.
+
+use crate::de::deserialize_filled_array;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::ops::{Deref, DerefMut};
+
+/// A numeric array that may have holes, keeping every value at its index.
+///
+/// PHP arrays built by assignment to indices, or with entries removed by
+/// `unset`, lack some indices. A `Sparse<T>` holds such an array as a
+/// `Vec<Option<T>>`, with missing indices becoming `None`, where
+/// [`deserialize_unordered_array`](crate::deserialize_unordered_array) would
+/// close the holes. Serializing writes the present entries under their
+/// indices, so the array is written back as it was read:
+///
+/// ```rust
+/// use serde_php::Sparse;
+///
+/// let input = br#"a:2:{i:3;s:5:"three";i:0;s:4:"zero";}"#;
+/// let slots: Sparse<String> = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(*slots, [Some("zero".to_owned()), None, None, Some("three".to_owned())]);
+///
+/// assert_eq!(
+///     serde_php::to_vec(&slots).unwrap(),
+///     &br#"a:2:{i:0;s:4:"zero";i:3;s:5:"three";}"#[..]
+/// );
+/// ```
+///
+/// Entries holding `null` become `None` as well, and are not written back.
+/// As with [`deserialize_filled_array`](crate::deserialize_filled_array), at
+/// most 65536 holes are filled in; more result in an error.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Sparse<T>(pub Vec<Option<T>>);
+
+impl<T> Sparse<T> {
+    /// Consumes the array, returning its entries.
+    pub fn into_inner(self) -> Vec<Option<T>> {
+        self.0
+    }
+}
+
+impl<T> Default for Sparse<T> {
+    fn default() -> Self {
+        Sparse(Vec::new())
+    }
+}
+
+impl<T> From<Vec<Option<T>>> for Sparse<T> {
+    fn from(entries: Vec<Option<T>>) -> Self {
+        Sparse(entries)
+    }
+}
+
+impl<T> Deref for Sparse<T> {
+    type Target = Vec<Option<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Sparse<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> Serialize for Sparse<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let len = self.0.iter().filter(|entry| entry.is_some()).count();
+        let mut map = serializer.serialize_map(Some(len))?;
+        for (idx, value) in self.0.iter().enumerate() {
+            if let Some(value) = value {
+                map.serialize_entry(&idx, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Sparse<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_filled_array(deserializer).map(Sparse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sparse;
+    use crate::{from_bytes, to_vec};
+
+    #[test]
+    fn keeps_positions() {
+        let input = b"a:3:{i:0;i:1;i:1;N;i:4;i:5;}";
+        let sparse: Sparse<u8> = from_bytes(input).unwrap();
+        assert_eq!(sparse.0, [Some(1), None, None, None, Some(5)]);
+        assert_eq!(to_vec(&sparse).unwrap(), &b"a:2:{i:0;i:1;i:4;i:5;}"[..]);
+
+        let empty: Sparse<u8> = from_bytes(b"a:0:{}").unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(to_vec(&Sparse::<u8>::default()).unwrap(), &b"a:0:{}"[..]);
+    }
+
+    #[test]
+    fn limits_holes() {
+        assert!(from_bytes::<Sparse<u8>>(b"a:1:{i:65536;i:1;}").is_ok());
+        assert!(from_bytes::<Sparse<u8>>(b"a:1:{i:65537;i:1;}").is_err());
+        assert!(from_bytes::<Sparse<u8>>(b"a:1:{i:-1;i:1;}").is_err());
+    }
+}
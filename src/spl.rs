@@ -0,0 +1,246 @@
+//! Data structures of PHP's Standard PHP Library (SPL).
+//!
+//! PHP serializes `SplDoublyLinkedList` and its subclasses `SplQueue` and
+//! `SplStack` as objects holding their iterator flags and the list of
+//! elements, e.g. snapshots of job queues kept by PHP workers:
+//!
+//! ```php
+//! $stack = new SplStack();
+//! $stack->push(1);
+//! $stack->push(2);
+//! serialize($stack); // O:8:"SplStack":3:{i:0;i:6;i:1;a:2:{i:0;i:1;i:1;i:2;}i:2;a:0:{}}
+//! ```
+//!
+//! [`SplList`] decodes these, regardless of the class name, so subclasses
+//! defined by applications are supported as well. Only the format of PHP 7.4
+//! and later is supported, earlier versions used `C:` (custom serialization).
+//!
+//! ```rust
+//! use serde_php::spl::SplList;
+//!
+//! let input = br#"O:8:"SplStack":3:{i:0;i:6;i:1;a:2:{i:0;i:1;i:1;i:2;}i:2;a:0:{}}"#;
+//! let stack: SplList<u32> = serde_php::from_bytes(input).unwrap();
+//! assert!(stack.is_lifo());
+//! assert_eq!(stack.items(), &[1, 2]);
+//! ```
+
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Struct name recognized by the deserializer, accepting objects of any class.
+pub(crate) const SPL_LIST_TOKEN: &str = "$serde_php::private::SplList";
+
+/// Field names, in the order of the positional keys PHP uses.
+const FIELDS: &[&str] = &["flags", "items", "members"];
+
+/// Iterator flag for last-in, first-out order (`SplDoublyLinkedList::IT_MODE_LIFO`).
+pub const IT_MODE_LIFO: i64 = 2;
+
+/// Iterator flag for removing elements while iterating
+/// (`SplDoublyLinkedList::IT_MODE_DELETE`).
+pub const IT_MODE_DELETE: i64 = 1;
+
+/// An `SplDoublyLinkedList`, `SplQueue` or `SplStack`.
+///
+/// Elements are kept in list order, i.e. from the bottom to the top of a
+/// stack. Properties of subclasses are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SplList<T> {
+    flags: i64,
+    items: Vec<T>,
+}
+
+impl<T> SplList<T> {
+    /// Iterator flags, see [`IT_MODE_LIFO`] and [`IT_MODE_DELETE`].
+    pub fn flags(&self) -> i64 {
+        self.flags
+    }
+
+    /// Returns whether the list iterates in last-in, first-out order, as
+    /// `SplStack` does.
+    pub fn is_lifo(&self) -> bool {
+        self.flags & IT_MODE_LIFO != 0
+    }
+
+    /// Elements, in list order.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Consumes the list, returning its elements in list order.
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T> From<SplList<T>> for Vec<T> {
+    fn from(list: SplList<T>) -> Self {
+        list.items
+    }
+}
+
+impl<'de, T> Deserialize<'de> for SplList<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(SPL_LIST_TOKEN, FIELDS, SplListVisitor(PhantomData))
+    }
+}
+
+/// Deserialize the elements of an SPL list into a `Vec`, discarding its
+/// flags.
+///
+/// For use with `#[serde(deserialize_with = "...")]`:
+///
+/// ```rust
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Worker {
+///     #[serde(deserialize_with = "serde_php::spl::items")]
+///     queue: Vec<String>,
+/// }
+///
+/// let input = br#"a:1:{s:5:"queue";O:8:"SplQueue":3:{i:0;i:4;i:1;a:1:{i:0;s:3:"job";}i:2;a:0:{}}}"#;
+/// let worker: Worker = serde_php::from_bytes(input).unwrap();
+/// assert_eq!(worker.queue, ["job"]);
+/// ```
+pub fn items<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    SplList::deserialize(deserializer).map(SplList::into_items)
+}
+
+struct SplListVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for SplListVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = SplList<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("an SplDoublyLinkedList")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut flags = None;
+        let mut items = None;
+
+        while let Some(field) = map.next_key::<Field>()? {
+            match field {
+                Field::Flags => flags = Some(map.next_value()?),
+                Field::Items => items = Some(map.next_value()?),
+                Field::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(SplList {
+            flags: flags.ok_or_else(|| de::Error::missing_field("flags"))?,
+            items: items.ok_or_else(|| de::Error::missing_field("items"))?,
+        })
+    }
+}
+
+/// Property of a serialized list, either by position or by name.
+enum Field {
+    Flags,
+    Items,
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = Field;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a property index or name")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Field, E> {
+                Ok(match v {
+                    0 => Field::Flags,
+                    1 => Field::Items,
+                    _ => Field::Other,
+                })
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Field, E> {
+                self.visit_i64(i64::try_from(v).unwrap_or(-1))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+                Ok(match v {
+                    "flags" => Field::Flags,
+                    "items" => Field::Items,
+                    _ => Field::Other,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(FieldVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SplList, IT_MODE_DELETE};
+    use crate::from_bytes;
+    use serde::Deserialize;
+
+    #[test]
+    fn decodes_lists() {
+        // PHP: $l = new SplDoublyLinkedList(); $l->push("a"); $l->push("bc");
+        // $l->setIteratorMode(SplDoublyLinkedList::IT_MODE_DELETE);
+        let input = br#"O:19:"SplDoublyLinkedList":3:{i:0;i:1;i:1;a:2:{i:0;s:1:"a";i:1;s:2:"bc";}i:2;a:0:{}}"#;
+
+        let list: SplList<String> = from_bytes(input).unwrap();
+        assert_eq!(list.flags(), IT_MODE_DELETE);
+        assert!(!list.is_lifo());
+        assert_eq!(Vec::from(list), ["a", "bc"]);
+    }
+
+    #[test]
+    fn decodes_subclasses() {
+        // Subclasses carry their own properties in the third element.
+        let input = br#"a:1:{s:4:"jobs";O:8:"JobStack":3:{i:0;i:6;i:1;a:1:{i:0;i:7;}i:2;a:1:{s:4:"name";s:4:"main";}}}"#;
+
+        #[derive(Deserialize)]
+        struct State {
+            #[serde(deserialize_with = "super::items")]
+            jobs: Vec<u8>,
+        }
+
+        let state: State = from_bytes(input).unwrap();
+        assert_eq!(state.jobs, [7]);
+
+        // Only explicitly requested lists are decoded, other objects still fail.
+        assert!(from_bytes::<std::collections::HashMap<String, Vec<u8>>>(input).is_err());
+    }
+
+    #[test]
+    fn rejects_other_values() {
+        assert!(from_bytes::<SplList<u8>>(b"a:0:{}").is_err());
+        assert!(from_bytes::<SplList<u8>>(br#"O:8:"SplStack":1:{i:0;i:6;}"#).is_err());
+        assert!(from_bytes::<SplList<u8>>(b"i:1;").is_err());
+    }
+}
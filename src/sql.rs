@@ -0,0 +1,122 @@
+//! Preprocessing of serialized values extracted from SQL dumps.
+//!
+//! Serialized values copied out of `mysqldump` output (or escaped using PHP's
+//! `addslashes`) are not valid input for deserialization: quotes, backslashes
+//! and control characters are backslash-escaped, and the value itself is
+//! usually still wrapped in quotes. Since the escaping changes the length of
+//! strings, the length prefixes no longer match up.
+//!
+//! The functions in this module reverse this escaping:
+//!
+//! ```rust
+//! use serde_php::{from_bytes, sql};
+//!
+//! let dumped = br#"'a:1:{s:4:\"name\";s:6:\"O\'Hara\";}'"#;
+//! let raw = sql::unquote(dumped);
+//! assert_eq!(raw, br#"a:1:{s:4:"name";s:6:"O'Hara";}"#.to_vec());
+//!
+//! let data: std::collections::HashMap<String, String> = from_bytes(&raw).unwrap();
+//! assert_eq!(data["name"], "O'Hara");
+//! ```
+
+/// Reverse MySQL/`addslashes` backslash escaping.
+///
+/// Supports the escape sequences documented for MySQL string literals
+/// (`\0`, `\'`, `\"`, `\b`, `\n`, `\r`, `\t`, `\Z` and `\\`). As in MySQL,
+/// `\%` and `\_` are kept as-is and the backslash is dropped for any other
+/// escaped character. A trailing lone backslash is preserved.
+pub fn unescape(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut iter = input.iter().copied();
+
+    while let Some(c) = iter.next() {
+        if c != b'\\' {
+            output.push(c);
+            continue;
+        }
+
+        match iter.next() {
+            Some(b'0') => output.push(b'\0'),
+            Some(b'b') => output.push(0x08),
+            Some(b'n') => output.push(b'\n'),
+            Some(b'r') => output.push(b'\r'),
+            Some(b't') => output.push(b'\t'),
+            Some(b'Z') => output.push(0x1a),
+            Some(c @ b'%') | Some(c @ b'_') => {
+                output.push(b'\\');
+                output.push(c);
+            }
+            Some(c) => output.push(c),
+            None => output.push(b'\\'),
+        }
+    }
+
+    output
+}
+
+/// Strip surrounding SQL quotes and reverse escaping.
+///
+/// If `input` is wrapped in matching single or double quotes, these are
+/// removed and doubled quote characters inside (`''`) are collapsed into a
+/// single one. The result is then passed through [`unescape`]. Input that is
+/// not quoted is only unescaped.
+pub fn unquote(input: &[u8]) -> Vec<u8> {
+    match input {
+        [q @ b'\'', inner @ .., end] | [q @ b'"', inner @ .., end] if q == end => {
+            let quote = *q;
+            let mut collapsed = Vec::with_capacity(inner.len());
+            let mut iter = inner.iter().copied().peekable();
+
+            while let Some(c) = iter.next() {
+                collapsed.push(c);
+
+                if c == b'\\' {
+                    // Escaped characters are handled by `unescape`.
+                    if let Some(next) = iter.next() {
+                        collapsed.push(next);
+                    }
+                } else if c == quote && iter.peek() == Some(&quote) {
+                    iter.next();
+                }
+            }
+
+            unescape(&collapsed)
+        }
+        _ => unescape(input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unescape, unquote};
+
+    #[test]
+    fn unescape_sequences() {
+        assert_eq!(unescape(br#"a\"b\'c\\d"#), br#"a"b'c\d"#.to_vec());
+        assert_eq!(unescape(br#"\0\n\r\t\Z\b"#), b"\0\n\r\t\x1a\x08".to_vec());
+        assert_eq!(unescape(br#"\%\_\x"#), br#"\%\_x"#.to_vec());
+        assert_eq!(unescape(br#"trailing\"#), br#"trailing\"#.to_vec());
+    }
+
+    #[test]
+    fn unescape_fixes_lengths() {
+        let dumped = br#"s:5:\"a\\\"b\";"#;
+        assert_eq!(unescape(dumped), br#"s:5:"a\"b";"#.to_vec());
+    }
+
+    #[test]
+    fn unquote_strips_quotes() {
+        assert_eq!(unquote(br#"'i:1;'"#), b"i:1;".to_vec());
+        assert_eq!(unquote(br#""i:1;""#), b"i:1;".to_vec());
+        assert_eq!(unquote(br#"'s:3:"a''b";'"#), br#"s:3:"a'b";"#.to_vec());
+        assert_eq!(unquote(br#"'s:3:"a\'b";'"#), br#"s:3:"a'b";"#.to_vec());
+        assert_eq!(unquote(br#"'\\'"#), br#"\"#.to_vec());
+    }
+
+    #[test]
+    fn unquote_leaves_unquoted_input() {
+        assert_eq!(unquote(br#"s:1:\"x\";"#), br#"s:1:"x";"#.to_vec());
+        assert_eq!(unquote(b"'"), b"'".to_vec());
+        assert_eq!(unquote(b"'mismatched\""), b"'mismatched\"".to_vec());
+    }
+}
@@ -0,0 +1,255 @@
+//! Byte string type.
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use std::borrow::{Borrow, Cow};
+use std::{fmt, ops, str};
+
+/// A PHP string.
+///
+/// PHP strings are sequences of bytes without any particular encoding. This
+/// type stores them as such, while offering conveniences for the common case
+/// of strings that happen to be UTF-8. Unlike `Vec<u8>`, it is (de)serialized
+/// as a string (`s:`) without requiring `serde_bytes`.
+///
+/// ```rust
+/// use serde_php::PhpString;
+///
+/// let s: PhpString = serde_php::from_bytes(b"s:4:\"caf\xe9\";").unwrap();
+/// assert_eq!(s.as_bytes(), b"caf\xe9");
+/// assert!(s.to_str().is_err());
+/// assert_eq!(s.to_string(), "caf\u{fffd}");
+/// assert_eq!(serde_php::to_vec(&s).unwrap(), b"s:4:\"caf\xe9\";");
+/// ```
+#[derive(Clone, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PhpString(Vec<u8>);
+
+impl PhpString {
+    /// Create an empty string.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the string data.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Consumes the string, returning its data.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Returns the string as `&str`, if it is valid UTF-8.
+    pub fn to_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(&self.0)
+    }
+
+    /// Returns the string as UTF-8, replacing invalid sequences with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+
+    /// Compare with another string, ignoring ASCII case like PHP's `strcasecmp`.
+    pub fn eq_ignore_ascii_case<S: AsRef<[u8]> + ?Sized>(&self, other: &S) -> bool {
+        self.0.eq_ignore_ascii_case(other.as_ref())
+    }
+}
+
+impl ops::Deref for PhpString {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for PhpString {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for PhpString {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for PhpString {
+    fn from(v: Vec<u8>) -> Self {
+        PhpString(v)
+    }
+}
+
+impl From<&[u8]> for PhpString {
+    fn from(v: &[u8]) -> Self {
+        PhpString(v.to_vec())
+    }
+}
+
+impl From<String> for PhpString {
+    fn from(v: String) -> Self {
+        PhpString(v.into_bytes())
+    }
+}
+
+impl From<&str> for PhpString {
+    fn from(v: &str) -> Self {
+        PhpString(v.as_bytes().to_vec())
+    }
+}
+
+impl From<PhpString> for Vec<u8> {
+    fn from(v: PhpString) -> Self {
+        v.0
+    }
+}
+
+impl PartialEq<[u8]> for PhpString {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&[u8]> for PhpString {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<str> for PhpString {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for PhpString {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<PhpString> for str {
+    fn eq(&self, other: &PhpString) -> bool {
+        self.as_bytes() == other.0
+    }
+}
+
+impl PartialEq<PhpString> for &str {
+    fn eq(&self, other: &PhpString) -> bool {
+        self.as_bytes() == other.0
+    }
+}
+
+/// Displays the string as UTF-8, replacing invalid sequences with `U+FFFD`.
+impl fmt::Display for PhpString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+/// Formats the string like a byte string literal, e.g. `b"caf\xe9"`.
+impl fmt::Debug for PhpString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("b\"")?;
+        for &c in &self.0 {
+            fmt::Display::fmt(&c.escape_ascii(), f)?;
+        }
+        f.write_str("\"")
+    }
+}
+
+impl Serialize for PhpString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for PhpString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(PhpStringVisitor)
+    }
+}
+
+/// Visitor accepting anything string-like.
+struct PhpStringVisitor;
+
+impl<'de> Visitor<'de> for PhpStringVisitor {
+    type Value = PhpString;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<PhpString, E> {
+        Ok(PhpString::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<PhpString, E> {
+        Ok(PhpString::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<PhpString, E> {
+        Ok(PhpString::from(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<PhpString, E> {
+        Ok(PhpString(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<PhpString, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(c) = seq.next_element()? {
+            data.push(c);
+        }
+        Ok(PhpString(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhpString;
+    use crate::{from_bytes, to_vec};
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn roundtrip_in_struct() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct User {
+            name: PhpString,
+            tags: Vec<PhpString>,
+        }
+
+        let input = b"a:2:{s:4:\"name\";s:3:\"J\xf6e\";s:4:\"tags\";a:1:{i:0;s:0:\"\";}}";
+        let user: User = from_bytes(input).unwrap();
+
+        assert_eq!(user.name, b"J\xf6e"[..]);
+        assert_eq!(user.tags, vec![PhpString::new()]);
+        assert_eq!(to_vec(&user).unwrap(), input.to_vec());
+    }
+
+    #[test]
+    fn comparison_and_formatting() {
+        let s = PhpString::from("Hello");
+
+        assert_eq!(s, "Hello");
+        assert_eq!("Hello", s);
+        assert_eq!(s, b"Hello"[..]);
+        assert!(s.eq_ignore_ascii_case("hELLO"));
+        assert_eq!(s.to_str(), Ok("Hello"));
+
+        let binary = PhpString::from(b"a\"\xff\n".to_vec());
+        assert_eq!(binary.to_string(), "a\"\u{fffd}\n");
+        assert_eq!(format!("{:?}", binary), r#"b"a\"\xff\n""#);
+    }
+}
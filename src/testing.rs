@@ -0,0 +1,230 @@
+//! Helpers for testing serialization code.
+//!
+//! [`assert_roundtrip`] checks that a value survives serialization and
+//! deserialization unchanged. Unlike a plain `assert_eq!`, failures are
+//! reported as a list of differences between the serialized structures,
+//! which keeps them readable for large, nested values:
+//!
+//! ```text
+//! value changed during round-trip:
+//!   ['users'][3]['email']: 'a@example.com' != NULL
+//!   ['users'][7]: missing on the right
+//! ```
+
+use crate::value::PhpValue;
+use crate::{from_bytes, to_vec};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+
+/// A single difference between two values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    /// Location of the difference, e.g. `['users'][3]`. Empty for the root.
+    pub path: String,
+    /// Value on the left, `None` if missing.
+    pub left: Option<PhpValue>,
+    /// Value on the right, `None` if missing.
+    pub right: Option<PhpValue>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "(root)"
+        } else {
+            &self.path
+        };
+
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => write!(f, "{}: {} != {}", path, left, right),
+            (Some(_), None) => write!(f, "{}: missing on the right", path),
+            (None, Some(_)) => write!(f, "{}: missing on the left", path),
+            (None, None) => write!(f, "{}: key order differs", path),
+        }
+    }
+}
+
+/// Compute the structural differences between two values.
+///
+/// Array entries are matched up by key. If two arrays contain the same keys
+/// in a different order, a difference with neither side set is reported for
+/// the array itself.
+pub fn diff(left: &PhpValue, right: &PhpValue) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_into(&mut differences, String::new(), left, right);
+    differences
+}
+
+fn diff_into(out: &mut Vec<Difference>, path: String, left: &PhpValue, right: &PhpValue) {
+    let (left_entries, right_entries) = match (left, right) {
+        (PhpValue::Array(l), PhpValue::Array(r)) => (l, r),
+        _ => {
+            // NaN is not equal to itself, but should not be reported.
+            if left != right && !(is_nan(left) && is_nan(right)) {
+                out.push(Difference {
+                    path,
+                    left: Some(left.clone()),
+                    right: Some(right.clone()),
+                });
+            }
+            return;
+        }
+    };
+
+    for (key, value) in left_entries {
+        let entry_path = format!("{}[{}]", path, key);
+        match find(right_entries, key) {
+            Some(other) => diff_into(out, entry_path, value, other),
+            None => out.push(Difference {
+                path: entry_path,
+                left: Some(value.clone()),
+                right: None,
+            }),
+        }
+    }
+
+    for (key, value) in right_entries {
+        if find(left_entries, key).is_none() {
+            out.push(Difference {
+                path: format!("{}[{}]", path, key),
+                left: None,
+                right: Some(value.clone()),
+            });
+        }
+    }
+
+    let same_keys = left_entries.len() == right_entries.len()
+        && left_entries
+            .iter()
+            .all(|(key, _)| find(right_entries, key).is_some());
+    let same_order = left_entries
+        .iter()
+        .zip(right_entries)
+        .all(|((l, _), (r, _))| l == r);
+
+    if same_keys && !same_order {
+        out.push(Difference {
+            path,
+            left: None,
+            right: None,
+        });
+    }
+}
+
+/// Find the value stored under `key`.
+fn find<'a>(entries: &'a [(PhpValue, PhpValue)], key: &PhpValue) -> Option<&'a PhpValue> {
+    entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// Returns whether the value is a NaN float.
+fn is_nan(value: &PhpValue) -> bool {
+    value
+        .as_number()
+        .is_some_and(|n| n.is_float() && n.as_f64().is_nan())
+}
+
+/// Assert that a value is unchanged after serializing and deserializing it.
+///
+/// Panics if serialization or deserialization fails, or if the deserialized
+/// value is not equal to the original. In the latter case, the structural
+/// differences between the serialized forms are printed.
+pub fn assert_roundtrip<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + fmt::Debug,
+{
+    let serialized = to_vec(value).expect("serialization failed");
+    let deserialized: T = from_bytes(&serialized).expect("deserialization failed");
+
+    if deserialized == *value {
+        return;
+    }
+
+    let reserialized = to_vec(&deserialized).expect("serialization of deserialized value failed");
+    let differences = diff(
+        &PhpValue::parse(&serialized).expect("serialized value is invalid"),
+        &PhpValue::parse(&reserialized).expect("serialized value is invalid"),
+    );
+
+    if differences.is_empty() {
+        panic!(
+            "value changed during round-trip, but serializes identically:\n  {:?}\n  {:?}",
+            value, deserialized
+        );
+    }
+
+    let lines: Vec<String> = differences
+        .iter()
+        .map(|difference| format!("  {}", difference))
+        .collect();
+    panic!("value changed during round-trip:\n{}", lines.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_roundtrip, diff};
+    use crate::value::PhpValue;
+    use serde::{Deserialize, Serialize};
+
+    fn parse(input: &[u8]) -> PhpValue {
+        PhpValue::parse(input).unwrap()
+    }
+
+    #[test]
+    fn diff_nested() {
+        let left = parse(br#"a:3:{s:1:"a";a:2:{i:0;i:1;i:1;i:2;}s:1:"b";N;s:1:"c";b:1;}"#);
+        let right = parse(br#"a:3:{s:1:"a";a:2:{i:0;i:1;i:1;i:3;}s:1:"c";b:1;s:1:"d";d:0.5;}"#);
+
+        let lines: Vec<String> = diff(&left, &right).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "['a'][1]: 2 != 3",
+                "['b']: missing on the right",
+                "['d']: missing on the left",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_order_and_root() {
+        let left = parse(b"a:2:{i:0;N;i:1;N;}");
+        let right = parse(b"a:2:{i:1;N;i:0;N;}");
+        assert_eq!(
+            diff(&left, &right)[0].to_string(),
+            "(root): key order differs"
+        );
+
+        assert!(diff(&left, &left).is_empty());
+        assert!(diff(&parse(b"d:NAN;"), &parse(b"d:NAN;")).is_empty());
+        assert_eq!(
+            diff(&parse(b"i:1;"), &parse(b"d:1;"))[0].to_string(),
+            "(root): 1 != 1.0"
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Lossy {
+        #[serde(skip_deserializing)]
+        name: String,
+        count: u32,
+    }
+
+    #[test]
+    fn roundtrip_passes() {
+        assert_roundtrip(&vec![1, 2, 3]);
+        assert_roundtrip(&Lossy {
+            name: String::new(),
+            count: 1,
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "['name']: 'bob' != ''")]
+    fn roundtrip_reports_differences() {
+        assert_roundtrip(&Lossy {
+            name: "bob".to_owned(),
+            count: 1,
+        });
+    }
+}
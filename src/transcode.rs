@@ -0,0 +1,173 @@
+//! Re-encoding of serialized data into other formats.
+//!
+//! Values are streamed from the deserializer straight into the serializer of
+//! the target format using `serde_transcode`, without building up a
+//! [`PhpValue`](crate::PhpValue) first. This keeps memory usage low when
+//! converting large payloads, e.g. in ETL jobs.
+//!
+//! ```rust
+//! # #[cfg(feature = "json")] {
+//! use serde_php::transcode::{transcode, PhpToJson};
+//!
+//! let input = br#"a:2:{s:4:"name";s:3:"Bob";s:4:"tags";a:2:{i:0;s:1:"a";i:1;N;}}"#;
+//! let json = transcode::<PhpToJson>(input).unwrap();
+//! assert_eq!(json, br#"{"name":"Bob","tags":["a",null]}"#);
+//! # }
+//! ```
+//!
+//! Strings that are valid UTF-8 are written as strings, all others as byte
+//! strings of the target format (arrays of numbers for JSON). Arrays whose
+//! first key is an integer are written as lists and must have keys `0`, `1`,
+//! ... in order; arrays with string keys are written as maps. `stdClass`
+//! objects are written as maps as well. Arrays that do not fit these rules,
+//! such as lists with holes, require converting to a
+//! [`PhpValue`](crate::PhpValue) first.
+
+use crate::de::{PhpDeserializer, PhpDeserializerBuilder};
+use crate::error::{Error, ErrorKind, Result};
+use serde::Deserializer;
+
+/// Target format for [`transcode`].
+pub trait Transcoder {
+    /// Write the value produced by `deserializer` to `out`.
+    ///
+    /// Errors of both the deserializer and the target serializer are
+    /// reported as the serializer's error type, so they are returned as
+    /// strings.
+    fn transcode<'de, D>(deserializer: D, out: &mut Vec<u8>) -> std::result::Result<(), String>
+    where
+        D: Deserializer<'de>;
+}
+
+/// Transcodes into JSON, see [`transcode`].
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug)]
+pub struct PhpToJson;
+
+#[cfg(feature = "json")]
+impl Transcoder for PhpToJson {
+    fn transcode<'de, D>(deserializer: D, out: &mut Vec<u8>) -> std::result::Result<(), String>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut serializer = serde_json::Serializer::new(out);
+        serde_transcode::transcode(deserializer, &mut serializer).map_err(|err| err.to_string())
+    }
+}
+
+/// Transcodes into MessagePack, see [`transcode`].
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Copy, Debug)]
+pub struct PhpToMsgpack;
+
+#[cfg(feature = "msgpack")]
+impl Transcoder for PhpToMsgpack {
+    fn transcode<'de, D>(deserializer: D, out: &mut Vec<u8>) -> std::result::Result<(), String>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut serializer = rmp_serde::Serializer::new(out);
+        serde_transcode::transcode(deserializer, &mut serializer).map_err(|err| err.to_string())
+    }
+}
+
+/// Transcode a serialized value into the format of `T`.
+///
+/// The input must contain exactly one value.
+pub fn transcode<T: Transcoder>(input: &[u8]) -> Result<Vec<u8>> {
+    transcode_with::<T>(&PhpDeserializerBuilder::new(), input)
+}
+
+/// Transcode a serialized value into the format of `T`, using the options of
+/// `builder`.
+///
+/// Strings are always passed on as text or bytes (see
+/// [`PhpDeserializerBuilder::byte_strings`]) and `stdClass` objects as maps
+/// (see [`PhpDeserializerBuilder::std_class_as_map`]), as other options
+/// cannot be represented faithfully.
+pub fn transcode_with<T: Transcoder>(
+    builder: &PhpDeserializerBuilder,
+    input: &[u8],
+) -> Result<Vec<u8>> {
+    let builder = builder.clone().byte_strings(true).std_class_as_map(true);
+    let mut des = PhpDeserializer::from_slice_unbuffered(input);
+    builder.configure(&mut des);
+
+    let mut out = Vec::new();
+    T::transcode(&mut des, &mut out).map_err(|message| {
+        Error::from(ErrorKind::TranscodeFailed(message)).with_offset(des.position())
+    })?;
+
+    if des.peek()?.is_some() {
+        return Err(Error::from(ErrorKind::TrailingData).with_offset(des.position()));
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{transcode, transcode_with, Transcoder};
+    use crate::de::PhpDeserializerBuilder;
+    use crate::error::ErrorKind;
+
+    /// Transcodes back into PHP's format, as a check independent of other
+    /// formats.
+    struct Php;
+
+    impl Transcoder for Php {
+        fn transcode<'de, D>(deserializer: D, out: &mut Vec<u8>) -> Result<(), String>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            crate::to_vec_append(out, &serde_transcode::Transcoder::new(deserializer))
+                .map_err(|err| err.to_string())
+        }
+    }
+
+    #[test]
+    fn roundtrips_through_php() {
+        let input = br#"a:3:{s:1:"a";a:2:{i:0;b:1;i:1;d:0.5;}s:1:"b";N;s:1:"c";i:-3;}"#;
+        assert_eq!(transcode::<Php>(input).unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let err = transcode::<Php>(b"a:2:{i:0;N;i:2;N;}").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TranscodeFailed(_)));
+
+        let err = transcode::<Php>(b"i:1;i:2;").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TrailingData));
+        assert_eq!(err.offset(), Some(4));
+
+        let builder = PhpDeserializerBuilder::new().max_depth(1);
+        assert!(transcode_with::<Php>(&builder, b"a:1:{i:0;a:0:{}}").is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn transcodes_to_json() {
+        use super::PhpToJson;
+
+        let input = b"a:4:{s:4:\"text\";s:2:\"hi\";s:3:\"bin\";s:2:\"\xff\x00\";s:3:\"obj\";O:8:\"stdClass\":1:{s:1:\"x\";d:1.5;}i:7;a:0:{}}";
+        let json: serde_json::Value =
+            serde_json::from_slice(&transcode::<PhpToJson>(input).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"text": "hi", "bin": [255, 0], "obj": {"x": 1.5}, "7": []})
+        );
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn transcodes_to_msgpack() {
+        use super::PhpToMsgpack;
+
+        let input = br#"a:2:{s:1:"a";a:1:{i:0;i:300;}s:1:"b";s:1:"x";}"#;
+        let msgpack = transcode::<PhpToMsgpack>(input).unwrap();
+        assert_eq!(
+            msgpack,
+            [0x82, 0xa1, b'a', 0x91, 0xcd, 0x01, 0x2c, 0xa1, b'b', 0xa1, b'x']
+        );
+    }
+}
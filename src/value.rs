@@ -0,0 +1,1348 @@
+//! Generic representation of PHP values.
+//!
+//! [`PhpValue`] can hold any value supported by this crate, without knowing its
+//! structure up front. This is useful for inspecting or converting payloads of
+//! unknown shape.
+
+use crate::de::ReferencePolicy;
+use crate::error::{Error, ErrorKind, Result};
+use crate::input::Input;
+use crate::lex::Lookahead1;
+use crate::number::Number;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::{fmt, ops};
+
+/// Handling of string data when parsing a [`PhpValue`].
+///
+/// PHP strings are byte strings without any particular encoding. Strings are
+/// always stored as bytes, the UTF-8 modes guarantee that these are valid
+/// UTF-8, making [`PhpValue::as_str`] infallible for all strings.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum StringMode {
+    /// Keep string data as-is. This is the default.
+    #[default]
+    Bytes,
+    /// Replace invalid UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// Note that this changes the length of affected strings, so the value no
+    /// longer serializes to the original input.
+    Utf8Lossy,
+    /// Fail on strings that are not valid UTF-8.
+    Utf8Strict,
+}
+
+/// A PHP value of arbitrary type.
+///
+/// Arrays keep their entries in the order they appeared in, along with the
+/// original keys. Keys are always either integers (`PhpValue::Number`) or
+/// `PhpValue::String`.
+///
+/// Parsing and writing a value is lossless: for any input produced by PHP's
+/// `serialize`, [`to_vec`](PhpValue::to_vec) reproduces the exact original
+/// bytes. This includes key order, integer versus string keys and the text of
+/// floats, which is written the way PHP does (e.g. `d:0.1;`, `d:1.0E+25;` or
+/// `d:INF;`).
+///
+/// Objects, where supported, are represented the way PHP represents objects
+/// of unknown classes: as an array of their properties, preceded by an entry
+/// with the key [`CLASS_NAME_KEY`](PhpValue::CLASS_NAME_KEY) holding the
+/// class name. Such arrays are written as objects of that class again.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PhpValue {
+    /// `null`
+    Null,
+    /// boolean
+    Bool(bool),
+    /// integer or float
+    Number(Number),
+    /// string (PHP strings are bytestrings)
+    String(Vec<u8>),
+    /// array (both numeric and associative)
+    Array(Vec<(PhpValue, PhpValue)>),
+}
+
+impl PhpValue {
+    /// Key of the entry holding the class name of an object, as used by PHP
+    /// for `__PHP_Incomplete_Class`.
+    pub const CLASS_NAME_KEY: &'static str = "__PHP_Incomplete_Class_Name";
+
+    /// String replacing redacted values, see [`redact_keys`](PhpValue::redact_keys).
+    pub const REDACTED: &'static str = "[REDACTED]";
+
+    /// Create an object of class `class` with the given properties.
+    ///
+    /// ```rust
+    /// use serde_php::PhpValue;
+    ///
+    /// let user = PhpValue::object("User", vec![("name".into(), "bob".into())]);
+    /// assert_eq!(user.class_name(), Some(&b"User"[..]));
+    /// assert_eq!(user.to_vec(), br#"O:4:"User":1:{s:4:"name";s:3:"bob";}"#.to_vec());
+    /// ```
+    pub fn object(class: impl Into<Vec<u8>>, properties: Vec<(PhpValue, PhpValue)>) -> PhpValue {
+        let mut entries = Vec::with_capacity(properties.len() + 1);
+        entries.push((
+            PhpValue::from(PhpValue::CLASS_NAME_KEY),
+            PhpValue::String(class.into()),
+        ));
+        entries.extend(properties);
+        PhpValue::Array(entries)
+    }
+
+    /// Returns the class name, if the value represents an object.
+    ///
+    /// That is the case for arrays whose first entry has the key
+    /// [`CLASS_NAME_KEY`](PhpValue::CLASS_NAME_KEY) and a string value.
+    pub fn class_name(&self) -> Option<&[u8]> {
+        match self.as_array()?.first()? {
+            (PhpValue::String(key), PhpValue::String(class))
+                if key == PhpValue::CLASS_NAME_KEY.as_bytes() =>
+            {
+                Some(class)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse a value from its serialized representation.
+    ///
+    /// Unlike `from_bytes`, the input must contain exactly one value; trailing
+    /// data is reported as an error.
+    pub fn parse(input: &[u8]) -> Result<PhpValue> {
+        PhpValue::parse_with(input, StringMode::Bytes)
+    }
+
+    /// Parse a value, handling string data according to `mode`.
+    ///
+    /// Array keys are subject to `mode` as well.
+    ///
+    /// ```rust
+    /// use serde_php::{PhpValue, StringMode};
+    ///
+    /// let input = b"s:3:\"a\xffb\";";
+    /// let value = PhpValue::parse_with(input, StringMode::Utf8Lossy).unwrap();
+    /// assert_eq!(value.as_str(), Some("a\u{fffd}b"));
+    /// assert!(PhpValue::parse_with(input, StringMode::Utf8Strict).is_err());
+    /// ```
+    pub fn parse_with(input: &[u8], mode: StringMode) -> Result<PhpValue> {
+        let options = ParseOptions {
+            mode,
+            ..ParseOptions::default()
+        };
+        parse_complete(input, &options)
+    }
+
+    /// Parse a value like [`parse`](PhpValue::parse), rejecting nesting
+    /// deeper than allowed by [`Profile::Hardened`](crate::Profile::Hardened).
+    ///
+    /// Use this for untrusted input, which could otherwise nest deep enough
+    /// to overflow the stack.
+    ///
+    /// ```rust
+    /// use serde_php::{ErrorKind, PhpValue};
+    ///
+    /// let deep = format!("{}N;{}", "a:1:{i:0;".repeat(129), "}".repeat(129));
+    /// let err = PhpValue::parse_bounded(deep.as_bytes()).unwrap_err();
+    /// assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(128)));
+    /// ```
+    pub fn parse_bounded(input: &[u8]) -> Result<PhpValue> {
+        let options = ParseOptions {
+            max_depth: Some(crate::de::HARDENED_MAX_DEPTH),
+            ..ParseOptions::default()
+        };
+        parse_complete(input, &options)
+    }
+
+    /// Parse a value, keeping objects of any class.
+    ///
+    /// Objects are represented as described [above](PhpValue), keeping their
+    /// class name and properties in order. Names of private and protected
+    /// properties keep their NUL-delimited prefixes, so payloads holding
+    /// objects of classes unknown to Rust can be passed through unchanged:
+    ///
+    /// ```rust
+    /// # use serde_php::PhpValue;
+    /// let input = b"O:4:\"Cart\":2:{s:11:\"\0Cart\0items\";a:0:{}s:8:\"\0*\0total\";d:9.5;}";
+    /// assert!(PhpValue::parse(input).is_err());
+    ///
+    /// let cart = PhpValue::parse_objects(input).unwrap();
+    /// assert_eq!(cart.class_name(), Some(&b"Cart"[..]));
+    /// assert_eq!(cart.get("\0*\0total").and_then(PhpValue::as_f64), Some(9.5));
+    /// assert_eq!(cart.to_vec(), &input[..]);
+    /// ```
+    ///
+    /// An array whose first entry has the key
+    /// [`CLASS_NAME_KEY`](PhpValue::CLASS_NAME_KEY) and a string value is
+    /// indistinguishable from an object and written as one. PHP never writes
+    /// such arrays itself. Objects of classes implementing `Serializable`
+    /// (`C:` records) are not supported, see [`PhpRawValue`](crate::PhpRawValue)
+    /// for passing them through.
+    pub fn parse_objects(input: &[u8]) -> Result<PhpValue> {
+        let options = ParseOptions {
+            any_class: true,
+            ..ParseOptions::default()
+        };
+        parse_complete(input, &options)
+    }
+
+    /// Write serialized representation of value.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        write_value(&mut writer, self).map_err(|err| ErrorKind::WriteSerialized(err).into())
+    }
+
+    /// Serialize value into byte vector.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_value(&mut buf, self).expect("writing to a `Vec` cannot fail");
+        buf
+    }
+
+    /// Look up an array entry by string key.
+    ///
+    /// As in PHP, keys that are canonical decimal integers refer to integer
+    /// keys, i.e. `get("3")` is the same as `get_index(3)`. Returns `None` if
+    /// the value is not an array or the key does not exist.
+    pub fn get(&self, key: &str) -> Option<&PhpValue> {
+        self.find(&array_key(key.as_bytes().to_vec()))
+    }
+
+    /// Mutable version of [`get`](PhpValue::get).
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut PhpValue> {
+        self.find_mut(&array_key(key.as_bytes().to_vec()))
+    }
+
+    /// Look up an array entry by integer key.
+    ///
+    /// Note that this is the key, not the position of the entry.
+    pub fn get_index(&self, index: i64) -> Option<&PhpValue> {
+        self.find(&PhpValue::from(index))
+    }
+
+    /// Mutable version of [`get_index`](PhpValue::get_index).
+    pub fn get_index_mut(&mut self, index: i64) -> Option<&mut PhpValue> {
+        self.find_mut(&PhpValue::from(index))
+    }
+
+    /// Returns whether the value is `null`.
+    pub fn is_null(&self) -> bool {
+        *self == PhpValue::Null
+    }
+
+    /// Returns the boolean, if the value is one.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            PhpValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer, if the value is an integer that fits into `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            PhpValue::Number(ref n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float, if it is a float or an integer.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().map(Number::as_f64)
+    }
+
+    /// Returns the number, if the value is one.
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            PhpValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw string data, if the value is a string.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            PhpValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the string, if the value is a string that is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    /// Returns the array entries, if the value is an array.
+    pub fn as_array(&self) -> Option<&Vec<(PhpValue, PhpValue)>> {
+        match self {
+            PhpValue::Array(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Returns the mutable array entries, if the value is an array.
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<(PhpValue, PhpValue)>> {
+        match self {
+            PhpValue::Array(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Find the value stored under `key`.
+    fn find(&self, key: &PhpValue) -> Option<&PhpValue> {
+        self.as_array()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Find the value stored under `key`, mutably.
+    fn find_mut(&mut self, key: &PhpValue) -> Option<&mut PhpValue> {
+        self.as_array_mut()?
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Find the value stored under `key`, inserting `null` if it is missing.
+    ///
+    /// `null` is turned into an empty array first, like PHP does. Panics if the
+    /// value is neither `null` nor an array.
+    fn find_or_insert(&mut self, key: PhpValue) -> &mut PhpValue {
+        if self.is_null() {
+            *self = PhpValue::Array(Vec::new());
+        }
+
+        let entries = match self {
+            PhpValue::Array(entries) => entries,
+            other => panic!("cannot index into non-array value {:?}", other),
+        };
+
+        let pos = match entries.iter().position(|(k, _)| *k == key) {
+            Some(pos) => pos,
+            None => {
+                entries.push((key, PhpValue::Null));
+                entries.len() - 1
+            }
+        };
+
+        &mut entries[pos].1
+    }
+
+    /// Format the value with bounded size, e.g. for logging.
+    ///
+    /// Values decoded from untrusted or simply large payloads may be huge,
+    /// and might hold data that does not belong in logs. The result formats
+    /// like [`Display`](#impl-Display-for-PhpValue), but cuts off long
+    /// strings, deeply nested arrays and arrays beyond a total number of
+    /// entries, noting how much was left out:
+    ///
+    /// ```rust
+    /// # use serde_php::PhpValue;
+    /// let value = PhpValue::parse(
+    ///     br#"a:3:{s:4:"user";a:2:{s:4:"name";s:3:"bob";s:2:"id";i:7;}s:3:"key";s:10:"0123456789";i:0;i:1;}"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let short = value.truncated().max_depth(1).max_entries(2).max_string_len(4);
+    /// assert_eq!(
+    ///     short.to_string(),
+    ///     "['user' => [...] (2 entries), 'key' => '0123'... (10 bytes), ... (1 more)]"
+    /// );
+    /// ```
+    pub fn truncated(&self) -> Truncated<'_> {
+        Truncated {
+            value: self,
+            bounds: Bounds {
+                depth: 4,
+                entries: 64,
+                string_len: 64,
+            },
+        }
+    }
+
+    /// Copy of the value with the entries under any of `keys` redacted, at
+    /// any depth.
+    ///
+    /// Keys are compared ignoring ASCII case. Redacted values are replaced
+    /// by the string [`REDACTED`](PhpValue::REDACTED), whatever their type,
+    /// so that the copy can be shared or logged, or serialized again using
+    /// [`to_vec`](PhpValue::to_vec):
+    ///
+    /// ```rust
+    /// # use serde_php::PhpValue;
+    /// let session = PhpValue::parse(
+    ///     br#"a:2:{s:4:"user";a:2:{s:4:"name";s:3:"bob";s:8:"Password";s:6:"hunter";}s:5:"token";i:42;}"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let redacted = session.redact_keys(&["password", "token"]);
+    /// assert_eq!(
+    ///     redacted.to_vec(),
+    ///     br#"a:2:{s:4:"user";a:2:{s:4:"name";s:3:"bob";s:8:"Password";s:10:"[REDACTED]";}s:5:"token";s:10:"[REDACTED]";}"#
+    /// );
+    /// ```
+    pub fn redact_keys(&self, keys: &[&str]) -> PhpValue {
+        self.redact_with(|key, _| keys.iter().any(|k| k.as_bytes().eq_ignore_ascii_case(key)))
+    }
+
+    /// Copy of the value with the entries selected by `redact` redacted, at
+    /// any depth.
+    ///
+    /// `redact` is called with the key and value of every array entry and
+    /// object property, integer keys being given as decimal text. Any
+    /// matcher can be used, e.g. a `regex::bytes::Regex` on keys or string
+    /// values. Entries it returns `true` for are replaced like by
+    /// [`redact_keys`](PhpValue::redact_keys), others are searched further.
+    ///
+    /// ```rust
+    /// # use serde_php::PhpValue;
+    /// let value = PhpValue::parse(br#"a:2:{s:5:"email";s:7:"a@b.org";s:7:"api_key";s:3:"xyz";}"#).unwrap();
+    /// let redacted = value.redact_with(|key, value| {
+    ///     key.ends_with(b"_key") || value.as_bytes().map_or(false, |v| v.contains(&b'@'))
+    /// });
+    /// assert_eq!(redacted["email"], PhpValue::from(PhpValue::REDACTED));
+    /// assert_eq!(redacted["api_key"], PhpValue::from(PhpValue::REDACTED));
+    /// ```
+    pub fn redact_with<F>(&self, mut redact: F) -> PhpValue
+    where
+        F: FnMut(&[u8], &PhpValue) -> bool,
+    {
+        self.redacted(&mut redact)
+    }
+
+    /// Copy of the value, with entries redacted according to `redact`.
+    fn redacted(&self, redact: &mut dyn FnMut(&[u8], &PhpValue) -> bool) -> PhpValue {
+        let entries = match self {
+            PhpValue::Array(entries) => entries,
+            other => return other.clone(),
+        };
+
+        // The class name of objects is kept, it is no property.
+        let skip = usize::from(self.class_name().is_some());
+        let mut copy = Vec::with_capacity(entries.len());
+        copy.extend_from_slice(&entries[..skip]);
+        for (key, value) in &entries[skip..] {
+            let text;
+            let key_bytes = match key {
+                PhpValue::String(key) => &key[..],
+                PhpValue::Number(n) => {
+                    text = n.to_string();
+                    text.as_bytes()
+                }
+                _ => &[],
+            };
+
+            let value = if redact(key_bytes, value) {
+                PhpValue::from(PhpValue::REDACTED)
+            } else {
+                value.redacted(redact)
+            };
+            copy.push((key.clone(), value));
+        }
+        PhpValue::Array(copy)
+    }
+
+    /// Returns whether the value is an array with keys `0..n`, in order.
+    pub fn is_list(&self) -> bool {
+        match self {
+            PhpValue::Array(entries) => entries
+                .iter()
+                .enumerate()
+                .all(|(idx, (key, _))| *key == PhpValue::from(idx as i64)),
+            _ => false,
+        }
+    }
+}
+
+/// Returned when indexing a missing entry.
+static NULL: PhpValue = PhpValue::Null;
+
+/// Look up entries by string key, returning `null` if missing.
+///
+/// ```rust
+/// # use serde_php::PhpValue;
+/// let value = PhpValue::parse(br#"a:2:{s:4:"name";s:3:"bob";i:7;b:1;}"#).unwrap();
+/// assert_eq!(value["name"].as_str(), Some("bob"));
+/// assert_eq!(value["7"], PhpValue::Bool(true));
+/// assert!(value["missing"]["nested"].is_null());
+/// ```
+impl ops::Index<&str> for PhpValue {
+    type Output = PhpValue;
+
+    fn index(&self, key: &str) -> &PhpValue {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+/// Look up entries by integer key, returning `null` if missing.
+impl ops::Index<i64> for PhpValue {
+    type Output = PhpValue;
+
+    fn index(&self, index: i64) -> &PhpValue {
+        self.get_index(index).unwrap_or(&NULL)
+    }
+}
+
+/// Look up entries by string key, inserting `null` if missing.
+///
+/// Panics if the value is neither an array nor `null`.
+impl ops::IndexMut<&str> for PhpValue {
+    fn index_mut(&mut self, key: &str) -> &mut PhpValue {
+        self.find_or_insert(array_key(key.as_bytes().to_vec()))
+    }
+}
+
+/// Look up entries by integer key, inserting `null` if missing.
+///
+/// Panics if the value is neither an array nor `null`.
+impl ops::IndexMut<i64> for PhpValue {
+    fn index_mut(&mut self, index: i64) -> &mut PhpValue {
+        self.find_or_insert(PhpValue::from(index))
+    }
+}
+
+impl From<bool> for PhpValue {
+    fn from(v: bool) -> Self {
+        PhpValue::Bool(v)
+    }
+}
+
+macro_rules! from_number {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for PhpValue {
+                fn from(v: $ty) -> Self {
+                    PhpValue::Number(v.into())
+                }
+            }
+        )*
+    };
+}
+
+from_number!(i8 i16 i32 i64 u8 u16 u32 u64 f32 f64);
+
+impl From<Number> for PhpValue {
+    fn from(n: Number) -> Self {
+        PhpValue::Number(n)
+    }
+}
+
+impl From<&str> for PhpValue {
+    fn from(s: &str) -> Self {
+        PhpValue::String(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for PhpValue {
+    fn from(s: String) -> Self {
+        PhpValue::String(s.into_bytes())
+    }
+}
+
+impl From<Vec<u8>> for PhpValue {
+    fn from(v: Vec<u8>) -> Self {
+        PhpValue::String(v)
+    }
+}
+
+/// Options for parsing a [`PhpValue`].
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ParseOptions<'a> {
+    /// Handling of string data.
+    pub(crate) mode: StringMode,
+    /// Classes whose objects are parsed as arrays of their properties.
+    ///
+    /// Objects of any other class are rejected.
+    pub(crate) allowed_classes: &'a [String],
+    /// Whether objects of all classes are parsed, regardless of
+    /// `allowed_classes`.
+    pub(crate) any_class: bool,
+    /// Limit on the nesting depth of arrays and objects.
+    pub(crate) max_depth: Option<usize>,
+}
+
+/// Parse input consisting of exactly one value.
+fn parse_complete(input: &[u8], options: &ParseOptions<'_>) -> Result<PhpValue> {
+    let mut lexer = Lookahead1::new(input);
+    lexer.set_input_len(input.len() as u64);
+    lexer.set_references(ReferencePolicy::Resolve);
+    lexer.set_max_depth(options.max_depth);
+    let value =
+        parse_value(&mut lexer, options).map_err(|err| err.with_offset(lexer.position()))?;
+
+    if lexer.peek()?.is_some() {
+        return Err(Error::from(ErrorKind::TrailingData).with_offset(lexer.position()));
+    }
+
+    Ok(value)
+}
+
+/// Parse a single value, including nested values.
+pub(crate) fn parse_value<R: Input>(
+    input: &mut Lookahead1<R>,
+    options: &ParseOptions<'_>,
+) -> Result<PhpValue> {
+    let mode = options.mode;
+    let sym = input.read_type()?;
+
+    if sym == b'N' {
+        input.expect(b';')?;
+        return Ok(PhpValue::Null);
+    }
+
+    input.expect(b':')?;
+
+    match sym {
+        b'b' => Ok(PhpValue::Bool(input.read_bool()?)),
+        b'i' => Ok(PhpValue::Number(Number::from_int_text(
+            &input.read_int_text()?,
+        ))),
+        b'd' => Ok(PhpValue::Number(input.read_float()?.into())),
+        b's' => {
+            let start = input.position() - 2;
+            string_value(input.read_raw_string()?, mode, start)
+        }
+        b'a' => {
+            let num_elements = input.read_array_header()?;
+            let mut entries = Vec::with_capacity(num_elements.min(1024));
+
+            for _ in 0..num_elements {
+                entries.push(parse_entry(input, options)?);
+            }
+
+            input.read_array_end()?;
+            Ok(PhpValue::Array(entries))
+        }
+        b'O' => {
+            let (class, num_properties) = input.read_object_header()?;
+            if !options.any_class
+                && !options
+                    .allowed_classes
+                    .iter()
+                    .any(|c| c.as_bytes() == class)
+            {
+                return Err(if options.allowed_classes.is_empty() {
+                    ErrorKind::UnsupportedPhpType {
+                        type_indicator: 'O',
+                    }
+                } else {
+                    ErrorKind::ClassNotAllowed(String::from_utf8_lossy(&class).into_owned())
+                }
+                .into());
+            }
+
+            let mut properties = Vec::with_capacity(num_properties.min(1024));
+            for _ in 0..num_properties {
+                properties.push(parse_entry(input, options)?);
+            }
+
+            input.read_array_end()?;
+            Ok(PhpValue::object(class, properties))
+        }
+        // Custom serialized objects and enum cases only have a meaning to
+        // their class.
+        b'C' | b'E' => Err(ErrorKind::UnsupportedPhpType {
+            type_indicator: char::from(sym),
+        }
+        .into()),
+        c => Err(ErrorKind::InvalidTypeIndicator(char::from(c)).into()),
+    }
+}
+
+/// Parse a single key-value pair of an array or object.
+fn parse_entry<R: Input>(
+    input: &mut Lookahead1<R>,
+    options: &ParseOptions<'_>,
+) -> Result<(PhpValue, PhpValue)> {
+    let key = match input.peek_type()? {
+        Some(b'i') => parse_value(input, options)?,
+        Some(b's') => {
+            let start = input.position();
+            input.expect_type(b's')?;
+            input.expect(b':')?;
+            string_value(input.read_key_string()?, options.mode, start)?
+        }
+        Some(c) => return Err(ErrorKind::UnsupportedArrayKeyType(char::from(c)).into()),
+        None => return Err(ErrorKind::UnexpectedEof.into()),
+    };
+    let value = parse_value(input, options)?;
+    Ok((key, value))
+}
+
+/// Convert string data found at `start` according to `mode`.
+fn string_value(data: Vec<u8>, mode: StringMode, start: u64) -> Result<PhpValue> {
+    match mode {
+        StringMode::Bytes => Ok(PhpValue::String(data)),
+        StringMode::Utf8Lossy => match String::from_utf8_lossy(&data) {
+            // Avoid copying when nothing needs to be replaced.
+            Cow::Borrowed(_) => Ok(PhpValue::String(data)),
+            Cow::Owned(replaced) => Ok(PhpValue::String(replaced.into_bytes())),
+        },
+        StringMode::Utf8Strict => match std::str::from_utf8(&data) {
+            Ok(_) => Ok(PhpValue::String(data)),
+            Err(err) => Err(Error::from(ErrorKind::Utf8Error(err)).with_offset(start)),
+        },
+    }
+}
+
+/// Write a single value, including nested values.
+fn write_value<W: Write>(out: &mut W, value: &PhpValue) -> io::Result<()> {
+    match value {
+        PhpValue::Null => out.write_all(b"N;"),
+        PhpValue::Bool(false) => out.write_all(b"b:0;"),
+        PhpValue::Bool(true) => out.write_all(b"b:1;"),
+        PhpValue::Number(n) => n.write_to(out),
+        PhpValue::String(v) => {
+            write!(out, "s:{}:\"", v.len())?;
+            out.write_all(v)?;
+            out.write_all(b"\";")
+        }
+        PhpValue::Array(entries) => {
+            let entries = match value.class_name() {
+                Some(class) => {
+                    write!(out, "O:{}:\"", class.len())?;
+                    out.write_all(class)?;
+                    write!(out, "\":{}:{{", entries.len() - 1)?;
+                    &entries[1..]
+                }
+                None => {
+                    write!(out, "a:{}:{{", entries.len())?;
+                    &entries[..]
+                }
+            };
+            for (key, value) in entries {
+                write_value(out, key)?;
+                write_value(out, value)?;
+            }
+            out.write_all(b"}")
+        }
+    }
+}
+
+/// Formats the value as PHP source code, similar to `var_export`.
+///
+/// Arrays use the short array syntax with explicit keys. The alternate flag
+/// (`{:#}`) spreads arrays over multiple lines with indentation.
+///
+/// ```rust
+/// # use serde_php::PhpValue;
+/// let value = PhpValue::parse(br#"a:2:{s:1:"a";i:1;s:1:"b";a:1:{i:0;s:4:"it's";}}"#).unwrap();
+/// assert_eq!(value.to_string(), r#"['a' => 1, 'b' => [0 => 'it\'s']]"#);
+/// ```
+impl fmt::Display for PhpValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries = usize::MAX;
+        export(f, self, 0, &Bounds::UNBOUNDED, &mut entries)
+    }
+}
+
+/// A [`PhpValue`] formatted with bounded size, see [`PhpValue::truncated`].
+///
+/// Formats like the `Display` implementation of the value, up to the
+/// configured limits. Omitted parts are replaced by an ellipsis, along with
+/// their size. `Debug` formats the same way, for use with loggers that
+/// expect it.
+#[derive(Clone, Copy)]
+pub struct Truncated<'a> {
+    value: &'a PhpValue,
+    bounds: Bounds,
+}
+
+impl Truncated<'_> {
+    /// Maximum nesting level of arrays shown. Arrays nested deeper are
+    /// replaced by the number of their entries. Defaults to 4.
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.bounds.depth = limit;
+        self
+    }
+
+    /// Maximum number of array entries shown, in total across all arrays.
+    /// Defaults to 64.
+    pub fn max_entries(mut self, limit: usize) -> Self {
+        self.bounds.entries = limit;
+        self
+    }
+
+    /// Maximum number of bytes shown of each string. Defaults to 64.
+    pub fn max_string_len(mut self, limit: usize) -> Self {
+        self.bounds.string_len = limit;
+        self
+    }
+}
+
+impl fmt::Display for Truncated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries = self.bounds.entries;
+        export(f, self.value, 0, &self.bounds, &mut entries)
+    }
+}
+
+impl fmt::Debug for Truncated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Limits on the parts of a value shown when formatting it.
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    depth: usize,
+    entries: usize,
+    string_len: usize,
+}
+
+impl Bounds {
+    const UNBOUNDED: Bounds = Bounds {
+        depth: usize::MAX,
+        entries: usize::MAX,
+        string_len: usize::MAX,
+    };
+}
+
+/// Write a value as PHP source, `depth` being the current nesting level.
+///
+/// Parts beyond `bounds` are left out, `entries_left` being the number of
+/// array entries that may still be written.
+fn export(
+    f: &mut fmt::Formatter<'_>,
+    value: &PhpValue,
+    depth: usize,
+    bounds: &Bounds,
+    entries_left: &mut usize,
+) -> fmt::Result {
+    match value {
+        PhpValue::Null => f.write_str("NULL"),
+        PhpValue::Bool(v) => write!(f, "{}", v),
+        PhpValue::Number(n) => n.export(f),
+        PhpValue::String(v) if v.len() > bounds.string_len => {
+            // Cut valid UTF-8 at a character boundary, so that it stays valid.
+            let mut len = bounds.string_len;
+            if std::str::from_utf8(v).is_ok() {
+                while matches!(v.get(len), Some(c) if c & 0xc0 == 0x80) {
+                    len -= 1;
+                }
+            }
+            export_string(f, &v[..len])?;
+            write!(f, "... ({} bytes)", v.len())
+        }
+        PhpValue::String(v) => export_string(f, v),
+        PhpValue::Array(entries) if entries.is_empty() => f.write_str("[]"),
+        PhpValue::Array(entries) if depth >= bounds.depth => {
+            write!(f, "[...] ({} entries)", entries.len())
+        }
+        PhpValue::Array(entries) => {
+            f.write_str("[")?;
+            for (idx, (key, value)) in entries.iter().enumerate() {
+                if f.alternate() {
+                    f.write_str("\n")?;
+                    write!(f, "{:width$}", "", width = (depth + 1) * 4)?;
+                } else if idx > 0 {
+                    f.write_str(", ")?;
+                }
+
+                if *entries_left == 0 {
+                    write!(f, "... ({} more)", entries.len() - idx)?;
+                    if f.alternate() {
+                        f.write_str(",")?;
+                    }
+                    break;
+                }
+                *entries_left -= 1;
+
+                export(f, key, depth + 1, bounds, entries_left)?;
+                f.write_str(" => ")?;
+                export(f, value, depth + 1, bounds, entries_left)?;
+
+                if f.alternate() {
+                    f.write_str(",")?;
+                }
+            }
+            if f.alternate() {
+                f.write_str("\n")?;
+                write!(f, "{:width$}", "", width = depth * 4)?;
+            }
+            f.write_str("]")
+        }
+    }
+}
+
+/// Write a string literal.
+///
+/// Single quotes are used where possible. Strings that are not valid UTF-8
+/// use double quotes, with the offending bytes written as hex escapes.
+fn export_string(f: &mut fmt::Formatter<'_>, value: &[u8]) -> fmt::Result {
+    if let Ok(s) = std::str::from_utf8(value) {
+        f.write_str("'")?;
+        for c in s.chars() {
+            if c == '\'' || c == '\\' {
+                f.write_str("\\")?;
+            }
+            write!(f, "{}", c)?;
+        }
+        return f.write_str("'");
+    }
+
+    f.write_str("\"")?;
+    for chunk in value.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' | '"' | '$' => write!(f, "\\{}", c)?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(f, "\\x{:02x}", byte)?;
+        }
+    }
+    f.write_str("\"")
+}
+
+impl Serialize for PhpValue {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PhpValue::Null => serializer.serialize_unit(),
+            PhpValue::Bool(v) => serializer.serialize_bool(*v),
+            PhpValue::Number(n) => n.serialize(serializer),
+            // Strings that happen to be valid UTF-8 are passed on as such,
+            // which results in much nicer output for most formats.
+            PhpValue::String(v) => match std::str::from_utf8(v) {
+                Ok(s) => serializer.serialize_str(s),
+                Err(_) => serializer.serialize_bytes(v),
+            },
+            PhpValue::Array(entries) if self.is_list() => {
+                let mut seq = serializer.serialize_seq(Some(entries.len()))?;
+                for (_, value) in entries {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            PhpValue::Array(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Value> for PhpValue {
+    /// Convert a JSON value, following the semantics of PHP's
+    /// `json_decode($input, true)`.
+    ///
+    /// Objects become associative arrays. As in PHP, object keys that are
+    /// canonical decimal integers are turned into integer keys.
+    fn from(value: serde_json::Value) -> Self {
+        use serde_json::Value;
+
+        match value {
+            Value::Null => PhpValue::Null,
+            Value::Bool(v) => PhpValue::Bool(v),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(v), _) => PhpValue::from(v),
+                (None, Some(v)) => PhpValue::from(v),
+                (None, None) => PhpValue::from(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => PhpValue::String(s.into_bytes()),
+            Value::Array(items) => PhpValue::Array(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, item)| (PhpValue::from(idx as i64), item.into()))
+                    .collect(),
+            ),
+            Value::Object(fields) => PhpValue::Array(
+                fields
+                    .into_iter()
+                    .map(|(key, item)| (array_key(key.into_bytes()), item.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Convert a string key into an array key the way PHP does.
+pub(crate) fn array_key(key: Vec<u8>) -> PhpValue {
+    let canonical = key == b"0" || !(key.starts_with(b"0") || key.starts_with(b"-0"));
+
+    match std::str::from_utf8(&key).map(str::parse::<i64>) {
+        Ok(Ok(v)) if canonical && !key.starts_with(b"+") => PhpValue::from(v),
+        _ => PhpValue::String(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhpValue, StringMode};
+    use crate::error::ErrorKind;
+    use crate::number::Number;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    macro_rules! assert_roundtrips {
+        ($input:expr, $expected:expr) => {
+            let input: &[u8] = $input;
+            let value = PhpValue::parse(input).expect("parsing failed");
+            assert_eq!(value, $expected);
+            assert_eq!(value.to_vec().as_slice(), input);
+        };
+    }
+
+    #[test]
+    fn parse_scalars() {
+        assert_roundtrips!(b"N;", PhpValue::Null);
+        assert_roundtrips!(b"b:1;", PhpValue::Bool(true));
+        assert_roundtrips!(b"i:-42;", PhpValue::from(-42));
+        assert_roundtrips!(b"d:1.5;", PhpValue::from(1.5));
+        assert_roundtrips!(br#"s:3:"a"b";"#, PhpValue::String(b"a\"b".to_vec()));
+    }
+
+    #[test]
+    fn parse_php_floats() {
+        // All of these are output of PHP's `serialize`.
+        assert_roundtrips!(b"d:0.1;", PhpValue::from(0.1));
+        assert_roundtrips!(b"d:-0;", PhpValue::from(-0.0));
+        assert_roundtrips!(b"d:1.0E+25;", PhpValue::from(1e25));
+        assert_roundtrips!(b"d:-1.5E-7;", PhpValue::from(-1.5e-7));
+        assert_roundtrips!(b"d:0.0001;", PhpValue::from(0.0001));
+        assert_roundtrips!(b"d:1.0E-5;", PhpValue::from(0.00001));
+        assert_roundtrips!(b"d:10000000000000000;", PhpValue::from(1e16));
+        assert_roundtrips!(b"d:1.0E+17;", PhpValue::from(1e17));
+        assert_roundtrips!(b"d:1.7976931348623157E+308;", PhpValue::from(f64::MAX));
+        assert_roundtrips!(b"d:-INF;", PhpValue::from(f64::NEG_INFINITY));
+
+        let nan = PhpValue::parse(b"d:NAN;").unwrap();
+        assert!(nan.as_f64().unwrap().is_nan());
+        assert_eq!(nan.to_vec(), b"d:NAN;".to_vec());
+    }
+
+    #[test]
+    fn roundtrip_objects() {
+        let input = br#"a:2:{s:4:"cart";O:4:"Cart":2:{s:5:"items";a:1:{i:0;O:8:"stdClass":0:{}}s:5:"total";d:9.5;}i:1;a:1:{s:27:"__PHP_Incomplete_Class_Name";i:3;}}"#;
+        let allowed = ["Cart".to_owned(), "stdClass".to_owned()];
+        let options = super::ParseOptions {
+            allowed_classes: &allowed,
+            ..super::ParseOptions::default()
+        };
+        let value = super::parse_value(&mut crate::lex::Lookahead1::new(&input[..]), &options)
+            .expect("parsing failed");
+
+        let cart = &value["cart"];
+        assert_eq!(cart.class_name(), Some(&b"Cart"[..]));
+        assert_eq!(cart["total"].as_f64(), Some(9.5));
+        assert_eq!(cart["items"][0].class_name(), Some(&b"stdClass"[..]));
+        // The marker needs a string value to denote an object.
+        assert_eq!(value[1].class_name(), None);
+        assert_eq!(value.class_name(), None);
+
+        assert_eq!(value.to_vec().as_slice(), &input[..]);
+    }
+
+    #[test]
+    fn roundtrip_unknown_objects() {
+        // PHP 8.2: two objects of a class with a private, a protected and a
+        // public property, the second one holding an `ArrayObject`.
+        let input = b"a:2:{i:0;O:5:\"Order\":3:{s:9:\"\0Order\0id\";i:1;s:7:\"\0*\0meta\";a:0:{}s:5:\"lines\";a:1:{i:0;O:8:\"stdClass\":1:{s:3:\"sku\";s:2:\"A1\";}}}i:1;O:5:\"Order\":3:{s:5:\"lines\";N;s:9:\"\0Order\0id\";i:2;s:7:\"\0*\0meta\";O:11:\"ArrayObject\":4:{i:0;i:0;i:1;a:0:{}i:2;a:0:{}i:3;N;}}}";
+        let value = PhpValue::parse_objects(input).unwrap();
+
+        let second = &value[1];
+        assert_eq!(second.class_name(), Some(&b"Order"[..]));
+        // Properties keep their order and mangled names.
+        let keys: Vec<_> = second.as_array().unwrap()[1..]
+            .iter()
+            .map(|(key, _)| key.as_bytes().unwrap())
+            .collect();
+        assert_eq!(keys, [&b"lines"[..], b"\0Order\0id", b"\0*\0meta"]);
+        assert_eq!(second["\0*\0meta"][3], PhpValue::Null);
+
+        assert_eq!(value.to_vec(), &input[..]);
+        assert_eq!(PhpValue::parse_objects(&value.to_vec()).unwrap(), value);
+        assert!(PhpValue::parse_objects(br#"C:3:"Foo":0:{}"#).is_err());
+    }
+
+    #[test]
+    fn parse_big_ints() {
+        let value =
+            PhpValue::parse(b"a:2:{i:0;i:99999999999999999999;i:1;i:-9223372036854775808;}")
+                .unwrap();
+        assert_eq!(value[0].as_i64(), None);
+        assert_eq!(
+            value[0].as_number().and_then(Number::as_big_int),
+            Some("99999999999999999999")
+        );
+        assert_eq!(value[1].as_i64(), Some(i64::MIN));
+        assert_eq!(
+            value.to_vec(),
+            b"a:2:{i:0;i:99999999999999999999;i:1;i:-9223372036854775808;}".to_vec()
+        );
+    }
+
+    #[test]
+    fn parse_string_modes() {
+        let input = b"a:1:{s:2:\"k\xe9\";s:5:\"caf\xc3\xa9\";}";
+
+        let value = PhpValue::parse_with(input, StringMode::Bytes).unwrap();
+        assert_eq!(value, PhpValue::parse(input).unwrap());
+        assert_eq!(value.to_vec(), input.to_vec());
+
+        let value = PhpValue::parse_with(input, StringMode::Utf8Lossy).unwrap();
+        assert_eq!(value["k\u{fffd}"].as_str(), Some("café"));
+
+        let err = PhpValue::parse_with(input, StringMode::Utf8Strict).unwrap_err();
+        match err.kind() {
+            ErrorKind::Utf8Error(_) => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(err.offset(), Some(5));
+        assert!(PhpValue::parse_with("s:5:\"café\";".as_bytes(), StringMode::Utf8Strict).is_ok());
+    }
+
+    #[test]
+    fn parse_mixed_array() {
+        assert_roundtrips!(
+            br#"a:3:{i:5;s:1:"x";s:3:"foo";a:0:{}i:0;N;}"#,
+            PhpValue::Array(vec![
+                (PhpValue::from(5), PhpValue::String(b"x".to_vec())),
+                (PhpValue::String(b"foo".to_vec()), PhpValue::Array(vec![])),
+                (PhpValue::from(0), PhpValue::Null),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_rejects_trailing_data() {
+        let err = PhpValue::parse(b"i:1;i:2;").unwrap_err();
+        match err.kind() {
+            ErrorKind::TrailingData => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(err.offset(), Some(4));
+    }
+
+    #[test]
+    fn is_list() {
+        assert!(PhpValue::parse(b"a:0:{}").unwrap().is_list());
+        assert!(PhpValue::parse(b"a:2:{i:0;N;i:1;N;}").unwrap().is_list());
+        assert!(!PhpValue::parse(b"a:2:{i:1;N;i:0;N;}").unwrap().is_list());
+        assert!(!PhpValue::Null.is_list());
+    }
+
+    #[test]
+    fn accessors() {
+        let value =
+            PhpValue::parse(br#"a:3:{s:4:"name";s:3:"bob";i:2;d:0.5;s:4:"list";a:1:{i:0;i:9;}}"#)
+                .unwrap();
+
+        assert_eq!(value.get("name").and_then(PhpValue::as_str), Some("bob"));
+        assert_eq!(value.get("2").and_then(PhpValue::as_f64), Some(0.5));
+        assert_eq!(value.get_index(2), value.get("2"));
+        assert_eq!(value["list"][0].as_i64(), Some(9));
+        assert_eq!(value["list"].as_array().map(Vec::len), Some(1));
+        assert_eq!(value["name"].as_bytes(), Some(&b"bob"[..]));
+        assert_eq!(value["name"].as_i64(), None);
+        assert!(value["missing"][3].is_null());
+        assert!(value.get("02").is_none());
+        assert!(PhpValue::from(1).get("x").is_none());
+    }
+
+    #[test]
+    fn index_mut() {
+        let mut value = PhpValue::Null;
+        value["a"]["b"] = PhpValue::Bool(true);
+        value["1"] = PhpValue::from(1);
+        value[1] = PhpValue::from(2);
+        *value.get_mut("a").unwrap() = PhpValue::from(3);
+
+        assert_eq!(value.to_vec(), br#"a:2:{s:1:"a";i:3;i:1;i:2;}"#.to_vec());
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_mut_non_array() {
+        let mut value = PhpValue::from(1);
+        value["a"] = PhpValue::Null;
+    }
+
+    #[test]
+    fn display_as_php() {
+        let value = PhpValue::parse(
+            br#"a:4:{i:0;N;s:1:"f";d:1;s:1:"s";s:5:"a\'b$";s:1:"a";a:2:{i:0;b:1;i:1;a:0:{}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value.to_string(),
+            r#"[0 => NULL, 'f' => 1.0, 's' => 'a\\\'b$', 'a' => [0 => true, 1 => []]]"#
+        );
+        assert_eq!(
+            format!("{:#}", value),
+            r#"[
+    0 => NULL,
+    'f' => 1.0,
+    's' => 'a\\\'b$',
+    'a' => [
+        0 => true,
+        1 => [],
+    ],
+]"#
+        );
+    }
+
+    #[test]
+    fn redacts_entries() {
+        let login = PhpValue::object(
+            "Login",
+            vec![
+                ("user".into(), "bob".into()),
+                (
+                    "password".into(),
+                    PhpValue::Array(vec![(0.into(), "x".into())]),
+                ),
+            ],
+        );
+        let value = PhpValue::Array(vec![
+            (0.into(), login),
+            (
+                1.into(),
+                PhpValue::Array(vec![("TOKEN".into(), PhpValue::Null)]),
+            ),
+            (2.into(), "password".into()),
+        ]);
+
+        let redacted = value.redact_keys(&["password", "token"]);
+        assert_eq!(
+            redacted.to_vec(),
+            &br#"a:3:{i:0;O:5:"Login":2:{s:4:"user";s:3:"bob";s:8:"password";s:10:"[REDACTED]";}i:1;a:1:{s:5:"TOKEN";s:10:"[REDACTED]";}i:2;s:8:"password";}"#[..]
+        );
+        // The original is left untouched.
+        assert_eq!(value[0]["password"][0], PhpValue::from("x"));
+
+        // Class names are not subject to redaction.
+        let strings = value.redact_with(|_, value| value.as_str().is_some());
+        assert_eq!(strings[0].class_name(), Some(&b"Login"[..]));
+        assert_eq!(strings[0]["user"].as_str(), Some(PhpValue::REDACTED));
+        assert_eq!(strings[2].as_str(), Some(PhpValue::REDACTED));
+
+        let by_index = value.redact_with(|key, _| key == b"2");
+        assert_eq!(by_index[2].as_str(), Some(PhpValue::REDACTED));
+        assert_eq!(by_index[1], value[1]);
+        assert_eq!(PhpValue::from(7).redact_keys(&["x"]), PhpValue::from(7));
+    }
+
+    #[test]
+    fn display_truncated() {
+        let value = PhpValue::Array(vec![
+            ("café".into(), "crème brûlée".into()),
+            ("raw".into(), PhpValue::String(vec![0xff; 8])),
+            (
+                "list".into(),
+                PhpValue::Array(vec![(
+                    0.into(),
+                    PhpValue::Array(vec![(0.into(), 1.into()), (1.into(), 2.into())]),
+                )]),
+            ),
+        ]);
+
+        let short = value.truncated().max_depth(2).max_string_len(4);
+        assert_eq!(
+            short.to_string(),
+            r#"['caf'... (5 bytes) => 'crè'... (15 bytes), 'raw' => "\xff\xff\xff\xff"... (8 bytes), 'list' => [0 => [...] (2 entries)]]"#
+        );
+        assert_eq!(format!("{:?}", short), short.to_string());
+        assert_eq!(
+            format!("{:#}", value.truncated().max_entries(1)),
+            "[\n    'café' => 'crème brûlée',\n    ... (2 more),\n]"
+        );
+
+        // Defaults bound the output of large values.
+        let large = PhpValue::Array(
+            (0..10_000)
+                .map(|idx| (idx.into(), "x".repeat(1000).into()))
+                .collect(),
+        );
+        assert!(large.truncated().to_string().len() < 10_000);
+        assert_eq!(value.truncated().to_string(), value.to_string());
+    }
+
+    #[test]
+    fn display_edge_cases() {
+        assert_eq!(
+            PhpValue::from(i64::MIN).to_string(),
+            "-9223372036854775807-1"
+        );
+        assert_eq!(PhpValue::from(f64::NEG_INFINITY).to_string(), "-INF");
+        assert_eq!(PhpValue::from(f64::NAN).to_string(), "NAN");
+        assert_eq!(PhpValue::from(1e100).to_string(), "1e100");
+        assert_eq!(
+            PhpValue::String(b"\xff\"$\n".to_vec()).to_string(),
+            r#""\xff\"\$\n""#
+        );
+    }
+
+    fn arb_value() -> impl Strategy<Value = PhpValue> {
+        let leaf = prop_oneof![
+            Just(PhpValue::Null),
+            any::<bool>().prop_map(PhpValue::Bool),
+            any::<i64>().prop_map(PhpValue::from),
+            any::<f64>().prop_map(PhpValue::from),
+            vec(any::<u8>(), 0..16).prop_map(PhpValue::String),
+        ];
+
+        leaf.prop_recursive(4, 64, 8, |inner| {
+            let key = prop_oneof![
+                any::<i64>().prop_map(PhpValue::from),
+                vec(any::<u8>(), 0..8).prop_map(PhpValue::String),
+            ];
+            vec((key, inner), 0..8).prop_map(PhpValue::Array)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip_bytes(value in arb_value()) {
+            let serialized = value.to_vec();
+            let parsed = PhpValue::parse(&serialized).expect("parsing failed");
+            prop_assert_eq!(parsed.to_vec(), serialized);
+        }
+
+        #[test]
+        fn roundtrip_float_bits(v in any::<f64>()) {
+            let serialized = PhpValue::from(v).to_vec();
+            let parsed = PhpValue::parse(&serialized).expect("parsing failed");
+            let actual = parsed.as_f64().unwrap();
+            prop_assert!(actual.to_bits() == v.to_bits() || (actual.is_nan() && v.is_nan()));
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn from_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(r#"{"07": "x", "7": true, "a": [1, 2.5, null]}"#).unwrap();
+        let value = PhpValue::from(json);
+
+        assert_eq!(
+            value.to_vec(),
+            br#"a:3:{s:2:"07";s:1:"x";i:7;b:1;s:1:"a";a:3:{i:0;i:1;i:1;d:2.5;i:2;N;}}"#.to_vec()
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn to_json() {
+        let value = PhpValue::parse(br#"a:2:{i:0;s:3:"foo";i:1;a:1:{s:1:"k";d:0.5;}}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"["foo",{"k":0.5}]"#
+        );
+    }
+}
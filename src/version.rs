@@ -0,0 +1,48 @@
+//! PHP versions differing in their serialization format.
+
+/// Version of PHP on the other side of the wire.
+///
+/// The serialization format has grown over time, and older versions of PHP
+/// write some values differently or cannot read newer tokens at all. Pinning
+/// the version selects which tokens are written by
+/// [`PhpSerializerBuilder::format_version`](crate::PhpSerializerBuilder::format_version)
+/// and accepted by
+/// [`PhpDeserializerBuilder::format_version`](crate::PhpDeserializerBuilder::format_version).
+///
+/// Versions are ordered by age, so `version >= FormatVersion::Php81` tests
+/// for support of a feature.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum FormatVersion {
+    /// PHP 5 up to 7.0, which write floats with 17 significant digits, e.g.
+    /// `d:0.10000000000000001;` for `0.1`.
+    Php5,
+    /// PHP 7.1 up to 8.0, which write floats in their shortest form that
+    /// reads back as the same value. This is the default.
+    #[default]
+    Php74,
+    /// PHP 8.1 and later, which additionally write enum cases as `E:` records
+    /// holding the enum and case name, e.g. `E:11:"Suit:Hearts";`.
+    Php81,
+}
+
+impl FormatVersion {
+    /// Whether enum cases (`E:`) are part of the format.
+    #[inline]
+    pub(crate) fn has_enums(self) -> bool {
+        self >= FormatVersion::Php81
+    }
+}
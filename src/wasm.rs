@@ -0,0 +1,95 @@
+//! WebAssembly bindings.
+//!
+//! With the `wasm` feature enabled, conversion between PHP-serialized data and
+//! JSON is exported through `wasm_bindgen`, allowing it to run entirely
+//! client-side in a browser. Build with e.g.
+//!
+//! ```text
+//! wasm-pack build --target web -- --features wasm
+//! ```
+//!
+//! From JavaScript, serialized data is passed as a `Uint8Array` and JSON as a
+//! string. Errors are thrown as JavaScript `Error` objects. Serialized data
+//! nested more than 128 levels deep is rejected.
+
+use crate::value::PhpValue;
+use wasm_bindgen::prelude::*;
+
+/// Convert PHP-serialized bytes to a JSON string.
+///
+/// If `pretty` is set, the JSON output is indented.
+#[wasm_bindgen(js_name = phpToJson)]
+pub fn php_to_json(input: &[u8], pretty: bool) -> Result<String, JsError> {
+    to_json(input, pretty).map_err(|msg| JsError::new(&msg))
+}
+
+/// Convert a JSON string to PHP-serialized bytes.
+#[wasm_bindgen(js_name = jsonToPhp)]
+pub fn json_to_php(json: &str) -> Result<Vec<u8>, JsError> {
+    from_json(json).map_err(|msg| JsError::new(&msg))
+}
+
+/// Check whether input is a single valid PHP-serialized value.
+///
+/// Returns `undefined` if it is, or a message describing the error otherwise.
+#[wasm_bindgen]
+pub fn validate(input: &[u8]) -> Option<String> {
+    PhpValue::parse_bounded(input)
+        .err()
+        .map(|err| err.to_string())
+}
+
+// `JsError` can only be constructed on wasm targets, the actual conversions are
+// kept separate to be testable natively.
+
+fn to_json(input: &[u8], pretty: bool) -> Result<String, String> {
+    let value = PhpValue::parse_bounded(input).map_err(|err| err.to_string())?;
+
+    let json = if pretty {
+        serde_json::to_string_pretty(&value)
+    } else {
+        serde_json::to_string(&value)
+    };
+
+    json.map_err(|err| err.to_string())
+}
+
+fn from_json(json: &str) -> Result<Vec<u8>, String> {
+    let parsed: serde_json::Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    Ok(PhpValue::from(parsed).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_json, to_json, validate};
+
+    #[test]
+    fn converts_both_ways() {
+        let input = br#"a:2:{s:5:"flags";a:2:{i:0;b:1;i:1;N;}s:4:"user";s:3:"bob";}"#;
+
+        let json = to_json(input, false).unwrap();
+        assert_eq!(json, r#"{"flags":[true,null],"user":"bob"}"#);
+        assert!(to_json(input, true).unwrap().contains('\n'));
+
+        assert_eq!(from_json(&json).unwrap(), input.to_vec());
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert!(validate(b"i:1;").is_none());
+        assert!(validate(b"i:1").is_some());
+        assert!(to_json(b"x:1;", false).is_err());
+        assert!(from_json("[").is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        // Deep enough to overflow the stack without a limit.
+        let mut input = b"a:1:{i:0;".repeat(1_000_000);
+        input.extend_from_slice(b"N;");
+        input.extend_from_slice(&b"}".repeat(1_000_000));
+
+        assert!(validate(&input).is_some());
+        assert!(to_json(&input, false).is_err());
+    }
+}